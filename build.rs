@@ -0,0 +1,4 @@
+fn main() {
+    // Migrations are embedded at compile time, so rebuild when they change
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS league_join_requests;
+DROP TABLE IF EXISTS matches;
+DROP TABLE IF EXISTS player_leagues;
+DROP TABLE IF EXISTS appointments;
+DROP TABLE IF EXISTS leagues;
+DROP TABLE IF EXISTS players;
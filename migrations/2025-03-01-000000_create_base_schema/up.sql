@@ -0,0 +1,92 @@
+-- Your SQL goes here
+
+-- The schema that used to live in init.sql, as the first migration. Tables are
+-- created only if missing so databases set up from init.sql can adopt the
+-- migration history.
+
+CREATE TABLE IF NOT EXISTS players (
+    player_id SERIAL PRIMARY KEY,
+    name VARCHAR(100) NOT NULL UNIQUE,
+    email VARCHAR(100) UNIQUE NOT NULL,
+    phone VARCHAR(100) DEFAULT NULL,
+    role VARCHAR(50) DEFAULT 'player',
+    password VARCHAR(255) NOT NULL, -- store hashed passwords
+    skill_level VARCHAR(50),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS leagues (
+    league_id SERIAL,
+    league_name VARCHAR(50) PRIMARY KEY NOT NULL UNIQUE,
+    description TEXT,
+    is_public BOOLEAN NOT NULL DEFAULT TRUE,
+    skill_level VARCHAR(50),
+    created_by VARCHAR(50) NOT NULL,  -- References the player who created the league
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_creator
+        FOREIGN KEY (created_by)
+            REFERENCES players(name)
+            ON DELETE CASCADE
+);
+
+-- init.sql did not have this column
+ALTER TABLE leagues ADD COLUMN IF NOT EXISTS is_public BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Match requests between two players; replaces init.sql's unused `appointment` table
+CREATE TABLE IF NOT EXISTS appointments (
+    appointment_id SERIAL PRIMARY KEY,
+    requester_id INT NOT NULL REFERENCES players(player_id) ON DELETE CASCADE,
+    opponent_id INT NOT NULL REFERENCES players(player_id) ON DELETE CASCADE,
+    league_id INT, -- this is nullable for non-league matches
+    start_time TIMESTAMP NOT NULL,
+    end_time TIMESTAMP NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending', -- possible values: pending, confirmed, canceled, declined
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS player_leagues (
+    player_id VARCHAR(50) NOT NULL,
+    league_id VARCHAR(50) NOT NULL,
+    role VARCHAR(20) NOT NULL DEFAULT 'player',  -- Role can be 'player' or 'admin'
+    joined_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    singles_ranking INT,
+    doubles_ranking INT,
+    PRIMARY KEY (player_id, league_id),
+    CONSTRAINT fk_player
+        FOREIGN KEY(player_id)
+            REFERENCES players(name)
+            ON DELETE CASCADE,
+    CONSTRAINT fk_league_join
+        FOREIGN KEY(league_id)
+            REFERENCES leagues(league_name)
+            ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS matches (
+    id SERIAL PRIMARY KEY,
+    match_type VARCHAR(20) NOT NULL,  -- 'Singles' or 'Doubles'
+    player1_id TEXT,
+    player2_id TEXT,
+    league_id TEXT NOT NULL,
+    team1_player1_id TEXT,
+    team1_player2_id TEXT,
+    team2_player1_id TEXT,
+    team2_player2_id TEXT,
+    datetime TEXT NOT NULL,
+    location TEXT NOT NULL,
+    score TEXT,
+    winner_id TEXT,
+    status TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS league_join_requests (
+    request_id SERIAL PRIMARY KEY,
+    league_id VARCHAR(50) NOT NULL,
+    player_id VARCHAR(50) NOT NULL,
+    description TEXT,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    notes TEXT
+);
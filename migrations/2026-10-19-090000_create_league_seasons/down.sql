@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE matches DROP COLUMN season_id;
+DROP TABLE season_members;
+DROP TABLE league_divisions;
+DROP TABLE league_seasons;
@@ -0,0 +1,49 @@
+-- Your SQL goes here
+
+-- Seasons give a league start/end dates and a registration deadline
+CREATE TABLE league_seasons (
+    season_id SERIAL PRIMARY KEY,
+    league_id VARCHAR(50) NOT NULL,
+    season_name VARCHAR(100) NOT NULL,
+    start_date DATE NOT NULL,
+    end_date DATE NOT NULL,
+    registration_deadline DATE,
+    status VARCHAR(20) NOT NULL DEFAULT 'open',  -- 'open' or 'closed'
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    closed_at TIMESTAMP,
+    UNIQUE (league_id, season_name),
+    CONSTRAINT fk_season_league
+        FOREIGN KEY(league_id)
+            REFERENCES leagues(league_name)
+            ON DELETE CASCADE
+);
+
+-- Divisions (boxes) inside a season; tier 1 is the top division
+CREATE TABLE league_divisions (
+    division_id SERIAL PRIMARY KEY,
+    season_id INT NOT NULL REFERENCES league_seasons(season_id) ON DELETE CASCADE,
+    division_name VARCHAR(100) NOT NULL,
+    tier INT NOT NULL,
+    promotion_spots INT NOT NULL DEFAULT 0,
+    relegation_spots INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (season_id, tier)
+);
+
+-- Members registered for a season and the division they play in
+CREATE TABLE season_members (
+    season_id INT NOT NULL REFERENCES league_seasons(season_id) ON DELETE CASCADE,
+    player_id VARCHAR(50) NOT NULL,
+    division_id INT REFERENCES league_divisions(division_id) ON DELETE SET NULL,
+    final_rank INT,
+    movement VARCHAR(20),  -- 'promoted', 'stayed' or 'relegated' once the season is closed
+    joined_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (season_id, player_id),
+    CONSTRAINT fk_season_player
+        FOREIGN KEY(player_id)
+            REFERENCES players(name)
+            ON DELETE CASCADE
+);
+
+-- Tag matches with the season they count towards
+ALTER TABLE matches ADD COLUMN season_id INT REFERENCES league_seasons(season_id) ON DELETE SET NULL;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+-- What happens to a member's pending and scheduled matches when they leave or are removed:
+-- 'cancel' cancels them, 'forfeit' cancels pending matches and awards scheduled ones to the opponent
+ALTER TABLE leagues ADD COLUMN departure_policy VARCHAR(20) NOT NULL DEFAULT 'cancel';
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+
+-- Invitations sent by league admins, to a registered player or to an email address
+CREATE TABLE league_invitations (
+    invitation_id SERIAL PRIMARY KEY,
+    league_id VARCHAR(50) NOT NULL,
+    invited_by VARCHAR(50) NOT NULL,
+    invitee_name VARCHAR(100),  -- set when the invitee is a registered player
+    invitee_email VARCHAR(100),  -- set when inviting by email, possibly someone not registered yet
+    token VARCHAR(64) NOT NULL UNIQUE,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',  -- pending, accepted, declined, revoked
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    responded_at TIMESTAMP,
+    CHECK (invitee_name IS NOT NULL OR invitee_email IS NOT NULL),
+    CONSTRAINT fk_invitation_league
+        FOREIGN KEY(league_id)
+            REFERENCES leagues(league_name)
+            ON DELETE CASCADE
+);
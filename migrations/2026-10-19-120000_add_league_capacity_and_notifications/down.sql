@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE notifications;
+ALTER TABLE leagues DROP COLUMN waitlist_enabled;
+ALTER TABLE leagues DROP COLUMN max_members;
@@ -0,0 +1,25 @@
+-- Your SQL goes here
+
+-- Optional member cap; NULL means the league has no limit
+ALTER TABLE leagues ADD COLUMN max_members INTEGER CHECK (max_members > 0);
+-- When a full league has a waitlist, extra players queue up as 'waitlisted' join requests
+ALTER TABLE leagues ADD COLUMN waitlist_enabled BOOLEAN NOT NULL DEFAULT TRUE;
+
+CREATE TABLE notifications (
+    notification_id SERIAL PRIMARY KEY,
+    player_id VARCHAR(50) NOT NULL,
+    message TEXT NOT NULL,
+    league_id VARCHAR(50),
+    is_read BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_notification_player
+        FOREIGN KEY(player_id)
+            REFERENCES players(name)
+            ON DELETE CASCADE,
+    CONSTRAINT fk_notification_league
+        FOREIGN KEY(league_id)
+            REFERENCES leagues(league_name)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX idx_notifications_player ON notifications(player_id);
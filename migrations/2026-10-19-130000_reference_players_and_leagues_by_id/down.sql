@@ -0,0 +1,147 @@
+-- This file should undo anything in `up.sql`
+
+-- Go back to name-based references. Player names must be unique again for
+-- this to work.
+
+CREATE FUNCTION pg_temp.player_name(id INTEGER) RETURNS VARCHAR AS $$
+    SELECT name FROM players WHERE player_id = id
+$$ LANGUAGE SQL STABLE;
+
+CREATE FUNCTION pg_temp.league_name(id INTEGER) RETURNS VARCHAR AS $$
+    SELECT league_name FROM leagues WHERE league_id = id
+$$ LANGUAGE SQL STABLE;
+
+DROP INDEX idx_matches_league;
+
+-- leagues.created_by
+ALTER TABLE leagues ADD COLUMN old_created_by VARCHAR(50);
+UPDATE leagues SET old_created_by = pg_temp.player_name(created_by);
+ALTER TABLE leagues DROP COLUMN created_by;
+ALTER TABLE leagues RENAME COLUMN old_created_by TO created_by;
+ALTER TABLE leagues ALTER COLUMN created_by SET NOT NULL;
+
+-- player_leagues
+ALTER TABLE player_leagues ADD COLUMN old_player_id VARCHAR(50), ADD COLUMN old_league_id VARCHAR(50);
+UPDATE player_leagues
+    SET old_player_id = pg_temp.player_name(player_id), old_league_id = pg_temp.league_name(league_id);
+ALTER TABLE player_leagues DROP COLUMN player_id, DROP COLUMN league_id;
+ALTER TABLE player_leagues RENAME COLUMN old_player_id TO player_id;
+ALTER TABLE player_leagues RENAME COLUMN old_league_id TO league_id;
+
+-- league_join_requests
+ALTER TABLE league_join_requests ADD COLUMN old_player_id VARCHAR(50), ADD COLUMN old_league_id VARCHAR(50);
+UPDATE league_join_requests
+    SET old_player_id = pg_temp.player_name(player_id), old_league_id = pg_temp.league_name(league_id);
+ALTER TABLE league_join_requests DROP COLUMN player_id, DROP COLUMN league_id;
+ALTER TABLE league_join_requests RENAME COLUMN old_player_id TO player_id;
+ALTER TABLE league_join_requests RENAME COLUMN old_league_id TO league_id;
+ALTER TABLE league_join_requests ALTER COLUMN player_id SET NOT NULL, ALTER COLUMN league_id SET NOT NULL;
+
+-- matches
+ALTER TABLE matches
+    ADD COLUMN old_league_id TEXT,
+    ADD COLUMN old_player1_id TEXT,
+    ADD COLUMN old_player2_id TEXT,
+    ADD COLUMN old_team1_player1_id TEXT,
+    ADD COLUMN old_team1_player2_id TEXT,
+    ADD COLUMN old_team2_player1_id TEXT,
+    ADD COLUMN old_team2_player2_id TEXT,
+    ADD COLUMN old_winner_id TEXT;
+UPDATE matches SET
+    old_league_id = pg_temp.league_name(league_id),
+    old_player1_id = pg_temp.player_name(player1_id),
+    old_player2_id = pg_temp.player_name(player2_id),
+    old_team1_player1_id = pg_temp.player_name(team1_player1_id),
+    old_team1_player2_id = pg_temp.player_name(team1_player2_id),
+    old_team2_player1_id = pg_temp.player_name(team2_player1_id),
+    old_team2_player2_id = pg_temp.player_name(team2_player2_id),
+    old_winner_id = pg_temp.player_name(winner_id);
+ALTER TABLE matches
+    DROP COLUMN league_id,
+    DROP COLUMN player1_id,
+    DROP COLUMN player2_id,
+    DROP COLUMN team1_player1_id,
+    DROP COLUMN team1_player2_id,
+    DROP COLUMN team2_player1_id,
+    DROP COLUMN team2_player2_id,
+    DROP COLUMN winner_id;
+ALTER TABLE matches RENAME COLUMN old_league_id TO league_id;
+ALTER TABLE matches RENAME COLUMN old_player1_id TO player1_id;
+ALTER TABLE matches RENAME COLUMN old_player2_id TO player2_id;
+ALTER TABLE matches RENAME COLUMN old_team1_player1_id TO team1_player1_id;
+ALTER TABLE matches RENAME COLUMN old_team1_player2_id TO team1_player2_id;
+ALTER TABLE matches RENAME COLUMN old_team2_player1_id TO team2_player1_id;
+ALTER TABLE matches RENAME COLUMN old_team2_player2_id TO team2_player2_id;
+ALTER TABLE matches RENAME COLUMN old_winner_id TO winner_id;
+ALTER TABLE matches ALTER COLUMN league_id SET NOT NULL;
+
+-- league_seasons
+ALTER TABLE league_seasons ADD COLUMN old_league_id VARCHAR(50);
+UPDATE league_seasons SET old_league_id = pg_temp.league_name(league_id);
+ALTER TABLE league_seasons DROP COLUMN league_id;
+ALTER TABLE league_seasons RENAME COLUMN old_league_id TO league_id;
+ALTER TABLE league_seasons ALTER COLUMN league_id SET NOT NULL;
+
+-- season_members
+ALTER TABLE season_members ADD COLUMN old_player_id VARCHAR(50);
+UPDATE season_members SET old_player_id = pg_temp.player_name(player_id);
+ALTER TABLE season_members DROP COLUMN player_id;
+ALTER TABLE season_members RENAME COLUMN old_player_id TO player_id;
+ALTER TABLE season_members ALTER COLUMN player_id SET NOT NULL;
+
+-- league_invitations
+ALTER TABLE league_invitations
+    ADD COLUMN old_league_id VARCHAR(50),
+    ADD COLUMN old_invited_by VARCHAR(50),
+    ADD COLUMN invitee_name VARCHAR(100);
+UPDATE league_invitations SET
+    old_league_id = pg_temp.league_name(league_id),
+    old_invited_by = pg_temp.player_name(invited_by),
+    invitee_name = pg_temp.player_name(invitee_id);
+ALTER TABLE league_invitations DROP COLUMN league_id, DROP COLUMN invited_by, DROP COLUMN invitee_id;
+ALTER TABLE league_invitations RENAME COLUMN old_league_id TO league_id;
+ALTER TABLE league_invitations RENAME COLUMN old_invited_by TO invited_by;
+ALTER TABLE league_invitations ALTER COLUMN league_id SET NOT NULL, ALTER COLUMN invited_by SET NOT NULL;
+ALTER TABLE league_invitations ADD CHECK (invitee_name IS NOT NULL OR invitee_email IS NOT NULL);
+
+-- notifications
+ALTER TABLE notifications ADD COLUMN old_player_id VARCHAR(50), ADD COLUMN old_league_id VARCHAR(50);
+UPDATE notifications
+    SET old_player_id = pg_temp.player_name(player_id), old_league_id = pg_temp.league_name(league_id);
+ALTER TABLE notifications DROP COLUMN player_id, DROP COLUMN league_id;
+ALTER TABLE notifications RENAME COLUMN old_player_id TO player_id;
+ALTER TABLE notifications RENAME COLUMN old_league_id TO league_id;
+ALTER TABLE notifications ALTER COLUMN player_id SET NOT NULL;
+
+-- Key leagues by name again and restore the name-based foreign keys
+ALTER TABLE players ADD CONSTRAINT players_name_key UNIQUE (name);
+ALTER TABLE leagues DROP CONSTRAINT leagues_pkey;
+ALTER TABLE leagues DROP CONSTRAINT leagues_league_name_key;
+ALTER TABLE leagues ADD PRIMARY KEY (league_name);
+
+ALTER TABLE leagues ADD CONSTRAINT leagues_created_by_fkey
+    FOREIGN KEY (created_by) REFERENCES players(name) ON DELETE CASCADE;
+
+ALTER TABLE player_leagues
+    ALTER COLUMN player_id SET NOT NULL,
+    ALTER COLUMN league_id SET NOT NULL,
+    ADD PRIMARY KEY (player_id, league_id),
+    ADD CONSTRAINT player_leagues_player_id_fkey FOREIGN KEY (player_id) REFERENCES players(name) ON DELETE CASCADE,
+    ADD CONSTRAINT player_leagues_league_id_fkey FOREIGN KEY (league_id) REFERENCES leagues(league_name) ON DELETE CASCADE;
+
+ALTER TABLE league_seasons
+    ADD UNIQUE (league_id, season_name),
+    ADD CONSTRAINT fk_season_league FOREIGN KEY (league_id) REFERENCES leagues(league_name) ON DELETE CASCADE;
+
+ALTER TABLE season_members
+    ADD PRIMARY KEY (season_id, player_id),
+    ADD CONSTRAINT fk_season_player FOREIGN KEY (player_id) REFERENCES players(name) ON DELETE CASCADE;
+
+ALTER TABLE league_invitations
+    ADD CONSTRAINT fk_invitation_league FOREIGN KEY (league_id) REFERENCES leagues(league_name) ON DELETE CASCADE;
+
+ALTER TABLE notifications
+    ADD CONSTRAINT fk_notification_player FOREIGN KEY (player_id) REFERENCES players(name) ON DELETE CASCADE,
+    ADD CONSTRAINT fk_notification_league FOREIGN KEY (league_id) REFERENCES leagues(league_name) ON DELETE CASCADE;
+
+CREATE INDEX idx_notifications_player ON notifications(player_id);
@@ -0,0 +1,184 @@
+-- Your SQL goes here
+
+-- Players and leagues used to be referenced by name. Every reference now
+-- points at players.player_id / leagues.league_id with a real foreign key,
+-- so renaming a player or two players sharing a name no longer breaks links.
+
+-- Existing references mostly hold a name; some early match rows hold the
+-- numeric id as text. Resolve both.
+CREATE FUNCTION pg_temp.player_ref(ref TEXT) RETURNS INTEGER AS $$
+    SELECT COALESCE(
+        (SELECT player_id FROM players WHERE name = ref),
+        (SELECT player_id FROM players
+            WHERE player_id = CASE WHEN ref ~ '^[0-9]{1,9}$' THEN ref::INTEGER END)
+    )
+$$ LANGUAGE SQL STABLE;
+
+CREATE FUNCTION pg_temp.league_ref(ref TEXT) RETURNS INTEGER AS $$
+    SELECT COALESCE(
+        (SELECT league_id FROM leagues WHERE league_name = ref),
+        (SELECT league_id FROM leagues
+            WHERE league_id = CASE WHEN ref ~ '^[0-9]{1,9}$' THEN ref::INTEGER END)
+    )
+$$ LANGUAGE SQL STABLE;
+
+-- leagues.created_by
+ALTER TABLE leagues ADD COLUMN new_created_by INTEGER;
+UPDATE leagues SET new_created_by = pg_temp.player_ref(created_by);
+ALTER TABLE leagues DROP COLUMN created_by;
+ALTER TABLE leagues RENAME COLUMN new_created_by TO created_by;
+ALTER TABLE leagues ALTER COLUMN created_by SET NOT NULL;
+
+-- player_leagues
+ALTER TABLE player_leagues ADD COLUMN new_player_id INTEGER, ADD COLUMN new_league_id INTEGER;
+UPDATE player_leagues
+    SET new_player_id = pg_temp.player_ref(player_id), new_league_id = pg_temp.league_ref(league_id);
+DELETE FROM player_leagues WHERE new_player_id IS NULL OR new_league_id IS NULL;
+ALTER TABLE player_leagues DROP COLUMN player_id, DROP COLUMN league_id;
+ALTER TABLE player_leagues RENAME COLUMN new_player_id TO player_id;
+ALTER TABLE player_leagues RENAME COLUMN new_league_id TO league_id;
+
+-- league_join_requests had no constraints, so drop rows that point nowhere
+ALTER TABLE league_join_requests ADD COLUMN new_player_id INTEGER, ADD COLUMN new_league_id INTEGER;
+UPDATE league_join_requests
+    SET new_player_id = pg_temp.player_ref(player_id), new_league_id = pg_temp.league_ref(league_id);
+DELETE FROM league_join_requests WHERE new_player_id IS NULL OR new_league_id IS NULL;
+ALTER TABLE league_join_requests DROP COLUMN player_id, DROP COLUMN league_id;
+ALTER TABLE league_join_requests RENAME COLUMN new_player_id TO player_id;
+ALTER TABLE league_join_requests RENAME COLUMN new_league_id TO league_id;
+
+-- matches: unknown players become NULL, matches in unknown leagues are dropped
+ALTER TABLE matches
+    ADD COLUMN new_league_id INTEGER,
+    ADD COLUMN new_player1_id INTEGER,
+    ADD COLUMN new_player2_id INTEGER,
+    ADD COLUMN new_team1_player1_id INTEGER,
+    ADD COLUMN new_team1_player2_id INTEGER,
+    ADD COLUMN new_team2_player1_id INTEGER,
+    ADD COLUMN new_team2_player2_id INTEGER,
+    ADD COLUMN new_winner_id INTEGER;
+UPDATE matches SET
+    new_league_id = pg_temp.league_ref(league_id),
+    new_player1_id = pg_temp.player_ref(player1_id),
+    new_player2_id = pg_temp.player_ref(player2_id),
+    new_team1_player1_id = pg_temp.player_ref(team1_player1_id),
+    new_team1_player2_id = pg_temp.player_ref(team1_player2_id),
+    new_team2_player1_id = pg_temp.player_ref(team2_player1_id),
+    new_team2_player2_id = pg_temp.player_ref(team2_player2_id),
+    new_winner_id = pg_temp.player_ref(winner_id);
+DELETE FROM matches WHERE new_league_id IS NULL;
+ALTER TABLE matches
+    DROP COLUMN league_id,
+    DROP COLUMN player1_id,
+    DROP COLUMN player2_id,
+    DROP COLUMN team1_player1_id,
+    DROP COLUMN team1_player2_id,
+    DROP COLUMN team2_player1_id,
+    DROP COLUMN team2_player2_id,
+    DROP COLUMN winner_id;
+ALTER TABLE matches RENAME COLUMN new_league_id TO league_id;
+ALTER TABLE matches RENAME COLUMN new_player1_id TO player1_id;
+ALTER TABLE matches RENAME COLUMN new_player2_id TO player2_id;
+ALTER TABLE matches RENAME COLUMN new_team1_player1_id TO team1_player1_id;
+ALTER TABLE matches RENAME COLUMN new_team1_player2_id TO team1_player2_id;
+ALTER TABLE matches RENAME COLUMN new_team2_player1_id TO team2_player1_id;
+ALTER TABLE matches RENAME COLUMN new_team2_player2_id TO team2_player2_id;
+ALTER TABLE matches RENAME COLUMN new_winner_id TO winner_id;
+ALTER TABLE matches ALTER COLUMN league_id SET NOT NULL;
+
+-- league_seasons
+ALTER TABLE league_seasons ADD COLUMN new_league_id INTEGER;
+UPDATE league_seasons SET new_league_id = pg_temp.league_ref(league_id);
+ALTER TABLE league_seasons DROP COLUMN league_id;
+ALTER TABLE league_seasons RENAME COLUMN new_league_id TO league_id;
+ALTER TABLE league_seasons ALTER COLUMN league_id SET NOT NULL;
+
+-- season_members
+ALTER TABLE season_members ADD COLUMN new_player_id INTEGER;
+UPDATE season_members SET new_player_id = pg_temp.player_ref(player_id);
+ALTER TABLE season_members DROP COLUMN player_id;
+ALTER TABLE season_members RENAME COLUMN new_player_id TO player_id;
+ALTER TABLE season_members ALTER COLUMN player_id SET NOT NULL;
+
+-- league_invitations: the invitee becomes invitee_id
+ALTER TABLE league_invitations
+    ADD COLUMN new_league_id INTEGER,
+    ADD COLUMN new_invited_by INTEGER,
+    ADD COLUMN invitee_id INTEGER;
+UPDATE league_invitations SET
+    new_league_id = pg_temp.league_ref(league_id),
+    new_invited_by = pg_temp.player_ref(invited_by),
+    invitee_id = pg_temp.player_ref(invitee_name);
+DELETE FROM league_invitations
+    WHERE new_invited_by IS NULL OR (invitee_id IS NULL AND invitee_email IS NULL);
+ALTER TABLE league_invitations DROP COLUMN league_id, DROP COLUMN invited_by, DROP COLUMN invitee_name;
+ALTER TABLE league_invitations RENAME COLUMN new_league_id TO league_id;
+ALTER TABLE league_invitations RENAME COLUMN new_invited_by TO invited_by;
+ALTER TABLE league_invitations ALTER COLUMN league_id SET NOT NULL;
+ALTER TABLE league_invitations ALTER COLUMN invited_by SET NOT NULL;
+ALTER TABLE league_invitations
+    ADD CONSTRAINT league_invitations_invitee_check CHECK (invitee_id IS NOT NULL OR invitee_email IS NOT NULL);
+
+-- notifications
+ALTER TABLE notifications ADD COLUMN new_player_id INTEGER, ADD COLUMN new_league_id INTEGER;
+UPDATE notifications
+    SET new_player_id = pg_temp.player_ref(player_id), new_league_id = pg_temp.league_ref(league_id);
+ALTER TABLE notifications DROP COLUMN player_id, DROP COLUMN league_id;
+ALTER TABLE notifications RENAME COLUMN new_player_id TO player_id;
+ALTER TABLE notifications RENAME COLUMN new_league_id TO league_id;
+ALTER TABLE notifications ALTER COLUMN player_id SET NOT NULL;
+
+-- leagues are keyed by league_id now; names stay unique. Player names no
+-- longer need to be unique. CASCADE also clears name-based foreign keys
+-- left on older tables.
+ALTER TABLE leagues DROP CONSTRAINT leagues_pkey CASCADE;
+ALTER TABLE leagues DROP CONSTRAINT IF EXISTS leagues_league_name_key CASCADE;
+ALTER TABLE leagues ADD PRIMARY KEY (league_id);
+ALTER TABLE leagues ADD CONSTRAINT leagues_league_name_key UNIQUE (league_name);
+ALTER TABLE players DROP CONSTRAINT IF EXISTS players_name_key CASCADE;
+
+ALTER TABLE leagues
+    ADD CONSTRAINT fk_creator FOREIGN KEY (created_by) REFERENCES players(player_id) ON DELETE CASCADE;
+
+ALTER TABLE player_leagues
+    ALTER COLUMN player_id SET NOT NULL,
+    ALTER COLUMN league_id SET NOT NULL,
+    ADD PRIMARY KEY (player_id, league_id),
+    ADD CONSTRAINT fk_player FOREIGN KEY (player_id) REFERENCES players(player_id) ON DELETE CASCADE,
+    ADD CONSTRAINT fk_league_join FOREIGN KEY (league_id) REFERENCES leagues(league_id) ON DELETE CASCADE;
+
+ALTER TABLE league_join_requests
+    ALTER COLUMN player_id SET NOT NULL,
+    ALTER COLUMN league_id SET NOT NULL,
+    ADD CONSTRAINT fk_join_request_player FOREIGN KEY (player_id) REFERENCES players(player_id) ON DELETE CASCADE,
+    ADD CONSTRAINT fk_join_request_league FOREIGN KEY (league_id) REFERENCES leagues(league_id) ON DELETE CASCADE;
+
+ALTER TABLE matches
+    ADD CONSTRAINT fk_match_league FOREIGN KEY (league_id) REFERENCES leagues(league_id) ON DELETE CASCADE,
+    ADD CONSTRAINT fk_match_player1 FOREIGN KEY (player1_id) REFERENCES players(player_id) ON DELETE SET NULL,
+    ADD CONSTRAINT fk_match_player2 FOREIGN KEY (player2_id) REFERENCES players(player_id) ON DELETE SET NULL,
+    ADD CONSTRAINT fk_match_team1_player1 FOREIGN KEY (team1_player1_id) REFERENCES players(player_id) ON DELETE SET NULL,
+    ADD CONSTRAINT fk_match_team1_player2 FOREIGN KEY (team1_player2_id) REFERENCES players(player_id) ON DELETE SET NULL,
+    ADD CONSTRAINT fk_match_team2_player1 FOREIGN KEY (team2_player1_id) REFERENCES players(player_id) ON DELETE SET NULL,
+    ADD CONSTRAINT fk_match_team2_player2 FOREIGN KEY (team2_player2_id) REFERENCES players(player_id) ON DELETE SET NULL,
+    ADD CONSTRAINT fk_match_winner FOREIGN KEY (winner_id) REFERENCES players(player_id) ON DELETE SET NULL;
+
+ALTER TABLE league_seasons
+    ADD UNIQUE (league_id, season_name),
+    ADD CONSTRAINT fk_season_league FOREIGN KEY (league_id) REFERENCES leagues(league_id) ON DELETE CASCADE;
+
+ALTER TABLE season_members
+    ADD PRIMARY KEY (season_id, player_id),
+    ADD CONSTRAINT fk_season_player FOREIGN KEY (player_id) REFERENCES players(player_id) ON DELETE CASCADE;
+
+ALTER TABLE league_invitations
+    ADD CONSTRAINT fk_invitation_league FOREIGN KEY (league_id) REFERENCES leagues(league_id) ON DELETE CASCADE,
+    ADD CONSTRAINT fk_invitation_sender FOREIGN KEY (invited_by) REFERENCES players(player_id) ON DELETE CASCADE,
+    ADD CONSTRAINT fk_invitation_invitee FOREIGN KEY (invitee_id) REFERENCES players(player_id) ON DELETE CASCADE;
+
+ALTER TABLE notifications
+    ADD CONSTRAINT fk_notification_player FOREIGN KEY (player_id) REFERENCES players(player_id) ON DELETE CASCADE,
+    ADD CONSTRAINT fk_notification_league FOREIGN KEY (league_id) REFERENCES leagues(league_id) ON DELETE CASCADE;
+
+CREATE INDEX idx_notifications_player ON notifications(player_id);
+CREATE INDEX idx_matches_league ON matches(league_id);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS idx_matches_datetime;
+
+ALTER TABLE matches
+    ALTER COLUMN datetime TYPE TEXT USING to_char(datetime AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS');
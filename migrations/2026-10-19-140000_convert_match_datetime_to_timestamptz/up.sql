@@ -0,0 +1,20 @@
+-- Your SQL goes here
+
+-- Match times were stored as free-form text. Strings with an explicit offset keep it,
+-- strings without one are read as UTC, and anything unparseable falls back to the
+-- time the match was created so no rows are lost.
+CREATE FUNCTION pg_temp.parse_match_datetime(raw TEXT, fallback TIMESTAMP) RETURNS TIMESTAMPTZ AS $$
+BEGIN
+    IF raw ~ '(Z|z|[+-][0-9]{2}(:?[0-9]{2})?)$' THEN
+        RETURN raw::timestamptz;
+    END IF;
+    RETURN raw::timestamp AT TIME ZONE 'UTC';
+EXCEPTION WHEN others THEN
+    RETURN fallback AT TIME ZONE 'UTC';
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE matches
+    ALTER COLUMN datetime TYPE TIMESTAMPTZ USING pg_temp.parse_match_datetime(datetime, created_at);
+
+CREATE INDEX idx_matches_datetime ON matches(datetime);
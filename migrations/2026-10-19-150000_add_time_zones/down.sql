@@ -0,0 +1,25 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE players DROP COLUMN time_zone;
+ALTER TABLE leagues DROP COLUMN time_zone;
+
+ALTER TABLE notifications ALTER COLUMN created_at TYPE TIMESTAMP;
+ALTER TABLE league_invitations
+    ALTER COLUMN expires_at TYPE TIMESTAMP,
+    ALTER COLUMN created_at TYPE TIMESTAMP,
+    ALTER COLUMN responded_at TYPE TIMESTAMP;
+ALTER TABLE season_members ALTER COLUMN joined_at TYPE TIMESTAMP;
+ALTER TABLE league_divisions ALTER COLUMN created_at TYPE TIMESTAMP;
+ALTER TABLE league_seasons
+    ALTER COLUMN created_at TYPE TIMESTAMP,
+    ALTER COLUMN closed_at TYPE TIMESTAMP;
+ALTER TABLE league_join_requests ALTER COLUMN created_at TYPE TIMESTAMP;
+ALTER TABLE player_leagues ALTER COLUMN joined_at TYPE TIMESTAMP;
+ALTER TABLE leagues ALTER COLUMN created_at TYPE TIMESTAMP;
+ALTER TABLE appointments
+    ALTER COLUMN start_time TYPE TIMESTAMP,
+    ALTER COLUMN end_time TYPE TIMESTAMP,
+    ALTER COLUMN created_at TYPE TIMESTAMP,
+    ALTER COLUMN updated_at TYPE TIMESTAMP;
+ALTER TABLE matches ALTER COLUMN created_at TYPE TIMESTAMP;
+ALTER TABLE players ALTER COLUMN created_at TYPE TIMESTAMP;
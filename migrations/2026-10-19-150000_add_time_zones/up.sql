@@ -0,0 +1,29 @@
+-- Your SQL goes here
+
+-- Timestamps were written as the server's local wall-clock time. Convert them to
+-- TIMESTAMPTZ, reading the existing values in the session time zone, so every
+-- instant is stored in UTC from now on.
+ALTER TABLE players ALTER COLUMN created_at TYPE TIMESTAMPTZ;
+ALTER TABLE matches ALTER COLUMN created_at TYPE TIMESTAMPTZ;
+ALTER TABLE appointments
+    ALTER COLUMN start_time TYPE TIMESTAMPTZ,
+    ALTER COLUMN end_time TYPE TIMESTAMPTZ,
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ,
+    ALTER COLUMN updated_at TYPE TIMESTAMPTZ;
+ALTER TABLE leagues ALTER COLUMN created_at TYPE TIMESTAMPTZ;
+ALTER TABLE player_leagues ALTER COLUMN joined_at TYPE TIMESTAMPTZ;
+ALTER TABLE league_join_requests ALTER COLUMN created_at TYPE TIMESTAMPTZ;
+ALTER TABLE league_seasons
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ,
+    ALTER COLUMN closed_at TYPE TIMESTAMPTZ;
+ALTER TABLE league_divisions ALTER COLUMN created_at TYPE TIMESTAMPTZ;
+ALTER TABLE season_members ALTER COLUMN joined_at TYPE TIMESTAMPTZ;
+ALTER TABLE league_invitations
+    ALTER COLUMN expires_at TYPE TIMESTAMPTZ,
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ,
+    ALTER COLUMN responded_at TYPE TIMESTAMPTZ;
+ALTER TABLE notifications ALTER COLUMN created_at TYPE TIMESTAMPTZ;
+
+-- IANA time zone names (e.g. 'Europe/Berlin') used to show match times locally
+ALTER TABLE leagues ADD COLUMN time_zone VARCHAR(64) NOT NULL DEFAULT 'UTC';
+ALTER TABLE players ADD COLUMN time_zone VARCHAR(64) NOT NULL DEFAULT 'UTC';
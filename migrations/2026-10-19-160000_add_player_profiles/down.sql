@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE email_changes;
+ALTER TABLE players DROP COLUMN avatar_url;
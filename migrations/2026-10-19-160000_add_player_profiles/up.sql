@@ -0,0 +1,17 @@
+-- Your SQL goes here
+
+ALTER TABLE players ADD COLUMN avatar_url VARCHAR(500);
+
+-- Requested email changes; the new address only replaces the old one once
+-- the player confirms it with the token mailed to the new address
+CREATE TABLE email_changes (
+    change_id SERIAL PRIMARY KEY,
+    player_id INTEGER NOT NULL REFERENCES players(player_id) ON DELETE CASCADE,
+    new_email VARCHAR(100) NOT NULL,
+    token VARCHAR(64) NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    confirmed_at TIMESTAMPTZ
+);
+
+CREATE INDEX email_changes_player_id_idx ON email_changes (player_id);
@@ -0,0 +1,26 @@
+-- Your SQL goes here
+
+-- Bearer tokens handed out at login. Only a SHA-256 hash of each token is
+-- kept, so the table cannot be used to sign in as anyone
+CREATE TABLE sessions (
+    session_id SERIAL PRIMARY KEY,
+    player_id INTEGER NOT NULL REFERENCES players(player_id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX sessions_player_id_idx ON sessions (player_id);
+
+-- Single-use codes mailed by the forgot-password flow, hashed like session tokens
+CREATE TABLE password_resets (
+    reset_id SERIAL PRIMARY KEY,
+    player_id INTEGER NOT NULL REFERENCES players(player_id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX password_resets_player_id_idx ON password_resets (player_id);
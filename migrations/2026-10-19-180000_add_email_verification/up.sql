@@ -0,0 +1,8 @@
+-- Your SQL goes here
+
+-- When the player proved they own their email address. Existing players
+-- count as verified. There is no default: every insert says whether the
+-- address is verified, and self-registration leaves it NULL until the
+-- signed link mailed to them is used
+ALTER TABLE players ADD COLUMN email_verified_at TIMESTAMPTZ;
+UPDATE players SET email_verified_at = CURRENT_TIMESTAMP;
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+
+-- Every password check made by login, kept both as an audit trail and to
+-- slow down guessing. Emails are stored trimmed and lowercased, whether or not
+-- they belong to a player; `cleared_at` is set when an admin unlocks the
+-- account or address, so earlier failures stop counting
+CREATE TABLE login_attempts (
+    attempt_id SERIAL PRIMARY KEY,
+    email VARCHAR(255) NOT NULL,
+    player_id INTEGER REFERENCES players(player_id) ON DELETE SET NULL,
+    ip_address VARCHAR(64),
+    outcome VARCHAR(20) NOT NULL
+        CHECK (outcome IN ('success', 'bad_password', 'unknown_email', 'locked')),
+    attempted_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    cleared_at TIMESTAMPTZ
+);
+
+CREATE INDEX login_attempts_email_idx ON login_attempts (email, attempted_at);
+CREATE INDEX login_attempts_ip_address_idx ON login_attempts (ip_address, attempted_at);
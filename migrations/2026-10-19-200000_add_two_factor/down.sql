@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+
+DELETE FROM login_attempts WHERE outcome IN ('code_required', 'bad_code');
+ALTER TABLE login_attempts DROP CONSTRAINT login_attempts_outcome_check;
+ALTER TABLE login_attempts ADD CONSTRAINT login_attempts_outcome_check
+    CHECK (outcome IN ('success', 'bad_password', 'unknown_email', 'locked'));
+
+ALTER TABLE leagues DROP COLUMN require_admin_two_factor;
+DROP TABLE recovery_codes;
+DROP TABLE totp_secrets;
@@ -0,0 +1,31 @@
+-- Your SQL goes here
+
+-- TOTP secrets, base32 encoded as authenticator apps expect. A secret without
+-- `enabled_at` belongs to an enrolment that has not been confirmed with a code
+-- yet. `last_used_step` is the time step of the last accepted code, so a code
+-- cannot be used twice
+CREATE TABLE totp_secrets (
+    player_id INTEGER PRIMARY KEY REFERENCES players(player_id) ON DELETE CASCADE,
+    secret VARCHAR(64) NOT NULL,
+    enabled_at TIMESTAMPTZ,
+    last_used_step BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Single-use codes for signing in without the authenticator, hashed like
+-- session tokens
+CREATE TABLE recovery_codes (
+    code_id SERIAL PRIMARY KEY,
+    player_id INTEGER NOT NULL REFERENCES players(player_id) ON DELETE CASCADE,
+    code_hash VARCHAR(64) NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX recovery_codes_player_id_idx ON recovery_codes (player_id);
+
+-- Leagues can insist that their admins sign in with a second factor
+ALTER TABLE leagues ADD COLUMN require_admin_two_factor BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE login_attempts DROP CONSTRAINT login_attempts_outcome_check;
+ALTER TABLE login_attempts ADD CONSTRAINT login_attempts_outcome_check
+    CHECK (outcome IN ('success', 'bad_password', 'unknown_email', 'locked', 'code_required', 'bad_code'));
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+
+-- Accounts at OpenID Connect providers that players sign in with, keyed by
+-- the provider's issuer URL and its id for the player (`sub`)
+CREATE TABLE player_identities (
+    identity_id SERIAL PRIMARY KEY,
+    player_id INTEGER NOT NULL REFERENCES players(player_id) ON DELETE CASCADE,
+    issuer VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    email VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_login_at TIMESTAMPTZ,
+    UNIQUE (issuer, subject)
+);
+
+CREATE INDEX player_identities_player_id_idx ON player_identities (player_id);
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+
+-- What a session was opened from, so players can tell their sessions apart,
+-- and roughly when it was last used
+ALTER TABLE sessions
+    ADD COLUMN user_agent VARCHAR(255),
+    ADD COLUMN ip_address VARCHAR(64),
+    ADD COLUMN last_seen_at TIMESTAMPTZ;
+
+UPDATE sessions SET last_seen_at = created_at;
@@ -0,0 +1,10 @@
+DELETE FROM login_attempts WHERE outcome = 'deactivated';
+ALTER TABLE login_attempts DROP CONSTRAINT login_attempts_outcome_check;
+ALTER TABLE login_attempts ADD CONSTRAINT login_attempts_outcome_check
+    CHECK (outcome IN ('success', 'bad_password', 'unknown_email', 'locked', 'code_required', 'bad_code'));
+
+ALTER TABLE players DROP COLUMN deactivated_at;
+
+ALTER TABLE players DROP CONSTRAINT players_role_check;
+ALTER TABLE players ALTER COLUMN role DROP NOT NULL;
+ALTER TABLE players ALTER COLUMN role DROP DEFAULT;
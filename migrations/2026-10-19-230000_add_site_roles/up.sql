@@ -0,0 +1,12 @@
+-- Roles used to be chosen by players themselves at registration, so none of
+-- them can be trusted; site admins are granted again with `admin set-role`.
+UPDATE players SET role = 'player';
+ALTER TABLE players ALTER COLUMN role SET DEFAULT 'player';
+ALTER TABLE players ALTER COLUMN role SET NOT NULL;
+ALTER TABLE players ADD CONSTRAINT players_role_check CHECK (role IN ('player', 'site_admin'));
+
+ALTER TABLE players ADD COLUMN deactivated_at TIMESTAMPTZ;
+
+ALTER TABLE login_attempts DROP CONSTRAINT login_attempts_outcome_check;
+ALTER TABLE login_attempts ADD CONSTRAINT login_attempts_outcome_check
+    CHECK (outcome IN ('success', 'bad_password', 'unknown_email', 'locked', 'code_required', 'bad_code', 'deactivated'));
@@ -0,0 +1,3 @@
+-- Deleted players keep their row, anonymized, so matches and standings still
+-- refer to them.
+ALTER TABLE players ADD COLUMN deleted_at TIMESTAMPTZ;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE sessions
+    DROP COLUMN two_factor_verified_at;
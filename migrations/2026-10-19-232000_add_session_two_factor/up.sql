@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+-- When the session's player last gave a two-factor code in it, so leagues
+-- that require two-factor authentication of admins can insist on it
+ALTER TABLE sessions
+    ADD COLUMN two_factor_verified_at TIMESTAMPTZ;
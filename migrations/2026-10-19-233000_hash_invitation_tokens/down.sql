@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+-- The hashes cannot be turned back into tokens, so open invitations have to
+-- be sent again
+ALTER TABLE league_invitations RENAME COLUMN token_hash TO token;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+
+-- Invitation tokens are stored hashed, like session and reset tokens, so a
+-- copy of the database cannot be used to accept invitations
+ALTER TABLE league_invitations RENAME COLUMN token TO token_hash;
+
+UPDATE league_invitations SET token_hash = encode(sha256(convert_to(token_hash, 'UTF8')), 'hex');
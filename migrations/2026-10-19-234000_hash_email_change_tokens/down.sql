@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+-- The hashes cannot be turned back into codes, so open email changes have to
+-- be requested again
+ALTER TABLE email_changes RENAME COLUMN token_hash TO token;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+
+-- Email change codes are stored hashed, like invitation and session tokens,
+-- so a copy of the database cannot be used to take over an address
+ALTER TABLE email_changes RENAME COLUMN token TO token_hash;
+
+UPDATE email_changes SET token_hash = encode(sha256(convert_to(token_hash, 'UTF8')), 'hex');
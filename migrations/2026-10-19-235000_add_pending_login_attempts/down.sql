@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+DELETE FROM login_attempts WHERE outcome = 'pending';
+ALTER TABLE login_attempts DROP CONSTRAINT login_attempts_outcome_check;
+ALTER TABLE login_attempts ADD CONSTRAINT login_attempts_outcome_check
+    CHECK (outcome IN ('success', 'bad_password', 'unknown_email', 'locked', 'code_required', 'bad_code', 'deactivated'));
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+
+-- Attempts are recorded as pending before the password is checked, and count
+-- as failures until their outcome is known
+ALTER TABLE login_attempts DROP CONSTRAINT login_attempts_outcome_check;
+ALTER TABLE login_attempts ADD CONSTRAINT login_attempts_outcome_check
+    CHECK (outcome IN ('pending', 'success', 'bad_password', 'unknown_email', 'locked', 'code_required', 'bad_code', 'deactivated'));
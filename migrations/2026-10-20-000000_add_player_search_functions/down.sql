@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP FUNCTION match_tier(TEXT, TEXT);
+DROP FUNCTION skill_rating(TEXT);
@@ -0,0 +1,26 @@
+-- Your SQL goes here
+
+-- Puts a skill level on the NTRP scale, like `search::skill_rating`: the
+-- named levels and ratings from 1.0 to 7.0, anything else is unrated
+CREATE FUNCTION skill_rating(level TEXT) RETURNS REAL AS $$
+    SELECT CASE
+        WHEN l = 'beginner' THEN 2.5
+        WHEN l = 'intermediate' THEN 3.5
+        WHEN l = 'advanced' THEN 4.5
+        WHEN l = 'expert' THEN 5.5
+        WHEN l ~ '^\+?([0-9]+\.?[0-9]*|\.[0-9]+)$' AND l::REAL BETWEEN 1.0 AND 7.0 THEN l::REAL
+    END
+    FROM (SELECT lower(trim(level)) AS l) AS normalized
+$$ LANGUAGE SQL IMMUTABLE;
+
+-- How well `value` matches `term`: 0 for an exact match, 1 for a prefix,
+-- 2 when a word starts with it and 3 otherwise, ignoring case
+CREATE FUNCTION match_tier(value TEXT, term TEXT) RETURNS INTEGER AS $$
+    SELECT CASE
+        WHEN v = t THEN 0
+        WHEN starts_with(v, t) THEN 1
+        WHEN EXISTS (SELECT 1 FROM regexp_split_to_table(v, '[^[:alnum:]]') AS word WHERE starts_with(word, t)) THEN 2
+        ELSE 3
+    END
+    FROM (SELECT lower(value) AS v, lower(trim(term)) AS t) AS normalized
+$$ LANGUAGE SQL IMMUTABLE;
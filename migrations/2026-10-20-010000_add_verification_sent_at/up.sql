@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+-- When a verification code was last mailed, so codes cannot be requested
+-- over and over for the same address
+ALTER TABLE players ADD COLUMN verification_sent_at TIMESTAMPTZ;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+-- Renamed players keep their new names
+DROP INDEX players_lower_email_key;
+DROP INDEX players_lower_name_key;
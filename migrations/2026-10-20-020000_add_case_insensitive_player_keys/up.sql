@@ -0,0 +1,17 @@
+-- Your SQL goes here
+
+-- Player names and email addresses are unique ignoring case. The
+-- application checks this before writing, and these indexes keep it true
+-- when two registrations or updates run at the same time.
+
+-- Names could repeat while they were not unique; every player after the
+-- first with a name gets their id appended
+UPDATE players SET name = name || ' (' || player_id || ')'
+WHERE player_id NOT IN (
+    SELECT DISTINCT ON (lower(name)) player_id FROM players ORDER BY lower(name), player_id
+);
+CREATE UNIQUE INDEX players_lower_name_key ON players (lower(name));
+
+-- Addresses that differ only in case likely belong to one person, so they are
+-- not renamed; creating the index fails and names them until they are merged
+CREATE UNIQUE INDEX players_lower_email_key ON players (lower(email));
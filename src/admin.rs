@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use bcrypt::{hash, DEFAULT_COST};
+use chrono::Utc;
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::api::leagues::{
+    append_note, has_room, insert_league, is_league_member, lock_league, validate_departure_policy, validate_max_members,
+};
+use crate::api::login_attempts::clear_failures;
+use crate::api::profiles::find_player_by_email;
+use crate::api::sessions::revoke_sessions;
+use crate::api::site_admin;
+use crate::calendar::parse_time_zone;
+use crate::db::models::{League, Match, NewLeague, Role};
+use crate::errors::AppError;
+use crate::personal_data::{delete_player, DeletionSummary};
+use crate::standings::{compute_doubles_standings, compute_standings, Standing};
+
+/// Creates a league on behalf of an existing player, who becomes its admin.
+pub fn create_league(conn: &mut PgConnection, new_league: NewLeague) -> Result<League, AppError> {
+    use crate::db::schema::players;
+
+    validate_departure_policy(&new_league.departure_policy)?;
+    validate_max_members(new_league.max_members)?;
+    parse_time_zone(&new_league.time_zone)?;
+
+    let creator_exists = players::table
+        .find(new_league.created_by)
+        .count()
+        .get_result::<i64>(conn)?;
+    if creator_exists == 0 {
+        return Err(AppError::BadRequest(format!("Player {} does not exist", new_league.created_by)));
+    }
+
+    conn.transaction(|conn| insert_league(conn, &new_league))
+        .map_err(AppError::from)
+}
+
+/// What happened to each line of a member import.
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub added: Vec<i32>,
+    pub already_members: Vec<i32>,
+    /// Lines that could not be imported, with the reason.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Adds players to a league as regular members. Each entry is a player id or
+/// an email address; blank entries and `#` comments are skipped. Privacy and
+/// join requests are bypassed, but the league's member limit is not.
+pub fn import_members(conn: &mut PgConnection, league: i32, entries: &[String]) -> Result<ImportSummary, AppError> {
+    use crate::db::schema::{player_leagues, players};
+
+    conn.transaction(|conn| {
+        let league_row = lock_league(conn, league)?;
+        let mut summary = ImportSummary::default();
+
+        for entry in entries.iter().map(|entry| entry.trim()) {
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+
+            let found = match entry.parse::<i32>() {
+                Ok(id) => players::table.find(id).select(players::player_id).first::<i32>(conn),
+                Err(_) => players::table
+                    .filter(players::email.eq(entry))
+                    .select(players::player_id)
+                    .first::<i32>(conn),
+            }
+            .optional()?;
+
+            let Some(member) = found else {
+                summary.failed.push((entry.to_string(), "no such player".into()));
+                continue;
+            };
+            if is_league_member(conn, league, member)? {
+                summary.already_members.push(member);
+                continue;
+            }
+            if !has_room(conn, &league_row)? {
+                summary.failed.push((entry.to_string(), "league is full".into()));
+                continue;
+            }
+
+            diesel::insert_into(player_leagues::table)
+                .values((
+                    player_leagues::player_id.eq(member),
+                    player_leagues::league_id.eq(league),
+                    player_leagues::role.eq("player"),
+                    player_leagues::joined_at.eq(Utc::now()),
+                ))
+                .execute(conn)?;
+            summary.added.push(member);
+        }
+
+        Ok(summary)
+    })
+}
+
+/// Replaces a player's password and signs out their sessions. Returns the
+/// player's id.
+pub fn reset_password(conn: &mut PgConnection, email: &str, new_password: &str) -> Result<i32, AppError> {
+    use crate::db::schema::players;
+
+    if new_password.is_empty() {
+        return Err(AppError::BadRequest("Password must not be empty".into()));
+    }
+    let hashed_password = hash(new_password, DEFAULT_COST).map_err(|_| AppError::InternalError)?;
+
+    let player = diesel::update(players::table.filter(players::email.eq(email)))
+        .set(players::password.eq(hashed_password))
+        .returning(players::player_id)
+        .get_result::<i32>(conn)
+        .optional()?
+        .ok_or(AppError::NotFound)?;
+    revoke_sessions(conn, player, None)?;
+    Ok(player)
+}
+
+/// Gives the player with an email address a site role, such as the first
+/// site admin. Returns the player's id.
+pub fn set_role(conn: &mut PgConnection, email: &str, role: Role) -> Result<i32, AppError> {
+    let player = find_player_by_email(conn, email)?.ok_or(AppError::NotFound)?;
+    Ok(site_admin::set_role(conn, player.player_id, role)?.player_id)
+}
+
+/// Deletes the account of the player with an email address, for members who
+/// ask for it without signing in. Returns the player's id.
+pub fn delete_account(conn: &mut PgConnection, email: &str) -> Result<(i32, DeletionSummary), AppError> {
+    let player = find_player_by_email(conn, email)?.ok_or(AppError::NotFound)?;
+    let summary = conn.transaction(|conn| delete_player(conn, player.player_id))?;
+    Ok((player.player_id, summary))
+}
+
+/// Lifts the login delay or lockout on an email address, a client address,
+/// or both together. Returns how many failed attempts were cleared.
+pub fn unlock_login(conn: &mut PgConnection, email: Option<&str>, ip_address: Option<&str>) -> Result<usize, AppError> {
+    if email.is_none() && ip_address.is_none() {
+        return Err(AppError::BadRequest("Give an email address, a client address or both".into()));
+    }
+    clear_failures(conn, email, ip_address)
+}
+
+/// Marks a match as completed with the given winner, whatever state it is in.
+/// The winner must be one of the match's players; for doubles any player of
+/// the winning team will do.
+pub fn force_complete_match(
+    conn: &mut PgConnection,
+    match_id: i32,
+    winner: i32,
+    score: Option<String>,
+) -> Result<Match, AppError> {
+    use crate::db::schema::matches;
+
+    let found = matches::table
+        .find(match_id)
+        .first::<Match>(conn)
+        .optional()?
+        .ok_or(AppError::NotFound)?;
+
+    let participants = [
+        found.player1_id,
+        found.player2_id,
+        found.team1_player1_id,
+        found.team1_player2_id,
+        found.team2_player1_id,
+        found.team2_player2_id,
+    ];
+    if !participants.contains(&Some(winner)) {
+        return Err(AppError::BadRequest(format!("Player {} did not play in match {}", winner, match_id)));
+    }
+
+    let notes = append_note(found.notes, format!("Completed by an administrator (was {})", found.status));
+    diesel::update(matches::table.find(match_id))
+        .set((
+            matches::status.eq("Completed"),
+            matches::winner_id.eq(Some(winner)),
+            matches::score.eq(score.or(found.score)),
+            matches::notes.eq(notes),
+        ))
+        .get_result::<Match>(conn)
+        .map_err(AppError::from)
+}
+
+/// A member's rankings after a recompute; `None` means no completed matches.
+#[derive(Debug, Serialize)]
+pub struct RankingUpdate {
+    pub player_id: i32,
+    pub singles_ranking: Option<i32>,
+    pub doubles_ranking: Option<i32>,
+}
+
+/// Ranks the players who have played at least once, keeping the table order.
+fn played_ranks(standings: &[Standing]) -> HashMap<i32, i32> {
+    standings
+        .iter()
+        .filter(|standing| standing.played > 0)
+        .zip(1..)
+        .map(|(standing, rank)| (standing.player_id, rank))
+        .collect()
+}
+
+/// Rebuilds the singles and doubles rankings of every member of a league from
+/// its completed matches, using the same ordering as season standings.
+pub fn recompute_rankings(conn: &mut PgConnection, league: i32) -> Result<Vec<RankingUpdate>, AppError> {
+    use crate::db::schema::{leagues, matches, player_leagues};
+
+    let exists = leagues::table.find(league).count().get_result::<i64>(conn)?;
+    if exists == 0 {
+        return Err(AppError::NotFound);
+    }
+
+    conn.transaction(|conn| {
+        let members = player_leagues::table
+            .filter(player_leagues::league_id.eq(league))
+            .select(player_leagues::player_id)
+            .order(player_leagues::player_id.asc())
+            .load::<i32>(conn)?;
+
+        let completed = matches::table
+            .filter(matches::league_id.eq(league))
+            .filter(matches::status.eq("Completed"))
+            .load::<Match>(conn)?;
+
+        let singles = played_ranks(&compute_standings(&members, &completed));
+        let doubles = played_ranks(&compute_doubles_standings(&members, &completed));
+
+        let mut updates = Vec::with_capacity(members.len());
+        for member in members {
+            let update = RankingUpdate {
+                player_id: member,
+                singles_ranking: singles.get(&member).copied(),
+                doubles_ranking: doubles.get(&member).copied(),
+            };
+            diesel::update(player_leagues::table)
+                .filter(player_leagues::league_id.eq(league))
+                .filter(player_leagues::player_id.eq(member))
+                .set((
+                    player_leagues::singles_ranking.eq(update.singles_ranking),
+                    player_leagues::doubles_ranking.eq(update.doubles_ranking),
+                ))
+                .execute(conn)?;
+            updates.push(update);
+        }
+
+        Ok(updates)
+    })
+}
@@ -0,0 +1,90 @@
+use actix_web::{web, HttpResponse, Responder};
+use diesel::prelude::*;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::api::sessions::CurrentPlayer;
+use crate::api::two_factor::{check_second_factor, two_factor_enabled};
+use crate::db::schema::players;
+use crate::db::DbPool;
+use crate::errors::AppError;
+use crate::personal_data::{delete_player, export_player, DeletionSummary, PlayerExport};
+
+/// Downloads everything stored about the signed-in player as JSON: their
+/// profile, league and season memberships, matches, appointments, join
+/// requests, invitations, notifications, sessions and linked identities.
+pub async fn export_account(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let player = current.player_id;
+    let export = web::block(move || -> Result<PlayerExport, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+        export_player(&mut conn, player)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error exporting player data: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"player-{}-data.json\"", player),
+        ))
+        .json(export))
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountInput {
+    pub password: String,
+    /// A code from the authenticator app or a recovery code, for players with
+    /// two-factor authentication on.
+    pub two_factor_code: Option<String>,
+}
+
+/// Deletes the signed-in player's account after checking their password,
+/// and their second factor if they have one. See `delete_player` for what
+/// is kept.
+pub async fn delete_account(
+    current: CurrentPlayer,
+    item: web::Json<DeleteAccountInput>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let item = item.into_inner();
+    let player = current.player_id;
+
+    let summary = web::block(move || -> Result<DeletionSummary, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let password_hash = players::table
+            .find(player)
+            .select(players::password)
+            .first::<String>(&mut conn)?;
+        if !bcrypt::verify(&item.password, &password_hash).unwrap_or(false) {
+            return Err(AppError::Forbidden("Wrong password".into()));
+        }
+        if two_factor_enabled(&mut conn, player)? {
+            let code = item.two_factor_code.as_deref().unwrap_or("");
+            if !check_second_factor(&mut conn, player, code)? {
+                return Err(AppError::Forbidden("Invalid two-factor code".into()));
+            }
+        }
+        conn.transaction(|conn| delete_player(conn, player))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error deleting account: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Your account has been deleted",
+        "success": true,
+        "left_leagues": summary.left_leagues,
+        "cancelled_matches": summary.cancelled_matches,
+        "forfeited_matches": summary.forfeited_matches,
+        "cancelled_appointments": summary.cancelled_appointments,
+    })))
+}
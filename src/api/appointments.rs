@@ -1,6 +1,9 @@
+use actix_web::middleware::from_fn;
 use actix_web::{web, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
 
+use crate::api::sessions;
+
 #[derive(Deserialize)]
 pub struct CreateAppointmentInput {
     pub requester_id: i32,
@@ -16,7 +19,7 @@ pub struct AppointmentResponse {
     pub status: String,
 }
 
-pub async fn create_appointment(item: web::Json<CreateAppointmentInput>) -> impl Responder {
+pub async fn create_appointment(_item: web::Json<CreateAppointmentInput>) -> impl Responder {
     // Insert your database logic here.
     let response = AppointmentResponse {
         appointment_id: 1,
@@ -31,14 +34,14 @@ pub struct UpdateAppointmentInput {
 }
 
 pub async fn update_appointment(
-    path: web::Path<i32>,
-    item: web::Json<UpdateAppointmentInput>,
+    _path: web::Path<i32>,
+    _item: web::Json<UpdateAppointmentInput>,
 ) -> impl Responder {
     // Update appointment logic goes here.
     HttpResponse::Ok().json("Appointment updated")
 }
 
-pub async fn cancel_appointment(path: web::Path<i32>) -> impl Responder {
+pub async fn cancel_appointment(_path: web::Path<i32>) -> impl Responder {
     // Logic to cancel the appointment goes here.
     HttpResponse::Ok().json("Appointment canceled")
 }
@@ -46,6 +49,7 @@ pub async fn cancel_appointment(path: web::Path<i32>) -> impl Responder {
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/appointments")
+            .wrap(from_fn(sessions::require_session))
             .route("", web::post().to(create_appointment))
             .route("/{appointment_id}", web::put().to(update_appointment))
             .route("/{appointment_id}", web::delete().to(cancel_appointment)),
@@ -0,0 +1,86 @@
+use actix_web::{web, HttpResponse, Responder};
+use diesel::prelude::*;
+use serde::Deserialize;
+
+use crate::api::leagues::ensure_league_admin;
+use crate::api::sessions::CurrentPlayer;
+use crate::api::site_admin::SiteAdmin;
+use crate::archive::{export_league, import_league, ImportReport, LeagueArchive};
+use crate::db::DbPool;
+use crate::errors::AppError;
+
+/// Largest archive accepted by the import endpoint.
+pub const ARCHIVE_SIZE_LIMIT: usize = 16 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct ArchiveImportQuery {
+    /// Name for the imported league when the archived one is taken.
+    pub league_name: Option<String>,
+}
+
+/// Downloads a league with its memberships, join requests, matches and
+/// appointments as a versioned JSON archive. Only league admins may export.
+pub async fn export_league_archive(
+    current: CurrentPlayer,
+    path: web::Path<i32>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let league = path.into_inner();
+
+    let archive = web::block(move || -> Result<LeagueArchive, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let archive = export_league(&mut conn, league)?;
+        ensure_league_admin(&mut conn, league, &current)?;
+        Ok(archive)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error exporting league archive: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"league-{}-archive.json\"", league),
+        ))
+        .json(archive))
+}
+
+/// Recreates a league from an archive under new ids. Nothing is written when
+/// the archive is invalid or its league name is already taken. Since an
+/// archive can add existing players to the league, only site admins may
+/// import.
+pub async fn import_league_archive(
+    _admin: SiteAdmin,
+    query: web::Query<ArchiveImportQuery>,
+    archive: web::Json<LeagueArchive>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let league_name = query.into_inner().league_name;
+    let archive = archive.into_inner();
+
+    let report = web::block(move || -> Result<ImportReport, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| import_league(conn, &archive, league_name))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error importing league archive: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Created().json(serde_json::json!({
+        "message": "League imported successfully",
+        "success": true,
+        "league_id": report.league_id,
+        "player_ids": report.player_ids,
+        "created_players": report.created_players,
+        "memberships": report.memberships,
+        "join_requests": report.join_requests,
+        "matches": report.matches,
+        "appointments": report.appointments
+    })))
+}
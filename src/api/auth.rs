@@ -1,8 +1,38 @@
-use actix_web::{web, HttpResponse, Responder};
+use std::sync::OnceLock;
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use bcrypt::DEFAULT_COST;
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
 use paperclip::actix::*;
 use serde::Deserialize;
 use serde_json::json;
-use crate::db::{DbPool, models::Player};
+use crate::api::login_attempts;
+use crate::api::profiles::{
+    ensure_email_free, ensure_name_free, find_player_by_email, validate_email, validate_name, validate_phone,
+    validate_skill_level,
+};
+use crate::api::sessions::{
+    hash_token, mark_two_factor_verified, new_token, revoke_sessions, start_session, ClientInfo, CurrentPlayer,
+};
+use crate::api::two_factor::{check_second_factor, two_factor_enabled};
+use crate::calendar::parse_time_zone;
+use crate::db::{DbPool, models::{NewPasswordReset, PasswordReset, Role}};
+use crate::db::schema::{password_resets, players};
+use crate::errors::AppError;
+use crate::mailer::{Email, Mailer};
+use crate::tokens::{SignedToken, TokenSigner};
+
+/// How long a mailed password reset code stays valid.
+pub const RESET_TOKEN_MINUTES: i64 = 60;
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+pub const MAX_PASSWORD_BYTES: usize = 72;
+/// How long the link mailed to verify an email address stays valid.
+pub const VERIFICATION_HOURS: i64 = 48;
+/// How long a player waits before another reset or verification code is
+/// mailed to them. Requests in between are answered as usual but send nothing.
+pub const MAIL_COOLDOWN_MINUTES: i64 = 5;
+const VERIFY_EMAIL: &str = "verify-email";
 
 
 #[derive(Deserialize, Apiv2Schema)]
@@ -11,86 +41,554 @@ pub struct RegisterInput {
     pub email: String,
     pub password: String,
     pub skill_level: Option<String>,
-    pub role: Option<String>,
     pub phone: Option<String>,
+    /// IANA time zone used to show match times; defaults to UTC.
+    pub time_zone: Option<String>,
 }
 
-#[api_v2_operation]
+/// Registers a player with an unverified email address and mails them a
+/// link to verify it. Until they do, they cannot join leagues or be put into
+/// matches. Invalid fields answer 400, and a name or email address that is
+/// already taken answers 409.
 pub async fn register(
     item: web::Json<RegisterInput>,
-    pool: web::Data<DbPool>
-) -> impl Responder {
-    let conn = &mut pool.get().expect("Failed to get DB connection");
-
-    use crate::db::schema::players::dsl::*;
-    use diesel::prelude::*;
-    use bcrypt::*;
-    use chrono::Local;
-
-    let hashed_password = hash(&item.password, DEFAULT_COST)
-        .expect("Failed to hash password");
-
-    match diesel::insert_into(players)
-        .values((
-            name.eq(&item.name),
-            email.eq(&item.email), 
-            password.eq(&hashed_password),
-            skill_level.eq(&item.skill_level),
-            role.eq(&item.role),
-            phone.eq(&item.phone),
-            created_at.eq(Local::now().naive_local())
-        ))
-        .execute(conn)
-    {
-        Ok(_) => HttpResponse::Created().json("User registered successfully"),
-        Err(error) => {
-            println!("Failed to register user");
-            println!("Error: {:?}", error);
-            HttpResponse::InternalServerError().json("Failed to register user")
-        }
+    pool: web::Data<DbPool>,
+    mailer: web::Data<dyn Mailer>,
+    signer: web::Data<TokenSigner>,
+) -> Result<impl Responder, AppError> {
+    let item = item.into_inner();
+    let name = item.name.trim().to_string();
+    let address = item.email.trim().to_string();
+    validate_name(&name)?;
+    validate_email(&address)?;
+    validate_password(&item.password)?;
+    let skill_level = item.skill_level.filter(|level| !level.trim().is_empty());
+    if let Some(level) = &skill_level {
+        validate_skill_level(level)?;
+    }
+    let phone = item.phone.filter(|phone| !phone.trim().is_empty());
+    if let Some(phone) = &phone {
+        validate_phone(phone)?;
     }
+    let zone = item.time_zone.unwrap_or_else(|| "UTC".to_string());
+    parse_time_zone(&zone)?;
+    let password_hash = hash_password(&item.password)?;
+
+    let new_player_id = web::block(move || -> Result<i32, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let new_player_id = conn.transaction(|conn| -> Result<i32, AppError> {
+            ensure_email_free(conn, &address)?;
+            ensure_name_free(conn, &name, None)?;
+
+            let new_player_id = diesel::insert_into(players::table)
+                .values((
+                    players::name.eq(&name),
+                    players::email.eq(&address),
+                    players::password.eq(&password_hash),
+                    players::skill_level.eq(&skill_level),
+                    players::role.eq(Role::Player.as_str()),
+                    players::phone.eq(&phone),
+                    players::created_at.eq(Utc::now()),
+                    players::time_zone.eq(&zone),
+                    players::email_verified_at.eq(None::<DateTime<Utc>>),
+                    players::verification_sent_at.eq(Utc::now()),
+                ))
+                .returning(players::player_id)
+                .get_result::<i32>(conn)?;
+            Ok(new_player_id)
+        })?;
+
+        // Sent once the account is stored; it exists either way and the
+        // player can ask for another code
+        if let Err(error) = send_verification(mailer.get_ref(), &signer, new_player_id, &address) {
+            eprintln!("Failed to send verification email: {:?}", error);
+        }
+        Ok(new_player_id)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error registering player: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "User registered successfully; check your email to verify your address",
+        "player_id": new_player_id,
+        "email_verified": false,
+    })))
 }
 
 #[derive(Deserialize, Apiv2Schema)]
 pub struct LoginInput {
     pub email: String,
     pub password: String,
+    /// A code from the authenticator app or a recovery code, for players with
+    /// two-factor authentication on.
+    pub two_factor_code: Option<String>,
 }
 
-#[api_v2_operation]
+/// Checks a password, and a second factor for players who have turned it on,
+/// and starts a session. Failed attempts are recorded per account and per
+/// client address; once there are too many, further attempts are answered
+/// with 429 until the delay has passed (see `login_attempts`).
 pub async fn login(
+    req: HttpRequest,
     item: web::Json<LoginInput>,
     pool: web::Data<DbPool>
-) -> impl Responder {
-    let conn = &mut pool.get().expect("Failed to get DB connection");
-
-    use crate::db::schema::players::dsl::*;
-    use diesel::prelude::*;
-    use bcrypt::*;
-
-    // Find user by email
-    let user_result = players
-        .filter(email.eq(&item.email))
-        .first::<Player>(conn);
-
-    match user_result {
-        Ok(user) => {
-            // Verify password
-            match verify(&item.password, &user.password) {
-                Ok(valid) => {
-                    if valid {
-                        let response = json!({
-                            "message": "Login successful",
-                            "user_id": user.name,
-                        });
-                        HttpResponse::Ok().json(response)
-                    } else {
-                        HttpResponse::Unauthorized().json("Invalid credentials")
-                    }
-                },
-                Err(_) => HttpResponse::InternalServerError().json("Error verifying password")
+) -> Result<impl Responder, AppError> {
+    let item = item.into_inner();
+    // No account has a longer address, and it could not be recorded as an attempt
+    if login_attempts::normalize_email(&item.email).chars().count() > login_attempts::MAX_EMAIL_LENGTH {
+        return Err(AppError::BadRequest("Email address is too long".into()));
+    }
+    let client = ClientInfo::from_request(&req);
+    let ip_address = client.ip_address.clone();
+
+    let response = web::block(move || -> Result<serde_json::Value, AppError> {
+        let conn = &mut pool.get().map_err(|_| AppError::InternalError)?;
+        let tracked_email = login_attempts::normalize_email(&item.email);
+        let ip_address = ip_address.as_deref();
+
+        let attempt = login_attempts::begin_attempt(conn, &tracked_email, ip_address)?;
+
+        let Some(user) = find_player_by_email(conn, &tracked_email)? else {
+            // Checking against a stand-in hash takes as long as a real check,
+            // so the response time does not tell which addresses exist
+            let _ = bcrypt::verify(&item.password, dummy_hash());
+            login_attempts::finish_attempt(conn, attempt, None, "unknown_email")?;
+            return Err(AppError::Unauthorized("Invalid credentials".into()));
+        };
+        if !bcrypt::verify(&item.password, &user.password).map_err(|_| AppError::InternalError)? {
+            login_attempts::finish_attempt(conn, attempt, Some(user.player_id), "bad_password")?;
+            return Err(AppError::Unauthorized("Invalid credentials".into()));
+        }
+        if user.deactivated_at.is_some() {
+            login_attempts::finish_attempt(conn, attempt, Some(user.player_id), "deactivated")?;
+            return Err(AppError::Forbidden("This account has been deactivated".into()));
+        }
+        let two_factor = two_factor_enabled(conn, user.player_id)?;
+        if two_factor {
+            let Some(code) = item.two_factor_code.as_deref().filter(|code| !code.trim().is_empty()) else {
+                login_attempts::finish_attempt(conn, attempt, Some(user.player_id), "code_required")?;
+                return Err(AppError::Unauthorized("Two-factor code required".into()));
+            };
+            if !check_second_factor(conn, user.player_id, code)? {
+                login_attempts::finish_attempt(conn, attempt, Some(user.player_id), "bad_code")?;
+                return Err(AppError::Unauthorized("Invalid two-factor code".into()));
+            }
+        }
+        login_attempts::finish_attempt(conn, attempt, Some(user.player_id), "success")?;
+
+        // Hashes made with an older, cheaper cost are upgraded while the password is at hand
+        if needs_rehash(&user.password) {
+            let rehashed = hash_password(&item.password).and_then(|rehashed| {
+                diesel::update(players::table.find(user.player_id))
+                    .set(players::password.eq(rehashed))
+                    .execute(conn)
+                    .map_err(AppError::from)
+            });
+            if let Err(error) = rehashed {
+                eprintln!("Failed to upgrade the password hash of player {}: {:?}", user.player_id, error);
             }
-        },
-        Err(_) => HttpResponse::Unauthorized().json("Invalid credentials")
+        }
+        let (token, session) = start_session(conn, user.player_id, &client)?;
+        if two_factor {
+            mark_two_factor_verified(conn, session.session_id)?;
+        }
+        Ok(json!({
+            "message": "Login successful",
+            "user_id": user.player_id,
+            "token": token,
+            "expires_at": session.expires_at,
+            "email_verified": user.email_verified_at.is_some(),
+        }))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error logging in: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// A hash of a random password, checked against when a login names an
+/// unknown address. Made on first use with the same cost as real hashes.
+fn dummy_hash() -> &'static str {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| {
+        bcrypt::hash(new_token(), DEFAULT_COST).expect("Failed to hash the stand-in password")
+    })
+}
+
+/// Whether a bcrypt hash was made with a lower cost than new hashes use.
+pub fn needs_rehash(password_hash: &str) -> bool {
+    password_hash
+        .split('$')
+        .nth(2)
+        .and_then(|cost| cost.parse::<u32>().ok())
+        .is_none_or(|cost| cost < DEFAULT_COST)
+}
+
+/// At least 8 characters with a letter and a digit or symbol. bcrypt only
+/// looks at the first 72 bytes, so longer passwords are refused rather than
+/// silently cut short.
+pub fn validate_password(password: &str) -> Result<(), AppError> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Passwords must be at least {} characters long",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+    if password.len() > MAX_PASSWORD_BYTES {
+        return Err(AppError::BadRequest(format!(
+            "Passwords must be at most {} bytes long",
+            MAX_PASSWORD_BYTES
+        )));
     }
+    if !password.chars().any(char::is_alphabetic) || password.chars().all(char::is_alphabetic) {
+        return Err(AppError::BadRequest(
+            "Passwords need at least one letter and one digit or symbol".into(),
+        ));
+    }
+    Ok(())
+}
+
+pub fn hash_password(password: &str) -> Result<String, AppError> {
+    bcrypt::hash(password, DEFAULT_COST).map_err(|_| AppError::InternalError)
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordInput {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+/// Changes the signed-in player's password. Their other sessions are signed
+/// out; the one making the change stays open.
+pub async fn change_password(
+    current: CurrentPlayer,
+    item: web::Json<ChangePasswordInput>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let item = item.into_inner();
+    validate_password(&item.new_password)?;
+
+    let signed_out = web::block(move || -> Result<usize, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let password_hash = players::table
+            .find(current.player_id)
+            .select(players::password)
+            .first::<String>(&mut conn)?;
+        if !bcrypt::verify(&item.current_password, &password_hash).unwrap_or(false) {
+            return Err(AppError::Forbidden("Wrong password".into()));
+        }
+        let new_hash = hash_password(&item.new_password)?;
+
+        conn.transaction(|conn| {
+            diesel::update(players::table.find(current.player_id))
+                .set(players::password.eq(new_hash))
+                .execute(conn)?;
+            revoke_sessions(conn, current.player_id, Some(current.session_id))
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error changing password: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Password changed",
+        "success": true,
+        "signed_out_sessions": signed_out
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordInput {
+    pub email: String,
+}
+
+/// Mails a single-use reset code to the address if it belongs to a player,
+/// at most once every `MAIL_COOLDOWN_MINUTES`. The answer is the same either
+/// way, also when sending fails, so it cannot be used to find out who has an
+/// account.
+pub async fn forgot_password(
+    item: web::Json<ForgotPasswordInput>,
+    pool: web::Data<DbPool>,
+    mailer: web::Data<dyn Mailer>,
+) -> Result<impl Responder, AppError> {
+    let address = item.into_inner().email.trim().to_string();
+
+    web::block(move || -> Result<(), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let Some(found) = find_player_by_email(&mut conn, &address)? else {
+            return Ok(());
+        };
+        let player = found.player_id;
+
+        let token = new_token();
+        let issued = conn.transaction(|conn| -> Result<bool, AppError> {
+            // Requests for the same player wait for each other, so parallel
+            // ones cannot all get past the cooldown
+            players::table
+                .find(player)
+                .select(players::player_id)
+                .for_update()
+                .first::<i32>(conn)?;
+            let now = Utc::now();
+            let recent = password_resets::table
+                .filter(password_resets::player_id.eq(player))
+                .filter(password_resets::created_at.gt(now - Duration::minutes(MAIL_COOLDOWN_MINUTES)))
+                .count()
+                .get_result::<i64>(conn)?;
+            if recent > 0 {
+                return Ok(false);
+            }
+
+            // Only the latest code works
+            diesel::delete(
+                password_resets::table
+                    .filter(password_resets::player_id.eq(player))
+                    .filter(password_resets::used_at.is_null()),
+            )
+            .execute(conn)?;
+
+            diesel::insert_into(password_resets::table)
+                .values(&NewPasswordReset {
+                    player_id: player,
+                    token_hash: hash_token(&token),
+                    expires_at: now + Duration::minutes(RESET_TOKEN_MINUTES),
+                    created_at: now,
+                })
+                .execute(conn)?;
+            Ok(true)
+        })?;
+        if !issued {
+            return Ok(());
+        }
+
+        let sent = mailer.send(Email {
+            to: found.email,
+            subject: "Reset your password".into(),
+            body: format!(
+                "Someone asked to reset the password of your tennis scheduler account. \
+                 To choose a new one, use this code within {} minutes: {}\n\n\
+                 If that was not you, ignore this email; your password has not changed.",
+                RESET_TOKEN_MINUTES, token
+            ),
+        });
+        if let Err(error) = sent {
+            eprintln!("Failed to send password reset email: {:?}", error);
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error requesting password reset: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Accepted().json(json!({
+        "message": "If that address is registered, a reset code is on its way",
+        "success": true
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordInput {
+    pub token: String,
+    pub new_password: String,
+}
+
+/// Sets a new password with a code from `forgot_password`. Every session of
+/// the player is signed out, since whoever held them may not be the owner.
+pub async fn reset_password(
+    item: web::Json<ResetPasswordInput>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let item = item.into_inner();
+    validate_password(&item.new_password)?;
+    let new_hash = hash_password(&item.new_password)?;
+
+    let player = web::block(move || -> Result<i32, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            let reset = password_resets::table
+                .filter(password_resets::token_hash.eq(hash_token(item.token.trim())))
+                .filter(password_resets::used_at.is_null())
+                .first::<PasswordReset>(conn)
+                .optional()?
+                .ok_or_else(|| AppError::BadRequest("Unknown or already used reset code".into()))?;
+            let now = Utc::now();
+            if reset.expires_at <= now {
+                return Err(AppError::BadRequest("The reset code has expired".into()));
+            }
+
+            diesel::update(players::table.find(reset.player_id))
+                .set(players::password.eq(new_hash))
+                .execute(conn)?;
+            diesel::update(password_resets::table.find(reset.reset_id))
+                .set(password_resets::used_at.eq(now))
+                .execute(conn)?;
+            revoke_sessions(conn, reset.player_id, None)?;
+            Ok(reset.player_id)
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error resetting password: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Password reset; sign in with the new password",
+        "success": true,
+        "player_id": player
+    })))
+}
+
+fn send_verification(mailer: &dyn Mailer, signer: &TokenSigner, player: i32, address: &str) -> Result<(), AppError> {
+    let token = signer.sign(VERIFY_EMAIL, player, address, Utc::now() + Duration::hours(VERIFICATION_HOURS));
+    mailer.send(Email {
+        to: address.to_string(),
+        subject: "Verify your email address".into(),
+        body: format!(
+            "Welcome to the tennis scheduler! To verify your email address, use this code \
+             within {} hours: {}\n\n\
+             If you did not sign up, ignore this email.",
+            VERIFICATION_HOURS, token
+        ),
+    })
+}
+
+/// Fails unless every given player has verified their email address.
+pub fn ensure_email_verified(conn: &mut PgConnection, player_ids: &[i32]) -> Result<(), AppError> {
+    let unverified = players::table
+        .filter(players::player_id.eq_any(player_ids))
+        .filter(players::email_verified_at.is_null())
+        .select(players::player_id)
+        .order(players::player_id)
+        .first::<i32>(conn)
+        .optional()?;
+    match unverified {
+        Some(player) => Err(AppError::Forbidden(format!(
+            "Player {} has not verified their email address yet",
+            player
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailInput {
+    pub token: String,
+}
+
+/// Marks a player's email address as verified with the code mailed to it.
+/// Codes only work for the address they were sent to.
+pub async fn verify_email(
+    item: web::Json<VerifyEmailInput>,
+    pool: web::Data<DbPool>,
+    signer: web::Data<TokenSigner>,
+) -> Result<impl Responder, AppError> {
+    let invalid = || AppError::BadRequest("Invalid or outdated verification code".into());
+    let token = SignedToken::parse(&item.token).ok_or_else(invalid)?;
+
+    let (player, already_verified) = web::block(move || -> Result<(i32, bool), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let (address, verified_at) = players::table
+            .find(token.player_id)
+            .select((players::email, players::email_verified_at))
+            .first::<(String, Option<DateTime<Utc>>)>(&mut conn)
+            .optional()?
+            .ok_or_else(invalid)?;
+        if !signer.verify(VERIFY_EMAIL, &token, &address) {
+            return Err(invalid());
+        }
+        if verified_at.is_some() {
+            return Ok((token.player_id, true));
+        }
+        if token.expires_at <= Utc::now() {
+            return Err(AppError::BadRequest("The verification code has expired; ask for a new one".into()));
+        }
+
+        diesel::update(players::table.find(token.player_id))
+            .set(players::email_verified_at.eq(Utc::now()))
+            .execute(&mut conn)?;
+        Ok((token.player_id, false))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error verifying email: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": if already_verified { "Email address was already verified" } else { "Email address verified" },
+        "success": true,
+        "player_id": player
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerificationInput {
+    pub email: String,
+}
+
+/// Mails a new verification code if the address belongs to a player who has
+/// not verified it yet, at most once every `MAIL_COOLDOWN_MINUTES`. The
+/// answer is the same either way, also when sending fails.
+pub async fn resend_verification(
+    item: web::Json<ResendVerificationInput>,
+    pool: web::Data<DbPool>,
+    mailer: web::Data<dyn Mailer>,
+    signer: web::Data<TokenSigner>,
+) -> Result<impl Responder, AppError> {
+    let address = item.into_inner().email.trim().to_string();
+
+    web::block(move || -> Result<(), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let Some(found) = find_player_by_email(&mut conn, &address)? else {
+            return Ok(());
+        };
+
+        // Claiming the send in the same statement keeps parallel requests
+        // from all getting past the cooldown
+        let now = Utc::now();
+        let player = diesel::update(players::table.find(found.player_id))
+            .filter(players::email_verified_at.is_null())
+            .filter(
+                players::verification_sent_at.is_null()
+                .or(players::verification_sent_at.lt(now - Duration::minutes(MAIL_COOLDOWN_MINUTES)))
+            )
+            .set(players::verification_sent_at.eq(now))
+            .returning(players::player_id)
+            .get_result::<i32>(&mut conn)
+            .optional()?;
+        if let Some(player) = player {
+            // Codes are tied to the address as stored
+            if let Err(error) = send_verification(mailer.get_ref(), &signer, player, &found.email) {
+                eprintln!("Failed to send verification email: {:?}", error);
+            }
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error resending verification email: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Accepted().json(json!({
+        "message": "If that address is waiting to be verified, a new code is on its way",
+        "success": true
+    })))
 }
@@ -0,0 +1,409 @@
+use actix_web::middleware::from_fn;
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use serde::Deserialize;
+
+use crate::api::auth::ensure_email_verified;
+use crate::api::leagues::{ensure_league_admin, has_room, lock_league};
+use crate::api::profiles::{find_player_by_email, find_player_by_name};
+use crate::api::sessions::{self, hash_token, new_token, CurrentPlayer};
+use crate::db::models::{LeagueInvitation, NewLeagueInvitation, Player};
+use crate::db::schema::{league_invitations, league_join_requests, leagues, player_leagues, players};
+use crate::db::DbPool;
+use crate::errors::AppError;
+use crate::mailer::{Email, Mailer};
+
+const DEFAULT_EXPIRY_DAYS: i64 = 7;
+const MAX_EXPIRY_DAYS: i64 = 30;
+
+#[derive(Deserialize)]
+pub struct CreateInvitationInput {
+    /// Id of a registered player to invite.
+    pub player_id: Option<i32>,
+    /// Name of a registered player to invite, ignoring case.
+    pub name: Option<String>,
+    /// Email to invite; the person does not need to be registered yet.
+    pub email: Option<String>,
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct InvitationQuery {
+    pub status: Option<String>,
+}
+
+fn is_member(conn: &mut PgConnection, league: i32, player: i32) -> Result<bool, AppError> {
+    let count = player_leagues::table
+        .filter(player_leagues::league_id.eq(league))
+        .filter(player_leagues::player_id.eq(player))
+        .count()
+        .get_result::<i64>(conn)
+        .map_err(|_| AppError::InternalError)?;
+    Ok(count > 0)
+}
+
+/// Looks an invitation up by the token that was mailed out.
+fn find_invitation(conn: &mut PgConnection, token: &str) -> Result<LeagueInvitation, AppError> {
+    league_invitations::table
+        .filter(league_invitations::token_hash.eq(hash_token(token)))
+        .first::<LeagueInvitation>(conn)
+        .optional()
+        .map_err(|_| AppError::InternalError)?
+        .ok_or(AppError::NotFound)
+}
+
+/// Loads a pending, unexpired invitation by its token.
+fn load_open_invitation(conn: &mut PgConnection, token: &str) -> Result<LeagueInvitation, AppError> {
+    let invitation = find_invitation(conn, token)?;
+
+    if invitation.status != "pending" {
+        return Err(AppError::BadRequest(format!("Invitation has already been {}", invitation.status)));
+    }
+    if invitation.expires_at < Utc::now() {
+        return Err(AppError::BadRequest("Invitation has expired".into()));
+    }
+    Ok(invitation)
+}
+
+/// Checks that the responding player is the one the invitation was addressed to,
+/// by id or, for email invitations, by their registered address.
+fn ensure_invitee(invitation: &LeagueInvitation, player: &Player) -> Result<(), AppError> {
+    let matches_id = invitation.invitee_id == Some(player.player_id);
+    let matches_email = invitation
+        .invitee_email
+        .as_deref()
+        .is_some_and(|email| email.eq_ignore_ascii_case(&player.email));
+
+    if matches_id || matches_email {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden("This invitation was sent to someone else".into()))
+    }
+}
+
+/// Invites a registered player, by id or name, or anyone by email. The
+/// token is mailed to the invitee and never handed back to the admin.
+pub async fn create_invitation(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+    mailer: web::Data<dyn Mailer>,
+    path: web::Path<i32>,
+    input: web::Json<CreateInvitationInput>,
+) -> Result<impl Responder, AppError> {
+    let league = path.into_inner();
+    let mut input = input.into_inner();
+    input.name = input.name.map(|name| name.trim().to_string()).filter(|name| !name.is_empty());
+    input.email = input.email.map(|email| email.trim().to_string()).filter(|email| !email.is_empty());
+
+    if input.player_id.is_none() && input.name.is_none() && input.email.is_none() {
+        return Err(AppError::BadRequest("One of player_id, name or email is required".into()));
+    }
+    let expires_in_days = input.expires_in_days.unwrap_or(DEFAULT_EXPIRY_DAYS);
+    if !(1..=MAX_EXPIRY_DAYS).contains(&expires_in_days) {
+        return Err(AppError::BadRequest(format!("expires_in_days must be between 1 and {}", MAX_EXPIRY_DAYS)));
+    }
+
+    let invitation = web::block(move || -> Result<LeagueInvitation, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            ensure_league_admin(conn, league, &current)?;
+
+            // Resolve the invitee to a registered player when we can
+            let invitee = match (input.player_id, &input.name, &input.email) {
+                (Some(invitee_id), _, _) => Some(
+                    players::table
+                        .find(invitee_id)
+                        .first::<Player>(conn)
+                        .optional()
+                        .map_err(|_| AppError::InternalError)?
+                        .ok_or_else(|| AppError::BadRequest(format!("Player {} does not exist", invitee_id)))?,
+                ),
+                (None, Some(name), _) => Some(
+                    find_player_by_name(conn, name)?
+                        .ok_or_else(|| AppError::BadRequest(format!("No player is called '{}'", name)))?,
+                ),
+                (None, None, Some(email)) => find_player_by_email(conn, email)?,
+                (None, None, None) => None,
+            };
+            let invitee_id = invitee.as_ref().map(|player| player.player_id);
+            let invitee_email = input.email.clone().or_else(|| invitee.as_ref().map(|player| player.email.clone()));
+
+            if let Some(player) = &invitee {
+                if is_member(conn, league, player.player_id)? {
+                    return Err(AppError::BadRequest(format!("{} is already a member of this league", player.name)));
+                }
+            }
+
+            let now = Utc::now();
+            let outstanding = league_invitations::table
+                .filter(league_invitations::league_id.eq(league))
+                .filter(league_invitations::status.eq("pending"))
+                .filter(league_invitations::expires_at.gt(now))
+                .filter(
+                    league_invitations::invitee_id.eq(invitee_id)
+                        .or(league_invitations::invitee_email.eq(&invitee_email))
+                )
+                .count()
+                .get_result::<i64>(conn)
+                .map_err(|_| AppError::InternalError)?;
+            if outstanding > 0 {
+                return Err(AppError::BadRequest("An invitation is already pending for this person".into()));
+            }
+
+            let token = new_token();
+            let invitation = diesel::insert_into(league_invitations::table)
+                .values(&NewLeagueInvitation {
+                    league_id: league,
+                    invited_by: current.player_id,
+                    invitee_id,
+                    invitee_email: invitee_email.clone(),
+                    token_hash: hash_token(&token),
+                    expires_at: now + Duration::days(expires_in_days),
+                    created_at: now,
+                })
+                .get_result::<LeagueInvitation>(conn)
+                .map_err(|_| AppError::InternalError)?;
+
+            let league_name = leagues::table
+                .find(league)
+                .select(leagues::league_name)
+                .first::<String>(conn)?;
+            let inviter = players::table
+                .find(current.player_id)
+                .select(players::name)
+                .first::<String>(conn)?;
+            mailer.send(Email {
+                to: invitee_email.ok_or(AppError::InternalError)?,
+                subject: format!("You are invited to join {}", league_name),
+                body: format!(
+                    "{} invited you to join the league {} on tennis scheduler. \
+                     To accept, sign in (or register with this address) and use this code before {}: {}\n\n\
+                     If you do not want to join, you can decline with the same code or ignore this email.",
+                    inviter,
+                    league_name,
+                    invitation.expires_at.format("%Y-%m-%d %H:%M UTC"),
+                    token
+                ),
+            })?;
+            Ok(invitation)
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error creating invitation: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Created().json(invitation))
+}
+
+/// Lists a league's invitations for its admins.
+pub async fn get_league_invitations(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    query: web::Query<InvitationQuery>,
+) -> Result<impl Responder, AppError> {
+    let league = path.into_inner();
+    let status = query.into_inner().status;
+
+    let invitations = web::block(move || -> Result<Vec<LeagueInvitation>, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        ensure_league_admin(&mut conn, league, &current)?;
+        let mut query = league_invitations::table
+            .filter(league_invitations::league_id.eq(league))
+            .order(league_invitations::created_at.desc())
+            .into_boxed();
+        if let Some(status) = status {
+            query = query.filter(league_invitations::status.eq(status));
+        }
+        query.load::<LeagueInvitation>(&mut conn).map_err(|_| AppError::InternalError)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error fetching invitations: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(invitations))
+}
+
+pub async fn revoke_invitation(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+    path: web::Path<(i32, i32)>,
+) -> Result<impl Responder, AppError> {
+    let (league, invitation_id) = path.into_inner();
+
+    web::block(move || -> Result<(), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        ensure_league_admin(&mut conn, league, &current)?;
+        let updated = diesel::update(league_invitations::table)
+            .filter(league_invitations::invitation_id.eq(invitation_id))
+            .filter(league_invitations::league_id.eq(league))
+            .filter(league_invitations::status.eq("pending"))
+            .set((
+                league_invitations::status.eq("revoked"),
+                league_invitations::responded_at.eq(Some(Utc::now())),
+            ))
+            .execute(&mut conn)
+            .map_err(|_| AppError::InternalError)?;
+
+        if updated == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error revoking invitation: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Invitation revoked successfully",
+        "success": true
+    })))
+}
+
+pub async fn get_invitation(
+    pool: web::Data<DbPool>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    let token = path.into_inner();
+
+    let invitation = web::block(move || -> Result<LeagueInvitation, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+        find_invitation(&mut conn, &token)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error fetching invitation: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(invitation))
+}
+
+/// Accepts an invitation for the signed-in player and adds them to the
+/// league.
+pub async fn accept_invitation(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    let token = path.into_inner();
+    let player_id = current.player_id;
+
+    let league = web::block(move || -> Result<i32, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            let invitation = load_open_invitation(conn, &token)?;
+            let player = players::table.find(player_id).first::<Player>(conn)?;
+            ensure_invitee(&invitation, &player)?;
+            ensure_email_verified(conn, &[player.player_id])?;
+
+            let now = Utc::now();
+            if !is_member(conn, invitation.league_id, player.player_id)? {
+                let league = lock_league(conn, invitation.league_id)?;
+                if !has_room(conn, &league)? {
+                    return Err(AppError::BadRequest("League is full".into()));
+                }
+
+                diesel::insert_into(player_leagues::table)
+                    .values((
+                        player_leagues::player_id.eq(player.player_id),
+                        player_leagues::league_id.eq(invitation.league_id),
+                        player_leagues::role.eq("player"),
+                        player_leagues::joined_at.eq(now),
+                    ))
+                    .execute(conn)
+                    .map_err(|_| AppError::InternalError)?;
+            }
+
+            diesel::update(league_invitations::table.find(invitation.invitation_id))
+                .set((
+                    league_invitations::status.eq("accepted"),
+                    league_invitations::invitee_id.eq(Some(player.player_id)),
+                    league_invitations::responded_at.eq(Some(now)),
+                ))
+                .execute(conn)
+                .map_err(|_| AppError::InternalError)?;
+
+            // Any join request they had open for this league is settled by the invitation
+            diesel::update(league_join_requests::table)
+                .filter(league_join_requests::league_id.eq(invitation.league_id))
+                .filter(league_join_requests::player_id.eq(player.player_id))
+                .filter(league_join_requests::status.eq("pending"))
+                .set((
+                    league_join_requests::status.eq("accepted"),
+                    league_join_requests::notes.eq(Some("Joined through an invitation")),
+                ))
+                .execute(conn)
+                .map_err(|_| AppError::InternalError)?;
+
+            Ok(invitation.league_id)
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error accepting invitation: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Invitation accepted successfully",
+        "success": true,
+        "league_id": league
+    })))
+}
+
+/// Declines an invitation. Holding the token is enough, so people who never
+/// registered can decline too.
+pub async fn decline_invitation(
+    pool: web::Data<DbPool>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    let token = path.into_inner();
+
+    web::block(move || -> Result<(), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let invitation = load_open_invitation(&mut conn, &token)?;
+        diesel::update(league_invitations::table.find(invitation.invitation_id))
+            .set((
+                league_invitations::status.eq("declined"),
+                league_invitations::responded_at.eq(Some(Utc::now())),
+            ))
+            .execute(&mut conn)
+            .map_err(|_| AppError::InternalError)?;
+        Ok(())
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error declining invitation: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Invitation declined successfully",
+        "success": true
+    })))
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    // Holding the token is enough to decline, so this comes before the
+    // scope that needs a session
+    cfg.route("/invitations/{token}/decline", web::post().to(decline_invitation));
+    cfg.service(
+        web::scope("/invitations")
+            .wrap(from_fn(sessions::require_session))
+            .route("/{token}", web::get().to(get_invitation))
+            .route("/{token}/accept", web::post().to(accept_invitation))
+    );
+}
@@ -1,14 +1,18 @@
 use actix_web::{web, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
 use paperclip::actix::*;
-use crate::db::{models::{League, NewLeague, LeagueJoinRequest, NewLeagueJoinRequest}, DbPool};
-use chrono::Local;
+use crate::db::{models::{League, NewLeague, LeagueJoinRequest, NewLeagueJoinRequest, Match, LeagueChanges}, DbPool};
+use crate::errors::AppError;
+use crate::api::auth::ensure_email_verified;
+use crate::api::notifications::notify;
+use crate::api::pagination::{PageMeta, PageQuery};
+use crate::api::sessions::CurrentPlayer;
+use crate::api::two_factor::two_factor_enabled;
+use crate::calendar::parse_time_zone;
+use chrono::Utc;
 use diesel::prelude::*;
 
-use crate::db::schema::leagues::dsl::{
-    leagues as all_leagues,
-    league_name
-};
+use crate::db::schema::leagues::dsl::leagues as all_leagues;
 use crate::db::schema::player_leagues::dsl::{
     player_leagues as all_player_leagues, 
     player_id, 
@@ -17,15 +21,39 @@ use crate::db::schema::player_leagues::dsl::{
     league_id
     
 };
-use crate::db::models::Player;
 
 #[derive(Debug, Deserialize)]
 pub struct CreateLeagueInput {
     pub league_name: String,
     pub description: Option<String>,
     pub skill_level: Option<String>,
-    pub created_by: String,
     pub is_public: Option<bool>,
+    pub departure_policy: Option<String>,
+    pub max_members: Option<i32>,
+    pub waitlist_enabled: Option<bool>,
+    /// IANA time zone the league plays in; defaults to UTC.
+    pub time_zone: Option<String>,
+}
+
+const DEPARTURE_POLICIES: [&str; 2] = ["cancel", "forfeit"];
+
+pub fn validate_departure_policy(policy: &str) -> Result<(), AppError> {
+    if DEPARTURE_POLICIES.contains(&policy) {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!(
+            "Invalid departure policy '{}', expected one of: {}",
+            policy,
+            DEPARTURE_POLICIES.join(", ")
+        )))
+    }
+}
+
+pub fn validate_max_members(max_members: Option<i32>) -> Result<(), AppError> {
+    match max_members {
+        Some(limit) if limit < 1 => Err(AppError::BadRequest("max_members must be at least 1".into())),
+        _ => Ok(()),
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -33,124 +61,622 @@ pub struct LeagueResponse {
     pub league_id: i32,
     pub league_name: String,
     pub description: Option<String>,
-    pub created_by: String,
-    pub created_at: chrono::NaiveDateTime,
+    pub created_by: i32,
+    pub created_at: chrono::DateTime<Utc>,
 }
 
 pub async fn get_league_by_id(
+    current: CurrentPlayer,
     path: web::Path<i32>,
     pool: web::Data<DbPool>
-) -> impl Responder {
+) -> Result<impl Responder, AppError> {
     let league_id_one = path.into_inner();
-    
-    let conn = &mut pool.get().expect("Failed to get DB connection");
 
-    match all_leagues.find(league_id_one).first::<League>(conn) {
-        Ok(league) => HttpResponse::Ok().json(league),
-        Err(_) => HttpResponse::NotFound().json("League not found")
-    }
+    let league = web::block(move || -> Result<League, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        load_visible_league(&mut conn, league_id_one, current.player_id)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error fetching league: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(league))
 }
 
-pub async fn get_league_by_name(
-    path: web::Path<String>,
+/// Creates a league with the signed-in player as its first admin. Only
+/// players who have verified their email address can.
+pub async fn create_league(
+    current: CurrentPlayer,
+    item: web::Json<CreateLeagueInput>,
     pool: web::Data<DbPool>
-) -> impl Responder {
-    let league_name_val = path.into_inner();
-    
-    let conn = &mut pool.get().expect("Failed to get DB connection");
+) -> Result<impl Responder, AppError> {
+    let item = item.into_inner();
+    let departure_policy = item.departure_policy.unwrap_or_else(|| "cancel".to_string());
+    validate_departure_policy(&departure_policy)?;
+    validate_max_members(item.max_members)?;
+    let time_zone = item.time_zone.unwrap_or_else(|| "UTC".to_string());
+    parse_time_zone(&time_zone)?;
 
-    match all_leagues
-        .filter(league_name.eq(league_name_val))
-        .first::<League>(conn) 
-    {
-        Ok(league) => HttpResponse::Ok().json(league),
-        Err(_) => HttpResponse::NotFound().json("League not found")
+    let new_league = NewLeague {
+        league_name: item.league_name,
+        description: item.description,
+        skill_level: item.skill_level,
+        created_by: current.player_id,
+        is_public: item.is_public.unwrap_or(true),
+        created_at: Utc::now(),
+        departure_policy,
+        max_members: item.max_members,
+        waitlist_enabled: item.waitlist_enabled.unwrap_or(true),
+        time_zone,
+    };
+
+    let league = web::block(move || -> Result<League, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+        ensure_email_verified(&mut conn, &[new_league.created_by])?;
+        Ok(conn.transaction(|conn| insert_league(conn, &new_league))?)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error creating league: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Created().json(league))
+}
+
+/// Inserts a league and makes its creator the first admin. Run it inside a
+/// transaction so a failed membership insert does not leave an orphan league.
+pub fn insert_league(conn: &mut PgConnection, new_league: &NewLeague) -> Result<League, diesel::result::Error> {
+    let league_result = diesel::insert_into(all_leagues)
+        .values(new_league)
+        .get_result::<League>(conn)?;
+
+    // Add creator as admin
+    diesel::insert_into(all_player_leagues)
+        .values((
+            player_id.eq(new_league.created_by),
+            league_id.eq(league_result.league_id),
+            role.eq("admin"),
+            joined_at.eq(Utc::now())
+        ))
+        .execute(conn)?;
+
+    Ok(league_result)
+}
+
+pub fn is_league_member(conn: &mut PgConnection, league: i32, member: i32) -> Result<bool, AppError> {
+    let count = all_player_leagues
+        .filter(league_id.eq(league))
+        .filter(player_id.eq(member))
+        .count()
+        .get_result::<i64>(conn)
+        .map_err(|_| AppError::InternalError)?;
+    Ok(count > 0)
+}
+
+/// Loads a league the player may look at: public leagues, and private ones
+/// they belong to. Other private leagues are `Forbidden`.
+pub fn load_visible_league(conn: &mut PgConnection, league: i32, viewer: i32) -> Result<League, AppError> {
+    let league = all_leagues.find(league).first::<League>(conn)?;
+    if !league.is_public && !is_league_member(conn, league.league_id, viewer)? {
+        return Err(AppError::Forbidden("This league is private".into()));
+    }
+    Ok(league)
+}
+
+/// Loads a league and locks its row until the transaction ends. Everything
+/// that adds members takes this lock first, so a `has_room` check still holds
+/// when the member is inserted.
+pub fn lock_league(conn: &mut PgConnection, league: i32) -> Result<League, AppError> {
+    Ok(all_leagues.find(league).for_update().first::<League>(conn)?)
+}
+
+/// Whether the league can take another member under its `max_members` limit.
+pub fn has_room(conn: &mut PgConnection, league: &League) -> Result<bool, AppError> {
+    let Some(limit) = league.max_members else {
+        return Ok(true);
+    };
+
+    let members = all_player_leagues
+        .filter(league_id.eq(league.league_id))
+        .count()
+        .get_result::<i64>(conn)
+        .map_err(|_| AppError::InternalError)?;
+    Ok(members < i64::from(limit))
+}
+
+/// Queues a player for a full league as a `waitlisted` join request, reusing
+/// any earlier request they made. Leagues without a waitlist refuse them.
+fn add_to_waitlist(
+    conn: &mut PgConnection,
+    league: &League,
+    member: i32,
+    request_description: Option<String>,
+) -> Result<(), AppError> {
+    use crate::db::schema::league_join_requests;
+
+    if !league.waitlist_enabled {
+        return Err(AppError::BadRequest("League is full".into()));
+    }
+
+    let existing = league_join_requests::table
+        .filter(league_join_requests::league_id.eq(league.league_id))
+        .filter(league_join_requests::player_id.eq(member))
+        .filter(league_join_requests::status.eq_any(["pending", "rejected", "waitlisted"]))
+        .first::<LeagueJoinRequest>(conn)
+        .optional()
+        .map_err(|_| AppError::InternalError)?;
+
+    match existing {
+        Some(request) if request.status == "waitlisted" => {
+            Err(AppError::BadRequest("Player is already on the waitlist".into()))
+        }
+        Some(request) => {
+            diesel::update(league_join_requests::table.find(request.request_id))
+                .set(league_join_requests::status.eq("waitlisted"))
+                .execute(conn)
+                .map_err(|_| AppError::InternalError)?;
+            Ok(())
+        }
+        None => {
+            let new_request = NewLeagueJoinRequest {
+                league_id: league.league_id,
+                player_id: member,
+                description: request_description,
+                notes: None,
+            };
+            diesel::insert_into(league_join_requests::table)
+                .values((&new_request, league_join_requests::status.eq("waitlisted")))
+                .execute(conn)
+                .map_err(|_| AppError::InternalError)?;
+            Ok(())
+        }
     }
 }
 
+/// Moves waitlisted players into the league, oldest request first, while the
+/// league has room, and notifies each of them. Returns the promoted players.
+pub fn promote_waitlisted(conn: &mut PgConnection, league: &League) -> Result<Vec<i32>, AppError> {
+    use crate::db::schema::league_join_requests;
 
-pub async fn create_league(
-    item: web::Json<CreateLeagueInput>,
-    pool: web::Data<DbPool>
-) -> impl Responder {
-    let conn = &mut pool.get().expect("Failed to get DB connection");
+    let mut promoted = Vec::new();
+    while has_room(conn, league)? {
+        let next = league_join_requests::table
+            .filter(league_join_requests::league_id.eq(league.league_id))
+            .filter(league_join_requests::status.eq("waitlisted"))
+            .order((league_join_requests::created_at.asc(), league_join_requests::request_id.asc()))
+            .first::<LeagueJoinRequest>(conn)
+            .optional()
+            .map_err(|_| AppError::InternalError)?;
 
-    // Start a transaction
-    conn.transaction(|conn| {
-        // Insert the league
-        let new_league = NewLeague {
-            league_name: item.league_name.to_string(),
-            description: item.description.clone(),
-            skill_level: item.skill_level.clone(),
-            created_by: item.created_by.to_string(),
-            is_public: item.is_public.unwrap_or(true),
-            created_at: Local::now().naive_local(),
+        let Some(request) = next else {
+            break;
         };
 
-        let league_result = diesel::insert_into(all_leagues)
-            .values(&new_league)
-            .get_result::<League>(conn)?;
-
-        // Add creator as admin
-        diesel::insert_into(all_player_leagues)
-            .values((
-                player_id.eq(&item.created_by),
-                league_id.eq(&league_result.league_name.to_string()),
-                role.eq("admin"),
-           
-                joined_at.eq(Local::now().naive_local())
+        if !is_league_member(conn, league.league_id, request.player_id)? {
+            diesel::insert_into(all_player_leagues)
+                .values((
+                    player_id.eq(request.player_id),
+                    league_id.eq(league.league_id),
+                    role.eq("player"),
+                    joined_at.eq(Utc::now())
+                ))
+                .execute(conn)
+                .map_err(|_| AppError::InternalError)?;
+
+            notify(
+                conn,
+                request.player_id,
+                Some(league.league_id),
+                format!("A spot opened up in {} and you have been moved off the waitlist into the league", league.league_name),
+            )?;
+            promoted.push(request.player_id);
+        }
+
+        diesel::update(league_join_requests::table.find(request.request_id))
+            .set((
+                league_join_requests::status.eq("accepted"),
+                league_join_requests::notes.eq(Some("Promoted from the waitlist")),
             ))
-            .execute(conn)?;
+            .execute(conn)
+            .map_err(|_| AppError::InternalError)?;
+    }
+
+    Ok(promoted)
+}
+
+/// Joins the signed-in player to a public league straight away. Private
+/// leagues only take members through an approved join request or an
+/// invitation. When the league is full the player goes on its waitlist
+/// instead.
+pub async fn join_league(
+    current: CurrentPlayer,
+    path: web::Path<i32>,
+    pool: web::Data<DbPool>
+) -> Result<impl Responder, AppError> {
+    let league_id_val = path.into_inner();
+    let member = current.player_id;
+
+    let waitlisted = web::block(move || -> Result<bool, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            let league = lock_league(conn, league_id_val)?;
+
+            if !league.is_public {
+                return Err(AppError::Forbidden("This league is private; send a join request instead".into()));
+            }
+            ensure_email_verified(conn, &[member])?;
+            if is_league_member(conn, league_id_val, member)? {
+                return Err(AppError::BadRequest("Player is already a member of this league".into()));
+            }
+            if !has_room(conn, &league)? {
+                add_to_waitlist(conn, &league, member, None)?;
+                return Ok(true);
+            }
 
-        Ok::<_, diesel::result::Error>(league_result)
+            diesel::insert_into(all_player_leagues)
+                .values((
+                    player_id.eq(member),
+                    league_id.eq(league_id_val),
+                    role.eq("player"),
+                    joined_at.eq(Utc::now())
+                ))
+                .execute(conn)
+                .map_err(|_| AppError::InternalError)?;
+            Ok(false)
+        })
     })
-    .map(|league| HttpResponse::Created().json(league))
-    .unwrap_or_else(|_| HttpResponse::InternalServerError().json("Failed to create league"))
+    .await
+    .map_err(|e| {
+        eprintln!("Error joining league: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    if waitlisted {
+        return Ok(HttpResponse::Accepted().json("League is full; you have been added to the waitlist"));
+    }
+    Ok(HttpResponse::Ok().json("Joined league successfully"))
 }
 
 #[derive(Debug, Deserialize)]
-pub struct JoinLeagueInput {
-    pub player_id: String,
+pub struct UpdateLeagueInput {
+    pub description: Option<String>,
+    pub skill_level: Option<String>,
+    pub is_public: Option<bool>,
+    pub departure_policy: Option<String>,
+    pub max_members: Option<i32>,
+    pub waitlist_enabled: Option<bool>,
+    pub time_zone: Option<String>,
+    /// Require admins to have two-factor authentication turned on.
+    pub require_admin_two_factor: Option<bool>,
 }
 
-pub async fn join_league(
-    path: web::Path<String>,
-    item: web::Json<JoinLeagueInput>,
+/// Fails with `Forbidden` unless the signed-in player is an admin of the
+/// league. Leagues that require two-factor authentication of admins also need
+/// it turned on, and a session in which a code was given.
+pub fn ensure_league_admin(conn: &mut PgConnection, league: i32, current: &CurrentPlayer) -> Result<(), AppError> {
+    use crate::db::schema::{leagues, player_leagues};
+
+    let member_role = player_leagues::table
+        .filter(player_leagues::league_id.eq(league))
+        .filter(player_leagues::player_id.eq(current.player_id))
+        .select(player_leagues::role)
+        .first::<String>(conn)
+        .optional()
+        .map_err(|_| AppError::InternalError)?;
+
+    if member_role.as_deref() != Some("admin") {
+        return Err(AppError::Forbidden("Only league admins can do this".into()));
+    }
+
+    let requires_two_factor = leagues::table
+        .find(league)
+        .select(leagues::require_admin_two_factor)
+        .first::<bool>(conn)?;
+    if requires_two_factor {
+        if !two_factor_enabled(conn, current.player_id)? {
+            return Err(AppError::Forbidden(
+                "This league requires its admins to turn on two-factor authentication".into(),
+            ));
+        }
+        if !current.two_factor_verified {
+            return Err(AppError::Forbidden(
+                "This league requires its admins to sign in with a two-factor code".into(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub async fn update_league(
+    current: CurrentPlayer,
+    path: web::Path<i32>,
+    item: web::Json<UpdateLeagueInput>,
     pool: web::Data<DbPool>
-) -> impl Responder {
+) -> Result<impl Responder, AppError> {
     let league_id_val = path.into_inner();
-    let conn = &mut pool.get().expect("Failed to get DB connection");
+    let item = item.into_inner();
 
-    match diesel::insert_into(all_player_leagues)
-        .values((
-            player_id.eq(item.player_id.to_string()),
-            league_id.eq(league_id_val),
-            role.eq("player"),
-            joined_at.eq(Local::now().naive_local())
-        ))
-        .execute(conn)
+    if let Some(policy) = &item.departure_policy {
+        validate_departure_policy(policy)?;
+    }
+    validate_max_members(item.max_members)?;
+    if let Some(zone) = &item.time_zone {
+        parse_time_zone(zone)?;
+    }
+    if item.description.is_none()
+        && item.skill_level.is_none()
+        && item.is_public.is_none()
+        && item.departure_policy.is_none()
+        && item.max_members.is_none()
+        && item.waitlist_enabled.is_none()
+        && item.time_zone.is_none()
+        && item.require_admin_two_factor.is_none()
     {
-        Ok(_) => HttpResponse::Ok().json("Joined league successfully"),
-        Err(_) => HttpResponse::InternalServerError().json("Failed to join league")
+        return Err(AppError::BadRequest("No league settings to update".into()));
+    }
+
+    let league = web::block(move || -> Result<League, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            ensure_league_admin(conn, league_id_val, &current)?;
+            // Otherwise the admin would be shut out by their own setting
+            if item.require_admin_two_factor == Some(true)
+                && !(two_factor_enabled(conn, current.player_id)? && current.two_factor_verified)
+            {
+                return Err(AppError::Forbidden(
+                    "Turn on two-factor authentication, and sign in with a code, before requiring it of admins".into(),
+                ));
+            }
+
+            let changes = LeagueChanges {
+                description: item.description,
+                skill_level: item.skill_level,
+                is_public: item.is_public,
+                departure_policy: item.departure_policy,
+                max_members: item.max_members,
+                waitlist_enabled: item.waitlist_enabled,
+                time_zone: item.time_zone,
+                require_admin_two_factor: item.require_admin_two_factor,
+            };
+            let league = diesel::update(all_leagues.find(league_id_val))
+                .set(&changes)
+                .get_result::<League>(conn)?;
+
+            // A raised limit lets people in from the waitlist
+            promote_waitlisted(conn, &league)?;
+            Ok(league)
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error updating league: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(league))
+}
+
+/// What happened to a departing member's open matches.
+#[derive(Debug, Default, Serialize)]
+pub struct DepartureSummary {
+    pub cancelled_matches: Vec<i32>,
+    pub forfeited_matches: Vec<i32>,
+    /// Waitlisted players who took the freed spot.
+    pub promoted_players: Vec<i32>,
+}
+
+pub fn append_note(existing: Option<String>, note: String) -> Option<String> {
+    match existing {
+        Some(existing_notes) => Some(format!("{}\n-----------------\n{}", existing_notes, note)),
+        None => Some(format!("-----------------\n{}", note)),
+    }
+}
+
+/// The player who wins a match the departing member forfeits: the singles
+/// opponent, or the first player of the other doubles team.
+fn forfeit_winner(m: &Match, member: i32) -> Option<i32> {
+    let is = |id: Option<i32>| id == Some(member);
+
+    if is(m.player1_id) {
+        m.player2_id
+    } else if is(m.player2_id) {
+        m.player1_id
+    } else if is(m.team1_player1_id) || is(m.team1_player2_id) {
+        m.team2_player1_id.or(m.team2_player2_id)
+    } else if is(m.team2_player1_id) || is(m.team2_player2_id) {
+        m.team1_player1_id.or(m.team1_player2_id)
+    } else {
+        None
     }
 }
 
+/// Fails with `BadRequest` and the given reason when the league has only one
+/// admin, so the last one cannot go. The admins' rows stay locked until the
+/// transaction ends, so two admins cannot step down at the same time.
+fn ensure_other_admins(conn: &mut PgConnection, league: i32, refusal: &str) -> Result<(), AppError> {
+    use crate::db::schema::player_leagues;
+
+    let admins = player_leagues::table
+        .filter(player_leagues::league_id.eq(league))
+        .filter(player_leagues::role.eq("admin"))
+        .select(player_leagues::player_id)
+        .for_update()
+        .load::<i32>(conn)
+        .map_err(|_| AppError::InternalError)?;
+
+    if admins.len() <= 1 {
+        return Err(AppError::BadRequest(refusal.into()));
+    }
+    Ok(())
+}
+
+/// Removes a member from a league and settles their pending and scheduled
+/// matches according to the league's departure policy.
+///
+/// Pending matches are always cancelled. Scheduled matches are cancelled, or
+/// awarded to the opponent as a walkover when the league policy is `forfeit`.
+/// The last admin of a league cannot be removed. The freed spot goes to the
+/// first player on the league's waitlist.
+pub fn remove_member(
+    conn: &mut PgConnection,
+    league: i32,
+    member: i32,
+    reason: &str,
+) -> Result<DepartureSummary, AppError> {
+    use crate::db::schema::{league_seasons, matches, player_leagues, season_members};
+
+    let league_row = lock_league(conn, league)?;
+
+    let member_role = player_leagues::table
+        .filter(player_leagues::league_id.eq(league))
+        .filter(player_leagues::player_id.eq(member))
+        .select(player_leagues::role)
+        .first::<String>(conn)
+        .optional()
+        .map_err(|_| AppError::InternalError)?
+        .ok_or(AppError::NotFound)?;
+
+    if member_role == "admin" {
+        ensure_other_admins(conn, league, "The last admin cannot leave the league; make another member an admin first")?;
+    }
+
+    let open_matches = matches::table
+        .filter(matches::league_id.eq(league))
+        .filter(matches::status.eq_any(["Pending", "Scheduled"]))
+        .filter(
+            matches::player1_id.eq(member)
+                .or(matches::player2_id.eq(member))
+                .or(matches::team1_player1_id.eq(member))
+                .or(matches::team1_player2_id.eq(member))
+                .or(matches::team2_player1_id.eq(member))
+                .or(matches::team2_player2_id.eq(member))
+        )
+        .load::<Match>(conn)
+        .map_err(|_| AppError::InternalError)?;
+
+    let mut summary = DepartureSummary::default();
+    for open_match in open_matches {
+        let winner = if open_match.status == "Scheduled" && league_row.departure_policy == "forfeit" {
+            forfeit_winner(&open_match, member)
+        } else {
+            None
+        };
+
+        match winner {
+            Some(winner) => {
+                let notes = append_note(open_match.notes, format!("Forfeited: player {} {}", member, reason));
+                diesel::update(matches::table.find(open_match.id))
+                    .set((
+                        matches::status.eq("Completed"),
+                        matches::winner_id.eq(Some(winner)),
+                        matches::score.eq(Some("W/O")),
+                        matches::notes.eq(notes),
+                    ))
+                    .execute(conn)
+                    .map_err(|_| AppError::InternalError)?;
+                summary.forfeited_matches.push(open_match.id);
+            }
+            None => {
+                let notes = append_note(open_match.notes, format!("Cancelled: player {} {}", member, reason));
+                diesel::update(matches::table.find(open_match.id))
+                    .set((
+                        matches::status.eq("Cancelled"),
+                        matches::notes.eq(notes),
+                    ))
+                    .execute(conn)
+                    .map_err(|_| AppError::InternalError)?;
+                summary.cancelled_matches.push(open_match.id);
+            }
+        }
+    }
+
+    // Drop them from seasons that are still running; closed seasons keep their history
+    let open_seasons = league_seasons::table
+        .filter(league_seasons::league_id.eq(league))
+        .filter(league_seasons::status.eq("open"))
+        .select(league_seasons::season_id);
+    diesel::delete(season_members::table)
+        .filter(season_members::player_id.eq(member))
+        .filter(season_members::season_id.eq_any(open_seasons))
+        .execute(conn)
+        .map_err(|_| AppError::InternalError)?;
+
+    diesel::delete(player_leagues::table)
+        .filter(player_leagues::league_id.eq(league))
+        .filter(player_leagues::player_id.eq(member))
+        .execute(conn)
+        .map_err(|_| AppError::InternalError)?;
+
+    summary.promoted_players = promote_waitlisted(conn, &league_row)?;
+
+    Ok(summary)
+}
+
+/// Takes the signed-in player out of the league. Admins remove other members
+/// with `remove_league_member`.
 pub async fn leave_league(
-    path: web::Path<String>,
-    item: web::Json<JoinLeagueInput>,
+    current: CurrentPlayer,
+    path: web::Path<i32>,
     pool: web::Data<DbPool>
-) -> impl Responder {
+) -> Result<impl Responder, AppError> {
     let league_id_val = path.into_inner();
-    let conn = &mut pool.get().expect("Failed to get DB connection");
+    let member = current.player_id;
 
-    match diesel::delete(all_player_leagues)
-        .filter(player_id.eq(item.player_id.to_string()))
-        .filter(league_id.eq(league_id_val))
-        .execute(conn)
-    {
-        Ok(_) => HttpResponse::Ok().json("Left league successfully"),
-        Err(_) => HttpResponse::InternalServerError().json("Failed to leave league")
-    }
+    let summary = web::block(move || -> Result<DepartureSummary, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| remove_member(conn, league_id_val, member, "left the league"))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error leaving league: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Left league successfully",
+        "success": true,
+        "cancelled_matches": summary.cancelled_matches,
+        "forfeited_matches": summary.forfeited_matches,
+        "promoted_players": summary.promoted_players
+    })))
+}
+
+pub async fn remove_league_member(
+    current: CurrentPlayer,
+    path: web::Path<(i32, i32)>,
+    pool: web::Data<DbPool>
+) -> Result<impl Responder, AppError> {
+    let (league_id_val, member) = path.into_inner();
+    let removed_by = current.player_id;
+
+    let summary = web::block(move || -> Result<DepartureSummary, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            ensure_league_admin(conn, league_id_val, &current)?;
+            remove_member(conn, league_id_val, member, &format!("was removed by {}", removed_by))
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error removing league member: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Member removed successfully",
+        "success": true,
+        "cancelled_matches": summary.cancelled_matches,
+        "forfeited_matches": summary.forfeited_matches,
+        "promoted_players": summary.promoted_players
+    })))
 }
 
 
@@ -158,28 +684,80 @@ pub async fn leave_league(
 #[derive(Debug, Deserialize)]
 pub struct LeagueQuery {
     search: Option<String>,
+    skill_level: Option<String>,
 }
 
+/// Fields league listings can be sorted by.
+pub const LEAGUE_SORT_FIELDS: [&str; 3] = ["league_name", "league_id", "created_at"];
+
+/// Lists public leagues, and the private ones the signed-in player belongs
+/// to.
 pub async fn search_leagues(
+    current: CurrentPlayer,
     query: web::Query<LeagueQuery>,
+    paging: web::Query<PageQuery>,
     pool: web::Data<DbPool>
-) -> impl Responder {
-    let conn = &mut pool.get().expect("Failed to get DB connection");
+) -> Result<impl Responder, AppError> {
+    let query = query.into_inner();
+    let page = paging.page()?;
+    let sort = paging.sort(&LEAGUE_SORT_FIELDS, "league_name")?;
 
-    let mut query_builder = all_leagues.into_boxed();
-    
-    if let Some(search_term) = &query.search {
-        query_builder = query_builder.filter(league_name.ilike(format!("%{}%", search_term)));
-    }
+    let (found, total) = web::block(move || -> Result<(Vec<League>, i64), AppError> {
+        use crate::db::schema::leagues;
 
-    match query_builder.load::<League>(conn) {
-        Ok(results) => HttpResponse::Ok().json(results),
-        Err(error) => {
-            println!("Failed to search leagues");
-            println!("Error: {:?}", error);
-            HttpResponse::InternalServerError().json("Failed to search leagues")
-        }
-    }
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let filtered = || {
+            let mut query_builder = leagues::table.into_boxed();
+
+            // Private leagues are only visible to their members
+            let viewer_leagues = all_player_leagues
+                .filter(player_id.eq(current.player_id))
+                .select(league_id);
+            query_builder = query_builder.filter(leagues::is_public.eq(true).or(leagues::league_id.eq_any(viewer_leagues)));
+
+            if let Some(search_term) = &query.search {
+                query_builder = query_builder.filter(leagues::league_name.ilike(format!("%{}%", search_term)));
+            }
+            if let Some(skill_level) = &query.skill_level {
+                query_builder = query_builder.filter(leagues::skill_level.ilike(skill_level.clone()));
+            }
+            query_builder
+        };
+
+        let total = filtered()
+            .count()
+            .get_result::<i64>(&mut conn)
+            .map_err(|_| AppError::InternalError)?;
+
+        let query_builder = filtered();
+        let query_builder = match (sort.field.as_str(), sort.descending) {
+            ("league_id", false) => query_builder.order(leagues::league_id.asc()),
+            ("league_id", true) => query_builder.order(leagues::league_id.desc()),
+            ("created_at", false) => query_builder.order((leagues::created_at.asc(), leagues::league_id.asc())),
+            ("created_at", true) => query_builder.order((leagues::created_at.desc(), leagues::league_id.desc())),
+            (_, false) => query_builder.order((leagues::league_name.asc(), leagues::league_id.asc())),
+            (_, true) => query_builder.order((leagues::league_name.desc(), leagues::league_id.desc())),
+        };
+
+        let found = query_builder
+            .limit(page.per_page)
+            .offset(page.offset())
+            .load::<League>(&mut conn)
+            .map_err(|_| AppError::InternalError)?;
+        Ok((found, total))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error searching leagues: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "count": found.len(),
+        "leagues": found,
+        "pagination": PageMeta::new(page, total)
+    })))
 }
 
 
@@ -188,65 +766,110 @@ pub struct UpdateRoleInput {
     pub role: String, // e.g., "admin" or "player"
 }
 
+/// Makes a member an admin or a regular player. Only league admins may, and
+/// the last admin cannot be made a regular player.
 pub async fn update_member_role(
-    path: web::Path<(String, String)>,
+    current: CurrentPlayer,
+    path: web::Path<(i32, i32)>,
     item: web::Json<UpdateRoleInput>,
     pool: web::Data<DbPool>
-) -> impl Responder {
+) -> Result<impl Responder, AppError> {
     let (league_id_val, player_id_val) = path.into_inner();
-    let conn = &mut pool.get().expect("Failed to get DB connection");
-    
-    use crate::db::schema::player_leagues;
-    match diesel::update(player_leagues::table)
-        .filter(player_leagues::league_id.eq(&league_id_val))
-        .filter(player_leagues::player_id.eq(&player_id_val))
-        .set(player_leagues::role.eq(&item.role))
-        .execute(conn) 
-    {
-        Ok(_) => (),
-        Err(_) => return HttpResponse::InternalServerError().json("Failed to update member role")
+    let new_role = item.into_inner().role;
+    if new_role != "admin" && new_role != "player" {
+        return Err(AppError::BadRequest("Role must be 'admin' or 'player'".into()));
     }
-    
-    HttpResponse::Ok().json(format!("Updated player {} in league {} to role {}", player_id_val, league_id_val, item.role))
+
+    let message = format!("Updated player {} in league {} to role {}", player_id_val, league_id_val, new_role);
+    web::block(move || -> Result<(), AppError> {
+        use crate::db::schema::player_leagues;
+
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            ensure_league_admin(conn, league_id_val, &current)?;
+
+            let member_role = player_leagues::table
+                .filter(player_leagues::league_id.eq(league_id_val))
+                .filter(player_leagues::player_id.eq(player_id_val))
+                .select(player_leagues::role)
+                .first::<String>(conn)
+                .optional()?
+                .ok_or(AppError::NotFound)?;
+            if member_role == "admin" && new_role != "admin" {
+                ensure_other_admins(conn, league_id_val, "The last admin cannot step down; make another member an admin first")?;
+            }
+
+            diesel::update(player_leagues::table)
+                .filter(player_leagues::league_id.eq(league_id_val))
+                .filter(player_leagues::player_id.eq(player_id_val))
+                .set(player_leagues::role.eq(&new_role))
+                .execute(conn)?;
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error updating member role: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(message))
 }
 
 #[derive(Serialize, Queryable, Debug)]
 pub struct LeaguePlayerInfo {
+    pub player_id: i32,
     pub name: String,
-    pub email: String,
+    /// Only shown to members of the league, as on profiles.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
     pub skill_level: Option<String>,
     pub role: String,
 }
 
+/// Lists a league's members. Email addresses are contact details, so only
+/// members of the league see them.
 #[api_v2_operation]
 pub async fn get_league_players(
-    path: web::Path<String>,
+    current: CurrentPlayer,
+    path: web::Path<i32>,
     pool: web::Data<DbPool>
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let league_id_val = path.into_inner();
-    let conn = &mut pool.get().expect("Failed to get DB connection");
 
-    use crate::db::schema::{players, player_leagues};
-    
-    match players::table
-        .inner_join(player_leagues::table.on(
-            players::name.eq(player_leagues::player_id)
-            .and(player_leagues::league_id.eq(league_id_val))
-        ))
-        .select((
-            players::name,
-            players::email,
-            players::skill_level,
-            player_leagues::role,
-        ))
-        .load::<LeaguePlayerInfo>(conn)
-    {
-        Ok(players) => HttpResponse::Ok().json(players),
-        Err(error) => {
-            println!("Failed to fetch league players: {:?}", error);
-            HttpResponse::InternalServerError().json("Failed to fetch league players")
+    let players = web::block(move || -> Result<Vec<LeaguePlayerInfo>, AppError> {
+        use crate::db::schema::{players, player_leagues};
+
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        load_visible_league(&mut conn, league_id_val, current.player_id)?;
+        let teammate = is_league_member(&mut conn, league_id_val, current.player_id)?;
+        let mut players = players::table
+            .inner_join(player_leagues::table.on(
+                players::player_id.eq(player_leagues::player_id)
+                .and(player_leagues::league_id.eq(league_id_val))
+            ))
+            .select((
+                players::player_id,
+                players::name,
+                players::email.nullable(),
+                players::skill_level,
+                player_leagues::role,
+            ))
+            .load::<LeaguePlayerInfo>(&mut conn)?;
+        if !teammate {
+            players.iter_mut().for_each(|player| player.email = None);
         }
-    }
+        Ok(players)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch league players: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(players))
 }
 
 #[derive(Serialize)]
@@ -256,17 +879,17 @@ pub struct PlayerLeagueRole {
 
 #[api_v2_operation]
 pub async fn get_player_league_role(
-    path: web::Path<(String, String)>,  // (league_id, player_name)
+    path: web::Path<(i32, i32)>,  // (league_id, player_id)
     pool: web::Data<DbPool>
 ) -> impl Responder {
-    let (league_id_val, player_name) = path.into_inner();
+    let (league_id_val, player_id_val) = path.into_inner();
     let conn = &mut pool.get().expect("Failed to get DB connection");
 
     use crate::db::schema::player_leagues::dsl::*;
     
     match player_leagues
         .filter(league_id.eq(league_id_val))
-        .filter(player_id.eq(player_name))
+        .filter(player_id.eq(player_id_val))
         .select(role)
         .first::<String>(conn)
     {
@@ -277,82 +900,104 @@ pub async fn get_player_league_role(
 
 #[derive(Debug, Deserialize)]
 pub struct JoinRequestInput {
-    pub player_id: String,
     pub description: Option<String>,
 }
 
+/// Creates a join request for the signed-in player, or resubmits a rejected
+/// one. When a public league is full the request goes straight onto the
+/// waitlist; full leagues without a waitlist refuse new requests.
 pub async fn create_join_request(
-    path: web::Path<String>,
+    current: CurrentPlayer,
+    path: web::Path<i32>,
     item: web::Json<JoinRequestInput>,
     pool: web::Data<DbPool>
-) -> impl Responder {
+) -> Result<impl Responder, AppError> {
     let league_id_val = path.into_inner();
-    let conn = &mut pool.get().expect("Failed to get DB connection");
+    let item = item.into_inner();
+    let member = current.player_id;
 
-    use crate::db::schema::league_join_requests::dsl::*;
+    let outcome = web::block(move || -> Result<&'static str, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
 
-    // First check if a rejected request already exists
-    let existing_request = league_join_requests
-        .filter(league_id.eq(&league_id_val))
-        .filter(player_id.eq(&item.player_id))
-        .filter(status.eq("rejected"))
-        .first::<LeagueJoinRequest>(conn)
-        .optional();
-
-    match existing_request {
-        Ok(Some(request)) => {
-            // Update the existing rejected request
-            let updated_description = match (request.description, &item.description) {
-                (Some(old_desc), Some(new_desc)) => Some(format!("{}\n\nNew request: {}", old_desc, new_desc)),
-                (None, Some(new_desc)) => Some(new_desc.clone()),
-                (Some(old_desc), None) => Some(old_desc),
-                (None, None) => None,
-            };
+        conn.transaction(|conn| {
+            use crate::db::schema::league_join_requests::dsl::*;
 
-            match diesel::update(league_join_requests.find(request.request_id))
-                .set((
-                    status.eq("pending"),
-                    description.eq(updated_description),
-                    notes.eq::<Option<String>>(None) // Clear any previous notes
-                ))
-                .execute(conn)
-            {
-                Ok(_) => HttpResponse::Ok().json("Join request resubmitted successfully"),
-                Err(error) => {
-                    println!("Failed to update existing join request: {:?}", error);
-                    HttpResponse::InternalServerError().json("Failed to resubmit join request")
+            let league = lock_league(conn, league_id_val)?;
+            ensure_email_verified(conn, &[member])?;
+
+            if !has_room(conn, &league)? {
+                if !league.waitlist_enabled {
+                    return Err(AppError::BadRequest("League is full".into()));
+                }
+                // Private leagues still review requests; accepting one waitlists the player
+                if league.is_public {
+                    add_to_waitlist(conn, &league, member, item.description.clone())?;
+                    return Ok("waitlisted");
                 }
             }
-        },
-        Ok(None) => {
-            // No existing rejected request, create a new one
-            let new_request = NewLeagueJoinRequest {
-                league_id: league_id_val,
-                player_id: item.player_id.clone(),
-                description: item.description.clone(),
-                notes: None,
-            };
 
-            match diesel::insert_into(league_join_requests)
-                .values(&new_request)
-                .execute(conn)
-            {
-                Ok(_) => HttpResponse::Created().json("Join request created successfully"),
-                Err(error) => {
-                    println!("Failed to create join request: {:?}", error);
-                    HttpResponse::InternalServerError().json("Failed to create join request")
+            // First check if a rejected request already exists
+            let existing_request = league_join_requests
+                .filter(league_id.eq(league_id_val))
+                .filter(player_id.eq(member))
+                .filter(status.eq("rejected"))
+                .first::<LeagueJoinRequest>(conn)
+                .optional()
+                .map_err(|_| AppError::InternalError)?;
+
+            match existing_request {
+                Some(request) => {
+                    // Update the existing rejected request
+                    let updated_description = match (request.description, &item.description) {
+                        (Some(old_desc), Some(new_desc)) => Some(format!("{}\n\nNew request: {}", old_desc, new_desc)),
+                        (None, Some(new_desc)) => Some(new_desc.clone()),
+                        (Some(old_desc), None) => Some(old_desc),
+                        (None, None) => None,
+                    };
+
+                    diesel::update(league_join_requests.find(request.request_id))
+                        .set((
+                            status.eq("pending"),
+                            description.eq(updated_description),
+                            notes.eq::<Option<String>>(None) // Clear any previous notes
+                        ))
+                        .execute(conn)
+                        .map_err(|_| AppError::InternalError)?;
+                    Ok("resubmitted")
+                }
+                None => {
+                    // No existing rejected request, create a new one
+                    let new_request = NewLeagueJoinRequest {
+                        league_id: league_id_val,
+                        player_id: member,
+                        description: item.description.clone(),
+                        notes: None,
+                    };
+
+                    diesel::insert_into(league_join_requests)
+                        .values(&new_request)
+                        .execute(conn)
+                        .map_err(|_| AppError::InternalError)?;
+                    Ok("created")
                 }
             }
-        },
-        Err(error) => {
-            println!("Error checking for existing join request: {:?}", error);
-            HttpResponse::InternalServerError().json("Failed to process join request")
-        }
-    }
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to process join request: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(match outcome {
+        "waitlisted" => HttpResponse::Accepted().json("League is full; your request has been added to the waitlist"),
+        "resubmitted" => HttpResponse::Ok().json("Join request resubmitted successfully"),
+        _ => HttpResponse::Created().json("Join request created successfully"),
+    })
 }
 
 pub async fn get_league_join_requests(
-    path: web::Path<String>,
+    path: web::Path<i32>,
     pool: web::Data<DbPool>
 ) -> impl Responder {
     let league_id_val = path.into_inner();
@@ -380,31 +1025,109 @@ pub struct UpdateJoinRequestInput {
 }
 
 
+/// Accepts or rejects a join request. Accepting adds the player to the league,
+/// or to its waitlist when the league is full.
 pub async fn update_join_request_status(
-    path: web::Path<(String, i32)>,  // (league_id, request_id)
+    current: CurrentPlayer,
+    path: web::Path<(i32, i32)>,  // (league_id, request_id)
     item: web::Json<UpdateJoinRequestInput>,
     pool: web::Data<DbPool>
-) -> impl Responder {
+) -> Result<impl Responder, AppError> {
     let (league_id_val, request_id_val) = path.into_inner();
-    let conn = &mut pool.get().expect("Failed to get DB connection");
+    let item = item.into_inner();
 
-    use crate::db::schema::league_join_requests::dsl::*;
+    if item.status != "accepted" && item.status != "rejected" {
+        return Err(AppError::BadRequest("Status must be 'accepted' or 'rejected'".into()));
+    }
 
-    match diesel::update(league_join_requests)
-        .filter(league_id.eq(league_id_val))
-        .filter(request_id.eq(request_id_val))
-        .set((
-            status.eq(&item.status),
-            notes.eq(&item.notes)
-        ))
-        .execute(conn)
-    {
-        Ok(_) => HttpResponse::Ok().json("Join request status updated successfully"),
-        Err(error) => {
-            println!("Failed to update join request status: {:?}", error);
-            HttpResponse::InternalServerError().json("Failed to update join request status")
-        }
+    let waitlisted = web::block(move || -> Result<bool, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            use crate::db::schema::league_join_requests;
+
+            ensure_league_admin(conn, league_id_val, &current)?;
+
+            let request = league_join_requests::table
+                .filter(league_join_requests::league_id.eq(league_id_val))
+                .filter(league_join_requests::request_id.eq(request_id_val))
+                .first::<LeagueJoinRequest>(conn)
+                .optional()
+                .map_err(|_| AppError::InternalError)?
+                .ok_or(AppError::NotFound)?;
+
+            let mut new_status = item.status.as_str();
+            if new_status == "accepted" && !is_league_member(conn, league_id_val, request.player_id)? {
+                let league = lock_league(conn, league_id_val)?;
+
+                if has_room(conn, &league)? {
+                    diesel::insert_into(all_player_leagues)
+                        .values((
+                            player_id.eq(request.player_id),
+                            league_id.eq(league_id_val),
+                            role.eq("player"),
+                            joined_at.eq(Utc::now())
+                        ))
+                        .execute(conn)
+                        .map_err(|_| AppError::InternalError)?;
+                } else if league.waitlist_enabled {
+                    new_status = "waitlisted";
+                } else {
+                    return Err(AppError::BadRequest("League is full".into()));
+                }
+            }
+
+            diesel::update(league_join_requests::table.find(request.request_id))
+                .set((
+                    league_join_requests::status.eq(new_status),
+                    league_join_requests::notes.eq(&item.notes)
+                ))
+                .execute(conn)
+                .map_err(|_| AppError::InternalError)?;
+
+            Ok(new_status == "waitlisted")
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to update join request status: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    if waitlisted {
+        return Ok(HttpResponse::Ok().json("Join request accepted; the league is full so the player was added to the waitlist"));
     }
+    Ok(HttpResponse::Ok().json("Join request status updated successfully"))
+}
+
+/// Lists a league's waitlist in the order players will be let in.
+pub async fn get_league_waitlist(
+    current: CurrentPlayer,
+    path: web::Path<i32>,
+    pool: web::Data<DbPool>
+) -> Result<impl Responder, AppError> {
+    let league_id_val = path.into_inner();
+
+    let waitlist = web::block(move || -> Result<Vec<LeagueJoinRequest>, AppError> {
+        use crate::db::schema::league_join_requests;
+
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        load_visible_league(&mut conn, league_id_val, current.player_id)?;
+        league_join_requests::table
+            .filter(league_join_requests::league_id.eq(league_id_val))
+            .filter(league_join_requests::status.eq("waitlisted"))
+            .order((league_join_requests::created_at.asc(), league_join_requests::request_id.asc()))
+            .load::<LeagueJoinRequest>(&mut conn)
+            .map_err(|_| AppError::InternalError)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error fetching league waitlist: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(waitlist))
 }
 
 
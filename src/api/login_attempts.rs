@@ -0,0 +1,192 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel::dsl::{count_star, max};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+
+use crate::db::models::NewLoginAttempt;
+use crate::db::schema::login_attempts;
+use crate::errors::AppError;
+
+/// How far back failed attempts count.
+pub const ATTEMPT_WINDOW_MINUTES: i64 = 15;
+/// Failures an account is allowed before each further attempt is delayed.
+pub const FREE_FAILURES: usize = 3;
+/// Failures after which an account is locked for `LOCKOUT_MINUTES`.
+pub const ACCOUNT_LOCKOUT_FAILURES: usize = 10;
+/// Failures from one address, across all accounts, after which it is locked.
+pub const IP_LOCKOUT_FAILURES: usize = 30;
+pub const LOCKOUT_MINUTES: i64 = 15;
+/// Longest email, in characters, that attempts can be recorded under.
+pub const MAX_EMAIL_LENGTH: usize = 255;
+
+/// Outcomes that count towards a delay or lockout. Attempts refused while
+/// locked, or that stopped to ask for a two-factor code, are recorded but do
+/// not count. Attempts still being checked count, so guesses sent at the same
+/// time cannot all get in under the limit.
+const FAILURES: [&str; 4] = ["pending", "bad_password", "unknown_email", "bad_code"];
+
+/// The form emails are tracked under, so changing their case does not
+/// start a fresh count.
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// How long an account must wait after its latest failure, given how many
+/// recent failures it has: nothing for the first few, then a delay that
+/// doubles with each failure, then a lockout.
+pub fn account_delay(failures: usize) -> Option<Duration> {
+    if failures < FREE_FAILURES {
+        None
+    } else if failures >= ACCOUNT_LOCKOUT_FAILURES {
+        Some(Duration::minutes(LOCKOUT_MINUTES))
+    } else {
+        Some(Duration::seconds(1 << (failures - FREE_FAILURES)))
+    }
+}
+
+/// How long an address must wait after its latest failure.
+pub fn ip_delay(failures: usize) -> Option<Duration> {
+    (failures >= IP_LOCKOUT_FAILURES).then(|| Duration::minutes(LOCKOUT_MINUTES))
+}
+
+/// Counts uncleared failures after `since` and finds the latest of them.
+fn failures_since(
+    conn: &mut PgConnection,
+    query: login_attempts::BoxedQuery<'_, Pg>,
+    since: DateTime<Utc>,
+) -> Result<(usize, Option<DateTime<Utc>>), AppError> {
+    let (count, latest) = query
+        .filter(login_attempts::outcome.eq_any(FAILURES))
+        .filter(login_attempts::cleared_at.is_null())
+        .filter(login_attempts::attempted_at.gt(since))
+        .select((count_star(), max(login_attempts::attempted_at)))
+        .first::<(i64, Option<DateTime<Utc>>)>(conn)?;
+    Ok((count as usize, latest))
+}
+
+/// Refuses a login with 429 while the account or the address it comes from
+/// has to wait. A successful login resets the account's count but not the
+/// address's, so signing in to one account does not allow more guesses at
+/// others.
+fn check_allowed(conn: &mut PgConnection, email: &str, ip_address: Option<&str>) -> Result<(), AppError> {
+    let now = Utc::now();
+    let window_start = now - Duration::minutes(ATTEMPT_WINDOW_MINUTES);
+
+    let last_success = login_attempts::table
+        .filter(login_attempts::email.eq(email))
+        .filter(login_attempts::outcome.eq("success"))
+        .select(max(login_attempts::attempted_at))
+        .first::<Option<DateTime<Utc>>>(conn)?;
+    let since = last_success.map_or(window_start, |success| success.max(window_start));
+    let account_query = login_attempts::table.filter(login_attempts::email.eq(email)).into_boxed();
+    let (failures, latest) = failures_since(conn, account_query, since)?;
+    let mut wait_until = latest.zip(account_delay(failures)).map(|(latest, delay)| latest + delay);
+
+    if let Some(ip_address) = ip_address {
+        let ip_query = login_attempts::table.filter(login_attempts::ip_address.eq(ip_address)).into_boxed();
+        let (failures, latest) = failures_since(conn, ip_query, window_start)?;
+        wait_until = wait_until.max(latest.zip(ip_delay(failures)).map(|(latest, delay)| latest + delay));
+    }
+
+    match wait_until.filter(|until| *until > now) {
+        Some(until) => {
+            let seconds = ((until - now).num_milliseconds() + 999) / 1000;
+            Err(AppError::TooManyRequests(
+                format!("Too many failed sign-in attempts; try again in {} seconds", seconds),
+                seconds,
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Waits for other attempts on the same email and address to be checked and
+/// recorded. The locks are held until the transaction ends.
+fn lock_attempts(conn: &mut PgConnection, email: &str, ip_address: Option<&str>) -> Result<(), AppError> {
+    let keys = std::iter::once(format!("login-email:{}", email)).chain(ip_address.map(|ip| format!("login-ip:{}", ip)));
+    // Always the email first, so two attempts cannot wait on each other
+    for key in keys {
+        diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .bind::<Text, _>(key)
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Checks that a login may go ahead and records it as `pending`, in one
+/// transaction that holds locks on the email and address, so parallel
+/// attempts are counted one after another. Refusals are recorded as
+/// `locked`. Returns the attempt to pass to `finish_attempt` once the
+/// credentials have been checked.
+pub fn begin_attempt(conn: &mut PgConnection, email: &str, ip_address: Option<&str>) -> Result<i32, AppError> {
+    conn.transaction(|conn| -> Result<Result<i32, AppError>, AppError> {
+        lock_attempts(conn, email, ip_address)?;
+        if let Err(refusal) = check_allowed(conn, email, ip_address) {
+            record_attempt(conn, email, None, ip_address, "locked")?;
+            return Ok(Err(refusal));
+        }
+        let attempt = diesel::insert_into(login_attempts::table)
+            .values(&NewLoginAttempt {
+                email: email.to_string(),
+                player_id: None,
+                ip_address: ip_address.map(str::to_string),
+                outcome: "pending".to_string(),
+                attempted_at: Utc::now(),
+            })
+            .returning(login_attempts::attempt_id)
+            .get_result::<i32>(conn)?;
+        Ok(Ok(attempt))
+    })?
+}
+
+/// Records how an attempt started with `begin_attempt` ended, with the same
+/// outcomes as `record_attempt`. The time is moved to now, so delays run
+/// from when a failure was known rather than from before the password check.
+pub fn finish_attempt(conn: &mut PgConnection, attempt: i32, player: Option<i32>, outcome: &str) -> Result<(), AppError> {
+    diesel::update(login_attempts::table.find(attempt))
+        .set((
+            login_attempts::player_id.eq(player),
+            login_attempts::outcome.eq(outcome),
+            login_attempts::attempted_at.eq(Utc::now()),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Records the outcome of a login attempt: `success`, `bad_password`,
+/// `unknown_email`, `locked`, `code_required`, `bad_code` or `deactivated`.
+pub fn record_attempt(
+    conn: &mut PgConnection,
+    email: &str,
+    player: Option<i32>,
+    ip_address: Option<&str>,
+    outcome: &str,
+) -> Result<(), AppError> {
+    diesel::insert_into(login_attempts::table)
+        .values(&NewLoginAttempt {
+            email: email.to_string(),
+            player_id: player,
+            ip_address: ip_address.map(str::to_string),
+            outcome: outcome.to_string(),
+            attempted_at: Utc::now(),
+        })
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Stops the uncleared failures for an email or an address from counting,
+/// lifting any delay or lockout. Returns how many were cleared.
+pub fn clear_failures(conn: &mut PgConnection, email: Option<&str>, ip_address: Option<&str>) -> Result<usize, AppError> {
+    let mut query = diesel::update(login_attempts::table)
+        .filter(login_attempts::outcome.eq_any(FAILURES))
+        .filter(login_attempts::cleared_at.is_null())
+        .into_boxed();
+    if let Some(email) = email {
+        query = query.filter(login_attempts::email.eq(normalize_email(email)));
+    }
+    if let Some(ip_address) = ip_address {
+        query = query.filter(login_attempts::ip_address.eq(ip_address));
+    }
+    Ok(query.set(login_attempts::cleared_at.eq(Utc::now())).execute(conn)?)
+}
@@ -1,71 +1,411 @@
+use std::collections::HashMap;
+
+use actix_web::middleware::from_fn;
 use actix_web::{web, HttpResponse, Responder};
+use chrono::{DateTime, LocalResult, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use crate::api::auth::ensure_email_verified;
+use crate::api::pagination::{Page, PageMeta, PageQuery, Sort};
+use crate::api::sessions::{self, CurrentPlayer};
+use crate::calendar::{local_rfc3339, zone_or_utc};
 use crate::db::{models::{NewMatch, Match}, DbPool, schema::matches};
-use crate::db::schema::{matches as matches_schema, player_leagues};
+use crate::db::schema::{leagues, matches as matches_schema, player_leagues, players};
 use crate::errors::AppError;
+use crate::api::seasons;
 use serde::{Deserialize, Serialize};
-use chrono::Utc;
 
 #[derive(Deserialize)]
 pub struct CreateMatchInput {
     pub match_type: String,
-    pub player1_id: Option<String>,
-    pub player2_id: Option<String>,
-    pub league_id: String,
-    pub team1_player1_id: Option<String>,
-    pub team1_player2_id: Option<String>,
-    pub team2_player1_id: Option<String>,
-    pub team2_player2_id: Option<String>,
+    pub player1_id: Option<i32>,
+    pub player2_id: Option<i32>,
+    pub league_id: i32,
+    pub team1_player1_id: Option<i32>,
+    pub team1_player2_id: Option<i32>,
+    pub team2_player1_id: Option<i32>,
+    pub team2_player2_id: Option<i32>,
     pub datetime: String,
     pub location: String,
     pub status: String,
     pub notes: Option<String>,
+    pub season_id: Option<i32>,
 }
 
 #[derive(Deserialize)]
 pub struct MatchQuery {
-    pub league_id: Option<String>,
+    pub league_id: Option<i32>,
     pub status: Option<String>,
+    pub season_id: Option<i32>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub viewer_id: Option<i32>,
+    /// `Singles` or `Doubles`.
+    pub match_type: Option<String>,
+    /// Only matches this player plays in.
+    pub participant_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct MatchRangeQuery {
+    /// Only matches starting at or after this time.
+    pub from: Option<String>,
+    /// Only matches starting before this time.
+    pub to: Option<String>,
+    /// Player whose time zone the match times are shown in.
+    pub viewer_id: Option<i32>,
+    /// `Singles` or `Doubles`.
+    pub match_type: Option<String>,
+    /// Only matches this player also plays in.
+    pub participant_id: Option<i32>,
+}
+
+/// Fields match listings can be sorted by.
+pub const MATCH_SORT_FIELDS: [&str; 3] = ["datetime", "created_at", "id"];
+
+/// Filters shared by the match listings.
+#[derive(Default)]
+pub struct MatchFilter {
+    pub league_id: Option<i32>,
+    pub statuses: Vec<String>,
+    pub season_id: Option<i32>,
+    pub match_type: Option<String>,
+    /// Every one of these players must play in the match.
+    pub participants: Vec<i32>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl MatchFilter {
+    fn query(&self) -> matches_schema::BoxedQuery<'static, Pg> {
+        let mut query = matches_schema::table.into_boxed();
+
+        if let Some(league_id) = self.league_id {
+            query = query.filter(matches_schema::league_id.eq(league_id));
+        }
+        if !self.statuses.is_empty() {
+            query = query.filter(matches_schema::status.eq_any(self.statuses.clone()));
+        }
+        if let Some(season_id) = self.season_id {
+            query = query.filter(matches_schema::season_id.eq(season_id));
+        }
+        if let Some(match_type) = &self.match_type {
+            query = query.filter(matches_schema::match_type.ilike(match_type.clone()));
+        }
+        for &player in &self.participants {
+            query = query.filter(
+                matches_schema::player1_id.eq(player)
+                .or(matches_schema::player2_id.eq(player))
+                .or(matches_schema::team1_player1_id.eq(player))
+                .or(matches_schema::team1_player2_id.eq(player))
+                .or(matches_schema::team2_player1_id.eq(player))
+                .or(matches_schema::team2_player2_id.eq(player))
+            );
+        }
+        if let Some(from) = self.from {
+            query = query.filter(matches_schema::datetime.ge(from));
+        }
+        if let Some(to) = self.to {
+            query = query.filter(matches_schema::datetime.lt(to));
+        }
+        query
+    }
+}
+
+/// Loads one page of the matches passing `filter`, along with how many
+/// matches pass it in total. Ties are broken by id so pages do not overlap.
+pub fn list_matches(
+    conn: &mut PgConnection,
+    filter: &MatchFilter,
+    page: Page,
+    sort: &Sort,
+) -> Result<(Vec<Match>, i64), AppError> {
+    let total = filter.query()
+        .count()
+        .get_result::<i64>(conn)
+        .map_err(|_| AppError::InternalError)?;
+
+    let query = filter.query();
+    let query = match (sort.field.as_str(), sort.descending) {
+        ("created_at", false) => query.order((matches_schema::created_at.asc(), matches_schema::id.asc())),
+        ("created_at", true) => query.order((matches_schema::created_at.desc(), matches_schema::id.desc())),
+        ("id", false) => query.order(matches_schema::id.asc()),
+        ("id", true) => query.order(matches_schema::id.desc()),
+        (_, false) => query.order((matches_schema::datetime.asc(), matches_schema::id.asc())),
+        (_, true) => query.order((matches_schema::datetime.desc(), matches_schema::id.desc())),
+    };
+
+    let found = query
+        .limit(page.per_page)
+        .offset(page.offset())
+        .load::<Match>(conn)
+        .map_err(|_| AppError::InternalError)?;
+    Ok((found, total))
+}
+
+fn validate_match_type(match_type: Option<&str>) -> Result<(), AppError> {
+    match match_type {
+        Some(value) if !["singles", "doubles"].contains(&value.to_lowercase().as_str()) => Err(AppError::BadRequest(
+            format!("Invalid match type '{}', expected Singles or Doubles", value)
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Runs a match listing and renders the page with its metadata.
+async fn match_page(
+    pool: web::Data<DbPool>,
+    filter: MatchFilter,
+    paging: &PageQuery,
+    viewer: Option<i32>,
+    context: &'static str,
+) -> Result<HttpResponse, AppError> {
+    validate_match_type(filter.match_type.as_deref())?;
+    let page = paging.page()?;
+    let sort = paging.sort(&MATCH_SORT_FIELDS, "datetime")?;
+
+    let (matches, total) = web::block(move || -> Result<(Vec<MatchView>, i64), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let (found, total) = list_matches(&mut conn, &filter, page, &sort)?;
+        Ok((render_matches(&mut conn, found, viewer)?, total))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error fetching {}: {:?}", context, e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "count": matches.len(),
+        "matches": matches,
+        "pagination": PageMeta::new(page, total)
+    })))
+}
+
+/// A match as returned by the API. `datetime` is in UTC; `local_datetime` is
+/// the same instant in `time_zone`.
+#[derive(Serialize)]
+pub struct MatchView {
+    #[serde(flatten)]
+    pub details: Match,
+    pub local_datetime: String,
+    pub time_zone: String,
+}
+
+/// Shows matches in the viewer's time zone, or each in its league's time zone
+/// when there is no viewer.
+pub fn render_matches(
+    conn: &mut PgConnection,
+    found: Vec<Match>,
+    viewer: Option<i32>,
+) -> Result<Vec<MatchView>, AppError> {
+    let viewer_zone = match viewer {
+        Some(viewer) => Some(
+            players::table
+                .find(viewer)
+                .select(players::time_zone)
+                .first::<String>(conn)
+                .optional()
+                .map_err(|_| AppError::InternalError)?
+                .ok_or(AppError::NotFound)?,
+        ),
+        None => None,
+    };
+
+    let league_zones: HashMap<i32, String> = match viewer_zone {
+        Some(_) => HashMap::new(),
+        None => leagues::table
+            .filter(leagues::league_id.eq_any(found.iter().map(|m| m.league_id).collect::<Vec<_>>()))
+            .select((leagues::league_id, leagues::time_zone))
+            .load::<(i32, String)>(conn)
+            .map_err(|_| AppError::InternalError)?
+            .into_iter()
+            .collect(),
+    };
+
+    Ok(found
+        .into_iter()
+        .map(|m| {
+            let zone_name = viewer_zone.as_ref().or_else(|| league_zones.get(&m.league_id));
+            let zone = zone_or_utc(zone_name.map(String::as_str).unwrap_or("UTC"));
+            MatchView {
+                local_datetime: local_rfc3339(m.datetime, zone),
+                time_zone: zone.name().to_string(),
+                details: m,
+            }
+        })
+        .collect())
 }
 
 #[derive(Deserialize)]
 pub struct AcceptMatchInput {
-    pub player_id: String,
     pub comments: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct RejectMatchInput {
-    pub player_id: String,
     pub reason: Option<String>,
 }
 
+/// Parses a match time. RFC 3339 values keep their offset; values without one,
+/// like `2025-05-15T14:00:00`, are local times in `zone`. Local times that fall
+/// into a daylight saving gap, or that occur twice when clocks go back, are
+/// rejected since they do not name a single instant.
+pub fn parse_match_datetime(value: &str, zone: Tz) -> Result<DateTime<Utc>, AppError> {
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(value) {
+        return Ok(parsed.with_timezone(&Utc));
+    }
+
+    let naive = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .ok_or_else(|| AppError::BadRequest(format!(
+            "Invalid datetime '{}', expected a time like 2025-05-15T14:00:00Z",
+            value
+        )))?;
+
+    match zone.from_local_datetime(&naive) {
+        LocalResult::Single(local) => Ok(local.with_timezone(&Utc)),
+        LocalResult::Ambiguous(_, _) => Err(AppError::BadRequest(format!(
+            "'{}' occurs twice in {}, add an offset like +02:00 to pick one",
+            value,
+            zone.name()
+        ))),
+        LocalResult::None => Err(AppError::BadRequest(format!(
+            "'{}' does not exist in {}",
+            value,
+            zone.name()
+        ))),
+    }
+}
+
+/// The time zone of a league, in which its local match times are read.
+pub fn league_zone(conn: &mut PgConnection, league_id: i32) -> Result<Tz, AppError> {
+    let name: String = leagues::table
+        .find(league_id)
+        .select(leagues::time_zone)
+        .first(conn)
+        .optional()?
+        .ok_or(AppError::NotFound)?;
+    Ok(zone_or_utc(&name))
+}
+
+/// Optional lower (inclusive) and upper (exclusive) bounds on a match's time.
+type DateRange = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+/// Parses the optional `from`/`to` bounds of a match listing. Bounds without an
+/// offset are read in the listed league's zone, or in UTC when no league is
+/// given.
+async fn parse_date_range(
+    pool: &web::Data<DbPool>,
+    league_id: Option<i32>,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<DateRange, AppError> {
+    let zone = match league_id {
+        Some(league_id) if from.is_some() || to.is_some() => {
+            let pool = pool.clone();
+            web::block(move || -> Result<Tz, AppError> {
+                let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+                league_zone(&mut conn, league_id)
+            })
+            .await
+            .map_err(|e| {
+                eprintln!("Error loading league time zone: {:?}", e);
+                AppError::InternalError
+            })??
+        }
+        _ => Tz::UTC,
+    };
+    let from = from.map(|value| parse_match_datetime(value, zone)).transpose()?;
+    let to = to.map(|value| parse_match_datetime(value, zone)).transpose()?;
+
+    if let (Some(from), Some(to)) = (from, to) {
+        if from >= to {
+            return Err(AppError::BadRequest("'from' must be before 'to'".into()));
+        }
+    }
+    Ok((from, to))
+}
+
+/// Ids of everyone playing in a match.
+fn match_players(m: &Match) -> Vec<i32> {
+    [
+        m.player1_id,
+        m.player2_id,
+        m.team1_player1_id,
+        m.team1_player2_id,
+        m.team2_player1_id,
+        m.team2_player2_id,
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Rejects players who are not playing in the match.
+fn ensure_participant(participants: &[i32], player: i32) -> Result<(), AppError> {
+    if participants.contains(&player) {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden("Only the match's players can do this".into()))
+    }
+}
+
+/// Proposes a match. The signed-in player must be one of its players.
 pub async fn create_match(
+    current: CurrentPlayer,
     pool: web::Data<DbPool>,
     match_data: web::Json<CreateMatchInput>,
 ) -> Result<impl Responder, AppError> {
-    let new_match = NewMatch {
-        match_type: match_data.match_type.clone(),
-        player1_id: match_data.player1_id.clone(),
-        player2_id: match_data.player2_id.clone(),
-        league_id: match_data.league_id.clone(),
-        team1_player1_id: match_data.team1_player1_id.clone(),
-        team1_player2_id: match_data.team1_player2_id.clone(),
-        team2_player1_id: match_data.team2_player1_id.clone(),
-        team2_player2_id: match_data.team2_player2_id.clone(),
-        datetime: match_data.datetime.clone(),
-        location: match_data.location.clone(),
-        score: None,
-        winner_id: None,
-        status: match_data.status.clone(),
-        notes: match_data.notes.clone(),
-    };
-    
+    let match_data = match_data.into_inner();
     let pool_clone = pool.clone();
     
     // Execute the database operation
-    let _ = web::block(move || {
+    web::block(move || {
         let mut conn = pool_clone.get().map_err(|_| AppError::InternalError)?;
+
+        // Local times are given on the league's clock
+        let zone = league_zone(&mut conn, match_data.league_id)?;
+        let datetime = parse_match_datetime(&match_data.datetime, zone)?;
+        let new_match = NewMatch {
+            match_type: match_data.match_type.clone(),
+            player1_id: match_data.player1_id,
+            player2_id: match_data.player2_id,
+            league_id: match_data.league_id,
+            team1_player1_id: match_data.team1_player1_id,
+            team1_player2_id: match_data.team1_player2_id,
+            team2_player1_id: match_data.team2_player1_id,
+            team2_player2_id: match_data.team2_player2_id,
+            datetime,
+            location: match_data.location.clone(),
+            score: None,
+            winner_id: None,
+            status: match_data.status.clone(),
+            notes: match_data.notes.clone(),
+            season_id: match_data.season_id,
+        };
+        
+        let participants: Vec<i32> = [
+            new_match.player1_id,
+            new_match.player2_id,
+            new_match.team1_player1_id,
+            new_match.team1_player2_id,
+            new_match.team2_player1_id,
+            new_match.team2_player2_id,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        ensure_participant(&participants, current.player_id)?;
+        ensure_email_verified(&mut conn, &participants)?;
+
+        // A match can only count towards an open season of its own league
+        if let Some(season_id) = new_match.season_id {
+            seasons::ensure_open_season(&mut conn, new_match.league_id, season_id)?;
+        }
         
         // Execute the insert and explicitly handle the result
         match diesel::insert_into(matches::table)
@@ -80,7 +420,7 @@ pub async fn create_match(
     .map_err(|e| {
         eprintln!("Error creating match: {:?}", e);
         AppError::InternalError
-    })?;
+    })??;
     
     Ok(HttpResponse::Created().json(serde_json::json!({
         "message": "Match created successfully",
@@ -88,17 +428,20 @@ pub async fn create_match(
     })))
 }
 
+/// Accepts a proposed match on behalf of the signed-in player, who must be
+/// playing in it.
 pub async fn accept_match(
+    current: CurrentPlayer,
     pool: web::Data<DbPool>,
     match_id: web::Path<i32>,
     input: web::Json<AcceptMatchInput>,
 ) -> Result<impl Responder, AppError> {
     let pool_clone = pool.clone();
     let match_id = match_id.into_inner();
-    let player_id = input.player_id.clone();
+    let player_id = current.player_id;
     let comments = input.comments.clone();
     
-    let result = web::block(move || {
+    web::block(move || {
         let mut conn = pool_clone.get().map_err(|_| AppError::InternalError)?;
         
         // First, check if the player is in the league
@@ -106,11 +449,12 @@ pub async fn accept_match(
             .filter(matches_schema::id.eq(match_id))
             .first(&mut conn)
             .map_err(|_| AppError::NotFound)?;
+        ensure_participant(&match_players(&match_details), player_id)?;
         
         // Check if player is in the league
         let player_in_league = player_leagues::table
-            .filter(player_leagues::player_id.eq(&player_id))
-            .filter(player_leagues::league_id.eq(&match_details.league_id))
+            .filter(player_leagues::player_id.eq(player_id))
+            .filter(player_leagues::league_id.eq(match_details.league_id))
             .count()
             .get_result::<i64>(&mut conn)
             .map_err(|_| AppError::InternalError)?;
@@ -122,10 +466,10 @@ pub async fn accept_match(
         // Check if all players are in the league based on match type
         if match_details.match_type.to_lowercase() == "singles" {
             // For singles matches, check player1_id and player2_id
-            if let Some(player1_id) = &match_details.player1_id {
+            if let Some(player1_id) = match_details.player1_id {
                 let player1_in_league = player_leagues::table
                     .filter(player_leagues::player_id.eq(player1_id))
-                    .filter(player_leagues::league_id.eq(&match_details.league_id))
+                    .filter(player_leagues::league_id.eq(match_details.league_id))
                     .count()
                     .get_result::<i64>(&mut conn)
                     .map_err(|_| AppError::InternalError)?;
@@ -135,10 +479,10 @@ pub async fn accept_match(
                 }
             }
             
-            if let Some(player2_id) = &match_details.player2_id {
+            if let Some(player2_id) = match_details.player2_id {
                 let player2_in_league = player_leagues::table
                     .filter(player_leagues::player_id.eq(player2_id))
-                    .filter(player_leagues::league_id.eq(&match_details.league_id))
+                    .filter(player_leagues::league_id.eq(match_details.league_id))
                     .count()
                     .get_result::<i64>(&mut conn)
                     .map_err(|_| AppError::InternalError)?;
@@ -150,10 +494,10 @@ pub async fn accept_match(
         } else if match_details.match_type.to_lowercase() == "doubles" {
             // For doubles matches, check team players
             // Check team1_player1_id
-            if let Some(team1_player1_id) = &match_details.team1_player1_id {
+            if let Some(team1_player1_id) = match_details.team1_player1_id {
                 let player_in_league = player_leagues::table
                     .filter(player_leagues::player_id.eq(team1_player1_id))
-                    .filter(player_leagues::league_id.eq(&match_details.league_id))
+                    .filter(player_leagues::league_id.eq(match_details.league_id))
                     .count()
                     .get_result::<i64>(&mut conn)
                     .map_err(|_| AppError::InternalError)?;
@@ -164,10 +508,10 @@ pub async fn accept_match(
             }
             
             // Check team1_player2_id
-            if let Some(team1_player2_id) = &match_details.team1_player2_id {
+            if let Some(team1_player2_id) = match_details.team1_player2_id {
                 let player_in_league = player_leagues::table
                     .filter(player_leagues::player_id.eq(team1_player2_id))
-                    .filter(player_leagues::league_id.eq(&match_details.league_id))
+                    .filter(player_leagues::league_id.eq(match_details.league_id))
                     .count()
                     .get_result::<i64>(&mut conn)
                     .map_err(|_| AppError::InternalError)?;
@@ -178,10 +522,10 @@ pub async fn accept_match(
             }
             
             // Check team2_player1_id
-            if let Some(team2_player1_id) = &match_details.team2_player1_id {
+            if let Some(team2_player1_id) = match_details.team2_player1_id {
                 let player_in_league = player_leagues::table
                     .filter(player_leagues::player_id.eq(team2_player1_id))
-                    .filter(player_leagues::league_id.eq(&match_details.league_id))
+                    .filter(player_leagues::league_id.eq(match_details.league_id))
                     .count()
                     .get_result::<i64>(&mut conn)
                     .map_err(|_| AppError::InternalError)?;
@@ -192,10 +536,10 @@ pub async fn accept_match(
             }
             
             // Check team2_player2_id
-            if let Some(team2_player2_id) = &match_details.team2_player2_id {
+            if let Some(team2_player2_id) = match_details.team2_player2_id {
                 let player_in_league = player_leagues::table
                     .filter(player_leagues::player_id.eq(team2_player2_id))
-                    .filter(player_leagues::league_id.eq(&match_details.league_id))
+                    .filter(player_leagues::league_id.eq(match_details.league_id))
                     .count()
                     .get_result::<i64>(&mut conn)
                     .map_err(|_| AppError::InternalError)?;
@@ -229,7 +573,7 @@ pub async fn accept_match(
     .map_err(|e| {
         eprintln!("Error accepting match: {:?}", e);
         AppError::InternalError
-    })?;
+    })??;
     
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Match accepted successfully",
@@ -237,17 +581,20 @@ pub async fn accept_match(
     })))
 }
 
+/// Rejects a proposed match on behalf of the signed-in player, who must be
+/// playing in it.
 pub async fn reject_match(
+    current: CurrentPlayer,
     pool: web::Data<DbPool>,
     match_id: web::Path<i32>,
     input: web::Json<RejectMatchInput>,
 ) -> Result<impl Responder, AppError> {
     let pool_clone = pool.clone();
     let match_id = match_id.into_inner();
-    let player_id = input.player_id.clone();
+    let player_id = current.player_id;
     let reason = input.reason.clone();
     
-    let result: Result<(), AppError> = web::block(move || {
+    web::block(move || -> Result<(), AppError> {
         let mut conn = pool_clone.get().map_err(|_| AppError::InternalError)?;
         
         // Check if the match exists
@@ -255,6 +602,7 @@ pub async fn reject_match(
             .filter(matches_schema::id.eq(match_id))
             .first(&mut conn)
             .map_err(|_| AppError::NotFound)?;
+        ensure_participant(&match_players(&match_details), player_id)?;
         
         // Update the match notes with rejection reason
         let updated_notes = match (match_details.notes, reason) {
@@ -280,7 +628,7 @@ pub async fn reject_match(
     .map_err(|e| {
         eprintln!("Error rejecting match: {:?}", e);
         AppError::InternalError
-    })?;
+    })??;
     
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Match rejected successfully",
@@ -291,127 +639,56 @@ pub async fn reject_match(
 pub async fn get_matches(
     pool: web::Data<DbPool>,
     query_params: web::Query<MatchQuery>,
+    paging: web::Query<PageQuery>,
 ) -> Result<impl Responder, AppError> {
-    let pool_clone = pool.clone();
-    let league_id = query_params.league_id.clone();
-    let status = query_params.status.clone();
-    
-    // Use web::block to run the database query in a blocking thread
-    let matches_result = web::block(move || -> Result<Vec<Match>, AppError> {
-        let mut conn = pool_clone.get().map_err(|_| AppError::InternalError)?;
-        
-        let mut query = matches_schema::table.into_boxed();
-        
-        // Apply filters if provided
-        if let Some(league_id) = league_id {
-            query = query.filter(matches_schema::league_id.eq(league_id));
-        }
-        
-        if let Some(status) = status {
-            query = query.filter(matches_schema::status.eq(status));
-        }
-        
-        query.load::<Match>(&mut conn)
-            .map_err(|_| AppError::InternalError)
-    })
-    .await
-    .map_err(|e| {
-        eprintln!("Error fetching matches: {:?}", e);
-        AppError::InternalError
-    })?;
-    
-    // Now matches_result is a Result<Vec<Match>, AppError>
-    // We need to unwrap it to get Vec<Match>
-    let matches = matches_result?;
-    let count = matches.len();
-    
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "matches": matches,
-        "count": count
-    })))
+    let query = query_params.into_inner();
+    let (from, to) = parse_date_range(&pool, query.league_id, query.from.as_deref(), query.to.as_deref()).await?;
+    let filter = MatchFilter {
+        league_id: query.league_id,
+        statuses: query.status.into_iter().collect(),
+        season_id: query.season_id,
+        match_type: query.match_type,
+        participants: query.participant_id.into_iter().collect(),
+        from,
+        to,
+    };
+
+    match_page(pool, filter, &paging, query.viewer_id, "matches").await
 }
 
 pub async fn get_player_matches(
     pool: web::Data<DbPool>,
-    player_id: web::Path<String>,
+    player_id: web::Path<i32>,
+    range: web::Query<MatchRangeQuery>,
+    paging: web::Query<PageQuery>,
 ) -> Result<impl Responder, AppError> {
-    let pool_clone = pool.clone();
     let player_id = player_id.into_inner();
-    
-    // Use web::block to run the database query in a blocking thread
-    let matches_result = web::block(move || -> Result<Vec<Match>, AppError> {
-        let mut conn = pool_clone.get().map_err(|_| AppError::InternalError)?;
-        
-        matches_schema::table
-            .filter(
-                matches_schema::player1_id.eq(&player_id)
-                .or(matches_schema::player2_id.eq(&player_id))
-                .or(matches_schema::team1_player1_id.eq(&player_id))
-                .or(matches_schema::team1_player2_id.eq(&player_id))
-                .or(matches_schema::team2_player1_id.eq(&player_id))
-                .or(matches_schema::team2_player2_id.eq(&player_id))
-            )
-            .load::<Match>(&mut conn)
-            .map_err(|_| AppError::InternalError)
-    })
-    .await
-    .map_err(|e| {
-        eprintln!("Error fetching player matches: {:?}", e);
-        AppError::InternalError
-    })?;
-    
-    // Now matches_result is a Result<Vec<Match>, AppError>
-    // We need to unwrap it to get Vec<Match>
-    let matches = matches_result?;
-    let count = matches.len();
-    
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "matches": matches,
-        "count": count
-    })))
+    let range = range.into_inner();
+    let (from, to) = parse_date_range(&pool, None, range.from.as_deref(), range.to.as_deref()).await?;
+    let filter = MatchFilter {
+        match_type: range.match_type,
+        participants: std::iter::once(player_id).chain(range.participant_id).collect(),
+        from,
+        to,
+        ..MatchFilter::default()
+    };
+
+    match_page(pool, filter, &paging, Some(range.viewer_id.unwrap_or(player_id)), "player matches").await
 }
 
 pub async fn get_player_pending_matches(
     pool: web::Data<DbPool>,
-    player_id: web::Path<String>,
+    player_id: web::Path<i32>,
+    paging: web::Query<PageQuery>,
 ) -> Result<impl Responder, AppError> {
-    let pool_clone = pool.clone();
     let player_id = player_id.into_inner();
-    
-    // Use web::block to run the database query in a blocking thread
-    let matches_result = web::block(move || -> Result<Vec<Match>, AppError> {
-        let mut conn = pool_clone.get().map_err(|_| AppError::InternalError)?;
-        
-        matches_schema::table
-            .filter(
-                matches_schema::status.eq("Pending")
-                .and(
-                    matches_schema::player1_id.eq(&player_id)
-                    .or(matches_schema::player2_id.eq(&player_id))
-                    .or(matches_schema::team1_player1_id.eq(&player_id))
-                    .or(matches_schema::team1_player2_id.eq(&player_id))
-                    .or(matches_schema::team2_player1_id.eq(&player_id))
-                    .or(matches_schema::team2_player2_id.eq(&player_id))
-                )
-            )
-            .load::<Match>(&mut conn)
-            .map_err(|_| AppError::InternalError)
-    })
-    .await
-    .map_err(|e| {
-        eprintln!("Error fetching player pending matches: {:?}", e);
-        AppError::InternalError
-    })?;
-    
-    // Now matches_result is a Result<Vec<Match>, AppError>
-    // We need to unwrap it to get Vec<Match>
-    let matches = matches_result?;
-    let count = matches.len();
-    
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "matches": matches,
-        "count": count
-    })))
+    let filter = MatchFilter {
+        statuses: vec!["Pending".to_string()],
+        participants: vec![player_id],
+        ..MatchFilter::default()
+    };
+
+    match_page(pool, filter, &paging, Some(player_id), "player pending matches").await
 }
 
 #[derive(Deserialize)]
@@ -421,51 +698,31 @@ pub struct LeagueMatchesBody {
 
 pub async fn get_league_matches(
     pool: web::Data<DbPool>,
-    league_id: web::Path<String>,
+    league_id: web::Path<i32>,
+    range: web::Query<MatchRangeQuery>,
+    paging: web::Query<PageQuery>,
     body: Option<web::Json<LeagueMatchesBody>>,
 ) -> Result<impl Responder, AppError> {
-    let pool_clone = pool.clone();
     let league_id = league_id.into_inner();
-    let statuses = body.map(|b| b.status.clone()).unwrap_or(None);
-    
-    // Use web::block to run the database query in a blocking thread
-    let matches_result = web::block(move || -> Result<Vec<Match>, AppError> {
-        let mut conn = pool_clone.get().map_err(|_| AppError::InternalError)?;
-        
-        let mut query = matches_schema::table
-            .filter(matches_schema::league_id.eq(league_id))
-            .into_boxed();
-        
-        // Apply status filter if provided
-        if let Some(statuses) = statuses {
-            if !statuses.is_empty() {
-                query = query.filter(matches_schema::status.eq_any(statuses));
-            }
-        }
-        
-        query.load::<Match>(&mut conn)
-            .map_err(|_| AppError::InternalError)
-    })
-    .await
-    .map_err(|e| {
-        eprintln!("Error fetching league matches: {:?}", e);
-        AppError::InternalError
-    })?;
-    
-    // Now matches_result is a Result<Vec<Match>, AppError>
-    // We need to unwrap it to get Vec<Match>
-    let matches = matches_result?;
-    let count = matches.len();
-    
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "matches": matches,
-        "count": count
-    })))
+    let range = range.into_inner();
+    let (from, to) = parse_date_range(&pool, Some(league_id), range.from.as_deref(), range.to.as_deref()).await?;
+    let filter = MatchFilter {
+        league_id: Some(league_id),
+        statuses: body.and_then(|b| b.into_inner().status).unwrap_or_default(),
+        match_type: range.match_type,
+        participants: range.participant_id.into_iter().collect(),
+        from,
+        to,
+        ..MatchFilter::default()
+    };
+
+    match_page(pool, filter, &paging, range.viewer_id, "league matches").await
 }
 
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/matches")
+            .wrap(from_fn(sessions::require_session))
             .route("", web::post().to(create_match))
             .route("", web::get().to(get_matches))
             .route("/player/{player_id}", web::get().to(get_player_matches))
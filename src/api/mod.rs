@@ -1,26 +1,86 @@
+use actix_web::middleware::from_fn;
 use actix_web::web;
 
-mod auth;
-mod appointments;
-mod leagues;
-mod players;
-mod matches;
+pub mod account;
+pub mod archives;
+pub mod auth;
+pub mod appointments;
+pub mod invitations;
+pub mod leagues;
+pub mod login_attempts;
+pub mod players;
+pub mod profiles;
+pub mod recommendations;
+pub mod matches;
+pub mod notifications;
+pub mod oidc;
+pub mod pagination;
+pub mod seasons;
+pub mod sessions;
+pub mod site_admin;
+pub mod spreadsheets;
+pub mod two_factor;
 
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api")
             .service(web::resource("/register").route(web::post().to(auth::register)))
             .service(web::resource("/login").route(web::post().to(auth::login)))
+            .route("/verify-email", web::post().to(auth::verify_email))
+            .route("/verify-email/resend", web::post().to(auth::resend_verification))
+            // Follows a mailed link, so it does not need a session; registered
+            // before the /players scope, which does
+            .route("/players/email/confirm", web::post().to(profiles::confirm_email_change))
+            .route("/account", web::delete().to(account::delete_account))
+            .route("/account/export", web::get().to(account::export_account))
+            .route("/logout", web::post().to(sessions::logout))
+            .route("/sessions", web::get().to(sessions::list_sessions))
+            .route("/sessions", web::delete().to(sessions::revoke_all_sessions))
+            .route("/sessions/{session_id}", web::delete().to(sessions::revoke_session))
+            .route("/password/change", web::post().to(auth::change_password))
+            .route("/password/forgot", web::post().to(auth::forgot_password))
+            .route("/password/reset", web::post().to(auth::reset_password))
+            .route("/oidc/login", web::get().to(oidc::start_login))
+            .route("/oidc/link", web::post().to(oidc::start_link))
+            .route("/oidc/callback", web::get().to(oidc::callback))
+            .route("/oidc/two-factor", web::post().to(oidc::complete_two_factor))
+            .route("/admin/players", web::get().to(site_admin::list_players))
+            .route("/admin/players/{player_id}/deactivate", web::post().to(site_admin::deactivate_player))
+            .route("/admin/players/{player_id}/reactivate", web::post().to(site_admin::reactivate_player))
+            .route("/admin/leagues", web::get().to(site_admin::list_leagues))
+            .route("/two-factor", web::get().to(two_factor::get_status))
+            .route("/two-factor/enroll", web::post().to(two_factor::enroll))
+            .route("/two-factor/confirm", web::post().to(two_factor::confirm))
+            .route("/two-factor/disable", web::post().to(two_factor::disable))
+            .route("/two-factor/recovery-codes", web::post().to(two_factor::regenerate_recovery_codes))
             // League endpoints:
             .service(
                 web::scope("/leagues")
+                    .wrap(from_fn(sessions::require_session))
                     .route("", web::get().to(leagues::search_leagues))
                     .route("", web::post().to(leagues::create_league))
-                    .route("/{league_id}", web::get().to(leagues::get_league_by_name))
+                    .service(
+                        web::resource("/import")
+                            .app_data(web::JsonConfig::default().limit(archives::ARCHIVE_SIZE_LIMIT))
+                            .route(web::post().to(archives::import_league_archive))
+                    )
+                    .route("/{league_id}", web::get().to(leagues::get_league_by_id))
+                    .route("/{league_id}", web::patch().to(leagues::update_league))
                     .route("/{league_id}/join", web::post().to(leagues::join_league))
+                    .route("/{league_id}/archive", web::get().to(archives::export_league_archive))
+                    .route("/{league_id}/leave", web::delete().to(leagues::leave_league))
                     .route("/{league_id}/join-requests", web::post().to(leagues::create_join_request))
                     .route("/{league_id}/join-requests", web::get().to(leagues::get_league_join_requests))
                     .route("/{league_id}/join-requests/{request_id}", web::patch().to(leagues::update_join_request_status))
+                    .route("/{league_id}/waitlist", web::get().to(leagues::get_league_waitlist))
+                    .route("/{league_id}/invitations", web::post().to(invitations::create_invitation))
+                    .route("/{league_id}/invitations", web::get().to(invitations::get_league_invitations))
+                    .route("/{league_id}/invitations/{invitation_id}", web::delete().to(invitations::revoke_invitation))
+                    // CSV import and export; registered before /members/{player_id} so they are matched first
+                    .route("/{league_id}/members/import", web::post().to(spreadsheets::import_league_members))
+                    .route("/{league_id}/members/export", web::get().to(spreadsheets::export_league_members))
+                    .route("/{league_id}/matches/export", web::get().to(spreadsheets::export_league_matches))
+                    .route("/{league_id}/standings/export", web::get().to(spreadsheets::export_league_standings))
                     .service(
                         web::resource("/{league_id}/players")
                             .route(web::get().to(leagues::get_league_players))
@@ -28,12 +88,24 @@ pub fn init_routes(cfg: &mut web::ServiceConfig) {
                     .service(
                         web::resource("/{league_id}/members/{player_id}")
                             .route(web::put().to(leagues::update_member_role))
+                            .route(web::delete().to(leagues::remove_league_member))
                     )
-                    .route("/{league_id}/players/{player_name}/role", web::get().to(leagues::get_player_league_role)),
+                    .route("/{league_id}/players/{player_id}/role", web::get().to(leagues::get_player_league_role))
+                    // Season endpoints:
+                    .route("/{league_id}/seasons", web::get().to(seasons::get_league_seasons))
+                    .route("/{league_id}/seasons", web::post().to(seasons::create_season))
+                    .route("/{league_id}/seasons/{season_id}", web::get().to(seasons::get_season))
+                    .route("/{league_id}/seasons/{season_id}/divisions", web::post().to(seasons::create_division))
+                    .route("/{league_id}/seasons/{season_id}/members", web::get().to(seasons::get_season_members))
+                    .route("/{league_id}/seasons/{season_id}/members", web::post().to(seasons::add_season_member))
+                    .route("/{league_id}/seasons/{season_id}/members/{player_id}", web::put().to(seasons::assign_member_division))
+                    .route("/{league_id}/seasons/{season_id}/standings", web::get().to(seasons::get_season_standings))
+                    .route("/{league_id}/seasons/{season_id}/close", web::post().to(seasons::close_season)),
             )
             // Other endpoints...
             .configure(players::init_routes)
             .configure(appointments::init_routes)
+            .configure(invitations::init_routes)
             .configure(matches::init_routes)
     );
 }
@@ -0,0 +1,103 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::Utc;
+use diesel::prelude::*;
+use serde::Deserialize;
+
+use crate::db::models::{NewNotification, Notification};
+use crate::db::schema::notifications;
+use crate::db::DbPool;
+use crate::errors::AppError;
+
+#[derive(Deserialize)]
+pub struct NotificationQuery {
+    /// Only return notifications that have not been read yet.
+    pub unread: Option<bool>,
+}
+
+/// Stores a notification for a player. Callers run this inside their own
+/// transaction so the notification is only kept if the change it reports is.
+pub fn notify(
+    conn: &mut PgConnection,
+    player: i32,
+    league: Option<i32>,
+    message: String,
+) -> Result<(), AppError> {
+    let new_notification = NewNotification {
+        player_id: player,
+        message,
+        league_id: league,
+        created_at: Utc::now(),
+    };
+
+    diesel::insert_into(notifications::table)
+        .values(&new_notification)
+        .execute(conn)
+        .map_err(|_| AppError::InternalError)?;
+    Ok(())
+}
+
+/// Lists a player's notifications, newest first.
+pub async fn get_player_notifications(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    query: web::Query<NotificationQuery>,
+) -> Result<impl Responder, AppError> {
+    let player = path.into_inner();
+    let unread_only = query.unread.unwrap_or(false);
+
+    let results = web::block(move || -> Result<Vec<Notification>, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let mut query = notifications::table
+            .filter(notifications::player_id.eq(player))
+            .into_boxed();
+        if unread_only {
+            query = query.filter(notifications::is_read.eq(false));
+        }
+
+        query
+            .order((notifications::created_at.desc(), notifications::notification_id.desc()))
+            .load::<Notification>(&mut conn)
+            .map_err(|_| AppError::InternalError)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error fetching notifications: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(results))
+}
+
+/// Marks one of a player's notifications as read.
+pub async fn mark_notification_read(
+    pool: web::Data<DbPool>,
+    path: web::Path<(i32, i32)>,
+) -> Result<impl Responder, AppError> {
+    let (player, notification_id) = path.into_inner();
+
+    web::block(move || -> Result<(), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let updated = diesel::update(notifications::table.find(notification_id))
+            .filter(notifications::player_id.eq(player))
+            .set(notifications::is_read.eq(true))
+            .execute(&mut conn)
+            .map_err(|_| AppError::InternalError)?;
+
+        if updated == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error updating notification: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Notification marked as read",
+        "success": true
+    })))
+}
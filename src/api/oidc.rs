@@ -0,0 +1,379 @@
+use actix_web::cookie::{time, Cookie, SameSite};
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Responder};
+use bcrypt::DEFAULT_COST;
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::api::login_attempts;
+use crate::api::profiles::{ensure_name_free, find_player_by_email, validate_email};
+use crate::api::sessions::{mark_two_factor_verified, new_token, start_session, ClientInfo, CurrentPlayer};
+use crate::api::two_factor::{check_second_factor, two_factor_enabled};
+use crate::db::models::{Player, PlayerIdentity};
+use crate::db::schema::{player_identities, players};
+use crate::db::DbPool;
+use crate::errors::AppError;
+use crate::oidc::{IdentityClaims, OidcProvider};
+use crate::tokens::{SignedToken, TokenSigner};
+
+/// How long a player has to finish signing in at the provider.
+pub const LOGIN_MINUTES: i64 = 10;
+/// How long a sign-in waits for the player's two-factor code.
+pub const TWO_FACTOR_MINUTES: i64 = 5;
+const STATE: &str = "oidc-state";
+const NONCE: &str = "oidc-nonce";
+const CODE_VERIFIER: &str = "oidc-code-verifier";
+const TWO_FACTOR: &str = "oidc-two-factor";
+/// The cookie that ties a sign-in to the browser that started it.
+pub const BINDING_COOKIE: &str = "oidc_binding";
+
+/// What the callback needs to finish a sign-in started here.
+struct LoginState {
+    nonce: String,
+    code_verifier: String,
+    /// The player linking the identity, or 0 for a plain sign-in.
+    player: i32,
+}
+
+impl LoginState {
+    /// Nothing is stored for a sign-in in progress: the state carries a
+    /// random id with the signer's MAC over it, and the nonce and PKCE
+    /// verifier are MACs over the same id, so only this server can work them
+    /// out again.
+    fn derive(signer: &TokenSigner, id: &str, player: i32, expires_at: DateTime<Utc>) -> Self {
+        LoginState {
+            nonce: signer.sign(NONCE, player, id, expires_at),
+            code_verifier: signer.sign(CODE_VERIFIER, player, id, expires_at),
+            player,
+        }
+    }
+
+    /// Starts a sign-in. The MAC in the state also covers `binding`, a
+    /// random value kept in a cookie of the browser starting the sign-in, so
+    /// a state cannot be finished from another browser: nobody can slip
+    /// their own sign-in, or their own identity to link, into someone else's
+    /// browser.
+    fn start(signer: &TokenSigner, player: i32, binding: &str) -> (String, Self) {
+        let id = new_token();
+        let expires_at = Utc::now() + Duration::minutes(LOGIN_MINUTES);
+        let state = format!("{}.{}", id, signer.sign(STATE, player, &bound(&id, binding), expires_at));
+        (state, Self::derive(signer, &id, player, expires_at))
+    }
+
+    fn resume(signer: &TokenSigner, state: &str, binding: Option<&str>) -> Result<Self, AppError> {
+        let invalid = || AppError::BadRequest("Invalid sign-in state".into());
+        let (id, token) = state.split_once('.').ok_or_else(invalid)?;
+        let token = SignedToken::parse(token).ok_or_else(invalid)?;
+        let binding = binding.ok_or_else(|| AppError::BadRequest("Finish signing in in the browser you started in".into()))?;
+        if !signer.verify(STATE, &token, &bound(id, binding)) {
+            return Err(invalid());
+        }
+        if token.expires_at < Utc::now() {
+            return Err(AppError::BadRequest("The sign-in took too long; start again".into()));
+        }
+        Ok(Self::derive(signer, id, token.player_id, token.expires_at))
+    }
+}
+
+fn bound(id: &str, binding: &str) -> String {
+    format!("{}:{}", id, binding)
+}
+
+/// The cookie holding `binding`. SameSite=Lax still sends it along when the
+/// provider redirects the browser back to the callback.
+fn binding_cookie(req: &HttpRequest, binding: String) -> Cookie<'static> {
+    Cookie::build(BINDING_COOKIE, binding)
+        .path("/api/oidc")
+        .http_only(true)
+        .secure(req.connection_info().scheme() == "https")
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::minutes(LOGIN_MINUTES))
+        .finish()
+}
+
+fn configured(provider: Option<web::Data<OidcProvider>>) -> Result<web::Data<OidcProvider>, AppError> {
+    provider.ok_or_else(|| AppError::BadRequest("Signing in with an identity provider is not set up".into()))
+}
+
+/// The name a new player gets: the provider's display name or the start of
+/// their email address, numbered when someone already has it.
+fn free_name(conn: &mut PgConnection, claims: &IdentityClaims, email: &str) -> Result<String, AppError> {
+    let base = claims
+        .name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| email.split('@').next().unwrap_or(email))
+        .chars()
+        .take(90)
+        .collect::<String>();
+
+    let mut number = 1;
+    loop {
+        let candidate = if number == 1 { base.clone() } else { format!("{} {}", base, number) };
+        match ensure_name_free(conn, &candidate, None) {
+            Err(AppError::Conflict(_)) => number += 1,
+            other => return other.map(|_| candidate),
+        }
+    }
+}
+
+/// Registers a player for an identity seen for the first time. They get a
+/// password nobody knows, which the forgot-password flow can replace, and
+/// their address counts as verified if the provider says it is.
+fn create_player(conn: &mut PgConnection, claims: &IdentityClaims, email: &str) -> Result<Player, AppError> {
+    validate_email(email)?;
+    let name = free_name(conn, claims, email)?;
+    let password_hash = bcrypt::hash(new_token(), DEFAULT_COST).map_err(|_| AppError::InternalError)?;
+    let now = Utc::now();
+
+    Ok(diesel::insert_into(players::table)
+        .values((
+            players::name.eq(&name),
+            players::email.eq(email),
+            players::password.eq(&password_hash),
+            players::created_at.eq(now),
+            players::time_zone.eq("UTC"),
+            players::email_verified_at.eq(claims.email_verified().then_some(now)),
+        ))
+        .get_result::<Player>(conn)?)
+}
+
+/// Finds the player an identity belongs to, linking it on first use: to the
+/// player linking it (`link_to`), to the player with the same email address
+/// when both sides have verified it, or else to a new player. Returns the
+/// player and whether they were created.
+pub fn resolve_identity(
+    conn: &mut PgConnection,
+    issuer: &str,
+    claims: &IdentityClaims,
+    link_to: Option<i32>,
+) -> Result<(Player, bool), AppError> {
+    conn.transaction(|conn| {
+        let now = Utc::now();
+        let known = player_identities::table
+            .filter(player_identities::issuer.eq(issuer))
+            .filter(player_identities::subject.eq(&claims.sub))
+            .first::<PlayerIdentity>(conn)
+            .optional()?;
+        if let Some(identity) = known {
+            if link_to.is_some_and(|player| player != identity.player_id) {
+                return Err(AppError::Conflict("This identity is already linked to another player".into()));
+            }
+            diesel::update(player_identities::table.find(identity.identity_id))
+                .set((player_identities::email.eq(&claims.email), player_identities::last_login_at.eq(now)))
+                .execute(conn)?;
+            return Ok((players::table.find(identity.player_id).first::<Player>(conn)?, false));
+        }
+
+        let (player, created) = match link_to {
+            Some(player) => (players::table.find(player).first::<Player>(conn)?, false),
+            None => {
+                let email = claims
+                    .email
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|email| !email.is_empty())
+                    .ok_or_else(|| AppError::BadRequest("The identity provider did not share an email address".into()))?;
+                match find_player_by_email(conn, email)? {
+                    Some(player) if claims.email_verified() && player.email_verified_at.is_some() => (player, false),
+                    Some(_) => {
+                        return Err(AppError::Conflict(format!(
+                            "{} is already registered; sign in with your password and link the identity from there",
+                            email
+                        )))
+                    }
+                    None => (create_player(conn, claims, email)?, true),
+                }
+            }
+        };
+
+        diesel::insert_into(player_identities::table)
+            .values((
+                player_identities::player_id.eq(player.player_id),
+                player_identities::issuer.eq(issuer),
+                player_identities::subject.eq(&claims.sub),
+                player_identities::email.eq(&claims.email),
+                player_identities::created_at.eq(now),
+                player_identities::last_login_at.eq(now),
+            ))
+            .execute(conn)?;
+        Ok((player, created))
+    })
+}
+
+/// Opens a session, answering like a password login. `two_factor_verified`
+/// says whether the player gave a two-factor code on the way.
+fn sign_in(
+    conn: &mut PgConnection,
+    player: &Player,
+    client: &ClientInfo,
+    two_factor_verified: bool,
+) -> Result<serde_json::Value, AppError> {
+    let (token, session) = start_session(conn, player.player_id, client)?;
+    if two_factor_verified {
+        mark_two_factor_verified(conn, session.session_id)?;
+    }
+    Ok(json!({
+        "message": "Login successful",
+        "user_id": player.player_id,
+        "token": token,
+        "expires_at": session.expires_at,
+        "email_verified": player.email_verified_at.is_some(),
+    }))
+}
+
+/// Sends the player to the identity provider to sign in.
+pub async fn start_login(
+    req: HttpRequest,
+    provider: Option<web::Data<OidcProvider>>,
+    signer: web::Data<TokenSigner>,
+) -> Result<impl Responder, AppError> {
+    let provider = configured(provider)?;
+    let binding = new_token();
+    let (state, login) = LoginState::start(&signer, 0, &binding);
+    let url = provider.authorization_url(&state, &login.nonce, &login.code_verifier).await?;
+
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, url))
+        .cookie(binding_cookie(&req, binding))
+        .finish())
+}
+
+/// Where to send the signed-in player so the identity they sign in with
+/// there is linked to their account. The answer sets the same cookie as
+/// `start_login`, so the browser has to keep it for the callback.
+pub async fn start_link(
+    req: HttpRequest,
+    current: CurrentPlayer,
+    provider: Option<web::Data<OidcProvider>>,
+    signer: web::Data<TokenSigner>,
+) -> Result<impl Responder, AppError> {
+    let provider = configured(provider)?;
+    let binding = new_token();
+    let (state, login) = LoginState::start(&signer, current.player_id, &binding);
+    let url = provider.authorization_url(&state, &login.nonce, &login.code_verifier).await?;
+
+    Ok(HttpResponse::Ok()
+        .cookie(binding_cookie(&req, binding))
+        .json(json!({ "authorization_url": url })))
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    /// Set by the provider instead of `code` when the sign-in failed.
+    pub error: Option<String>,
+}
+
+/// Finishes a sign-in at the provider. Players with two-factor
+/// authentication on get a short-lived `two_factor_token` instead of a
+/// session, to trade for one with a code at `POST /api/oidc/two-factor`.
+pub async fn callback(
+    req: HttpRequest,
+    query: web::Query<CallbackQuery>,
+    provider: Option<web::Data<OidcProvider>>,
+    signer: web::Data<TokenSigner>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let provider = configured(provider)?;
+    let query = query.into_inner();
+    if let Some(error) = query.error {
+        return Err(AppError::Unauthorized(format!("The identity provider refused the sign-in: {}", error)));
+    }
+    let (Some(code), Some(state)) = (query.code, query.state) else {
+        return Err(AppError::BadRequest("Both code and state are required".into()));
+    };
+    let binding = req.cookie(BINDING_COOKIE);
+    let login = LoginState::resume(&signer, &state, binding.as_ref().map(Cookie::value))?;
+    let claims = provider.exchange_code(&code, &login.code_verifier, &login.nonce).await?;
+    let issuer = provider.issuer().to_string();
+    let client = ClientInfo::from_request(&req);
+
+    let response = web::block(move || -> Result<serde_json::Value, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+        let ip_address = client.ip_address.as_deref();
+
+        let link_to = (login.player != 0).then_some(login.player);
+        let (player, created) = resolve_identity(&mut conn, &issuer, &claims, link_to)?;
+        // Recorded like password logins, so they show up in the same history
+        let tracked_email = login_attempts::normalize_email(&player.email);
+        if player.deactivated_at.is_some() {
+            login_attempts::record_attempt(&mut conn, &tracked_email, Some(player.player_id), ip_address, "deactivated")?;
+            return Err(AppError::Forbidden("This account has been deactivated".into()));
+        }
+        if two_factor_enabled(&mut conn, player.player_id)? {
+            login_attempts::record_attempt(&mut conn, &tracked_email, Some(player.player_id), ip_address, "code_required")?;
+            let expires_at = Utc::now() + Duration::minutes(TWO_FACTOR_MINUTES);
+            return Ok(json!({
+                "message": "Enter a code from your authenticator app to finish signing in",
+                "two_factor_required": true,
+                "two_factor_token": signer.sign(TWO_FACTOR, player.player_id, "", expires_at),
+            }));
+        }
+        let mut response = sign_in(&mut conn, &player, &client, false)?;
+        login_attempts::record_attempt(&mut conn, &tracked_email, Some(player.player_id), ip_address, "success")?;
+        response["new_player"] = json!(created);
+        Ok(response)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error finishing identity provider sign-in: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    // The state has been used; the cookie is no longer needed
+    let mut spent = binding_cookie(&req, String::new());
+    spent.make_removal();
+    Ok(HttpResponse::Ok().cookie(spent).json(response))
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorInput {
+    pub two_factor_token: String,
+    /// A code from the authenticator app or a recovery code.
+    pub code: String,
+}
+
+/// Finishes an identity provider sign-in for a player with two-factor
+/// authentication on. Wrong codes count towards the player's login delays.
+pub async fn complete_two_factor(
+    req: HttpRequest,
+    item: web::Json<TwoFactorInput>,
+    signer: web::Data<TokenSigner>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let item = item.into_inner();
+    let client = ClientInfo::from_request(&req);
+    let ip_address = client.ip_address.clone();
+    let token = SignedToken::parse(&item.two_factor_token)
+        .filter(|token| signer.verify(TWO_FACTOR, token, ""))
+        .ok_or_else(|| AppError::BadRequest("Invalid two-factor token".into()))?;
+    if token.expires_at < Utc::now() {
+        return Err(AppError::BadRequest("The sign-in took too long; start again".into()));
+    }
+
+    let response = web::block(move || -> Result<serde_json::Value, AppError> {
+        let conn = &mut pool.get().map_err(|_| AppError::InternalError)?;
+        let ip_address = ip_address.as_deref();
+
+        let player = players::table.find(token.player_id).first::<Player>(conn)?;
+        let tracked_email = login_attempts::normalize_email(&player.email);
+        let attempt = login_attempts::begin_attempt(conn, &tracked_email, ip_address)?;
+        if !check_second_factor(conn, player.player_id, &item.code)? {
+            login_attempts::finish_attempt(conn, attempt, Some(player.player_id), "bad_code")?;
+            return Err(AppError::Unauthorized("Invalid two-factor code".into()));
+        }
+        login_attempts::finish_attempt(conn, attempt, Some(player.player_id), "success")?;
+        sign_in(conn, &player, &client, true)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error finishing identity provider sign-in: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+use crate::errors::AppError;
+
+pub const DEFAULT_PER_PAGE: i64 = 50;
+pub const MAX_PER_PAGE: i64 = 200;
+
+/// Paging and sorting parameters shared by list endpoints. Read it as a
+/// second `web::Query` next to the endpoint's own filters.
+#[derive(Debug, Default, Deserialize)]
+pub struct PageQuery {
+    /// 1-based page number; defaults to the first page.
+    pub page: Option<i64>,
+    /// Items per page, up to `MAX_PER_PAGE`.
+    pub per_page: Option<i64>,
+    /// Field to sort by; prefix it with `-` for descending order.
+    pub sort: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    pub page: i64,
+    pub per_page: i64,
+}
+
+impl Page {
+    pub fn offset(&self) -> i64 {
+        (self.page - 1) * self.per_page
+    }
+}
+
+/// A requested sort order, checked against the fields an endpoint allows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sort {
+    pub field: String,
+    pub descending: bool,
+}
+
+impl PageQuery {
+    pub fn page(&self) -> Result<Page, AppError> {
+        let page = self.page.unwrap_or(1);
+        let per_page = self.per_page.unwrap_or(DEFAULT_PER_PAGE);
+
+        if page < 1 {
+            return Err(AppError::BadRequest("page must be at least 1".into()));
+        }
+        if !(1..=MAX_PER_PAGE).contains(&per_page) {
+            return Err(AppError::BadRequest(format!("per_page must be between 1 and {}", MAX_PER_PAGE)));
+        }
+        Ok(Page { page, per_page })
+    }
+
+    /// Parses `sort`, falling back to `default` (ascending) when it is not given.
+    pub fn sort(&self, allowed: &[&str], default: &str) -> Result<Sort, AppError> {
+        let requested = self.sort.as_deref().unwrap_or(default);
+        let (field, descending) = match requested.strip_prefix('-') {
+            Some(field) => (field, true),
+            None => (requested, false),
+        };
+
+        if !allowed.contains(&field) {
+            return Err(AppError::BadRequest(format!(
+                "Cannot sort by '{}', expected one of: {}",
+                field,
+                allowed.join(", ")
+            )));
+        }
+        Ok(Sort { field: field.to_string(), descending })
+    }
+}
+
+/// Page metadata returned next to a page of results.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct PageMeta {
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+    pub total_pages: i64,
+}
+
+impl PageMeta {
+    pub fn new(page: Page, total: i64) -> Self {
+        PageMeta {
+            page: page.page,
+            per_page: page.per_page,
+            total,
+            total_pages: (total + page.per_page - 1) / page.per_page,
+        }
+    }
+}
@@ -1,89 +1,554 @@
+use std::collections::HashMap;
+
+use actix_web::middleware::from_fn;
 use actix_web::{web, HttpResponse, Responder};
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use serde::Deserialize;
 
 use paperclip::actix::*;
-use crate::db::{DbPool, models::Player};
+use crate::api::{notifications, profiles, recommendations, sessions, spreadsheets};
+use crate::api::pagination::{PageMeta, PageQuery};
+use crate::api::profiles::{are_teammates, PlayerSummary};
+use crate::api::sessions::CurrentPlayer;
+use crate::api::site_admin::{set_role, SiteAdmin};
+use crate::calendar::{parse_time_zone, render_ics, zone_or_utc, CalendarEvent, MATCH_DURATION_MINUTES};
+use crate::db::{DbPool, models::{Match, Player, Role}};
+use crate::errors::AppError;
+use crate::search::{is_free_on, opponents, skill_rating, Busy, Candidate, RankedPlayer};
 use diesel::prelude::*;
 
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/players")
+            .wrap(from_fn(sessions::require_session))
             .route("", web::get().to(get_all_players))
             .route("/search", web::get().to(search_players))
+            .route("/import", web::post().to(spreadsheets::import_players))
+            .route("/{player_id}", web::get().to(profiles::get_profile))
+            .route("/{player_id}", web::patch().to(profiles::update_profile))
+            .route("/{player_id}/email", web::post().to(profiles::request_email_change))
             .route("/{player_id}/calendar", web::get().to(get_calendar))
-            .route("/{player_id}/role", web::patch().to(update_player_role)),
+            .route("/{player_id}/recommendations", web::get().to(recommendations::get_recommendations))
+            .route("/{player_id}/notifications", web::get().to(notifications::get_player_notifications))
+            .route("/{player_id}/notifications/{notification_id}/read", web::post().to(notifications::mark_notification_read))
+            .route("/{player_id}/role", web::patch().to(update_player_role))
+            .route("/{player_id}/time-zone", web::patch().to(update_player_time_zone)),
     );
 }
 
-#[api_v2_operation]
-pub async fn get_all_players(pool: web::Data<DbPool>) -> impl Responder {
-    let conn = &mut pool.get().expect("Failed to get DB connection");
-
-    use crate::db::schema::players::dsl::*;
+/// SQL functions used by the player search. `skill_rating`, which mirrors
+/// `search::skill_rating`, and `match_tier` come from the
+/// `add_player_search_functions` migration.
+mod sql {
+    use diesel::sql_types::{Float, Nullable, Text};
 
-    match players.load::<Player>(conn) {
-        Ok(all_players) => HttpResponse::Ok().json(all_players),
-        Err(_) => HttpResponse::InternalServerError().json("Failed to fetch players")
+    diesel::define_sql_function! {
+        fn lower(text: Text) -> Text;
+    }
+    diesel::define_sql_function! {
+        fn abs(value: Nullable<Float>) -> Nullable<Float>;
+    }
+    diesel::define_sql_function! {
+        fn skill_rating(level: Nullable<Text>) -> Nullable<Float>;
+    }
+    diesel::define_sql_function! {
+        fn match_tier(value: Text, term: Text) -> diesel::sql_types::Integer;
     }
 }
 
-#[api_v2_operation]
+#[derive(Deserialize, Apiv2Schema)]
+pub struct PlayerQuery {
+    /// Part of the player's name, matched case-insensitively.
+    name: Option<String>,
+    skill_level: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    /// Part of the player's name.
+    name: Option<String>,
+    /// The player's email address, matched in full.
+    email: Option<String>,
+    skill_level: Option<String>,
+    /// Lowest skill level, as an NTRP rating like `3.5` or a named level.
+    skill_min: Option<String>,
+    /// Highest skill level, as for `skill_min`.
+    skill_max: Option<String>,
+    /// Only members of this league.
+    league_id: Option<i32>,
+    /// Only players without a match or appointment on this day, in their own time zone.
+    available_on: Option<NaiveDate>,
+    /// Player looking for opponents. They are left out of the results, and
+    /// players closest to their skill level come first.
+    player_id: Option<i32>,
+    /// With `player_id`, leave out players they already played a completed match against.
+    unplayed: Option<bool>,
+}
+
+/// Fields player listings can be sorted by.
+pub const PLAYER_SORT_FIELDS: [&str; 4] = ["name", "player_id", "skill_level", "created_at"];
+
+/// Finds players by any combination of name, email, skill, league membership
+/// and availability, best matches first. Meant for finding opponents; only
+/// active accounts are listed.
 pub async fn search_players(
     query: web::Query<SearchQuery>,
+    paging: web::Query<PageQuery>,
     pool: web::Data<DbPool>
-) -> impl Responder {
-    let conn = &mut pool.get().expect("Failed to get DB connection");
-
-    use crate::db::schema::players::dsl::*;
-    
-    let search_pattern = format!("%{}%", query.name.to_lowercase());
-    
-    match players
-        .filter(name.ilike(&search_pattern))
-        .load::<Player>(conn) 
-    {
-        Ok(found_players) => HttpResponse::Ok().json(found_players),
-        Err(_) => HttpResponse::InternalServerError().json("Failed to search players")
+) -> Result<impl Responder, AppError> {
+    let query = query.into_inner();
+    let page = paging.page()?;
+    let sort = paging.sort(&["relevance", "name"], "relevance")?;
+
+    let parse_skill = |level: Option<&str>| {
+        level
+            .map(|level| skill_rating(level).ok_or_else(|| AppError::BadRequest(format!(
+                "Unknown skill level '{}', expected an NTRP rating like 3.5 or one of beginner, intermediate, advanced, expert",
+                level
+            ))))
+            .transpose()
+    };
+    let skill_min = parse_skill(query.skill_min.as_deref())?;
+    let skill_max = parse_skill(query.skill_max.as_deref())?;
+    if let (Some(min), Some(max)) = (skill_min, skill_max) {
+        if min > max {
+            return Err(AppError::BadRequest("skill_min must not be above skill_max".into()));
+        }
+    }
+    if query.unplayed.unwrap_or(false) && query.player_id.is_none() {
+        return Err(AppError::BadRequest("unplayed needs a player_id".into()));
     }
+
+    let (results, total) = web::block(move || -> Result<(Vec<RankedPlayer>, i64), AppError> {
+        use crate::db::schema::{matches, player_leagues, players};
+
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let searcher_level = match query.player_id {
+            Some(searcher) => players::table
+                .find(searcher)
+                .select(players::skill_level)
+                .first::<Option<String>>(&mut conn)
+                .optional()
+                .map_err(|_| AppError::InternalError)?
+                .ok_or(AppError::NotFound)?,
+            None => None,
+        };
+        let rating = searcher_level.as_deref().and_then(skill_rating);
+
+        let played: Vec<i32> = match (query.player_id, query.unplayed.unwrap_or(false)) {
+            (Some(searcher), true) => matches::table
+                .filter(matches::status.eq("Completed"))
+                .filter(
+                    matches::player1_id.eq(searcher)
+                    .or(matches::player2_id.eq(searcher))
+                    .or(matches::team1_player1_id.eq(searcher))
+                    .or(matches::team1_player2_id.eq(searcher))
+                    .or(matches::team2_player1_id.eq(searcher))
+                    .or(matches::team2_player2_id.eq(searcher))
+                )
+                .load::<Match>(&mut conn)
+                .map_err(|_| AppError::InternalError)?
+                .iter()
+                .flat_map(|m| opponents(m, searcher))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let booked = match query.available_on {
+            Some(date) => booked_on(&mut conn, date)?,
+            None => Vec::new(),
+        };
+
+        let filtered = || {
+            let mut candidates = players::table
+                .filter(players::deactivated_at.is_null())
+                .into_boxed();
+            if let Some(name) = &query.name {
+                candidates = candidates.filter(players::name.ilike(format!("%{}%", name)));
+            }
+            if let Some(email) = &query.email {
+                candidates = candidates.filter(sql::lower(players::email).eq(email.trim().to_lowercase()));
+            }
+            if let Some(skill_level) = &query.skill_level {
+                candidates = candidates.filter(players::skill_level.ilike(skill_level.clone()));
+            }
+            if let Some(min) = skill_min {
+                candidates = candidates.filter(sql::skill_rating(players::skill_level).ge(min));
+            }
+            if let Some(max) = skill_max {
+                candidates = candidates.filter(sql::skill_rating(players::skill_level).le(max));
+            }
+            if let Some(league) = query.league_id {
+                let members = player_leagues::table
+                    .filter(player_leagues::league_id.eq(league))
+                    .select(player_leagues::player_id);
+                candidates = candidates.filter(players::player_id.eq_any(members));
+            }
+            if let Some(searcher) = query.player_id {
+                candidates = candidates.filter(players::player_id.ne(searcher));
+            }
+            if !played.is_empty() {
+                candidates = candidates.filter(players::player_id.ne_all(&played));
+            }
+            if !booked.is_empty() {
+                candidates = candidates.filter(players::player_id.ne_all(&booked));
+            }
+            candidates
+        };
+
+        let total = filtered()
+            .count()
+            .get_result::<i64>(&mut conn)
+            .map_err(|_| AppError::InternalError)?;
+
+        // Closest name match first, then closest skill with unrated players
+        // last, then name
+        let tier = || sql::match_tier(players::name, query.name.clone().unwrap_or_default());
+        let difference = || sql::abs(sql::skill_rating(players::skill_level) - rating);
+        let found = filtered();
+        let found = match (sort.field.as_str(), sort.descending) {
+            ("name", false) => found.order((sql::lower(players::name).asc(), players::player_id.asc())),
+            ("name", true) => found.order((sql::lower(players::name).desc(), players::player_id.desc())),
+            (_, false) => found.order((
+                tier().asc(),
+                difference().asc().nulls_last(),
+                sql::lower(players::name).asc(),
+                players::player_id.asc(),
+            )),
+            (_, true) => found.order((
+                tier().desc(),
+                difference().desc().nulls_first(),
+                sql::lower(players::name).desc(),
+                players::player_id.desc(),
+            )),
+        };
+
+        let results = found
+            .limit(page.per_page)
+            .offset(page.offset())
+            .select(((players::player_id, players::name, players::skill_level, players::time_zone), difference()))
+            .load::<(Candidate, Option<f32>)>(&mut conn)
+            .map_err(|_| AppError::InternalError)?
+            .into_iter()
+            .map(|(player, skill_difference)| RankedPlayer { player, skill_difference })
+            .collect();
+        Ok((results, total))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error searching players: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "count": results.len(),
+        "players": results,
+        "pagination": PageMeta::new(page, total)
+    })))
 }
 
-#[derive(Deserialize, Apiv2Schema)]
-pub struct SearchQuery {
-    name: String,
+/// Players with a pending or scheduled match or an appointment on `date`,
+/// taken in each player's own time zone.
+fn booked_on(conn: &mut PgConnection, date: NaiveDate) -> Result<Vec<i32>, AppError> {
+    use crate::db::schema::players;
+
+    // Wide enough to cover the day in every time zone
+    let window_start = date.and_time(NaiveTime::MIN).and_utc() - Duration::days(1);
+    let window_end = window_start + Duration::days(3);
+    let busy = booked_times(conn, None, window_start, window_end)?;
+
+    let zones: HashMap<i32, String> = players::table
+        .filter(players::player_id.eq_any(busy.keys().copied().collect::<Vec<_>>()))
+        .select((players::player_id, players::time_zone))
+        .load::<(i32, String)>(conn)
+        .map_err(|_| AppError::InternalError)?
+        .into_iter()
+        .collect();
+    Ok(busy
+        .into_iter()
+        .filter(|(id, slots)| !is_free_on(date, zone_or_utc(zones.get(id).map_or("UTC", String::as_str)), slots))
+        .map(|(id, _)| id)
+        .collect())
+}
+
+/// The times each of `players`, or everyone with `None`, is booked between
+/// `from` and `to`: their pending and scheduled matches, and appointments
+/// that are not cancelled.
+pub fn booked_times(
+    conn: &mut PgConnection,
+    players: Option<&[i32]>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<HashMap<i32, Vec<Busy>>, AppError> {
+    use crate::db::schema::{appointments, matches};
+
+    let mut busy: HashMap<i32, Vec<Busy>> = HashMap::new();
+
+    let mut booked = matches::table
+        .filter(matches::status.ne_all(["Cancelled", "Rejected"]))
+        .filter(matches::datetime.lt(to))
+        .filter(matches::datetime.gt(from - Duration::minutes(MATCH_DURATION_MINUTES)))
+        .into_boxed();
+    if let Some(players) = players {
+        booked = booked.filter(
+            matches::player1_id.eq_any(players)
+            .or(matches::player2_id.eq_any(players))
+            .or(matches::team1_player1_id.eq_any(players))
+            .or(matches::team1_player2_id.eq_any(players))
+            .or(matches::team2_player1_id.eq_any(players))
+            .or(matches::team2_player2_id.eq_any(players))
+        );
+    }
+    let booked = booked
+        .load::<Match>(conn)
+        .map_err(|_| AppError::InternalError)?;
+    for m in booked {
+        let slot = (m.datetime, m.datetime + Duration::minutes(MATCH_DURATION_MINUTES));
+        for id in [m.player1_id, m.player2_id, m.team1_player1_id, m.team1_player2_id, m.team2_player1_id, m.team2_player2_id]
+            .into_iter()
+            .flatten()
+        {
+            busy.entry(id).or_default().push(slot);
+        }
+    }
+
+    let mut booked_appointments = appointments::table
+        .filter(appointments::status.ne_all(["cancelled", "rejected", "declined"]))
+        .filter(appointments::start_time.lt(to))
+        .filter(appointments::end_time.gt(from))
+        .into_boxed();
+    if let Some(players) = players {
+        booked_appointments = booked_appointments
+            .filter(appointments::requester_id.eq_any(players).or(appointments::opponent_id.eq_any(players)));
+    }
+    let booked_appointments = booked_appointments
+        .select((appointments::requester_id, appointments::opponent_id, appointments::start_time, appointments::end_time))
+        .load::<(i32, i32, DateTime<Utc>, DateTime<Utc>)>(conn)
+        .map_err(|_| AppError::InternalError)?;
+    for (requester, opponent, start, end) in booked_appointments {
+        busy.entry(requester).or_default().push((start, end));
+        busy.entry(opponent).or_default().push((start, end));
+    }
+
+    Ok(busy)
 }
 
-pub async fn get_calendar(path: web::Path<i32>) -> impl Responder {
-    // Retrieve player's calendar from the database.
-    HttpResponse::Ok().json("Player calendar data")
+/// Lists players, optionally only those whose name contains `name` or with
+/// the given `skill_level`.
+pub async fn get_all_players(
+    query: web::Query<PlayerQuery>,
+    paging: web::Query<PageQuery>,
+    pool: web::Data<DbPool>
+) -> Result<impl Responder, AppError> {
+    let PlayerQuery { name, skill_level } = query.into_inner();
+    let page = paging.page()?;
+    let sort = paging.sort(&PLAYER_SORT_FIELDS, "name")?;
+
+    let (found, total) = web::block(move || -> Result<(Vec<PlayerSummary>, i64), AppError> {
+        use crate::db::schema::players;
+
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let filtered = || {
+            let mut query = players::table.into_boxed();
+            if let Some(name) = &name {
+                query = query.filter(players::name.ilike(format!("%{}%", name)));
+            }
+            if let Some(skill_level) = &skill_level {
+                query = query.filter(players::skill_level.ilike(skill_level.clone()));
+            }
+            query
+        };
+
+        let total = filtered()
+            .count()
+            .get_result::<i64>(&mut conn)
+            .map_err(|_| AppError::InternalError)?;
+
+        let query = filtered();
+        let query = match (sort.field.as_str(), sort.descending) {
+            ("player_id", false) => query.order(players::player_id.asc()),
+            ("player_id", true) => query.order(players::player_id.desc()),
+            ("skill_level", false) => query.order((players::skill_level.asc(), players::player_id.asc())),
+            ("skill_level", true) => query.order((players::skill_level.desc(), players::player_id.desc())),
+            ("created_at", false) => query.order((players::created_at.asc(), players::player_id.asc())),
+            ("created_at", true) => query.order((players::created_at.desc(), players::player_id.desc())),
+            (_, false) => query.order((players::name.asc(), players::player_id.asc())),
+            (_, true) => query.order((players::name.desc(), players::player_id.desc())),
+        };
+
+        let found = query
+            .limit(page.per_page)
+            .offset(page.offset())
+            .select((players::player_id, players::name, players::skill_level, players::avatar_url, players::time_zone))
+            .load::<PlayerSummary>(&mut conn)
+            .map_err(|_| AppError::InternalError)?;
+        Ok((found, total))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error fetching players: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "count": found.len(),
+        "players": found,
+        "pagination": PageMeta::new(page, total)
+    })))
 }
 
-#[derive(Deserialize, Apiv2Schema)]
+/// Exports a player's upcoming and past matches as an iCalendar feed, with
+/// times in the player's time zone. Cancelled and rejected matches are left out.
+/// Only the player and their league teammates can read it.
+pub async fn get_calendar(
+    current: CurrentPlayer,
+    path: web::Path<i32>,
+    pool: web::Data<DbPool>
+) -> Result<HttpResponse, AppError> {
+    let player = path.into_inner();
+
+    let calendar = web::block(move || -> Result<String, AppError> {
+        use crate::db::schema::{leagues, matches, players};
+
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let zone_name = players::table
+            .find(player)
+            .select(players::time_zone)
+            .first::<String>(&mut conn)
+            .optional()
+            .map_err(|_| AppError::InternalError)?
+            .ok_or(AppError::NotFound)?;
+        if current.player_id != player && !are_teammates(&mut conn, player, current.player_id)? {
+            return Err(AppError::Forbidden("Only the player and their teammates can see this calendar".into()));
+        }
+
+        let found = matches::table
+            .filter(
+                matches::player1_id.eq(player)
+                .or(matches::player2_id.eq(player))
+                .or(matches::team1_player1_id.eq(player))
+                .or(matches::team1_player2_id.eq(player))
+                .or(matches::team2_player1_id.eq(player))
+                .or(matches::team2_player2_id.eq(player))
+            )
+            .filter(matches::status.ne_all(["Cancelled", "Rejected"]))
+            .order((matches::datetime.asc(), matches::id.asc()))
+            .load::<Match>(&mut conn)
+            .map_err(|_| AppError::InternalError)?;
+
+        let league_names: HashMap<i32, String> = leagues::table
+            .filter(leagues::league_id.eq_any(found.iter().map(|m| m.league_id).collect::<Vec<_>>()))
+            .select((leagues::league_id, leagues::league_name))
+            .load::<(i32, String)>(&mut conn)
+            .map_err(|_| AppError::InternalError)?
+            .into_iter()
+            .collect();
+
+        let events: Vec<CalendarEvent> = found
+            .into_iter()
+            .map(|m| CalendarEvent {
+                uid: format!("match-{}@tennis-scheduler", m.id),
+                start: m.datetime,
+                end: m.datetime + Duration::minutes(MATCH_DURATION_MINUTES),
+                summary: match league_names.get(&m.league_id) {
+                    Some(league) => format!("{} match ({})", m.match_type, league),
+                    None => format!("{} match", m.match_type),
+                },
+                location: Some(m.location),
+                confirmed: m.status != "Pending",
+            })
+            .collect();
+
+        Ok(render_ics(&events, zone_or_utc(&zone_name), Utc::now()))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error exporting calendar: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .body(calendar))
+}
+
+#[derive(Deserialize)]
 pub struct UpdateRoleInput {
-    pub role: String,
+    pub role: Role,
 }
 
-#[api_v2_operation]
+/// Changes a player's site role. Only site admins can do this, and not to
+/// themselves, so the site cannot be left without one by accident.
 pub async fn update_player_role(
-    path: web::Path<String>,
+    admin: SiteAdmin,
+    path: web::Path<i32>,
     item: web::Json<UpdateRoleInput>,
     pool: web::Data<DbPool>
-) -> impl Responder {
-    let playername = path.into_inner();
-    let conn = &mut pool.get().expect("Failed to get DB connection");
-
-    use crate::db::schema::players::dsl::*;
-
-    match diesel::update(players)
-        .filter(name.eq(&playername))
-        .set(role.eq(&item.role))
-        .execute(conn)
-    {
-        Ok(_) => HttpResponse::Ok().json("Role updated successfully"),
-        Err(error) => {
-            println!("Failed to update role: {:?}", error);
-            HttpResponse::InternalServerError().json("Failed to update role")
-        }
+) -> Result<impl Responder, AppError> {
+    let player = path.into_inner();
+    if player == admin.player_id {
+        return Err(AppError::BadRequest("You cannot change your own role".into()));
     }
+    let role = item.into_inner().role;
+
+    let updated = web::block(move || -> Result<Player, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+        set_role(&mut conn, player, role)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error updating role: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+#[derive(Deserialize, Apiv2Schema)]
+pub struct UpdateTimeZoneInput {
+    pub time_zone: String,
 }
 
+/// Sets the time zone a player's match times are shown in. Players can only
+/// change their own.
+pub async fn update_player_time_zone(
+    path: web::Path<i32>,
+    item: web::Json<UpdateTimeZoneInput>,
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>
+) -> Result<impl Responder, AppError> {
+    let player = path.into_inner();
+    if player != current.player_id {
+        return Err(AppError::Forbidden("Players can only change their own time zone".into()));
+    }
+    let zone = parse_time_zone(&item.time_zone)?;
+
+    web::block(move || -> Result<(), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        use crate::db::schema::players::dsl::*;
 
+        let updated = diesel::update(players.find(player))
+            .set(time_zone.eq(zone.name()))
+            .execute(&mut conn)
+            .map_err(|_| AppError::InternalError)?;
+
+        if updated == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error updating time zone: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Time zone updated successfully",
+        "time_zone": zone.name(),
+        "success": true
+    })))
+}
@@ -0,0 +1,488 @@
+use actix_web::{web, HttpResponse, Responder};
+use bcrypt::verify;
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::api::sessions::{hash_token, new_token, CurrentPlayer};
+use crate::calendar::parse_time_zone;
+use crate::db::models::{EmailChange, NewEmailChange, Player};
+use crate::db::schema::{email_changes, leagues, player_leagues, players};
+use crate::db::DbPool;
+use crate::errors::AppError;
+use crate::mailer::{Email, Mailer};
+use crate::search::skill_rating;
+
+/// How long the link mailed for an email change stays valid.
+pub const EMAIL_CHANGE_HOURS: i64 = 24;
+
+/// What anyone can see of a player.
+#[derive(Serialize)]
+pub struct Profile {
+    pub player_id: i32,
+    pub name: String,
+    pub skill_level: Option<String>,
+    pub avatar_url: Option<String>,
+    pub time_zone: String,
+    pub created_at: DateTime<Utc>,
+    pub leagues: Vec<ProfileLeague>,
+    /// `private` when the viewer may see contact details, otherwise `public`.
+    pub visibility: &'static str,
+    #[serde(flatten)]
+    pub contact: Option<Contact>,
+}
+
+/// Contact details, only shown to the player and their league teammates.
+#[derive(Serialize)]
+pub struct Contact {
+    pub email: String,
+    pub email_verified: bool,
+    pub phone: Option<String>,
+}
+
+/// A player as shown in listings, without contact details.
+#[derive(Serialize, Queryable)]
+pub struct PlayerSummary {
+    pub player_id: i32,
+    pub name: String,
+    pub skill_level: Option<String>,
+    pub avatar_url: Option<String>,
+    pub time_zone: String,
+}
+
+#[derive(Serialize, Queryable)]
+pub struct ProfileLeague {
+    pub league_id: i32,
+    pub league_name: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateProfileInput {
+    pub name: Option<String>,
+    /// An NTRP rating like `3.5` or beginner, intermediate, advanced or expert;
+    /// an empty string clears it.
+    pub skill_level: Option<String>,
+    /// An empty string clears it.
+    pub phone: Option<String>,
+    /// An http(s) link to a picture; an empty string clears it.
+    pub avatar_url: Option<String>,
+    pub time_zone: Option<String>,
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = crate::db::schema::players)]
+struct ProfileChanges {
+    name: Option<String>,
+    skill_level: Option<Option<String>>,
+    phone: Option<Option<String>>,
+    avatar_url: Option<Option<String>>,
+    time_zone: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailInput {
+    pub new_email: String,
+    /// The player's current password.
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmEmailInput {
+    pub token: String,
+}
+
+pub fn validate_name(name: &str) -> Result<(), AppError> {
+    match name.trim().chars().count() {
+        0 => Err(AppError::BadRequest("Name must not be empty".into())),
+        1..=100 => Ok(()),
+        _ => Err(AppError::BadRequest("Name must be at most 100 characters".into())),
+    }
+}
+
+/// An NTRP rating such as `3.5`, or one of the named levels.
+pub fn validate_skill_level(level: &str) -> Result<(), AppError> {
+    match skill_rating(level) {
+        Some(_) => Ok(()),
+        None => Err(AppError::BadRequest(format!(
+            "Unknown skill level '{}', expected an NTRP rating like 3.5 or one of beginner, intermediate, advanced, expert",
+            level
+        ))),
+    }
+}
+
+/// A plain sanity check: one `@`, something before it, a dotted domain
+/// after it and no whitespace. Whether the address works is checked by
+/// mailing it.
+pub fn validate_email(email: &str) -> Result<(), AppError> {
+    let valid = email.len() <= 100
+        && !email.chars().any(char::is_whitespace)
+        && match email.split_once('@') {
+            Some((local, domain)) => {
+                !local.is_empty()
+                    && !domain.contains('@')
+                    && domain.split('.').count() >= 2
+                    && domain.split('.').all(|part| !part.is_empty())
+            }
+            None => false,
+        };
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!("'{}' is not a valid email address", email)))
+    }
+}
+
+/// Digits with optional spaces, dashes, dots, parentheses and a leading `+`;
+/// between 7 and 15 digits.
+pub fn validate_phone(phone: &str) -> Result<(), AppError> {
+    let digits = phone.chars().filter(char::is_ascii_digit).count();
+    let allowed = phone
+        .char_indices()
+        .all(|(i, c)| c.is_ascii_digit() || " -.()".contains(c) || (c == '+' && i == 0));
+    if allowed && (7..=15).contains(&digits) {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!("'{}' is not a valid phone number", phone)))
+    }
+}
+
+pub fn validate_avatar_url(url: &str) -> Result<(), AppError> {
+    let has_host = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .is_some_and(|rest| !rest.is_empty() && !rest.starts_with('/'));
+    if has_host && url.len() <= 500 && !url.chars().any(char::is_whitespace) {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest("avatar_url must be an http or https link of at most 500 characters".into()))
+    }
+}
+
+/// `Some(None)` for an empty string, which clears an optional field.
+fn clearable(value: Option<String>) -> Option<Option<String>> {
+    value.map(|value| {
+        let value = value.trim().to_string();
+        (!value.is_empty()).then_some(value)
+    })
+}
+
+/// Whether two players are members of at least one common league.
+pub fn are_teammates(conn: &mut PgConnection, player: i32, other: i32) -> Result<bool, AppError> {
+    let own_leagues = player_leagues::table
+        .filter(player_leagues::player_id.eq(player))
+        .select(player_leagues::league_id)
+        .load::<i32>(conn)?;
+    let shared = player_leagues::table
+        .filter(player_leagues::player_id.eq(other))
+        .filter(player_leagues::league_id.eq_any(own_leagues))
+        .count()
+        .get_result::<i64>(conn)?;
+    Ok(shared > 0)
+}
+
+fn load_profile(conn: &mut PgConnection, player: i32, viewer: i32) -> Result<Profile, AppError> {
+    let found = players::table
+        .find(player)
+        .first::<Player>(conn)
+        .optional()?
+        .ok_or(AppError::NotFound)?;
+
+    let leagues = player_leagues::table
+        .inner_join(leagues::table.on(leagues::league_id.eq(player_leagues::league_id)))
+        .filter(player_leagues::player_id.eq(player))
+        .order(leagues::league_name.asc())
+        .select((leagues::league_id, leagues::league_name))
+        .load::<ProfileLeague>(conn)?;
+
+    let private = viewer == player || are_teammates(conn, player, viewer)?;
+
+    Ok(Profile {
+        player_id: found.player_id,
+        name: found.name,
+        skill_level: found.skill_level,
+        avatar_url: found.avatar_url,
+        time_zone: found.time_zone,
+        created_at: found.created_at,
+        leagues,
+        visibility: if private { "private" } else { "public" },
+        contact: private.then_some(Contact {
+            email: found.email,
+            email_verified: found.email_verified_at.is_some(),
+            phone: found.phone,
+        }),
+    })
+}
+
+/// Shows a player's profile. Email and phone are only included for the
+/// player themselves and for players who share a league with them.
+pub async fn get_profile(
+    current: CurrentPlayer,
+    path: web::Path<i32>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let player = path.into_inner();
+    let viewer = current.player_id;
+
+    let profile = web::block(move || -> Result<Profile, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+        load_profile(&mut conn, player, viewer)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error loading profile: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+/// Lets a player change their name, skill level, phone, avatar and time
+/// zone. The email address has its own endpoint since it needs confirming.
+pub async fn update_profile(
+    current: CurrentPlayer,
+    path: web::Path<i32>,
+    item: web::Json<UpdateProfileInput>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let player = path.into_inner();
+    let item = item.into_inner();
+
+    if current.player_id != player {
+        return Err(AppError::Forbidden("Players can only edit their own profile".into()));
+    }
+    let changes = ProfileChanges {
+        name: item.name.map(|name| name.trim().to_string()),
+        skill_level: clearable(item.skill_level),
+        phone: clearable(item.phone),
+        avatar_url: clearable(item.avatar_url),
+        time_zone: item.time_zone,
+    };
+    if let Some(name) = &changes.name {
+        validate_name(name)?;
+    }
+    if let Some(Some(level)) = &changes.skill_level {
+        validate_skill_level(level)?;
+    }
+    if let Some(Some(phone)) = &changes.phone {
+        validate_phone(phone)?;
+    }
+    if let Some(Some(url)) = &changes.avatar_url {
+        validate_avatar_url(url)?;
+    }
+    if let Some(zone) = &changes.time_zone {
+        parse_time_zone(zone)?;
+    }
+    if changes.name.is_none()
+        && changes.skill_level.is_none()
+        && changes.phone.is_none()
+        && changes.avatar_url.is_none()
+        && changes.time_zone.is_none()
+    {
+        return Err(AppError::BadRequest("No profile fields to update".into()));
+    }
+
+    let profile = web::block(move || -> Result<Profile, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            if let Some(name) = &changes.name {
+                ensure_name_free(conn, name, Some(player))?;
+            }
+
+            let updated = diesel::update(players::table.find(player))
+                .set(&changes)
+                .execute(conn)?;
+            if updated == 0 {
+                return Err(AppError::NotFound);
+            }
+            load_profile(conn, player, player)
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error updating profile: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+/// Starts an email change. The player confirms it with their password, and
+/// the new address only takes effect once the token mailed to it is sent
+/// back to the confirm endpoint.
+pub async fn request_email_change(
+    current: CurrentPlayer,
+    path: web::Path<i32>,
+    item: web::Json<ChangeEmailInput>,
+    pool: web::Data<DbPool>,
+    mailer: web::Data<dyn Mailer>,
+) -> Result<impl Responder, AppError> {
+    let player = path.into_inner();
+    if current.player_id != player {
+        return Err(AppError::Forbidden("Players can only change their own email address".into()));
+    }
+    let item = item.into_inner();
+    let new_email = item.new_email.trim().to_string();
+    validate_email(&new_email)?;
+
+    let change = web::block(move || -> Result<EmailChange, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            let (current_email, password_hash) = players::table
+                .find(player)
+                .select((players::email, players::password))
+                .first::<(String, String)>(conn)
+                .optional()?
+                .ok_or(AppError::NotFound)?;
+            if !verify(&item.password, &password_hash).unwrap_or(false) {
+                return Err(AppError::Forbidden("Wrong password".into()));
+            }
+            if current_email.eq_ignore_ascii_case(&new_email) {
+                return Err(AppError::BadRequest("That is already your email address".into()));
+            }
+            ensure_email_free(conn, &new_email)?;
+
+            // Only the latest request counts
+            diesel::delete(
+                email_changes::table
+                    .filter(email_changes::player_id.eq(player))
+                    .filter(email_changes::confirmed_at.is_null()),
+            )
+            .execute(conn)?;
+
+            let now = Utc::now();
+            let token = new_token();
+            let change = diesel::insert_into(email_changes::table)
+                .values(&NewEmailChange {
+                    player_id: player,
+                    new_email: new_email.clone(),
+                    token_hash: hash_token(&token),
+                    expires_at: now + Duration::hours(EMAIL_CHANGE_HOURS),
+                    created_at: now,
+                })
+                .get_result::<EmailChange>(conn)?;
+
+            mailer.send(Email {
+                to: change.new_email.clone(),
+                subject: "Confirm your new email address".into(),
+                body: format!(
+                    "Someone asked to use this address for a tennis scheduler account. \
+                     To confirm, use this code within {} hours: {}\n\n\
+                     If that was not you, ignore this email.",
+                    EMAIL_CHANGE_HOURS, token
+                ),
+            })?;
+            Ok(change)
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error requesting email change: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "message": "Check the new address for a confirmation code",
+        "success": true,
+        "new_email": change.new_email,
+        "expires_at": change.expires_at
+    })))
+}
+
+/// Applies a requested email change, using the token mailed to the new address.
+pub async fn confirm_email_change(
+    item: web::Json<ConfirmEmailInput>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let token = item.into_inner().token;
+
+    let (player, new_email) = web::block(move || -> Result<(i32, String), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            let change = email_changes::table
+                .filter(email_changes::token_hash.eq(hash_token(&token)))
+                .filter(email_changes::confirmed_at.is_null())
+                .first::<EmailChange>(conn)
+                .optional()?
+                .ok_or_else(|| AppError::BadRequest("Unknown or already used confirmation code".into()))?;
+            let now = Utc::now();
+            if change.expires_at <= now {
+                return Err(AppError::BadRequest("The confirmation code has expired".into()));
+            }
+            // Someone may have registered the address in the meantime
+            ensure_email_free(conn, &change.new_email)?;
+
+            // Using the mailed token proves the player owns the new address
+            diesel::update(players::table.find(change.player_id))
+                .set((players::email.eq(&change.new_email), players::email_verified_at.eq(now)))
+                .execute(conn)?;
+            diesel::update(email_changes::table.find(change.change_id))
+                .set(email_changes::confirmed_at.eq(now))
+                .execute(conn)?;
+            Ok((change.player_id, change.new_email))
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error confirming email change: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Email address changed",
+        "success": true,
+        "player_id": player,
+        "email": new_email
+    })))
+}
+
+diesel::define_sql_function! {
+    fn lower(text: diesel::sql_types::Text) -> diesel::sql_types::Text;
+}
+
+/// The player with the address, ignoring case.
+pub fn find_player_by_email(conn: &mut PgConnection, email: &str) -> Result<Option<Player>, AppError> {
+    Ok(players::table
+        .filter(lower(players::email).eq(email.to_lowercase()))
+        .first::<Player>(conn)
+        .optional()?)
+}
+
+/// The player with the name, ignoring case.
+pub fn find_player_by_name(conn: &mut PgConnection, name: &str) -> Result<Option<Player>, AppError> {
+    Ok(players::table
+        .filter(lower(players::name).eq(name.to_lowercase()))
+        .first::<Player>(conn)
+        .optional()?)
+}
+
+/// Fails with `Conflict` when another player already uses the address,
+/// ignoring case.
+pub fn ensure_email_free(conn: &mut PgConnection, email: &str) -> Result<(), AppError> {
+    let taken = players::table
+        .filter(lower(players::email).eq(email.to_lowercase()))
+        .count()
+        .get_result::<i64>(conn)?;
+    if taken > 0 {
+        Err(AppError::Conflict(format!("{} is already registered", email)))
+    } else {
+        Ok(())
+    }
+}
+
+/// Fails with `Conflict` when a player other than `except` already has the
+/// name, ignoring case.
+pub fn ensure_name_free(conn: &mut PgConnection, name: &str, except: Option<i32>) -> Result<(), AppError> {
+    let taken = players::table
+        .filter(lower(players::name).eq(name.to_lowercase()))
+        .filter(players::player_id.ne_all(except.into_iter().collect::<Vec<_>>()))
+        .count()
+        .get_result::<i64>(conn)?;
+    if taken > 0 {
+        Err(AppError::Conflict(format!("The name '{}' is already taken", name)))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use diesel::prelude::*;
+use serde::Deserialize;
+
+use crate::api::players::booked_times;
+use crate::api::sessions::CurrentPlayer;
+use crate::calendar::zone_or_utc;
+use crate::db::models::Match;
+use crate::db::schema::{matches, player_leagues, players};
+use crate::db::DbPool;
+use crate::errors::AppError;
+use crate::matchmaking::{top_recommendations, OpponentFactors, Recommendation};
+use crate::search::{is_free_on, opponents, skill_rating};
+
+pub const DEFAULT_WINDOW_DAYS: i64 = 7;
+pub const MAX_WINDOW_DAYS: i64 = 28;
+pub const DEFAULT_RECOMMENDATIONS: usize = 10;
+pub const MAX_RECOMMENDATIONS: usize = 50;
+
+#[derive(Deserialize)]
+pub struct RecommendationQuery {
+    /// Only suggest members of this league; by default any of the player's leagues.
+    pub league_id: Option<i32>,
+    /// First day availability is compared on; defaults to today in the player's time zone.
+    pub from: Option<NaiveDate>,
+    /// How many days availability is compared over.
+    pub days: Option<i64>,
+    /// How many opponents to suggest.
+    pub limit: Option<usize>,
+}
+
+/// A fellow league member who could be suggested as an opponent.
+struct Member {
+    name: String,
+    skill_level: Option<String>,
+    time_zone: String,
+    ranking_gap: Option<i32>,
+}
+
+/// Suggests opponents for a player from the leagues they play in, scored on
+/// how close their level is, how long ago the two last met, how many days in
+/// the coming window both are free, and how many open matches the opponent
+/// already has. Players can only ask for their own suggestions, and
+/// deactivated or deleted accounts are never suggested.
+pub async fn get_recommendations(
+    current: CurrentPlayer,
+    path: web::Path<i32>,
+    query: web::Query<RecommendationQuery>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let player = path.into_inner();
+    if player != current.player_id {
+        return Err(AppError::Forbidden("Players can only see their own recommendations".into()));
+    }
+    let query = query.into_inner();
+
+    let days = query.days.unwrap_or(DEFAULT_WINDOW_DAYS);
+    if !(1..=MAX_WINDOW_DAYS).contains(&days) {
+        return Err(AppError::BadRequest(format!("days must be between 1 and {}", MAX_WINDOW_DAYS)));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_RECOMMENDATIONS);
+    if !(1..=MAX_RECOMMENDATIONS).contains(&limit) {
+        return Err(AppError::BadRequest(format!("limit must be between 1 and {}", MAX_RECOMMENDATIONS)));
+    }
+
+    let recommendations = web::block(move || -> Result<Vec<Recommendation>, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let (skill_level, zone_name) = players::table
+            .find(player)
+            .select((players::skill_level, players::time_zone))
+            .first::<(Option<String>, String)>(&mut conn)
+            .optional()?
+            .ok_or(AppError::NotFound)?;
+        let zone = zone_or_utc(&zone_name);
+        let rating = skill_level.as_deref().and_then(skill_rating);
+
+        let mut own_rankings: HashMap<i32, Option<i32>> = player_leagues::table
+            .filter(player_leagues::player_id.eq(player))
+            .select((player_leagues::league_id, player_leagues::singles_ranking))
+            .load::<(i32, Option<i32>)>(&mut conn)?
+            .into_iter()
+            .collect();
+        if let Some(league) = query.league_id {
+            if !own_rankings.contains_key(&league) {
+                return Err(AppError::BadRequest(format!("Player {} is not a member of league {}", player, league)));
+            }
+            own_rankings.retain(|id, _| *id == league);
+        }
+
+        let rows = player_leagues::table
+            .inner_join(players::table.on(players::player_id.eq(player_leagues::player_id)))
+            .filter(player_leagues::league_id.eq_any(own_rankings.keys().copied().collect::<Vec<_>>()))
+            .filter(player_leagues::player_id.ne(player))
+            .filter(players::deactivated_at.is_null())
+            .select((
+                player_leagues::player_id,
+                player_leagues::league_id,
+                player_leagues::singles_ranking,
+                players::name,
+                players::skill_level,
+                players::time_zone,
+            ))
+            .load::<(i32, i32, Option<i32>, String, Option<String>, String)>(&mut conn)?;
+
+        let mut members: HashMap<i32, Member> = HashMap::new();
+        for (member, league, ranking, name, skill_level, time_zone) in rows {
+            let gap = match (own_rankings.get(&league).copied().flatten(), ranking) {
+                (Some(own), Some(theirs)) => Some((own - theirs).abs()),
+                _ => None,
+            };
+            let entry = members.entry(member).or_insert(Member { name, skill_level, time_zone, ranking_gap: None });
+            entry.ranking_gap = match (entry.ranking_gap, gap) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+        }
+        let ids: Vec<i32> = members.keys().copied().collect();
+
+        let now = Utc::now();
+        let mut last_met: HashMap<i32, DateTime<Utc>> = HashMap::new();
+        let played = matches::table
+            .filter(matches::status.ne_all(["Cancelled", "Rejected"]))
+            .filter(
+                matches::player1_id.eq(player)
+                .or(matches::player2_id.eq(player))
+                .or(matches::team1_player1_id.eq(player))
+                .or(matches::team1_player2_id.eq(player))
+                .or(matches::team2_player1_id.eq(player))
+                .or(matches::team2_player2_id.eq(player))
+            )
+            .load::<Match>(&mut conn)?;
+        for m in &played {
+            for opponent in opponents(m, player) {
+                let last = last_met.entry(opponent).or_insert(m.datetime);
+                *last = (*last).max(m.datetime);
+            }
+        }
+
+        let mut open_matches: HashMap<i32, usize> = HashMap::new();
+        let open = matches::table
+            .filter(matches::status.eq_any(["Pending", "Scheduled"]))
+            .filter(matches::datetime.ge(now))
+            .filter(
+                matches::player1_id.eq_any(&ids)
+                .or(matches::player2_id.eq_any(&ids))
+                .or(matches::team1_player1_id.eq_any(&ids))
+                .or(matches::team1_player2_id.eq_any(&ids))
+                .or(matches::team2_player1_id.eq_any(&ids))
+                .or(matches::team2_player2_id.eq_any(&ids))
+            )
+            .load::<Match>(&mut conn)?;
+        for m in open {
+            for id in [m.player1_id, m.player2_id, m.team1_player1_id, m.team1_player2_id, m.team2_player1_id, m.team2_player2_id]
+                .into_iter()
+                .flatten()
+            {
+                *open_matches.entry(id).or_default() += 1;
+            }
+        }
+
+        let first_day = query.from.unwrap_or_else(|| now.with_timezone(&zone).date_naive());
+        let window: Vec<NaiveDate> = first_day.iter_days().take(days as usize).collect();
+        // Wide enough to cover the window in every time zone
+        let window_start = first_day.and_time(NaiveTime::MIN).and_utc() - Duration::days(1);
+        let window_end = window_start + Duration::days(days + 2);
+        let mut everyone = ids.clone();
+        everyone.push(player);
+        let busy = booked_times(&mut conn, Some(&everyone), window_start, window_end)?;
+        let slots = |id: i32| busy.get(&id).map(Vec::as_slice).unwrap_or_default();
+        let own_free: Vec<bool> = window.iter().map(|day| is_free_on(*day, zone, slots(player))).collect();
+
+        let recommendations = members
+            .into_iter()
+            .map(|(id, member)| {
+                let member_zone = zone_or_utc(&member.time_zone);
+                let factors = OpponentFactors {
+                    skill_difference: match (rating, member.skill_level.as_deref().and_then(skill_rating)) {
+                        (Some(own), Some(theirs)) => Some((own - theirs).abs()),
+                        _ => None,
+                    },
+                    ranking_gap: member.ranking_gap,
+                    days_since_last_meeting: last_met.get(&id).map(|last| (now - *last).num_days().max(0)),
+                    shared_free_days: window
+                        .iter()
+                        .zip(&own_free)
+                        .filter(|(day, own)| **own && is_free_on(**day, member_zone, slots(id)))
+                        .count(),
+                    days_considered: window.len(),
+                    open_matches: open_matches.get(&id).copied().unwrap_or(0),
+                };
+                Recommendation {
+                    player_id: id,
+                    name: member.name,
+                    skill_level: member.skill_level,
+                    score: factors.score(),
+                    factors,
+                }
+            })
+            .collect();
+
+        Ok(top_recommendations(recommendations, limit))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error recommending opponents: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "player_id": player,
+        "count": recommendations.len(),
+        "recommendations": recommendations
+    })))
+}
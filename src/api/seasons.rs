@@ -0,0 +1,638 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{NaiveDate, Utc};
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde::{Deserialize, Serialize};
+
+use crate::api::leagues::{ensure_league_admin, load_visible_league};
+use crate::api::sessions::CurrentPlayer;
+use crate::db::models::{
+    LeagueDivision, LeagueSeason, Match, NewLeagueDivision, NewLeagueSeason, NewSeasonMember, SeasonMember,
+};
+use crate::calendar::zone_or_utc;
+use crate::db::schema::{league_divisions, league_seasons, leagues, matches as matches_schema, player_leagues, season_members};
+use crate::db::DbPool;
+use crate::errors::AppError;
+use crate::standings::{self, Standing};
+
+#[derive(Deserialize)]
+pub struct CreateSeasonInput {
+    pub season_name: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub registration_deadline: Option<NaiveDate>,
+    /// Closed season to copy divisions from; its members are carried over
+    /// into the division their promotion or relegation puts them in.
+    pub previous_season_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateDivisionInput {
+    pub division_name: String,
+    pub tier: i32,
+    pub promotion_spots: Option<i32>,
+    pub relegation_spots: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct AddSeasonMemberInput {
+    pub player_id: i32,
+    pub division_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct AssignDivisionInput {
+    pub division_id: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct SeasonDetails {
+    #[serde(flatten)]
+    pub season: LeagueSeason,
+    pub divisions: Vec<LeagueDivision>,
+}
+
+#[derive(Serialize)]
+pub struct StandingEntry {
+    #[serde(flatten)]
+    pub standing: Standing,
+    pub movement: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct DivisionStandings {
+    pub division_id: i32,
+    pub division_name: String,
+    pub tier: i32,
+    pub standings: Vec<StandingEntry>,
+}
+
+/// Maps a failed insert to a client error when it hit a uniqueness or foreign key constraint.
+fn insert_error(error: DieselError, duplicate_message: &str) -> AppError {
+    match error {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            AppError::BadRequest(duplicate_message.into())
+        }
+        DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => AppError::NotFound,
+        error => {
+            eprintln!("Database error: {:?}", error);
+            AppError::InternalError
+        }
+    }
+}
+
+/// Loads a season and makes sure it belongs to the league in the path.
+pub fn load_season(conn: &mut PgConnection, league: i32, season_id: i32) -> Result<LeagueSeason, AppError> {
+    league_seasons::table
+        .filter(league_seasons::season_id.eq(season_id))
+        .filter(league_seasons::league_id.eq(league))
+        .first::<LeagueSeason>(conn)
+        .optional()
+        .map_err(|_| AppError::InternalError)?
+        .ok_or(AppError::NotFound)
+}
+
+fn load_divisions(conn: &mut PgConnection, season_id: i32) -> Result<Vec<LeagueDivision>, AppError> {
+    league_divisions::table
+        .filter(league_divisions::season_id.eq(season_id))
+        .order(league_divisions::tier.asc())
+        .load::<LeagueDivision>(conn)
+        .map_err(|_| AppError::InternalError)
+}
+
+fn ensure_division_in_season(conn: &mut PgConnection, season_id: i32, division_id: i32) -> Result<(), AppError> {
+    let found = league_divisions::table
+        .filter(league_divisions::division_id.eq(division_id))
+        .filter(league_divisions::season_id.eq(season_id))
+        .count()
+        .get_result::<i64>(conn)
+        .map_err(|_| AppError::InternalError)?;
+
+    if found == 0 {
+        return Err(AppError::BadRequest("Division does not belong to this season".into()));
+    }
+    Ok(())
+}
+
+/// Computes the standings of every division in a season from its completed matches.
+pub fn season_standings(conn: &mut PgConnection, season: &LeagueSeason) -> Result<Vec<DivisionStandings>, AppError> {
+    let divisions = load_divisions(conn, season.season_id)?;
+    let members = season_members::table
+        .filter(season_members::season_id.eq(season.season_id))
+        .load::<SeasonMember>(conn)
+        .map_err(|_| AppError::InternalError)?;
+    let season_matches = matches_schema::table
+        .filter(matches_schema::season_id.eq(season.season_id))
+        .load::<Match>(conn)
+        .map_err(|_| AppError::InternalError)?;
+
+    Ok(divisions
+        .into_iter()
+        .map(|division| {
+            let division_members: Vec<&SeasonMember> = members
+                .iter()
+                .filter(|member| member.division_id == Some(division.division_id))
+                .collect();
+            let player_ids: Vec<i32> = division_members.iter().map(|member| member.player_id).collect();
+
+            let standings = standings::compute_standings(&player_ids, &season_matches)
+                .into_iter()
+                .map(|standing| {
+                    let movement = division_members
+                        .iter()
+                        .find(|member| member.player_id == standing.player_id)
+                        .and_then(|member| member.movement.clone());
+                    StandingEntry { standing, movement }
+                })
+                .collect();
+
+            DivisionStandings {
+                division_id: division.division_id,
+                division_name: division.division_name,
+                tier: division.tier,
+                standings,
+            }
+        })
+        .collect())
+}
+
+pub async fn create_season(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    input: web::Json<CreateSeasonInput>,
+) -> Result<impl Responder, AppError> {
+    let league = path.into_inner();
+    let input = input.into_inner();
+
+    if input.end_date < input.start_date {
+        return Err(AppError::BadRequest("Season end date must not be before its start date".into()));
+    }
+    if let Some(deadline) = input.registration_deadline {
+        if deadline > input.end_date {
+            return Err(AppError::BadRequest("Registration deadline must not be after the season ends".into()));
+        }
+    }
+
+    let season = web::block(move || -> Result<LeagueSeason, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            ensure_league_admin(conn, league, &current)?;
+            let previous = match input.previous_season_id {
+                Some(previous_id) => {
+                    let previous = load_season(conn, league, previous_id)?;
+                    if previous.status != "closed" {
+                        return Err(AppError::BadRequest("Previous season must be closed before carrying it over".into()));
+                    }
+                    Some(previous)
+                }
+                None => None,
+            };
+
+            let now = Utc::now();
+            let season = diesel::insert_into(league_seasons::table)
+                .values(&NewLeagueSeason {
+                    league_id: league,
+                    season_name: input.season_name.clone(),
+                    start_date: input.start_date,
+                    end_date: input.end_date,
+                    registration_deadline: input.registration_deadline,
+                    created_at: now,
+                })
+                .get_result::<LeagueSeason>(conn)
+                .map_err(|error| insert_error(error, "A season with this name already exists in the league"))?;
+
+            if let Some(previous) = previous {
+                carry_over_season(conn, league, &previous, &season)?;
+            }
+
+            Ok(season)
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error creating season: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Created().json(season))
+}
+
+/// Copies the divisions of a closed season into a new one and seeds the
+/// members that are still in the league, applying promotion and relegation.
+fn carry_over_season(
+    conn: &mut PgConnection,
+    league: i32,
+    previous: &LeagueSeason,
+    season: &LeagueSeason,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    let previous_divisions = load_divisions(conn, previous.season_id)?;
+
+    let new_divisions: Vec<NewLeagueDivision> = previous_divisions
+        .iter()
+        .map(|division| NewLeagueDivision {
+            season_id: season.season_id,
+            division_name: division.division_name.clone(),
+            tier: division.tier,
+            promotion_spots: division.promotion_spots,
+            relegation_spots: division.relegation_spots,
+            created_at: now,
+        })
+        .collect();
+    let created = diesel::insert_into(league_divisions::table)
+        .values(&new_divisions)
+        .get_results::<LeagueDivision>(conn)
+        .map_err(|_| AppError::InternalError)?;
+
+    let current_members: Vec<i32> = player_leagues::table
+        .filter(player_leagues::league_id.eq(league))
+        .select(player_leagues::player_id)
+        .load::<i32>(conn)
+        .map_err(|_| AppError::InternalError)?;
+    let previous_members = season_members::table
+        .filter(season_members::season_id.eq(previous.season_id))
+        .filter(season_members::player_id.eq_any(&current_members))
+        .load::<SeasonMember>(conn)
+        .map_err(|_| AppError::InternalError)?;
+
+    let seeded: Vec<NewSeasonMember> = previous_members
+        .into_iter()
+        .map(|member| {
+            let previous_tier = member.division_id.and_then(|division_id| {
+                previous_divisions
+                    .iter()
+                    .find(|division| division.division_id == division_id)
+                    .map(|division| division.tier)
+            });
+            // Tiers need not be consecutive, so move to the nearest one above or below
+            let next_tier = previous_tier.map(|tier| {
+                let tiers = created.iter().map(|division| division.tier);
+                let moved = match member.movement.as_deref() {
+                    Some("promoted") => tiers.filter(|&other| other < tier).max(),
+                    Some("relegated") => tiers.filter(|&other| other > tier).min(),
+                    _ => None,
+                };
+                moved.unwrap_or(tier)
+            });
+            let division_id = next_tier.and_then(|tier| {
+                created
+                    .iter()
+                    .find(|division| division.tier == tier)
+                    .map(|division| division.division_id)
+            });
+
+            NewSeasonMember {
+                season_id: season.season_id,
+                player_id: member.player_id,
+                division_id,
+                joined_at: now,
+            }
+        })
+        .collect();
+
+    diesel::insert_into(season_members::table)
+        .values(&seeded)
+        .execute(conn)
+        .map_err(|_| AppError::InternalError)?;
+
+    Ok(())
+}
+
+pub async fn get_league_seasons(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+) -> Result<impl Responder, AppError> {
+    let league = path.into_inner();
+
+    let seasons = web::block(move || -> Result<Vec<LeagueSeason>, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        load_visible_league(&mut conn, league, current.player_id)?;
+
+        league_seasons::table
+            .filter(league_seasons::league_id.eq(league))
+            .order(league_seasons::start_date.desc())
+            .load::<LeagueSeason>(&mut conn)
+            .map_err(|_| AppError::InternalError)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error fetching seasons: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(seasons))
+}
+
+pub async fn get_season(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+    path: web::Path<(i32, i32)>,
+) -> Result<impl Responder, AppError> {
+    let (league, season_id) = path.into_inner();
+
+    let details = web::block(move || -> Result<SeasonDetails, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        load_visible_league(&mut conn, league, current.player_id)?;
+        let season = load_season(&mut conn, league, season_id)?;
+        let divisions = load_divisions(&mut conn, season_id)?;
+        Ok(SeasonDetails { season, divisions })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error fetching season: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(details))
+}
+
+pub async fn create_division(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+    path: web::Path<(i32, i32)>,
+    input: web::Json<CreateDivisionInput>,
+) -> Result<impl Responder, AppError> {
+    let (league, season_id) = path.into_inner();
+    let input = input.into_inner();
+
+    let promotion_spots = input.promotion_spots.unwrap_or(0);
+    let relegation_spots = input.relegation_spots.unwrap_or(0);
+    if input.tier < 1 || promotion_spots < 0 || relegation_spots < 0 {
+        return Err(AppError::BadRequest("Tier must be at least 1 and spots must not be negative".into()));
+    }
+
+    let division = web::block(move || -> Result<LeagueDivision, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        ensure_league_admin(&mut conn, league, &current)?;
+        let season = load_season(&mut conn, league, season_id)?;
+        if season.status == "closed" {
+            return Err(AppError::BadRequest("Season is closed".into()));
+        }
+
+        diesel::insert_into(league_divisions::table)
+            .values(&NewLeagueDivision {
+                season_id,
+                division_name: input.division_name,
+                tier: input.tier,
+                promotion_spots,
+                relegation_spots,
+                created_at: Utc::now(),
+            })
+            .get_result::<LeagueDivision>(&mut conn)
+            .map_err(|error| insert_error(error, "A division with this tier already exists in the season"))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error creating division: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Created().json(division))
+}
+
+/// Registers a league member for a season. Players may register themselves
+/// until the registration deadline; registering someone else, or placing
+/// them in a division, is up to league admins.
+pub async fn add_season_member(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+    path: web::Path<(i32, i32)>,
+    input: web::Json<AddSeasonMemberInput>,
+) -> Result<impl Responder, AppError> {
+    let (league, season_id) = path.into_inner();
+    let input = input.into_inner();
+
+    let member = web::block(move || -> Result<SeasonMember, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        if input.player_id != current.player_id || input.division_id.is_some() {
+            ensure_league_admin(&mut conn, league, &current)?;
+        }
+        let season = load_season(&mut conn, league, season_id)?;
+        if season.status == "closed" {
+            return Err(AppError::BadRequest("Season is closed".into()));
+        }
+        if let Some(deadline) = season.registration_deadline {
+            // The deadline is a calendar day in the league's own time zone
+            let league_zone = leagues::table
+                .find(league)
+                .select(leagues::time_zone)
+                .first::<String>(&mut conn)
+                .map_err(|_| AppError::InternalError)?;
+            if Utc::now().with_timezone(&zone_or_utc(&league_zone)).date_naive() > deadline {
+                return Err(AppError::BadRequest("Registration for this season has closed".into()));
+            }
+        }
+
+        let in_league = player_leagues::table
+            .filter(player_leagues::player_id.eq(input.player_id))
+            .filter(player_leagues::league_id.eq(league))
+            .count()
+            .get_result::<i64>(&mut conn)
+            .map_err(|_| AppError::InternalError)?;
+        if in_league == 0 {
+            return Err(AppError::BadRequest("Player is not in the league".into()));
+        }
+
+        if let Some(division_id) = input.division_id {
+            ensure_division_in_season(&mut conn, season_id, division_id)?;
+        }
+
+        diesel::insert_into(season_members::table)
+            .values(&NewSeasonMember {
+                season_id,
+                player_id: input.player_id,
+                division_id: input.division_id,
+                joined_at: Utc::now(),
+            })
+            .get_result::<SeasonMember>(&mut conn)
+            .map_err(|error| insert_error(error, "Player is already registered for this season"))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error adding season member: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Created().json(member))
+}
+
+pub async fn get_season_members(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+    path: web::Path<(i32, i32)>,
+) -> Result<impl Responder, AppError> {
+    let (league, season_id) = path.into_inner();
+
+    let members = web::block(move || -> Result<Vec<SeasonMember>, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        load_visible_league(&mut conn, league, current.player_id)?;
+        load_season(&mut conn, league, season_id)?;
+        season_members::table
+            .filter(season_members::season_id.eq(season_id))
+            .order(season_members::player_id.asc())
+            .load::<SeasonMember>(&mut conn)
+            .map_err(|_| AppError::InternalError)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error fetching season members: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(members))
+}
+
+pub async fn assign_member_division(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+    path: web::Path<(i32, i32, i32)>,
+    input: web::Json<AssignDivisionInput>,
+) -> Result<impl Responder, AppError> {
+    let (league, season_id, member_id) = path.into_inner();
+    let division_id = input.division_id;
+
+    web::block(move || -> Result<(), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        ensure_league_admin(&mut conn, league, &current)?;
+        let season = load_season(&mut conn, league, season_id)?;
+        if season.status == "closed" {
+            return Err(AppError::BadRequest("Season is closed".into()));
+        }
+        if let Some(division_id) = division_id {
+            ensure_division_in_season(&mut conn, season_id, division_id)?;
+        }
+
+        let updated = diesel::update(season_members::table)
+            .filter(season_members::season_id.eq(season_id))
+            .filter(season_members::player_id.eq(member_id))
+            .set(season_members::division_id.eq(division_id))
+            .execute(&mut conn)
+            .map_err(|_| AppError::InternalError)?;
+
+        if updated == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error assigning division: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Division assigned successfully",
+        "success": true
+    })))
+}
+
+pub async fn get_season_standings(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+    path: web::Path<(i32, i32)>,
+) -> Result<impl Responder, AppError> {
+    let (league, season_id) = path.into_inner();
+
+    let standings = web::block(move || -> Result<Vec<DivisionStandings>, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        load_visible_league(&mut conn, league, current.player_id)?;
+        let season = load_season(&mut conn, league, season_id)?;
+        season_standings(&mut conn, &season)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error fetching standings: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(standings))
+}
+
+/// Closes a season: records each member's final rank and whether they are
+/// promoted, relegated or stay in their division for the next season.
+pub async fn close_season(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+    path: web::Path<(i32, i32)>,
+) -> Result<impl Responder, AppError> {
+    let (league, season_id) = path.into_inner();
+
+    let standings = web::block(move || -> Result<Vec<DivisionStandings>, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            ensure_league_admin(conn, league, &current)?;
+            let season = load_season(conn, league, season_id)?;
+            if season.status == "closed" {
+                return Err(AppError::BadRequest("Season is already closed".into()));
+            }
+
+            let divisions = load_divisions(conn, season_id)?;
+            let top_tier = divisions.first().map(|division| division.tier);
+            let bottom_tier = divisions.last().map(|division| division.tier);
+            let computed = season_standings(conn, &season)?;
+
+            for (division, table) in divisions.iter().zip(&computed) {
+                let promotion_spots = if Some(division.tier) == top_tier { 0 } else { division.promotion_spots };
+                let relegation_spots = if Some(division.tier) == bottom_tier { 0 } else { division.relegation_spots };
+                let standings: Vec<Standing> = table.standings.iter().map(|entry| entry.standing.clone()).collect();
+                let movements = standings::assign_movements(
+                    &standings,
+                    promotion_spots.max(0) as usize,
+                    relegation_spots.max(0) as usize,
+                );
+
+                for (standing, (_, movement)) in standings.iter().zip(movements) {
+                    diesel::update(season_members::table)
+                        .filter(season_members::season_id.eq(season_id))
+                        .filter(season_members::player_id.eq(standing.player_id))
+                        .set((
+                            season_members::final_rank.eq(Some(standing.rank)),
+                            season_members::movement.eq(Some(movement.as_str())),
+                        ))
+                        .execute(conn)
+                        .map_err(|_| AppError::InternalError)?;
+                }
+            }
+
+            let closed = diesel::update(league_seasons::table.find(season_id))
+                .set((
+                    league_seasons::status.eq("closed"),
+                    league_seasons::closed_at.eq(Some(Utc::now())),
+                ))
+                .get_result::<LeagueSeason>(conn)
+                .map_err(|_| AppError::InternalError)?;
+
+            season_standings(conn, &closed)
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error closing season: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(standings))
+}
+
+/// Checks that a season tagged on a match belongs to the match's league and is still open.
+pub fn ensure_open_season(conn: &mut PgConnection, league: i32, season_id: i32) -> Result<(), AppError> {
+    let season = load_season(conn, league, season_id).map_err(|error| match error {
+        AppError::NotFound => AppError::BadRequest("Season does not belong to this league".into()),
+        error => error,
+    })?;
+    if season.status == "closed" {
+        return Err(AppError::BadRequest("Season is closed".into()));
+    }
+    Ok(())
+}
@@ -0,0 +1,336 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{http::header, web, FromRequest, HttpMessage, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::db::models::{NewSession, Session};
+use crate::db::schema::{players, sessions};
+use crate::db::DbPool;
+use crate::errors::AppError;
+
+/// How long a login stays valid.
+pub const SESSION_DAYS: i64 = 30;
+/// How stale a session's last-seen time may get before a request updates it,
+/// so every request does not write to the database.
+pub const LAST_SEEN_MINUTES: i64 = 5;
+/// The longest user agent kept with a session.
+const USER_AGENT_LENGTH: usize = 255;
+
+/// A fresh random token for a session or a mailed code.
+pub fn new_token() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// What is stored in place of a token, so a copy of the database cannot be
+/// used to sign in.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// The device a session was opened from, shown to the player when they list
+/// their sessions.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl ClientInfo {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        ClientInfo {
+            user_agent: req
+                .headers()
+                .get(header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(|agent| agent.trim().chars().take(USER_AGENT_LENGTH).collect::<String>())
+                .filter(|agent| !agent.is_empty()),
+            ip_address: req.peer_addr().map(|addr| addr.ip().to_string()),
+        }
+    }
+}
+
+/// Opens a session for a player and returns its bearer token, which is only
+/// available here. Deactivated players are refused with 403.
+pub fn start_session(conn: &mut PgConnection, player: i32, client: &ClientInfo) -> Result<(String, Session), AppError> {
+    let deactivated_at = players::table
+        .find(player)
+        .select(players::deactivated_at)
+        .first::<Option<DateTime<Utc>>>(conn)?;
+    if deactivated_at.is_some() {
+        return Err(AppError::Forbidden("This account has been deactivated".into()));
+    }
+
+    let token = new_token();
+    let now = Utc::now();
+    let session = diesel::insert_into(sessions::table)
+        .values(&NewSession {
+            player_id: player,
+            token_hash: hash_token(&token),
+            created_at: now,
+            expires_at: now + Duration::days(SESSION_DAYS),
+            user_agent: client.user_agent.clone(),
+            ip_address: client.ip_address.clone(),
+            last_seen_at: Some(now),
+        })
+        .get_result::<Session>(conn)?;
+    Ok((token, session))
+}
+
+/// Revokes a player's open sessions, apart from `keep` if given, and returns
+/// how many were revoked.
+pub fn revoke_sessions(conn: &mut PgConnection, player: i32, keep: Option<i32>) -> Result<usize, AppError> {
+    let revoked = diesel::update(
+        sessions::table
+            .filter(sessions::player_id.eq(player))
+            .filter(sessions::revoked_at.is_null())
+            .filter(sessions::session_id.ne_all(keep.into_iter().collect::<Vec<_>>())),
+    )
+    .set(sessions::revoked_at.eq(Utc::now()))
+    .execute(conn)?;
+    Ok(revoked)
+}
+
+/// Records that the session's player gave a two-factor code in it.
+pub fn mark_two_factor_verified(conn: &mut PgConnection, session: i32) -> Result<(), AppError> {
+    diesel::update(sessions::table.find(session))
+        .set(sessions::two_factor_verified_at.eq(Utc::now()))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Looks up the open session a bearer token belongs to.
+pub fn find_session(conn: &mut PgConnection, token: &str) -> Result<Option<Session>, AppError> {
+    Ok(sessions::table
+        .filter(sessions::token_hash.eq(hash_token(token)))
+        .filter(sessions::revoked_at.is_null())
+        .filter(sessions::expires_at.gt(Utc::now()))
+        .first::<Session>(conn)
+        .optional()?)
+}
+
+/// Records that a session was just used, unless that was already recorded in
+/// the last `LAST_SEEN_MINUTES`.
+pub fn touch_session(conn: &mut PgConnection, session: &Session) -> Result<(), AppError> {
+    let now = Utc::now();
+    if session.last_seen_at.is_some_and(|seen| seen > now - Duration::minutes(LAST_SEEN_MINUTES)) {
+        return Ok(());
+    }
+    diesel::update(sessions::table.find(session.session_id))
+        .set(sessions::last_seen_at.eq(now))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// The player signed in with the request's `Authorization: Bearer` token.
+/// Handlers that take it answer 401 when the token is missing, unknown,
+/// expired or revoked.
+#[derive(Debug, Clone)]
+pub struct CurrentPlayer {
+    pub player_id: i32,
+    pub session_id: i32,
+    /// Whether a two-factor code was given when the session was opened, or
+    /// later in it.
+    pub two_factor_verified: bool,
+}
+
+impl FromRequest for CurrentPlayer {
+    type Error = AppError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        // Already checked by `require_session`
+        if let Some(current) = req.extensions().get::<CurrentPlayer>().cloned() {
+            return Box::pin(async move { Ok(current) });
+        }
+
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string());
+        let pool = req.app_data::<web::Data<DbPool>>().cloned();
+
+        Box::pin(async move {
+            let token = token.ok_or_else(|| AppError::Unauthorized("Sign in first".into()))?;
+            let pool = pool.ok_or(AppError::InternalError)?;
+            let session = web::block(move || -> Result<Option<Session>, AppError> {
+                let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+                let session = find_session(&mut conn, &token)?;
+                if let Some(session) = &session {
+                    touch_session(&mut conn, session)?;
+                }
+                Ok(session)
+            })
+            .await
+            .map_err(|e| {
+                eprintln!("Error checking session: {:?}", e);
+                AppError::InternalError
+            })??
+            .ok_or_else(|| AppError::Unauthorized("Your session has expired; sign in again".into()))?;
+
+            Ok(CurrentPlayer {
+                player_id: session.player_id,
+                session_id: session.session_id,
+                two_factor_verified: session.two_factor_verified_at.is_some(),
+            })
+        })
+    }
+}
+
+// The session comes from the Authorization header, which the API docs do not
+// describe as a parameter
+impl paperclip::v2::schema::Apiv2Schema for CurrentPlayer {}
+impl paperclip::actix::OperationModifier for CurrentPlayer {}
+
+/// Middleware for the scopes that are only open to signed-in players. It
+/// answers 401 unless the request carries an open session, and hands that
+/// session on to `CurrentPlayer` so it is only looked up once.
+pub async fn require_session(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    match CurrentPlayer::extract(req.request()).await {
+        Ok(current) => {
+            req.extensions_mut().insert(current);
+            Ok(next.call(req).await?.map_into_left_body())
+        }
+        Err(error) => Ok(req.error_response(error).map_into_right_body()),
+    }
+}
+
+/// The signed-in player's open sessions, most recently used first. The one
+/// the request was made with is marked `current`.
+pub async fn list_sessions(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let open = web::block(move || -> Result<Vec<Session>, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        Ok(sessions::table
+            .filter(sessions::player_id.eq(current.player_id))
+            .filter(sessions::revoked_at.is_null())
+            .filter(sessions::expires_at.gt(Utc::now()))
+            .order((sessions::last_seen_at.desc().nulls_last(), sessions::session_id.desc()))
+            .load::<Session>(&mut conn)?)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error listing sessions: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    let listed: Vec<serde_json::Value> = open
+        .into_iter()
+        .map(|session| {
+            let is_current = session.session_id == current.session_id;
+            let mut listed = json!(session);
+            listed["current"] = json!(is_current);
+            listed
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(listed))
+}
+
+/// Signs out of one of the player's sessions, such as a lost phone. Other
+/// players' sessions are answered with 404.
+pub async fn revoke_session(
+    current: CurrentPlayer,
+    path: web::Path<i32>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let session_id = path.into_inner();
+    web::block(move || -> Result<(), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let revoked = diesel::update(
+            sessions::table
+                .find(session_id)
+                .filter(sessions::player_id.eq(current.player_id))
+                .filter(sessions::revoked_at.is_null()),
+        )
+        .set(sessions::revoked_at.eq(Utc::now()))
+        .execute(&mut conn)?;
+        if revoked == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error revoking session: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Session signed out",
+        "success": true,
+    })))
+}
+
+/// Signs out of the session the request was made with.
+pub async fn logout(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    web::block(move || -> Result<(), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        diesel::update(sessions::table.find(current.session_id))
+            .set(sessions::revoked_at.eq(Utc::now()))
+            .execute(&mut conn)?;
+        Ok(())
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error logging out: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Logged out",
+        "success": true,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct RevokeAllQuery {
+    /// Keep the session the request was made with, signing out everywhere
+    /// else.
+    #[serde(default)]
+    pub keep_current: bool,
+}
+
+/// Signs out of all of the player's sessions, or all but the current one.
+pub async fn revoke_all_sessions(
+    current: CurrentPlayer,
+    query: web::Query<RevokeAllQuery>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let keep = query.keep_current.then_some(current.session_id);
+    let revoked = web::block(move || -> Result<usize, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+        revoke_sessions(&mut conn, current.player_id, keep)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error revoking sessions: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Sessions signed out",
+        "success": true,
+        "revoked": revoked,
+    })))
+}
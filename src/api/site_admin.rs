@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+use diesel::dsl::count_star;
+use diesel::prelude::*;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::api::leagues::LEAGUE_SORT_FIELDS;
+use crate::api::pagination::{PageMeta, PageQuery};
+use crate::api::players::PLAYER_SORT_FIELDS;
+use crate::api::sessions::{revoke_sessions, CurrentPlayer};
+use crate::db::models::{League, Player, Role};
+use crate::db::schema::{leagues, player_leagues, players};
+use crate::db::DbPool;
+use crate::errors::AppError;
+
+/// Whether a player is a site admin.
+pub fn is_site_admin(conn: &mut PgConnection, player: i32) -> Result<bool, AppError> {
+    let role = players::table
+        .find(player)
+        .select(players::role)
+        .first::<String>(conn)
+        .optional()?;
+    Ok(role.as_deref() == Some(Role::SiteAdmin.as_str()))
+}
+
+/// The signed-in player, who must be a site admin. Handlers that take it
+/// answer 401 without a session and 403 for everyone else.
+pub struct SiteAdmin {
+    pub player_id: i32,
+    pub session_id: i32,
+}
+
+impl FromRequest for SiteAdmin {
+    type Error = AppError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let current = CurrentPlayer::from_request(req, payload);
+        let pool = req.app_data::<web::Data<DbPool>>().cloned();
+
+        Box::pin(async move {
+            let current = current.await?;
+            let pool = pool.ok_or(AppError::InternalError)?;
+            let player = current.player_id;
+            let allowed = web::block(move || -> Result<bool, AppError> {
+                let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+                is_site_admin(&mut conn, player)
+            })
+            .await
+            .map_err(|e| {
+                eprintln!("Error checking site role: {:?}", e);
+                AppError::InternalError
+            })??;
+            if !allowed {
+                return Err(AppError::Forbidden("Only site admins can do this".into()));
+            }
+
+            Ok(SiteAdmin { player_id: current.player_id, session_id: current.session_id })
+        })
+    }
+}
+
+/// Gives a player a site role and returns the updated player.
+pub fn set_role(conn: &mut PgConnection, player: i32, role: Role) -> Result<Player, AppError> {
+    Ok(diesel::update(players::table.find(player))
+        .set(players::role.eq(role.as_str()))
+        .get_result::<Player>(conn)?)
+}
+
+#[derive(Deserialize)]
+pub struct AdminPlayerQuery {
+    /// Part of the player's name or email address, matched case-insensitively.
+    pub search: Option<String>,
+    pub role: Option<Role>,
+    /// Only deactivated players, or only active ones.
+    pub deactivated: Option<bool>,
+}
+
+/// Every player with their email address, role and account state.
+pub async fn list_players(
+    _admin: SiteAdmin,
+    query: web::Query<AdminPlayerQuery>,
+    paging: web::Query<PageQuery>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let query = query.into_inner();
+    let page = paging.page()?;
+    let sort = paging.sort(&PLAYER_SORT_FIELDS, "player_id")?;
+
+    let (found, total) = web::block(move || -> Result<(Vec<Player>, i64), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let filtered = || {
+            let mut filtered = players::table.into_boxed();
+            if let Some(search) = &query.search {
+                let pattern = format!("%{}%", search);
+                filtered = filtered.filter(players::name.ilike(pattern.clone()).or(players::email.ilike(pattern)));
+            }
+            if let Some(role) = query.role {
+                filtered = filtered.filter(players::role.eq(role.as_str()));
+            }
+            match query.deactivated {
+                Some(true) => filtered = filtered.filter(players::deactivated_at.is_not_null()),
+                Some(false) => filtered = filtered.filter(players::deactivated_at.is_null()),
+                None => {}
+            }
+            filtered
+        };
+
+        let total = filtered().count().get_result::<i64>(&mut conn)?;
+        let sorted = match (sort.field.as_str(), sort.descending) {
+            ("name", false) => filtered().order((players::name.asc(), players::player_id.asc())),
+            ("name", true) => filtered().order((players::name.desc(), players::player_id.desc())),
+            ("skill_level", false) => filtered().order((players::skill_level.asc(), players::player_id.asc())),
+            ("skill_level", true) => filtered().order((players::skill_level.desc(), players::player_id.desc())),
+            ("created_at", false) => filtered().order((players::created_at.asc(), players::player_id.asc())),
+            ("created_at", true) => filtered().order((players::created_at.desc(), players::player_id.desc())),
+            (_, false) => filtered().order(players::player_id.asc()),
+            (_, true) => filtered().order(players::player_id.desc()),
+        };
+        let found = sorted
+            .limit(page.per_page)
+            .offset(page.offset())
+            .load::<Player>(&mut conn)?;
+        Ok((found, total))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error listing players for site admin: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "count": found.len(),
+        "players": found,
+        "pagination": PageMeta::new(page, total)
+    })))
+}
+
+/// Stops a player from signing in and signs out their sessions. Their
+/// leagues and matches are left as they are.
+pub async fn deactivate_player(
+    admin: SiteAdmin,
+    path: web::Path<i32>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let player = path.into_inner();
+    if player == admin.player_id {
+        return Err(AppError::BadRequest("You cannot deactivate your own account".into()));
+    }
+
+    let (deactivated, sessions_revoked) = web::block(move || -> Result<(Player, usize), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            let found = players::table.find(player).first::<Player>(conn)?;
+            let deactivated = match found.deactivated_at {
+                Some(_) => found,
+                None => diesel::update(players::table.find(player))
+                    .set(players::deactivated_at.eq(Utc::now()))
+                    .get_result::<Player>(conn)?,
+            };
+            let sessions_revoked = revoke_sessions(conn, player, None)?;
+            Ok((deactivated, sessions_revoked))
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error deactivating player: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Player deactivated",
+        "success": true,
+        "player": deactivated,
+        "sessions_revoked": sessions_revoked,
+    })))
+}
+
+/// Lets a deactivated player sign in again. Deleted accounts stay
+/// deactivated.
+pub async fn reactivate_player(
+    _admin: SiteAdmin,
+    path: web::Path<i32>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let player = path.into_inner();
+    let reactivated = web::block(move || -> Result<Player, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let found = players::table.find(player).first::<Player>(&mut conn)?;
+        if found.deleted_at.is_some() {
+            return Err(AppError::Conflict("Deleted accounts cannot be reactivated".into()));
+        }
+        Ok(diesel::update(players::table.find(player))
+            .set(players::deactivated_at.eq(None::<chrono::DateTime<Utc>>))
+            .get_result::<Player>(&mut conn)?)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error reactivating player: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Player reactivated",
+        "success": true,
+        "player": reactivated,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct AdminLeagueQuery {
+    /// Part of the league's name, matched case-insensitively.
+    pub search: Option<String>,
+}
+
+/// Every league, private ones included, with how many members each has.
+pub async fn list_leagues(
+    _admin: SiteAdmin,
+    query: web::Query<AdminLeagueQuery>,
+    paging: web::Query<PageQuery>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let query = query.into_inner();
+    let page = paging.page()?;
+    let sort = paging.sort(&LEAGUE_SORT_FIELDS, "league_id")?;
+
+    let (found, total) = web::block(move || -> Result<(Vec<serde_json::Value>, i64), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let filtered = || {
+            let mut filtered = leagues::table.into_boxed();
+            if let Some(search) = &query.search {
+                filtered = filtered.filter(leagues::league_name.ilike(format!("%{}%", search)));
+            }
+            filtered
+        };
+
+        let total = filtered().count().get_result::<i64>(&mut conn)?;
+        let sorted = match (sort.field.as_str(), sort.descending) {
+            ("league_name", false) => filtered().order((leagues::league_name.asc(), leagues::league_id.asc())),
+            ("league_name", true) => filtered().order((leagues::league_name.desc(), leagues::league_id.desc())),
+            ("created_at", false) => filtered().order((leagues::created_at.asc(), leagues::league_id.asc())),
+            ("created_at", true) => filtered().order((leagues::created_at.desc(), leagues::league_id.desc())),
+            (_, false) => filtered().order(leagues::league_id.asc()),
+            (_, true) => filtered().order(leagues::league_id.desc()),
+        };
+        let found = sorted
+            .limit(page.per_page)
+            .offset(page.offset())
+            .load::<League>(&mut conn)?;
+
+        let league_ids: Vec<i32> = found.iter().map(|league| league.league_id).collect();
+        let member_counts: HashMap<i32, i64> = player_leagues::table
+            .filter(player_leagues::league_id.eq_any(&league_ids))
+            .group_by(player_leagues::league_id)
+            .select((player_leagues::league_id, count_star()))
+            .load::<(i32, i64)>(&mut conn)?
+            .into_iter()
+            .collect();
+
+        let found = found
+            .into_iter()
+            .map(|league| {
+                let member_count = member_counts.get(&league.league_id).copied().unwrap_or(0);
+                let mut listed = json!(league);
+                listed["member_count"] = json!(member_count);
+                listed
+            })
+            .collect();
+        Ok((found, total))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error listing leagues for site admin: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "count": found.len(),
+        "leagues": found,
+        "pagination": PageMeta::new(page, total)
+    })))
+}
@@ -0,0 +1,525 @@
+use std::collections::{HashMap, HashSet};
+
+use actix_web::{web, HttpResponse, Responder};
+use chrono::Utc;
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::api::auth::{hash_password, validate_password};
+use crate::api::leagues::{ensure_league_admin, has_room, is_league_member, load_visible_league, lock_league};
+use crate::api::profiles::{
+    ensure_email_free, ensure_name_free, find_player_by_email, validate_email, validate_name, validate_phone,
+    validate_skill_level,
+};
+use crate::api::seasons::{load_season, season_standings};
+use crate::api::sessions::CurrentPlayer;
+use crate::api::site_admin::SiteAdmin;
+use crate::calendar::{local_rfc3339, parse_time_zone, zone_or_utc};
+use crate::db::models::{League, Match};
+use crate::db::schema::{leagues, matches, player_leagues, players};
+use crate::db::DbPool;
+use crate::errors::AppError;
+use crate::spreadsheet::{parse_rows, to_csv, ParsedRows, RowError};
+use crate::standings::compute_standings;
+
+const MEMBER_ROLES: [&str; 2] = ["player", "admin"];
+
+/// One line of a player import.
+#[derive(Deserialize)]
+pub struct PlayerRow {
+    pub name: String,
+    pub email: String,
+    pub password: String,
+    pub skill_level: Option<String>,
+    pub phone: Option<String>,
+    pub time_zone: Option<String>,
+}
+
+/// One line of a membership import; the player is named by id or email.
+#[derive(Deserialize)]
+pub struct MemberRow {
+    pub player_id: Option<i32>,
+    pub email: Option<String>,
+    pub role: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ImportedRow {
+    pub line: u64,
+    pub player_id: i32,
+}
+
+#[derive(Deserialize)]
+pub struct StandingsExportQuery {
+    /// Export the season's division tables instead of the league-wide one.
+    pub season_id: Option<i32>,
+}
+
+const MEMBER_EXPORT_HEADER: [&str; 8] = [
+    "player_id", "name", "email", "skill_level", "role", "singles_ranking", "doubles_ranking", "joined_at",
+];
+
+#[derive(Serialize, Queryable)]
+struct MemberExportRow {
+    player_id: i32,
+    name: String,
+    email: String,
+    skill_level: Option<String>,
+    role: String,
+    singles_ranking: Option<i32>,
+    doubles_ranking: Option<i32>,
+    #[serde(serialize_with = "utc_rfc3339")]
+    joined_at: chrono::DateTime<Utc>,
+}
+
+const MATCH_EXPORT_HEADER: [&str; 17] = [
+    "id", "match_type", "status", "datetime", "local_datetime", "location", "season_id",
+    "player1_id", "player2_id", "team1_player1_id", "team1_player2_id", "team2_player1_id", "team2_player2_id",
+    "winner_id", "score", "notes", "created_at",
+];
+
+#[derive(Serialize)]
+struct MatchExportRow {
+    id: i32,
+    match_type: String,
+    status: String,
+    datetime: String,
+    local_datetime: String,
+    location: String,
+    season_id: Option<i32>,
+    player1_id: Option<i32>,
+    player2_id: Option<i32>,
+    team1_player1_id: Option<i32>,
+    team1_player2_id: Option<i32>,
+    team2_player1_id: Option<i32>,
+    team2_player2_id: Option<i32>,
+    winner_id: Option<i32>,
+    score: Option<String>,
+    notes: Option<String>,
+    created_at: String,
+}
+
+impl MatchExportRow {
+    fn new(m: Match, zone: Tz) -> Self {
+        MatchExportRow {
+            id: m.id,
+            match_type: m.match_type,
+            status: m.status,
+            datetime: local_rfc3339(m.datetime, Tz::UTC),
+            local_datetime: local_rfc3339(m.datetime, zone),
+            location: m.location,
+            season_id: m.season_id,
+            player1_id: m.player1_id,
+            player2_id: m.player2_id,
+            team1_player1_id: m.team1_player1_id,
+            team1_player2_id: m.team1_player2_id,
+            team2_player1_id: m.team2_player1_id,
+            team2_player2_id: m.team2_player2_id,
+            winner_id: m.winner_id,
+            score: m.score,
+            notes: m.notes,
+            created_at: local_rfc3339(m.created_at, Tz::UTC),
+        }
+    }
+}
+
+const STANDINGS_EXPORT_HEADER: [&str; 9] = [
+    "division", "tier", "rank", "player_id", "name", "played", "wins", "losses", "movement",
+];
+
+#[derive(Serialize)]
+struct StandingExportRow {
+    division: Option<String>,
+    tier: Option<i32>,
+    rank: i32,
+    player_id: i32,
+    name: String,
+    played: i32,
+    wins: i32,
+    losses: i32,
+    movement: Option<String>,
+}
+
+fn utc_rfc3339<S: serde::Serializer>(value: &chrono::DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&local_rfc3339(*value, Tz::UTC))
+}
+
+fn csv_response(filename: String, body: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", filename)))
+        .body(body)
+}
+
+fn load_league(conn: &mut PgConnection, league: i32) -> Result<League, AppError> {
+    leagues::table
+        .find(league)
+        .first::<League>(conn)
+        .optional()
+        .map_err(|_| AppError::InternalError)?
+        .ok_or(AppError::NotFound)
+}
+
+/// A player row that passed the same checks as registration.
+struct CheckedPlayer {
+    name: String,
+    email: String,
+    skill_level: Option<String>,
+    phone: Option<String>,
+    time_zone: String,
+}
+
+/// The text of a validation error, for reporting it against a row.
+fn row_message(error: AppError) -> String {
+    match error {
+        AppError::BadRequest(message) | AppError::Conflict(message) => message,
+        other => other.to_string(),
+    }
+}
+
+/// Checks a player row on its own, before it is compared with the database,
+/// with the rules registration applies.
+fn validate_player_row(row: &PlayerRow) -> Result<CheckedPlayer, AppError> {
+    let name = row.name.trim().to_string();
+    let email = row.email.trim().to_string();
+    validate_name(&name)?;
+    validate_email(&email)?;
+    validate_password(&row.password)?;
+    let skill_level = row.skill_level.clone().filter(|level| !level.trim().is_empty());
+    if let Some(level) = &skill_level {
+        validate_skill_level(level)?;
+    }
+    let phone = row.phone.clone().filter(|phone| !phone.trim().is_empty());
+    if let Some(phone) = &phone {
+        validate_phone(phone)?;
+    }
+    let time_zone = row.time_zone.clone().unwrap_or_else(|| "UTC".to_string());
+    parse_time_zone(&time_zone)?;
+    Ok(CheckedPlayer { name, email, skill_level, phone, time_zone })
+}
+
+/// Registers players from a CSV file with the columns `name`, `email`,
+/// `password` and optionally `skill_level`, `phone` and `time_zone`.
+///
+/// Valid rows are imported and the rest are reported back by line, so a
+/// sheet can be fixed and only the failed lines sent again. Rows go through
+/// the same checks as registration, and only site admins may import.
+pub async fn import_players(
+    _admin: SiteAdmin,
+    body: String,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let ParsedRows { rows, mut errors } = parse_rows::<PlayerRow>(&body)?;
+
+    let imported = web::block(move || -> Result<(Vec<ImportedRow>, Vec<RowError>), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            let mut imported = Vec::new();
+            let mut seen = HashSet::new();
+
+            for (line, row) in rows {
+                let player = match validate_player_row(&row) {
+                    Ok(player) => player,
+                    Err(error) => {
+                        errors.push(RowError::new(line, row_message(error)));
+                        continue;
+                    }
+                };
+                if !seen.insert(player.email.to_lowercase()) {
+                    errors.push(RowError::new(line, format!("{} appears more than once in the file", player.email)));
+                    continue;
+                }
+                let free = ensure_email_free(conn, &player.email)
+                    .and_then(|_| ensure_name_free(conn, &player.name, None));
+                match free {
+                    Ok(()) => {}
+                    Err(error @ AppError::Conflict(_)) => {
+                        errors.push(RowError::new(line, row_message(error)));
+                        continue;
+                    }
+                    Err(error) => return Err(error),
+                }
+
+                let hashed_password = hash_password(&row.password)?;
+                let new_player = diesel::insert_into(players::table)
+                    .values((
+                        players::name.eq(&player.name),
+                        players::email.eq(&player.email),
+                        players::password.eq(&hashed_password),
+                        players::skill_level.eq(&player.skill_level),
+                        players::phone.eq(&player.phone),
+                        players::created_at.eq(Utc::now()),
+                        players::time_zone.eq(&player.time_zone),
+                        // The site admin importing them vouches for the address
+                        players::email_verified_at.eq(Some(Utc::now())),
+                    ))
+                    .returning(players::player_id)
+                    .get_result::<i32>(conn)?;
+                imported.push(ImportedRow { line, player_id: new_player });
+            }
+
+            errors.sort_by_key(|error| error.line);
+            Ok((imported, errors))
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error importing players: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    let (imported, errors) = imported;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": format!("Imported {} player(s), {} row(s) failed", imported.len(), errors.len()),
+        "success": errors.is_empty(),
+        "imported": imported,
+        "errors": errors
+    })))
+}
+
+/// Adds existing players to a league from a CSV file with a `player_id` or
+/// `email` column and an optional `role` (`player` or `admin`).
+///
+/// Only league admins may import. Players who are already members, or who
+/// would take the league over its member limit, are reported as row errors.
+pub async fn import_league_members(
+    current: CurrentPlayer,
+    path: web::Path<i32>,
+    body: String,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let league = path.into_inner();
+    let ParsedRows { rows, mut errors } = parse_rows::<MemberRow>(&body)?;
+
+    let imported = web::block(move || -> Result<(Vec<ImportedRow>, Vec<RowError>), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        conn.transaction(|conn| {
+            let league_row = lock_league(conn, league)?;
+            ensure_league_admin(conn, league, &current)?;
+
+            let mut imported = Vec::new();
+            for (line, row) in rows {
+                let member_role = row.role.clone().unwrap_or_else(|| "player".to_string()).to_lowercase();
+                if !MEMBER_ROLES.contains(&member_role.as_str()) {
+                    errors.push(RowError::new(line, format!("unknown role '{}', expected player or admin", member_role)));
+                    continue;
+                }
+
+                let found = match (row.player_id, &row.email) {
+                    (Some(id), _) => players::table.find(id).select(players::player_id).first::<i32>(conn).optional()?,
+                    (None, Some(email)) => find_player_by_email(conn, email.trim())?.map(|player| player.player_id),
+                    (None, None) => {
+                        errors.push(RowError::new(line, "player_id or email is required"));
+                        continue;
+                    }
+                };
+                let Some(member) = found else {
+                    errors.push(RowError::new(line, "no such player"));
+                    continue;
+                };
+                if is_league_member(conn, league, member)? {
+                    errors.push(RowError::new(line, format!("player {} is already a member", member)));
+                    continue;
+                }
+                if !has_room(conn, &league_row)? {
+                    errors.push(RowError::new(line, "league is full"));
+                    continue;
+                }
+
+                diesel::insert_into(player_leagues::table)
+                    .values((
+                        player_leagues::player_id.eq(member),
+                        player_leagues::league_id.eq(league),
+                        player_leagues::role.eq(&member_role),
+                        player_leagues::joined_at.eq(Utc::now()),
+                    ))
+                    .execute(conn)?;
+                imported.push(ImportedRow { line, player_id: member });
+            }
+
+            errors.sort_by_key(|error| error.line);
+            Ok((imported, errors))
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error importing league members: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    let (imported, errors) = imported;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": format!("Imported {} member(s), {} row(s) failed", imported.len(), errors.len()),
+        "success": errors.is_empty(),
+        "imported": imported,
+        "errors": errors
+    })))
+}
+
+/// Exports a league's members with their roles, rankings and email
+/// addresses. Only league admins may export.
+pub async fn export_league_members(
+    current: CurrentPlayer,
+    path: web::Path<i32>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let league = path.into_inner();
+
+    let body = web::block(move || -> Result<String, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        load_league(&mut conn, league)?;
+        ensure_league_admin(&mut conn, league, &current)?;
+        let members = player_leagues::table
+            .inner_join(players::table.on(players::player_id.eq(player_leagues::player_id)))
+            .filter(player_leagues::league_id.eq(league))
+            .order(players::player_id.asc())
+            .select((
+                players::player_id,
+                players::name,
+                players::email,
+                players::skill_level,
+                player_leagues::role,
+                player_leagues::singles_ranking,
+                player_leagues::doubles_ranking,
+                player_leagues::joined_at,
+            ))
+            .load::<MemberExportRow>(&mut conn)
+            .map_err(|_| AppError::InternalError)?;
+
+        to_csv(&MEMBER_EXPORT_HEADER, &members)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error exporting league members: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(csv_response(format!("league-{}-members.csv", league), body))
+}
+
+/// Exports every match of a league, oldest first, with times in UTC and in
+/// the league's time zone.
+pub async fn export_league_matches(
+    current: CurrentPlayer,
+    path: web::Path<i32>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let league = path.into_inner();
+
+    let body = web::block(move || -> Result<String, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let league_row = load_visible_league(&mut conn, league, current.player_id)?;
+        let zone = zone_or_utc(&league_row.time_zone);
+        let rows: Vec<MatchExportRow> = matches::table
+            .filter(matches::league_id.eq(league))
+            .order((matches::datetime.asc(), matches::id.asc()))
+            .load::<Match>(&mut conn)
+            .map_err(|_| AppError::InternalError)?
+            .into_iter()
+            .map(|m| MatchExportRow::new(m, zone))
+            .collect();
+
+        to_csv(&MATCH_EXPORT_HEADER, &rows)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error exporting league matches: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(csv_response(format!("league-{}-matches.csv", league), body))
+}
+
+/// Exports standings as CSV: the league-wide singles table built from all of
+/// the league's completed matches, or a season's division tables when
+/// `season_id` is given.
+pub async fn export_league_standings(
+    current: CurrentPlayer,
+    path: web::Path<i32>,
+    query: web::Query<StandingsExportQuery>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let league = path.into_inner();
+    let season_id = query.season_id;
+
+    let body = web::block(move || -> Result<String, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        load_visible_league(&mut conn, league, current.player_id)?;
+        let names: HashMap<i32, String> = player_leagues::table
+            .inner_join(players::table.on(players::player_id.eq(player_leagues::player_id)))
+            .filter(player_leagues::league_id.eq(league))
+            .select((players::player_id, players::name))
+            .load::<(i32, String)>(&mut conn)
+            .map_err(|_| AppError::InternalError)?
+            .into_iter()
+            .collect();
+        let name_of = |player: i32| names.get(&player).cloned().unwrap_or_default();
+
+        let rows: Vec<StandingExportRow> = match season_id {
+            Some(season_id) => {
+                let season = load_season(&mut conn, league, season_id)?;
+                season_standings(&mut conn, &season)?
+                    .into_iter()
+                    .flat_map(|division| {
+                        let division_name = division.division_name;
+                        let tier = division.tier;
+                        division.standings.into_iter().map(move |entry| (division_name.clone(), tier, entry))
+                    })
+                    .map(|(division, tier, entry)| StandingExportRow {
+                        division: Some(division),
+                        tier: Some(tier),
+                        rank: entry.standing.rank,
+                        player_id: entry.standing.player_id,
+                        name: name_of(entry.standing.player_id),
+                        played: entry.standing.played,
+                        wins: entry.standing.wins,
+                        losses: entry.standing.losses,
+                        movement: entry.movement,
+                    })
+                    .collect()
+            }
+            None => {
+                let mut members: Vec<i32> = names.keys().copied().collect();
+                members.sort_unstable();
+                let completed = matches::table
+                    .filter(matches::league_id.eq(league))
+                    .filter(matches::status.eq("Completed"))
+                    .load::<Match>(&mut conn)
+                    .map_err(|_| AppError::InternalError)?;
+                compute_standings(&members, &completed)
+                    .into_iter()
+                    .map(|standing| StandingExportRow {
+                        division: None,
+                        tier: None,
+                        rank: standing.rank,
+                        player_id: standing.player_id,
+                        name: name_of(standing.player_id),
+                        played: standing.played,
+                        wins: standing.wins,
+                        losses: standing.losses,
+                        movement: None,
+                    })
+                    .collect()
+            }
+        };
+
+        to_csv(&STANDINGS_EXPORT_HEADER, &rows)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error exporting standings: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    let filename = match season_id {
+        Some(season_id) => format!("league-{}-season-{}-standings.csv", league, season_id),
+        None => format!("league-{}-standings.csv", league),
+    };
+    Ok(csv_response(filename, body))
+}
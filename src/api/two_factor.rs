@@ -0,0 +1,339 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::{web, HttpResponse, Responder};
+use chrono::Utc;
+use diesel::prelude::*;
+use serde::Deserialize;
+use serde_json::json;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::api::sessions::{hash_token, mark_two_factor_verified, new_token, CurrentPlayer};
+use crate::db::models::TotpSecret;
+use crate::db::schema::{leagues, player_leagues, players, recovery_codes, totp_secrets};
+use crate::db::DbPool;
+use crate::errors::AppError;
+
+/// Shown next to the account in authenticator apps.
+const ISSUER: &str = "Tennis Scheduler";
+const CODE_DIGITS: usize = 6;
+const STEP_SECONDS: u64 = 30;
+/// How many recovery codes a player gets at a time.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+fn totp(secret: &str, account: &str) -> Result<TOTP, AppError> {
+    let secret = Secret::Encoded(secret.to_string()).to_bytes().map_err(|_| AppError::InternalError)?;
+    TOTP::new(Algorithm::SHA1, CODE_DIGITS, 1, STEP_SECONDS, secret, Some(ISSUER.into()), account.to_string())
+        .map_err(|_| AppError::InternalError)
+}
+
+fn now_seconds() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0)
+}
+
+/// The time step a code belongs to, allowing one step of clock drift either
+/// way.
+fn matching_step(totp: &TOTP, code: &str, now: u64) -> Option<i64> {
+    let current = now / STEP_SECONDS;
+    (current.saturating_sub(1)..=current + 1)
+        .find(|step| totp.generate(step * STEP_SECONDS) == code)
+        .map(|step| step as i64)
+}
+
+/// Recovery codes are handed out as `xxxxx-xxxxx` but accepted with any case
+/// or dashes.
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase()
+}
+
+fn player_email(conn: &mut PgConnection, player: i32) -> Result<String, AppError> {
+    Ok(players::table.find(player).select(players::email).first::<String>(conn)?)
+}
+
+fn find_secret(conn: &mut PgConnection, player: i32) -> Result<Option<TotpSecret>, AppError> {
+    Ok(totp_secrets::table.find(player).first::<TotpSecret>(conn).optional()?)
+}
+
+/// Whether the player has confirmed a two-factor enrolment.
+pub fn two_factor_enabled(conn: &mut PgConnection, player: i32) -> Result<bool, AppError> {
+    Ok(find_secret(conn, player)?.is_some_and(|secret| secret.enabled_at.is_some()))
+}
+
+/// Accepts a code from the player's authenticator app or one of their unused
+/// recovery codes, using it up. App codes are only accepted once each.
+pub fn check_second_factor(conn: &mut PgConnection, player: i32, code: &str) -> Result<bool, AppError> {
+    let Some(secret) = find_secret(conn, player)?.filter(|secret| secret.enabled_at.is_some()) else {
+        return Ok(false);
+    };
+
+    let code = code.trim();
+    let totp = totp(&secret.secret, &player_email(conn, player)?)?;
+    if let Some(step) = matching_step(&totp, code, now_seconds()) {
+        if secret.last_used_step.is_some_and(|last| step <= last) {
+            return Ok(false);
+        }
+        diesel::update(totp_secrets::table.find(player))
+            .set(totp_secrets::last_used_step.eq(step))
+            .execute(conn)?;
+        return Ok(true);
+    }
+
+    let used = diesel::update(
+        recovery_codes::table
+            .filter(recovery_codes::player_id.eq(player))
+            .filter(recovery_codes::code_hash.eq(hash_token(&normalize_recovery_code(code))))
+            .filter(recovery_codes::used_at.is_null()),
+    )
+    .set(recovery_codes::used_at.eq(Utc::now()))
+    .execute(conn)?;
+    Ok(used > 0)
+}
+
+/// Replaces the player's recovery codes with a fresh set, which is only
+/// available here.
+fn issue_recovery_codes(conn: &mut PgConnection, player: i32) -> Result<Vec<String>, AppError> {
+    diesel::delete(recovery_codes::table.filter(recovery_codes::player_id.eq(player))).execute(conn)?;
+    let codes = (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let token = new_token();
+            format!("{}-{}", &token[..5], &token[5..10])
+        })
+        .collect::<Vec<_>>();
+    let rows = codes
+        .iter()
+        .map(|code| {
+            (
+                recovery_codes::player_id.eq(player),
+                recovery_codes::code_hash.eq(hash_token(&normalize_recovery_code(code))),
+            )
+        })
+        .collect::<Vec<_>>();
+    diesel::insert_into(recovery_codes::table).values(&rows).execute(conn)?;
+    Ok(codes)
+}
+
+/// Leagues where the player is an admin and admins must use two-factor
+/// authentication.
+fn leagues_requiring_two_factor(conn: &mut PgConnection, player: i32) -> Result<Vec<i32>, AppError> {
+    Ok(player_leagues::table
+        .inner_join(leagues::table.on(leagues::league_id.eq(player_leagues::league_id)))
+        .filter(player_leagues::player_id.eq(player))
+        .filter(player_leagues::role.eq("admin"))
+        .filter(leagues::require_admin_two_factor.eq(true))
+        .select(leagues::league_id)
+        .order(leagues::league_id)
+        .load::<i32>(conn)?)
+}
+
+/// Whether two-factor authentication is on for the signed-in player, how many
+/// recovery codes they have left and which leagues require it of them.
+pub async fn get_status(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let status = web::block(move || -> Result<serde_json::Value, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let recovery_codes_left = recovery_codes::table
+            .filter(recovery_codes::player_id.eq(current.player_id))
+            .filter(recovery_codes::used_at.is_null())
+            .count()
+            .get_result::<i64>(&mut conn)?;
+        Ok(json!({
+            "enabled": two_factor_enabled(&mut conn, current.player_id)?,
+            "recovery_codes_left": recovery_codes_left,
+            "required_by_leagues": leagues_requiring_two_factor(&mut conn, current.player_id)?,
+        }))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error fetching two-factor status: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(status))
+}
+
+/// Starts enrolment with a new secret. The provisioning URI is what
+/// authenticator apps read from a QR code; two-factor authentication only
+/// comes on once a code from the app has been confirmed.
+pub async fn enroll(
+    current: CurrentPlayer,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let (secret, provisioning_uri) = web::block(move || -> Result<(String, String), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        if two_factor_enabled(&mut conn, current.player_id)? {
+            return Err(AppError::Conflict("Two-factor authentication is already on".into()));
+        }
+        let secret = Secret::generate_secret().to_encoded().to_string();
+        let provisioning_uri = totp(&secret, &player_email(&mut conn, current.player_id)?)?.get_url();
+
+        diesel::insert_into(totp_secrets::table)
+            .values((
+                totp_secrets::player_id.eq(current.player_id),
+                totp_secrets::secret.eq(&secret),
+                totp_secrets::created_at.eq(Utc::now()),
+            ))
+            .on_conflict(totp_secrets::player_id)
+            .do_update()
+            .set((
+                totp_secrets::secret.eq(&secret),
+                totp_secrets::last_used_step.eq(None::<i64>),
+                totp_secrets::created_at.eq(Utc::now()),
+            ))
+            .execute(&mut conn)?;
+        Ok((secret, provisioning_uri))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error enrolling in two-factor authentication: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Add the account to your authenticator app, then confirm it with a code",
+        "success": true,
+        "secret": secret,
+        "provisioning_uri": provisioning_uri,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmTwoFactorInput {
+    pub code: String,
+}
+
+/// Turns two-factor authentication on once the player shows a code from the
+/// app they enrolled, and hands out their recovery codes.
+pub async fn confirm(
+    current: CurrentPlayer,
+    item: web::Json<ConfirmTwoFactorInput>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let code = item.into_inner().code;
+
+    let codes = web::block(move || -> Result<Vec<String>, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        let secret = find_secret(&mut conn, current.player_id)?
+            .ok_or_else(|| AppError::BadRequest("Start two-factor enrolment first".into()))?;
+        if secret.enabled_at.is_some() {
+            return Err(AppError::Conflict("Two-factor authentication is already on".into()));
+        }
+        let totp = totp(&secret.secret, &player_email(&mut conn, current.player_id)?)?;
+        let step = matching_step(&totp, code.trim(), now_seconds())
+            .ok_or_else(|| AppError::BadRequest("That code does not match; check your device's clock".into()))?;
+
+        conn.transaction(|conn| {
+            diesel::update(totp_secrets::table.find(current.player_id))
+                .set((totp_secrets::enabled_at.eq(Utc::now()), totp_secrets::last_used_step.eq(step)))
+                .execute(conn)?;
+            // The code just shown counts for the session it was shown in
+            mark_two_factor_verified(conn, current.session_id)?;
+            issue_recovery_codes(conn, current.player_id)
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error confirming two-factor authentication: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Two-factor authentication is on; keep the recovery codes somewhere safe",
+        "success": true,
+        "recovery_codes": codes,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct DisableTwoFactorInput {
+    pub password: String,
+    /// A code from the authenticator app or a recovery code.
+    pub code: String,
+}
+
+/// Turns two-factor authentication off, given the password and a current
+/// code. Admins of leagues that require it have to keep it on.
+pub async fn disable(
+    current: CurrentPlayer,
+    item: web::Json<DisableTwoFactorInput>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let item = item.into_inner();
+
+    web::block(move || -> Result<(), AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        if !two_factor_enabled(&mut conn, current.player_id)? {
+            return Err(AppError::BadRequest("Two-factor authentication is not on".into()));
+        }
+        let required_by = leagues_requiring_two_factor(&mut conn, current.player_id)?;
+        if !required_by.is_empty() {
+            return Err(AppError::Forbidden(format!(
+                "Admins of league(s) {:?} must keep two-factor authentication on",
+                required_by
+            )));
+        }
+        let password_hash = players::table
+            .find(current.player_id)
+            .select(players::password)
+            .first::<String>(&mut conn)?;
+        if !bcrypt::verify(&item.password, &password_hash).unwrap_or(false) {
+            return Err(AppError::Forbidden("Wrong password".into()));
+        }
+        if !check_second_factor(&mut conn, current.player_id, &item.code)? {
+            return Err(AppError::Forbidden("Invalid two-factor code".into()));
+        }
+
+        conn.transaction(|conn| {
+            diesel::delete(recovery_codes::table.filter(recovery_codes::player_id.eq(current.player_id)))
+                .execute(conn)?;
+            diesel::delete(totp_secrets::table.find(current.player_id)).execute(conn)?;
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error disabling two-factor authentication: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Two-factor authentication is off",
+        "success": true
+    })))
+}
+
+/// Replaces the player's recovery codes, given a current code.
+pub async fn regenerate_recovery_codes(
+    current: CurrentPlayer,
+    item: web::Json<ConfirmTwoFactorInput>,
+    pool: web::Data<DbPool>,
+) -> Result<impl Responder, AppError> {
+    let code = item.into_inner().code;
+
+    let codes = web::block(move || -> Result<Vec<String>, AppError> {
+        let mut conn = pool.get().map_err(|_| AppError::InternalError)?;
+
+        if !two_factor_enabled(&mut conn, current.player_id)? {
+            return Err(AppError::BadRequest("Two-factor authentication is not on".into()));
+        }
+        if !check_second_factor(&mut conn, current.player_id, &code)? {
+            return Err(AppError::Forbidden("Invalid two-factor code".into()));
+        }
+        conn.transaction(|conn| issue_recovery_codes(conn, current.player_id))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Error replacing recovery codes: {:?}", e);
+        AppError::InternalError
+    })??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "New recovery codes issued; the old ones no longer work",
+        "success": true,
+        "recovery_codes": codes,
+    })))
+}
@@ -0,0 +1,373 @@
+use std::collections::{HashMap, HashSet};
+
+use bcrypt::{hash, DEFAULT_COST};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::profiles::{find_player_by_email, find_player_by_name};
+use crate::db::models::{Appointment, League, LeagueJoinRequest, Match};
+use crate::db::schema::{appointments, league_join_requests, leagues, matches, player_leagues, players};
+use crate::errors::AppError;
+
+/// Identifies a league archive, so other JSON is not mistaken for one.
+pub const ARCHIVE_FORMAT: &str = "tennis-scheduler/league";
+
+/// Bumped whenever the archive layout changes; imports refuse other versions.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// A league with everything that hangs off it, for backups and for moving a
+/// league between environments. Ids are those of the exporting database;
+/// an import gives every row a new id and matches players up by email.
+#[derive(Serialize, Deserialize)]
+pub struct LeagueArchive {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub league: League,
+    /// Every player the other sections refer to. Passwords are not exported.
+    pub players: Vec<ArchivePlayer>,
+    pub memberships: Vec<ArchiveMembership>,
+    pub join_requests: Vec<LeagueJoinRequest>,
+    /// Matches keep their `season_id` in the archive, but seasons are not
+    /// part of it, so imported matches are not linked to a season.
+    pub matches: Vec<Match>,
+    pub appointments: Vec<Appointment>,
+}
+
+#[derive(Serialize, Deserialize, Queryable)]
+pub struct ArchivePlayer {
+    pub player_id: i32,
+    pub name: String,
+    pub email: String,
+    pub skill_level: Option<String>,
+    pub phone: Option<String>,
+    pub time_zone: String,
+}
+
+#[derive(Serialize, Deserialize, Queryable)]
+pub struct ArchiveMembership {
+    pub player_id: i32,
+    pub role: String,
+    pub singles_ranking: Option<i32>,
+    pub doubles_ranking: Option<i32>,
+    pub joined_at: DateTime<Utc>,
+}
+
+/// What an import created, with the ids the rows got in this database.
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub league_id: i32,
+    /// Archive player id to the id of the same player here.
+    pub player_ids: HashMap<i32, i32>,
+    /// Players that did not exist here and were created without a usable
+    /// password; they need a password reset before they can log in.
+    pub created_players: Vec<i32>,
+    pub memberships: usize,
+    pub join_requests: usize,
+    pub matches: usize,
+    pub appointments: usize,
+}
+
+/// Bundles a league and its memberships, join requests, matches and
+/// appointments, plus the players they refer to.
+pub fn export_league(conn: &mut PgConnection, league: i32) -> Result<LeagueArchive, AppError> {
+    let league_row = leagues::table
+        .find(league)
+        .first::<League>(conn)
+        .optional()?
+        .ok_or(AppError::NotFound)?;
+
+    let memberships = player_leagues::table
+        .filter(player_leagues::league_id.eq(league))
+        .order(player_leagues::player_id.asc())
+        .select((
+            player_leagues::player_id,
+            player_leagues::role,
+            player_leagues::singles_ranking,
+            player_leagues::doubles_ranking,
+            player_leagues::joined_at,
+        ))
+        .load::<ArchiveMembership>(conn)?;
+    let join_requests = league_join_requests::table
+        .filter(league_join_requests::league_id.eq(league))
+        .order(league_join_requests::request_id.asc())
+        .load::<LeagueJoinRequest>(conn)?;
+    let league_matches = matches::table
+        .filter(matches::league_id.eq(league))
+        .order(matches::id.asc())
+        .load::<Match>(conn)?;
+    let league_appointments = appointments::table
+        .filter(appointments::league_id.eq(league))
+        .order(appointments::appointment_id.asc())
+        .load::<Appointment>(conn)?;
+
+    let archive_players = players::table
+        .filter(players::player_id.eq_any(referenced_players(
+            &league_row,
+            &memberships,
+            &join_requests,
+            &league_matches,
+            &league_appointments,
+        )))
+        .order(players::player_id.asc())
+        .select((
+            players::player_id,
+            players::name,
+            players::email,
+            players::skill_level,
+            players::phone,
+            players::time_zone,
+        ))
+        .load::<ArchivePlayer>(conn)?;
+
+    Ok(LeagueArchive {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        exported_at: Utc::now(),
+        league: league_row,
+        players: archive_players,
+        memberships,
+        join_requests,
+        matches: league_matches,
+        appointments: league_appointments,
+    })
+}
+
+/// Every player id the league's rows point at.
+fn referenced_players(
+    league: &League,
+    memberships: &[ArchiveMembership],
+    join_requests: &[LeagueJoinRequest],
+    league_matches: &[Match],
+    league_appointments: &[Appointment],
+) -> Vec<i32> {
+    let mut ids: HashSet<i32> = HashSet::new();
+    ids.insert(league.created_by);
+    ids.extend(memberships.iter().map(|membership| membership.player_id));
+    ids.extend(join_requests.iter().map(|request| request.player_id));
+    for m in league_matches {
+        ids.extend(
+            [
+                m.player1_id,
+                m.player2_id,
+                m.team1_player1_id,
+                m.team1_player2_id,
+                m.team2_player1_id,
+                m.team2_player2_id,
+                m.winner_id,
+            ]
+            .into_iter()
+            .flatten(),
+        );
+    }
+    for appointment in league_appointments {
+        ids.insert(appointment.requester_id);
+        ids.insert(appointment.opponent_id);
+    }
+    let mut ids: Vec<i32> = ids.into_iter().collect();
+    ids.sort_unstable();
+    ids
+}
+
+/// Checks that an archive can be imported before anything is written: the
+/// format and version are known, every referenced player is in the archive,
+/// players that have to be created have a free name, and the league name is
+/// free. All problems are reported together.
+pub fn check_archive(conn: &mut PgConnection, archive: &LeagueArchive, league_name: &str) -> Result<(), AppError> {
+    if archive.format != ARCHIVE_FORMAT {
+        return Err(AppError::BadRequest(format!("Not a league archive (format '{}')", archive.format)));
+    }
+    if archive.version != ARCHIVE_VERSION {
+        return Err(AppError::BadRequest(format!(
+            "Unsupported archive version {}, expected {}",
+            archive.version, ARCHIVE_VERSION
+        )));
+    }
+
+    let mut problems = Vec::new();
+
+    let mut archive_ids = HashSet::new();
+    let mut emails = HashSet::new();
+    for player in &archive.players {
+        if !archive_ids.insert(player.player_id) {
+            problems.push(format!("player {} is listed more than once", player.player_id));
+        }
+        if !emails.insert(player.email.to_lowercase()) {
+            problems.push(format!("email {} is used by more than one player", player.email));
+        }
+    }
+    let referenced = referenced_players(
+        &archive.league,
+        &archive.memberships,
+        &archive.join_requests,
+        &archive.matches,
+        &archive.appointments,
+    );
+    for missing in referenced.iter().filter(|id| !archive_ids.contains(id)) {
+        problems.push(format!("player {} is referenced but not included", missing));
+    }
+
+    // Players not matched by email are created, and names are unique
+    // ignoring case
+    let mut new_names = HashSet::new();
+    for player in &archive.players {
+        if find_player_by_email(conn, &player.email)?.is_some() {
+            continue;
+        }
+        if !new_names.insert(player.name.to_lowercase()) || find_player_by_name(conn, &player.name)?.is_some() {
+            problems.push(format!("player {} needs a new account but the name '{}' is taken", player.player_id, player.name));
+        }
+    }
+
+    let name_taken = leagues::table
+        .filter(leagues::league_name.eq(league_name))
+        .count()
+        .get_result::<i64>(conn)?;
+    if name_taken > 0 {
+        problems.push(format!("a league named '{}' already exists", league_name));
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!("Archive cannot be imported: {}", problems.join("; "))))
+    }
+}
+
+/// Recreates an archived league under new ids, optionally under a new name.
+///
+/// Players are matched to existing ones by email, ignoring case; the rest
+/// are created.
+/// Run it inside a transaction so a failed import leaves nothing behind.
+pub fn import_league(
+    conn: &mut PgConnection,
+    archive: &LeagueArchive,
+    league_name: Option<String>,
+) -> Result<ImportReport, AppError> {
+    let league_name = league_name.unwrap_or_else(|| archive.league.league_name.clone());
+    check_archive(conn, archive, &league_name)?;
+
+    let mut report = ImportReport::default();
+
+    for player in &archive.players {
+        let local_id = match find_player_by_email(conn, &player.email)? {
+            Some(existing) => existing.player_id,
+            None => {
+                let unusable_password = hash(Uuid::new_v4().to_string(), DEFAULT_COST).map_err(|_| AppError::InternalError)?;
+                let id = diesel::insert_into(players::table)
+                    .values((
+                        players::name.eq(&player.name),
+                        players::email.eq(&player.email),
+                        players::password.eq(unusable_password),
+                        players::skill_level.eq(&player.skill_level),
+                        players::phone.eq(&player.phone),
+                        players::time_zone.eq(&player.time_zone),
+                        players::created_at.eq(Utc::now()),
+                        // An archive does not prove who owns the address
+                        players::email_verified_at.eq(None::<DateTime<Utc>>),
+                    ))
+                    .returning(players::player_id)
+                    .get_result::<i32>(conn)?;
+                report.created_players.push(id);
+                id
+            }
+        };
+        report.player_ids.insert(player.player_id, local_id);
+    }
+
+    // check_archive made sure every referenced player is in the map
+    let ids = report.player_ids.clone();
+    let player = |id: i32| ids[&id];
+    let optional_player = |id: Option<i32>| id.map(player);
+
+    let source = &archive.league;
+    report.league_id = diesel::insert_into(leagues::table)
+        .values((
+            leagues::league_name.eq(&league_name),
+            leagues::description.eq(&source.description),
+            leagues::is_public.eq(source.is_public),
+            leagues::skill_level.eq(&source.skill_level),
+            leagues::created_by.eq(player(source.created_by)),
+            leagues::created_at.eq(source.created_at),
+            leagues::departure_policy.eq(&source.departure_policy),
+            leagues::max_members.eq(source.max_members),
+            leagues::waitlist_enabled.eq(source.waitlist_enabled),
+            leagues::time_zone.eq(&source.time_zone),
+            leagues::require_admin_two_factor.eq(source.require_admin_two_factor),
+        ))
+        .returning(leagues::league_id)
+        .get_result::<i32>(conn)?;
+    let league = report.league_id;
+
+    for membership in &archive.memberships {
+        diesel::insert_into(player_leagues::table)
+            .values((
+                player_leagues::player_id.eq(player(membership.player_id)),
+                player_leagues::league_id.eq(league),
+                player_leagues::role.eq(&membership.role),
+                player_leagues::singles_ranking.eq(membership.singles_ranking),
+                player_leagues::doubles_ranking.eq(membership.doubles_ranking),
+                player_leagues::joined_at.eq(membership.joined_at),
+            ))
+            .execute(conn)?;
+        report.memberships += 1;
+    }
+
+    for request in &archive.join_requests {
+        diesel::insert_into(league_join_requests::table)
+            .values((
+                league_join_requests::league_id.eq(league),
+                league_join_requests::player_id.eq(player(request.player_id)),
+                league_join_requests::description.eq(&request.description),
+                league_join_requests::status.eq(&request.status),
+                league_join_requests::created_at.eq(request.created_at),
+                league_join_requests::notes.eq(&request.notes),
+            ))
+            .execute(conn)?;
+        report.join_requests += 1;
+    }
+
+    for m in &archive.matches {
+        diesel::insert_into(matches::table)
+            .values((
+                matches::match_type.eq(&m.match_type),
+                matches::player1_id.eq(optional_player(m.player1_id)),
+                matches::player2_id.eq(optional_player(m.player2_id)),
+                matches::league_id.eq(league),
+                matches::team1_player1_id.eq(optional_player(m.team1_player1_id)),
+                matches::team1_player2_id.eq(optional_player(m.team1_player2_id)),
+                matches::team2_player1_id.eq(optional_player(m.team2_player1_id)),
+                matches::team2_player2_id.eq(optional_player(m.team2_player2_id)),
+                matches::datetime.eq(m.datetime),
+                matches::location.eq(&m.location),
+                matches::score.eq(&m.score),
+                matches::winner_id.eq(optional_player(m.winner_id)),
+                matches::status.eq(&m.status),
+                matches::notes.eq(&m.notes),
+                matches::created_at.eq(m.created_at),
+            ))
+            .execute(conn)?;
+        report.matches += 1;
+    }
+
+    for appointment in &archive.appointments {
+        diesel::insert_into(appointments::table)
+            .values((
+                appointments::requester_id.eq(player(appointment.requester_id)),
+                appointments::opponent_id.eq(player(appointment.opponent_id)),
+                appointments::league_id.eq(Some(league)),
+                appointments::start_time.eq(appointment.start_time),
+                appointments::end_time.eq(appointment.end_time),
+                appointments::status.eq(&appointment.status),
+                appointments::created_at.eq(appointment.created_at),
+                appointments::updated_at.eq(appointment.updated_at),
+            ))
+            .execute(conn)?;
+        report.appointments += 1;
+    }
+
+    report.created_players.sort_unstable();
+    Ok(report)
+}
@@ -0,0 +1,76 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use chrono_tz::Tz;
+
+use crate::errors::AppError;
+
+/// How long a calendar entry for a match lasts; matches have no end time.
+pub const MATCH_DURATION_MINUTES: i64 = 90;
+
+/// Parses an IANA time zone name such as `Europe/Berlin`.
+pub fn parse_time_zone(name: &str) -> Result<Tz, AppError> {
+    name.parse::<Tz>()
+        .map_err(|_| AppError::BadRequest(format!("Unknown time zone '{}', expected an IANA name like Europe/Berlin", name)))
+}
+
+/// The zone stored for a player or league. Names are validated when they are
+/// saved, so anything unparseable falls back to UTC.
+pub fn zone_or_utc(name: &str) -> Tz {
+    name.parse::<Tz>().unwrap_or(Tz::UTC)
+}
+
+/// Renders an instant in a zone as RFC 3339 with that zone's offset.
+pub fn local_rfc3339(instant: DateTime<Utc>, zone: Tz) -> String {
+    instant.with_timezone(&zone).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// One entry of an iCalendar export.
+#[derive(Debug, Clone)]
+pub struct CalendarEvent {
+    pub uid: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub summary: String,
+    pub location: Option<String>,
+    /// Whether the event is agreed on (`CONFIRMED`) or still open (`TENTATIVE`).
+    pub confirmed: bool,
+}
+
+/// Escapes text values as required by RFC 5545.
+fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Renders events as an iCalendar document with start and end times in `zone`.
+pub fn render_ics(events: &[CalendarEvent], zone: Tz, stamp: DateTime<Utc>) -> String {
+    let local = |instant: DateTime<Utc>| instant.with_timezone(&zone).format("%Y%m%dT%H%M%S").to_string();
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Tennis Scheduler//Matches//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!("X-WR-TIMEZONE:{}", zone.name()),
+    ];
+
+    for event in events {
+        lines.push("BEGIN:VEVENT".into());
+        lines.push(format!("UID:{}", event.uid));
+        lines.push(format!("DTSTAMP:{}", stamp.format("%Y%m%dT%H%M%SZ")));
+        lines.push(format!("DTSTART;TZID={}:{}", zone.name(), local(event.start)));
+        lines.push(format!("DTEND;TZID={}:{}", zone.name(), local(event.end)));
+        lines.push(format!("SUMMARY:{}", escape_text(&event.summary)));
+        if let Some(location) = &event.location {
+            lines.push(format!("LOCATION:{}", escape_text(location)));
+        }
+        lines.push(format!("STATUS:{}", if event.confirmed { "CONFIRMED" } else { "TENTATIVE" }));
+        lines.push("END:VEVENT".into());
+    }
+
+    lines.push("END:VCALENDAR".into());
+    lines.join("\r\n") + "\r\n"
+}
@@ -1,16 +1,74 @@
 use std::env;
 use dotenv::dotenv;
+use tennis_scheduler::mailer::{MailTransport, SmtpSecurity, SmtpSettings};
+use tennis_scheduler::oidc::OidcSettings;
 
 pub struct Config {
     pub database_url: String,
     pub server_addr: String,
+    /// Apply pending migrations before the server starts (`RUN_MIGRATIONS=true`).
+    pub run_migrations: bool,
+    /// Key for signing tokens such as email verification links (`TOKEN_SECRET`).
+    pub token_secret: String,
+    /// Identity provider players can sign in with, when `OIDC_ISSUER` is set
+    /// (along with `OIDC_CLIENT_ID`, `OIDC_CLIENT_SECRET` and `OIDC_REDIRECT_URL`).
+    pub oidc: Option<OidcSettings>,
+    /// Where mail goes (`MAIL_TRANSPORT`): `smtp`, configured with `SMTP_HOST`,
+    /// `SMTP_FROM` and optionally `SMTP_PORT`, `SMTP_SECURITY`, `SMTP_USERNAME`
+    /// and `SMTP_PASSWORD`, or `log` to print mail, for development only.
+    pub mail: MailTransport,
 }
 
 impl Config {
     pub fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL")?;
+        let database_url = Self::database_url()?;
         let server_addr = env::var("SERVER_ADDR")?;
-        Ok(Config { database_url, server_addr })
+        let run_migrations = env::var("RUN_MIGRATIONS")
+            .map(|value| matches!(value.to_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+        let token_secret = env::var("TOKEN_SECRET")?;
+        let oidc = match env::var("OIDC_ISSUER") {
+            Ok(issuer) => Some(OidcSettings {
+                issuer,
+                client_id: env::var("OIDC_CLIENT_ID")?,
+                client_secret: env::var("OIDC_CLIENT_SECRET")?,
+                redirect_url: env::var("OIDC_REDIRECT_URL")?,
+            }),
+            Err(_) => None,
+        };
+        let mail = Self::mail_transport()?;
+        Ok(Config { database_url, server_addr, run_migrations, token_secret, oidc, mail })
+    }
+
+    fn mail_transport() -> Result<MailTransport, Box<dyn std::error::Error>> {
+        match env::var("MAIL_TRANSPORT").map(|value| value.to_lowercase()).as_deref() {
+            Ok("smtp") => {
+                let security = match env::var("SMTP_SECURITY") {
+                    Ok(value) => SmtpSecurity::parse(&value)
+                        .ok_or_else(|| format!("SMTP_SECURITY must be tls, starttls or none, not '{}'", value))?,
+                    Err(_) => SmtpSecurity::StartTls,
+                };
+                let port = match env::var("SMTP_PORT") {
+                    Ok(value) => value.parse()?,
+                    Err(_) => security.default_port(),
+                };
+                Ok(MailTransport::Smtp(SmtpSettings {
+                    host: env::var("SMTP_HOST")?,
+                    port,
+                    security,
+                    username: env::var("SMTP_USERNAME").ok(),
+                    password: env::var("SMTP_PASSWORD").ok(),
+                    from: env::var("SMTP_FROM")?,
+                }))
+            }
+            Ok("log") => Ok(MailTransport::Log),
+            _ => Err("MAIL_TRANSPORT must be smtp, or log for development".into()),
+        }
+    }
+
+    /// Only the database URL, for commands that do not start the server.
+    pub fn database_url() -> Result<String, Box<dyn std::error::Error>> {
+        dotenv().ok();
+        Ok(env::var("DATABASE_URL")?)
     }
 }
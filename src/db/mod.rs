@@ -1,8 +1,15 @@
-use diesel::pg::PgConnection;
+use diesel::pg::{Pg, PgConnection};
 use diesel::r2d2::{self, ConnectionManager};
+use diesel::migration::MigrationSource;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 
 pub type DbPool = r2d2::Pool<ConnectionManager<PgConnection>>;
 
+/// Every migration under `migrations/`, compiled into the binary.
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+pub type MigrationError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
 pub fn establish_connection(database_url: &str) -> DbPool {
     let manager = ConnectionManager::<PgConnection>::new(database_url);
     r2d2::Pool::builder()
@@ -10,5 +17,36 @@ pub fn establish_connection(database_url: &str) -> DbPool {
         .expect("Failed to create pool.")
 }
 
+/// Applies the migrations that have not run yet and returns their versions.
+pub fn run_pending_migrations(conn: &mut PgConnection) -> Result<Vec<String>, MigrationError> {
+    let applied = conn.run_pending_migrations(MIGRATIONS)?;
+    Ok(applied.iter().map(|version| version.to_string()).collect())
+}
+
+/// Reverts the most recently applied migration and returns its version.
+pub fn revert_last_migration(conn: &mut PgConnection) -> Result<String, MigrationError> {
+    let reverted = conn.revert_last_migration(MIGRATIONS)?;
+    Ok(reverted.to_string())
+}
+
+/// Lists every embedded migration with whether it has been applied.
+pub fn migration_status(conn: &mut PgConnection) -> Result<Vec<(String, bool)>, MigrationError> {
+    let applied: Vec<String> = conn
+        .applied_migrations()?
+        .iter()
+        .map(|version| version.to_string())
+        .collect();
+
+    let migrations = MigrationSource::<Pg>::migrations(&MIGRATIONS)?;
+    Ok(migrations
+        .iter()
+        .map(|migration| {
+            let name = migration.name();
+            let is_applied = applied.contains(&name.version().to_string());
+            (name.to_string(), is_applied)
+        })
+        .collect())
+}
+
 pub mod models;
 pub mod schema;
@@ -1,4 +1,4 @@
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use diesel::prelude::*;
 
@@ -8,11 +8,58 @@ pub struct Player {
     pub player_id: i32,
     pub name: String,
     pub email: String,
+    #[serde(skip_serializing)]
     pub password: String,
     pub skill_level: Option<String>,
-    pub role: Option<String>,
+    #[diesel(deserialize_as = String)]
+    pub role: Role,
     pub phone: Option<String>,
-    pub created_at: NaiveDateTime,
+    pub created_at: DateTime<Utc>,
+    pub time_zone: String,  // IANA name, e.g. "Europe/Berlin"
+    pub avatar_url: Option<String>,
+    pub email_verified_at: Option<DateTime<Utc>>,  // None until the player confirms their address
+    pub deactivated_at: Option<DateTime<Utc>>,  // Deactivated players cannot sign in
+    pub deleted_at: Option<DateTime<Utc>>,  // Set when the player deleted their account; the row is anonymized
+    pub verification_sent_at: Option<DateTime<Utc>>,  // When a verification code was last mailed
+}
+
+/// What a player may do across the whole site, as opposed to their role in a
+/// league. Stored in `players.role`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Player,
+    /// Manages players and sees every league.
+    SiteAdmin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Player => "player",
+            Role::SiteAdmin => "site_admin",
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "player" => Ok(Role::Player),
+            "site_admin" => Ok(Role::SiteAdmin),
+            other => Err(format!("Unknown role '{}', expected player or site_admin", other)),
+        }
+    }
+}
+
+impl TryFrom<String> for Role {
+    type Error = String;
+
+    fn try_from(role: String) -> Result<Self, Self::Error> {
+        role.parse()
+    }
 }
 
 #[derive(Queryable, Selectable, Serialize, Deserialize)]
@@ -20,11 +67,18 @@ pub struct Player {
 pub struct League {
     pub league_id: i32,
     pub league_name: String,
-    pub skill_level: Option<String>,
-    pub is_public: bool,
     pub description: Option<String>,
-    pub created_by: String,
-    pub created_at: chrono::NaiveDateTime,
+    pub is_public: bool,
+    pub skill_level: Option<String>,
+    pub created_by: i32,
+    pub created_at: DateTime<Utc>,
+    pub departure_policy: String,  // "cancel" or "forfeit"
+    pub max_members: Option<i32>,  // None means no limit
+    pub waitlist_enabled: bool,
+    pub time_zone: String,  // IANA name, e.g. "Europe/Berlin"
+    /// Admins must have two-factor authentication turned on to act as admins.
+    #[serde(default)]
+    pub require_admin_two_factor: bool,
 }
 
 #[derive(Queryable, Serialize, Deserialize)]
@@ -35,7 +89,7 @@ pub struct LeaguePlayer {
     pub doubles_ranking: Option<i32>,
     pub role: String,  // app role: "admin", "user", etc.
     pub league_role: String,  // league role: "manager" or "player"
-    pub joined_at: chrono::NaiveDateTime,
+    pub joined_at: DateTime<Utc>,
 }
 
 #[derive(Queryable, Serialize, Deserialize)]
@@ -44,11 +98,11 @@ pub struct Appointment {
     pub requester_id: i32,
     pub opponent_id: i32,
     pub league_id: Option<i32>,
-    pub start_time: NaiveDateTime,
-    pub end_time: NaiveDateTime,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
     pub status: String,
-    pub created_at: NaiveDateTime,
-    pub updated_at: NaiveDateTime,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Insertable)]
@@ -57,56 +111,74 @@ pub struct NewLeague {
     pub league_name: String,
     pub description: Option<String>,
     pub skill_level: Option<String>,
-    pub created_by: String,
+    pub created_by: i32,
     pub is_public: bool,
-    pub created_at: chrono::NaiveDateTime,
+    pub created_at: DateTime<Utc>,
+    pub departure_policy: String,
+    pub max_members: Option<i32>,
+    pub waitlist_enabled: bool,
+    pub time_zone: String,
 }
 
 
+#[derive(AsChangeset)]
+#[diesel(table_name = crate::db::schema::leagues)]
+pub struct LeagueChanges {
+    pub description: Option<String>,
+    pub skill_level: Option<String>,
+    pub is_public: Option<bool>,
+    pub departure_policy: Option<String>,
+    pub max_members: Option<i32>,
+    pub waitlist_enabled: Option<bool>,
+    pub time_zone: Option<String>,
+    pub require_admin_two_factor: Option<bool>,
+}
+
 #[derive(Queryable, Insertable, Serialize, Deserialize, Clone, Selectable)]
 #[diesel(table_name = crate::db::schema::matches)]
 pub struct Match {
     pub id: i32,
     pub match_type: String,
-    pub player1_id: Option<String>,
-    pub player2_id: Option<String>,
-    pub league_id: String,
-    pub team1_player1_id: Option<String>,
-    pub team1_player2_id: Option<String>,
-    pub team2_player1_id: Option<String>,
-    pub team2_player2_id: Option<String>,
-    pub datetime: String,
+    pub player1_id: Option<i32>,
+    pub player2_id: Option<i32>,
+    pub league_id: i32,
+    pub team1_player1_id: Option<i32>,
+    pub team1_player2_id: Option<i32>,
+    pub team2_player1_id: Option<i32>,
+    pub team2_player2_id: Option<i32>,
+    pub datetime: DateTime<Utc>,
     pub location: String,
     pub score: Option<String>,
-    pub winner_id: Option<String>,
+    pub winner_id: Option<i32>,
     pub status: String,
     pub notes: Option<String>,
-    pub created_at: chrono::NaiveDateTime,
+    pub created_at: DateTime<Utc>,
+    pub season_id: Option<i32>,
 }
 
 #[derive(Insertable, Deserialize)]
 #[diesel(table_name = crate::db::schema::matches)]
 pub struct NewMatch {
     pub match_type: String,
-    pub player1_id: Option<String>,
-    pub player2_id: Option<String>,
-    pub league_id: String,
-    pub team1_player1_id: Option<String>,
-    pub team1_player2_id: Option<String>,
-    pub team2_player1_id: Option<String>,
-    pub team2_player2_id: Option<String>,
-    pub datetime: String,
+    pub player1_id: Option<i32>,
+    pub player2_id: Option<i32>,
+    pub league_id: i32,
+    pub team1_player1_id: Option<i32>,
+    pub team1_player2_id: Option<i32>,
+    pub team2_player1_id: Option<i32>,
+    pub team2_player2_id: Option<i32>,
+    pub datetime: DateTime<Utc>,
     pub location: String,
     pub score: Option<String>,
-    pub winner_id: Option<String>,
+    pub winner_id: Option<i32>,
     pub status: String,
     pub notes: Option<String>,
+    pub season_id: Option<i32>,
 }
 
 #[derive(Deserialize)]
 pub struct UpdateMatchInput {
-    pub date: Option<String>,
-    pub time: Option<String>,
+    pub datetime: Option<String>,
     pub location: Option<String>,
     pub score: Option<String>,
     pub winner_id: Option<i32>,
@@ -117,24 +189,237 @@ pub struct UpdateMatchInput {
 #[derive(Queryable, Serialize, Deserialize)]
 pub struct LeagueJoinRequest {
     pub request_id: i32,
-    pub league_id: String,
-    pub player_id: String,
+    pub league_id: i32,
+    pub player_id: i32,
     pub description: Option<String>,
     pub status: String,
-    pub created_at: NaiveDateTime,
+    pub created_at: DateTime<Utc>,
     pub notes: Option<String>,
 }
 
 #[derive(Insertable)]
 #[diesel(table_name = crate::db::schema::league_join_requests)]
 pub struct NewLeagueJoinRequest {
-    pub league_id: String,
-    pub player_id: String,
+    pub league_id: i32,
+    pub player_id: i32,
     pub description: Option<String>,
     pub notes: Option<String>,
 }
 
+#[derive(Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::db::schema::league_seasons)]
+pub struct LeagueSeason {
+    pub season_id: i32,
+    pub league_id: i32,
+    pub season_name: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub registration_deadline: Option<NaiveDate>,
+    pub status: String,  // "open" or "closed"
+    pub created_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+}
 
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::league_seasons)]
+pub struct NewLeagueSeason {
+    pub league_id: i32,
+    pub season_name: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub registration_deadline: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+}
 
+#[derive(Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::db::schema::league_divisions)]
+pub struct LeagueDivision {
+    pub division_id: i32,
+    pub season_id: i32,
+    pub division_name: String,
+    pub tier: i32,  // 1 is the top division
+    pub promotion_spots: i32,
+    pub relegation_spots: i32,
+    pub created_at: DateTime<Utc>,
+}
 
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::league_divisions)]
+pub struct NewLeagueDivision {
+    pub season_id: i32,
+    pub division_name: String,
+    pub tier: i32,
+    pub promotion_spots: i32,
+    pub relegation_spots: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::db::schema::season_members)]
+pub struct SeasonMember {
+    pub season_id: i32,
+    pub player_id: i32,
+    pub division_id: Option<i32>,
+    pub final_rank: Option<i32>,
+    pub movement: Option<String>,  // "promoted", "stayed" or "relegated"
+    pub joined_at: DateTime<Utc>,
+}
 
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::season_members)]
+pub struct NewSeasonMember {
+    pub season_id: i32,
+    pub player_id: i32,
+    pub division_id: Option<i32>,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::db::schema::league_invitations)]
+pub struct LeagueInvitation {
+    pub invitation_id: i32,
+    pub league_id: i32,
+    pub invited_by: i32,
+    pub invitee_id: Option<i32>,
+    pub invitee_email: Option<String>,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub status: String,  // "pending", "accepted", "declined" or "revoked"
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub responded_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::league_invitations)]
+pub struct NewLeagueInvitation {
+    pub league_id: i32,
+    pub invited_by: i32,
+    pub invitee_id: Option<i32>,
+    pub invitee_email: Option<String>,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::db::schema::notifications)]
+pub struct Notification {
+    pub notification_id: i32,
+    pub player_id: i32,
+    pub message: String,
+    pub league_id: Option<i32>,
+    pub is_read: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::notifications)]
+pub struct NewNotification {
+    pub player_id: i32,
+    pub message: String,
+    pub league_id: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::email_changes)]
+pub struct EmailChange {
+    pub change_id: i32,
+    pub player_id: i32,
+    pub new_email: String,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::email_changes)]
+pub struct NewEmailChange {
+    pub player_id: i32,
+    pub new_email: String,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name = crate::db::schema::sessions)]
+pub struct Session {
+    pub session_id: i32,
+    pub player_id: i32,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+    pub two_factor_verified_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::sessions)]
+pub struct NewSession {
+    pub player_id: i32,
+    pub token_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::password_resets)]
+pub struct PasswordReset {
+    pub reset_id: i32,
+    pub player_id: i32,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::password_resets)]
+pub struct NewPasswordReset {
+    pub player_id: i32,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::login_attempts)]
+pub struct NewLoginAttempt {
+    pub email: String,
+    pub player_id: Option<i32>,
+    pub ip_address: Option<String>,
+    pub outcome: String,
+    pub attempted_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::totp_secrets)]
+pub struct TotpSecret {
+    pub player_id: i32,
+    pub secret: String,
+    pub enabled_at: Option<DateTime<Utc>>,
+    pub last_used_step: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name = crate::db::schema::player_identities)]
+pub struct PlayerIdentity {
+    pub identity_id: i32,
+    pub player_id: i32,
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+}
@@ -28,9 +28,15 @@ diesel::table! {
         email -> Varchar,
         password -> Varchar,
         skill_level -> Nullable<Varchar>,
-        role -> Nullable<Varchar>,
+        role -> Varchar,
         phone -> Nullable<Varchar>,
-        created_at -> Timestamp,
+        created_at -> Timestamptz,
+        time_zone -> Varchar,
+        avatar_url -> Nullable<Varchar>,
+        email_verified_at -> Nullable<Timestamptz>,
+        deactivated_at -> Nullable<Timestamptz>,
+        deleted_at -> Nullable<Timestamptz>,
+        verification_sent_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -38,20 +44,21 @@ diesel::table! {
     matches (id) {
         id -> Int4,
         match_type -> VarChar,
-        player1_id -> Nullable<Text>,
-        player2_id -> Nullable< Text>,
-        league_id -> Text,
-        team1_player1_id -> Nullable<Text>,
-        team1_player2_id -> Nullable<Text>,
-        team2_player1_id -> Nullable<Text>,
-        team2_player2_id -> Nullable<Text>,
-        datetime -> Text,
+        player1_id -> Nullable<Int4>,
+        player2_id -> Nullable<Int4>,
+        league_id -> Int4,
+        team1_player1_id -> Nullable<Int4>,
+        team1_player2_id -> Nullable<Int4>,
+        team2_player1_id -> Nullable<Int4>,
+        team2_player2_id -> Nullable<Int4>,
+        datetime -> Timestamptz,
         location -> Text,
         score -> Nullable<Text>,
-        winner_id -> Nullable<Text>,
+        winner_id -> Nullable<Int4>,
         status -> Text,
         notes -> Nullable<Text>,
-        created_at -> Timestamp,
+        created_at -> Timestamptz,
+        season_id -> Nullable<Int4>,
     }
 }
 
@@ -61,11 +68,11 @@ diesel::table! {
         requester_id -> Int4,
         opponent_id -> Int4,
         league_id -> Nullable<Int4>,
-        start_time -> Timestamp,
-        end_time -> Timestamp,
+        start_time -> Timestamptz,
+        end_time -> Timestamptz,
         status -> Varchar,
-        created_at -> Timestamp,
-        updated_at -> Timestamp,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -76,34 +83,183 @@ diesel::table! {
         description -> Nullable<Text>,
         is_public -> Bool,
         skill_level -> Nullable<Varchar>,
-        created_by -> Varchar,
-        created_at -> Timestamp,
+        created_by -> Int4,
+        created_at -> Timestamptz,
+        departure_policy -> Varchar,
+        max_members -> Nullable<Int4>,
+        waitlist_enabled -> Bool,
+        time_zone -> Varchar,
+        require_admin_two_factor -> Bool,
     }
 }
 
 diesel::table! {
     player_leagues (player_id, league_id) {
-        player_id -> VarChar,
-        league_id -> VarChar,
+        player_id -> Int4,
+        league_id -> Int4,
         role -> Varchar,
         singles_ranking -> Nullable<Int4>,
         doubles_ranking -> Nullable<Int4>,
-        joined_at -> Timestamp,
+        joined_at -> Timestamptz,
     }
 }
 
 diesel::table! {
     league_join_requests (request_id) {
         request_id -> Int4,
-        league_id -> Varchar,
-        player_id -> Varchar,
+        league_id -> Int4,
+        player_id -> Int4,
         description -> Nullable<Text>,
         status -> Varchar,
-        created_at -> Timestamp,
+        created_at -> Timestamptz,
         notes -> Nullable<Text>,
     }
 }
 
+diesel::table! {
+    league_seasons (season_id) {
+        season_id -> Int4,
+        league_id -> Int4,
+        season_name -> Varchar,
+        start_date -> Date,
+        end_date -> Date,
+        registration_deadline -> Nullable<Date>,
+        status -> Varchar,
+        created_at -> Timestamptz,
+        closed_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    league_divisions (division_id) {
+        division_id -> Int4,
+        season_id -> Int4,
+        division_name -> Varchar,
+        tier -> Int4,
+        promotion_spots -> Int4,
+        relegation_spots -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    season_members (season_id, player_id) {
+        season_id -> Int4,
+        player_id -> Int4,
+        division_id -> Nullable<Int4>,
+        final_rank -> Nullable<Int4>,
+        movement -> Nullable<Varchar>,
+        joined_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    league_invitations (invitation_id) {
+        invitation_id -> Int4,
+        league_id -> Int4,
+        invited_by -> Int4,
+        invitee_id -> Nullable<Int4>,
+        invitee_email -> Nullable<Varchar>,
+        token_hash -> Varchar,
+        status -> Varchar,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+        responded_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    notifications (notification_id) {
+        notification_id -> Int4,
+        player_id -> Int4,
+        message -> Text,
+        league_id -> Nullable<Int4>,
+        is_read -> Bool,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    email_changes (change_id) {
+        change_id -> Int4,
+        player_id -> Int4,
+        new_email -> Varchar,
+        token_hash -> Varchar,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+        confirmed_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    sessions (session_id) {
+        session_id -> Int4,
+        player_id -> Int4,
+        token_hash -> Varchar,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+        user_agent -> Nullable<Varchar>,
+        ip_address -> Nullable<Varchar>,
+        last_seen_at -> Nullable<Timestamptz>,
+        two_factor_verified_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    password_resets (reset_id) {
+        reset_id -> Int4,
+        player_id -> Int4,
+        token_hash -> Varchar,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+        used_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    login_attempts (attempt_id) {
+        attempt_id -> Int4,
+        email -> Varchar,
+        player_id -> Nullable<Int4>,
+        ip_address -> Nullable<Varchar>,
+        outcome -> Varchar,
+        attempted_at -> Timestamptz,
+        cleared_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    totp_secrets (player_id) {
+        player_id -> Int4,
+        secret -> Varchar,
+        enabled_at -> Nullable<Timestamptz>,
+        last_used_step -> Nullable<Int8>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    recovery_codes (code_id) {
+        code_id -> Int4,
+        player_id -> Int4,
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    player_identities (identity_id) {
+        identity_id -> Int4,
+        player_id -> Int4,
+        issuer -> Varchar,
+        subject -> Varchar,
+        email -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+        last_login_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::allow_tables_to_appear_in_same_query!(
     players,
     player_leagues,
@@ -111,4 +267,16 @@ diesel::allow_tables_to_appear_in_same_query!(
     matches,
     appointments,
     league_join_requests,
+    league_seasons,
+    league_divisions,
+    season_members,
+    league_invitations,
+    notifications,
+    email_changes,
+    sessions,
+    password_resets,
+    login_attempts,
+    totp_secrets,
+    recovery_codes,
+    player_identities,
 );
@@ -1,4 +1,4 @@
-use actix_web::{HttpResponse, ResponseError};
+use actix_web::{http::header, HttpResponse, ResponseError};
 use derive_more::Display;
 use serde::Serialize;
 
@@ -10,13 +10,48 @@ pub enum AppError {
     BadRequest(String),
     #[display(fmt = "Not Found")]
     NotFound,
+    #[display(fmt = "Forbidden: {}", _0)]
+    Forbidden(String),
+    #[display(fmt = "Unauthorized: {}", _0)]
+    Unauthorized(String),
+    #[display(fmt = "Conflict: {}", _0)]
+    Conflict(String),
+    /// Refused until the given number of seconds has passed.
+    #[display(fmt = "Too Many Requests: {}", _0)]
+    TooManyRequests(String, i64),
 }
 
+impl std::error::Error for AppError {}
+
+// Lets `#[api_v2_operation]` handlers return `AppError`; the API docs do not
+// list the error responses
+impl paperclip::v2::schema::Apiv2Errors for AppError {}
+
 #[derive(Serialize)]
 struct ErrorResponse {
     error: String,
 }
 
+impl From<diesel::result::Error> for AppError {
+    fn from(error: diesel::result::Error) -> Self {
+        use diesel::result::{DatabaseErrorKind, Error};
+
+        match error {
+            Error::NotFound => AppError::NotFound,
+            // Player names and email addresses are unique ignoring case; a
+            // write that raced past the checks for that ends up here
+            Error::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => match info.constraint_name() {
+                Some("players_lower_name_key") => AppError::Conflict("That name is already taken".into()),
+                Some("players_lower_email_key" | "players_email_key") => {
+                    AppError::Conflict("That email address is already registered".into())
+                }
+                _ => AppError::InternalError,
+            },
+            _ => AppError::InternalError,
+        }
+    }
+}
+
 impl ResponseError for AppError {
     fn error_response(&self) -> HttpResponse {
         match self {
@@ -35,6 +70,28 @@ impl ResponseError for AppError {
                     error: "Resource Not Found".into(),
                 })
             }
+            AppError::Forbidden(message) => {
+                HttpResponse::Forbidden().json(ErrorResponse {
+                    error: message.clone(),
+                })
+            }
+            AppError::Unauthorized(message) => {
+                HttpResponse::Unauthorized().json(ErrorResponse {
+                    error: message.clone(),
+                })
+            }
+            AppError::Conflict(message) => {
+                HttpResponse::Conflict().json(ErrorResponse {
+                    error: message.clone(),
+                })
+            }
+            AppError::TooManyRequests(message, retry_after) => {
+                HttpResponse::TooManyRequests()
+                    .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+                    .json(ErrorResponse {
+                        error: message.clone(),
+                    })
+            }
         }
     }
 }
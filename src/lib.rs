@@ -1,3 +1,14 @@
+pub mod admin;
 pub mod api;
+pub mod archive;
+pub mod calendar;
 pub mod db;
-pub mod errors; 
\ No newline at end of file
+pub mod errors;
+pub mod mailer;
+pub mod matchmaking;
+pub mod oidc;
+pub mod personal_data;
+pub mod search;
+pub mod spreadsheet;
+pub mod standings;
+pub mod tokens;
\ No newline at end of file
@@ -0,0 +1,285 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+use crate::errors::AppError;
+
+/// A message to a single recipient.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Sends mail on behalf of the API. Handlers take it as
+/// `web::Data<dyn Mailer>`, so the server and tests can plug in their own.
+pub trait Mailer: Send + Sync {
+    fn send(&self, email: Email) -> Result<(), AppError>;
+}
+
+/// Prints mail to standard output instead of sending it, for development.
+/// Codes in the mail end up in the server log, so never use it in production.
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+    fn send(&self, email: Email) -> Result<(), AppError> {
+        println!("Mail to {}: {}\n{}", email.to, email.subject, email.body);
+        Ok(())
+    }
+}
+
+/// Keeps mail in memory so tests can read what would have been sent.
+#[derive(Default)]
+pub struct MemoryMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl MemoryMailer {
+    /// Everything sent so far, oldest first.
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().map(|sent| sent.clone()).unwrap_or_default()
+    }
+
+    /// The last message sent to `to`, if any.
+    pub fn last_to(&self, to: &str) -> Option<Email> {
+        self.sent().into_iter().rev().find(|email| email.to == to)
+    }
+}
+
+impl Mailer for MemoryMailer {
+    fn send(&self, email: Email) -> Result<(), AppError> {
+        self.sent.lock().map_err(|_| AppError::InternalError)?.push(email);
+        Ok(())
+    }
+}
+
+/// How the connection to the SMTP server is secured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    /// TLS from the start, usually on port 465.
+    Tls,
+    /// Plain at first, upgraded with `STARTTLS`, usually on port 587.
+    StartTls,
+    /// No encryption, for a relay on the same host or network.
+    None,
+}
+
+impl SmtpSecurity {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "tls" => Some(SmtpSecurity::Tls),
+            "starttls" => Some(SmtpSecurity::StartTls),
+            "none" => Some(SmtpSecurity::None),
+            _ => None,
+        }
+    }
+
+    /// The port normally used with this kind of connection.
+    pub fn default_port(self) -> u16 {
+        match self {
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::None => 25,
+        }
+    }
+}
+
+/// The SMTP server mail is handed to, and the sender it goes out as.
+#[derive(Debug, Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    /// Credentials for `AUTH PLAIN`; without them no login is attempted.
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// The `From` address, such as `Tennis Scheduler <noreply@example.com>`.
+    pub from: String,
+}
+
+/// Where mail goes, chosen by the server's configuration.
+#[derive(Debug, Clone)]
+pub enum MailTransport {
+    Smtp(SmtpSettings),
+    /// Print mail with [`LogMailer`], for development.
+    Log,
+}
+
+impl MailTransport {
+    pub fn mailer(self) -> Arc<dyn Mailer> {
+        match self {
+            MailTransport::Smtp(settings) => Arc::new(SmtpMailer::new(settings)),
+            MailTransport::Log => Arc::new(LogMailer),
+        }
+    }
+}
+
+/// How long to wait for the SMTP server before giving up on a message.
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sends each message over its own SMTP connection. Handlers send mail from
+/// inside `web::block`, so blocking here is fine.
+pub struct SmtpMailer {
+    settings: SmtpSettings,
+}
+
+impl SmtpMailer {
+    pub fn new(settings: SmtpSettings) -> Self {
+        SmtpMailer { settings }
+    }
+
+    fn deliver(&self, email: &Email) -> io::Result<()> {
+        let settings = &self.settings;
+        let tcp = TcpStream::connect((settings.host.as_str(), settings.port))?;
+        tcp.set_read_timeout(Some(SMTP_TIMEOUT))?;
+        tcp.set_write_timeout(Some(SMTP_TIMEOUT))?;
+
+        let mut session = match settings.security {
+            SmtpSecurity::Tls => SmtpSession::new(Box::new(tls_stream(tcp, &settings.host)?)),
+            SmtpSecurity::StartTls | SmtpSecurity::None => SmtpSession::new(Box::new(tcp)),
+        };
+        session.expect(220)?;
+        session.command("EHLO localhost", 250)?;
+
+        if settings.security == SmtpSecurity::StartTls {
+            session.command("STARTTLS", 220)?;
+            // Nothing is pipelined, so no reply is left in the buffer
+            let SmtpSession { stream } = session;
+            let tcp = stream.into_inner();
+            session = SmtpSession::new(Box::new(StreamOwned::new(tls_connection(&settings.host)?, tcp)));
+            session.command("EHLO localhost", 250)?;
+        }
+
+        if let (Some(username), Some(password)) = (&settings.username, &settings.password) {
+            let credentials = STANDARD.encode(format!("\0{}\0{}", username, password));
+            session.command(&format!("AUTH PLAIN {}", credentials), 235)?;
+        }
+
+        session.command(&format!("MAIL FROM:<{}>", address_of(&settings.from)), 250)?;
+        session.command(&format!("RCPT TO:<{}>", email.to), 250)?;
+        session.command("DATA", 354)?;
+        session.write(&message(&settings.from, email))?;
+        session.command(".", 250)?;
+        // The message is accepted; a failed goodbye does not matter
+        let _ = session.command("QUIT", 221);
+        Ok(())
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: Email) -> Result<(), AppError> {
+        // Line breaks would let a recipient or subject add their own headers
+        if [&email.to, &email.subject].iter().any(|value| value.contains(['\r', '\n'])) {
+            return Err(AppError::BadRequest("Mail headers must not contain line breaks".into()));
+        }
+        self.deliver(&email).map_err(|error| {
+            eprintln!("Failed to send mail to {}: {}", email.to, error);
+            AppError::InternalError
+        })
+    }
+}
+
+/// A connection to the server, plain or encrypted.
+trait SmtpStream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> SmtpStream for T {}
+
+struct SmtpSession {
+    stream: BufReader<Box<dyn SmtpStream>>,
+}
+
+impl SmtpSession {
+    fn new(stream: Box<dyn SmtpStream>) -> Self {
+        SmtpSession { stream: BufReader::new(stream) }
+    }
+
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        let stream = self.stream.get_mut();
+        stream.write_all(text.as_bytes())?;
+        stream.flush()
+    }
+
+    /// Sends one command and checks the reply code.
+    fn command(&mut self, line: &str, code: u16) -> io::Result<()> {
+        self.write(&format!("{}\r\n", line))?;
+        self.expect(code)
+    }
+
+    /// Reads a reply, which may span several `250-` lines, and checks its code.
+    fn expect(&mut self, code: u16) -> io::Result<()> {
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "SMTP server closed the connection"));
+            }
+            let line = line.trim_end();
+            if line.get(..3).and_then(|reply| reply.parse::<u16>().ok()) != Some(code) {
+                return Err(io::Error::other(format!("SMTP server answered '{}', expected {}", line, code)));
+            }
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn tls_connection(host: &str) -> io::Result<ClientConnection> {
+    let roots = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let config = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = ServerName::try_from(host.to_string()).map_err(io::Error::other)?;
+    ClientConnection::new(Arc::new(config), name).map_err(io::Error::other)
+}
+
+fn tls_stream(tcp: TcpStream, host: &str) -> io::Result<StreamOwned<ClientConnection, TcpStream>> {
+    Ok(StreamOwned::new(tls_connection(host)?, tcp))
+}
+
+/// The bare address in `Name <address>`, or the whole value without brackets.
+fn address_of(mailbox: &str) -> &str {
+    match (mailbox.find('<'), mailbox.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &mailbox[start + 1..end],
+        _ => mailbox.trim(),
+    }
+}
+
+/// Encodes a header value that is not plain ASCII, as RFC 2047 asks.
+fn header_text(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", STANDARD.encode(value))
+    }
+}
+
+/// The message as sent after `DATA`: headers, then the body with CRLF line
+/// endings and leading dots doubled, so no line ends the message early.
+fn message(from: &str, email: &Email) -> String {
+    let mut text = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\n\
+         Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+        from,
+        email.to,
+        header_text(&email.subject),
+        Utc::now().to_rfc2822(),
+    );
+    for line in email.body.lines() {
+        if line.starts_with('.') {
+            text.push('.');
+        }
+        text.push_str(line);
+        text.push_str("\r\n");
+    }
+    text
+}
@@ -1,22 +1,302 @@
-mod api;
 mod config;
-mod db;
-mod errors; // if you add custom errors
 
 use actix_web::{App, HttpServer, web};
 use actix_cors::Cors;
 use actix_web::http::header;
+use chrono::Utc;
+use clap::{Parser, Subcommand};
 use config::Config;
-use db::establish_connection;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tennis_scheduler::{admin, api, archive};
+use tennis_scheduler::mailer::Mailer;
+use tennis_scheduler::oidc::OidcProvider;
+use tennis_scheduler::tokens::TokenSigner;
+use tennis_scheduler::db::{self, establish_connection, models::{NewLeague, Role}, schema::leagues};
+use uuid::Uuid;
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
+#[derive(Parser)]
+#[command(name = "tennis_scheduler", about = "Tennis match scheduling API")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Start the HTTP server (the default)
+    Serve,
+    /// Manage the database schema
+    Migrate {
+        #[command(subcommand)]
+        action: Option<MigrateAction>,
+    },
+    /// Fix up league, player and match data without touching SQL
+    Admin {
+        #[command(subcommand)]
+        action: AdminAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// Apply all pending migrations (the default)
+    Run,
+    /// Revert the most recently applied migration
+    Revert,
+    /// List migrations and whether they have been applied
+    Status,
+}
+
+#[derive(Subcommand)]
+enum AdminAction {
+    /// Create a league; the creating player becomes its admin
+    CreateLeague {
+        /// Name of the new league
+        name: String,
+        /// Player who owns the league
+        #[arg(long)]
+        created_by: i32,
+        #[arg(long)]
+        description: Option<String>,
+        #[arg(long)]
+        skill_level: Option<String>,
+        /// Only admit players through join requests or invitations
+        #[arg(long)]
+        private: bool,
+        #[arg(long)]
+        max_members: Option<i32>,
+        /// Refuse players once the league is full instead of waitlisting them
+        #[arg(long)]
+        no_waitlist: bool,
+        /// What happens to a departing member's matches: cancel or forfeit
+        #[arg(long, default_value = "cancel")]
+        departure_policy: String,
+        /// IANA time zone the league plays in
+        #[arg(long, default_value = "UTC")]
+        time_zone: String,
+    },
+    /// Add players to a league from a file with one player id or email per line
+    ImportMembers {
+        league_id: i32,
+        file: PathBuf,
+    },
+    /// Set a new password for a player, generating one if none is given
+    ResetPassword {
+        email: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Give a player a site role: player or site_admin
+    SetRole {
+        email: String,
+        role: Role,
+    },
+    /// Delete a player's account, anonymizing them in past matches
+    DeleteAccount {
+        email: String,
+    },
+    /// Lift the login delay or lockout after too many failed attempts
+    UnlockLogin {
+        /// Account to unlock
+        email: Option<String>,
+        /// Client address to unlock
+        #[arg(long)]
+        ip: Option<String>,
+    },
+    /// Mark a match as completed with the given winner
+    CompleteMatch {
+        match_id: i32,
+        #[arg(long)]
+        winner: i32,
+        #[arg(long)]
+        score: Option<String>,
+    },
+    /// Rebuild singles and doubles rankings from completed matches
+    RecomputeRankings {
+        /// League to recompute; every league when left out
+        league_id: Option<i32>,
+    },
+    /// Write a league and its members, matches and appointments to a JSON archive
+    ExportLeague {
+        league_id: i32,
+        /// File to write; standard output when left out
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Recreate a league from a JSON archive under new ids
+    ImportLeague {
+        file: PathBuf,
+        /// Name for the league when the archived name is already taken
+        #[arg(long)]
+        league_name: Option<String>,
+    },
+}
+
+fn run_admin(action: AdminAction) -> Result<(), Box<dyn std::error::Error>> {
+    let pool = establish_connection(&Config::database_url()?);
+    let mut conn = pool.get()?;
+
+    match action {
+        AdminAction::CreateLeague {
+            name,
+            created_by,
+            description,
+            skill_level,
+            private,
+            max_members,
+            no_waitlist,
+            departure_policy,
+            time_zone,
+        } => {
+            let league = admin::create_league(&mut conn, NewLeague {
+                league_name: name,
+                description,
+                skill_level,
+                created_by,
+                is_public: !private,
+                created_at: Utc::now(),
+                departure_policy,
+                max_members,
+                waitlist_enabled: !no_waitlist,
+                time_zone,
+            })?;
+            println!("Created league {} ({})", league.league_id, league.league_name);
+        }
+        AdminAction::ImportMembers { league_id, file } => {
+            let entries: Vec<String> = fs::read_to_string(&file)?.lines().map(String::from).collect();
+            let summary = admin::import_members(&mut conn, league_id, &entries)?;
+            println!(
+                "Added {} member(s), {} already in the league, {} failed",
+                summary.added.len(),
+                summary.already_members.len(),
+                summary.failed.len()
+            );
+            for (entry, reason) in summary.failed {
+                println!("  {}: {}", entry, reason);
+            }
+        }
+        AdminAction::ResetPassword { email, password } => {
+            let generated = password.is_none();
+            let password = password.unwrap_or_else(|| Uuid::new_v4().simple().to_string()[..12].to_string());
+            let player = admin::reset_password(&mut conn, &email, &password)?;
+            println!("Password reset for player {}", player);
+            if generated {
+                println!("New password: {}", password);
+            }
+        }
+        AdminAction::SetRole { email, role } => {
+            let player = admin::set_role(&mut conn, &email, role)?;
+            println!("Player {} is now {}", player, role.as_str());
+        }
+        AdminAction::DeleteAccount { email } => {
+            let (player, summary) = admin::delete_account(&mut conn, &email)?;
+            println!(
+                "Deleted the account of player {}: left {} league(s), cancelled {} and forfeited {} match(es)",
+                player,
+                summary.left_leagues.len(),
+                summary.cancelled_matches.len(),
+                summary.forfeited_matches.len()
+            );
+        }
+        AdminAction::UnlockLogin { email, ip } => {
+            let cleared = admin::unlock_login(&mut conn, email.as_deref(), ip.as_deref())?;
+            println!("Cleared {} failed login attempt(s)", cleared);
+        }
+        AdminAction::CompleteMatch { match_id, winner, score } => {
+            let completed = admin::force_complete_match(&mut conn, match_id, winner, score)?;
+            println!(
+                "Match {} completed, won by player {} ({})",
+                completed.id,
+                winner,
+                completed.score.as_deref().unwrap_or("no score")
+            );
+        }
+        AdminAction::RecomputeRankings { league_id } => {
+            let league_ids = match league_id {
+                Some(id) => vec![id],
+                None => leagues::table.select(leagues::league_id).order(leagues::league_id).load::<i32>(&mut conn)?,
+            };
+            for league in league_ids {
+                let updates = admin::recompute_rankings(&mut conn, league)?;
+                println!("League {}: recomputed rankings for {} member(s)", league, updates.len());
+            }
+        }
+        AdminAction::ExportLeague { league_id, output } => {
+            let league_archive = archive::export_league(&mut conn, league_id)?;
+            let json = serde_json::to_string_pretty(&league_archive)?;
+            match output {
+                Some(path) => {
+                    fs::write(&path, json)?;
+                    eprintln!("Exported league {} to {}", league_id, path.display());
+                }
+                None => println!("{}", json),
+            }
+        }
+        AdminAction::ImportLeague { file, league_name } => {
+            let league_archive: archive::LeagueArchive = serde_json::from_str(&fs::read_to_string(&file)?)?;
+            let report = conn.transaction(|conn| archive::import_league(conn, &league_archive, league_name))?;
+            println!(
+                "Imported league {} with {} membership(s), {} join request(s), {} match(es) and {} appointment(s)",
+                report.league_id, report.memberships, report.join_requests, report.matches, report.appointments
+            );
+            if !report.created_players.is_empty() {
+                println!("Created players without a password: {:?}", report.created_players);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn migrate(action: MigrateAction) -> Result<(), db::MigrationError> {
+    let database_url = Config::database_url().map_err(|error| error.to_string())?;
+    let mut conn = PgConnection::establish(&database_url)?;
+
+    match action {
+        MigrateAction::Run => {
+            let applied = db::run_pending_migrations(&mut conn)?;
+            if applied.is_empty() {
+                println!("Database is up to date");
+            }
+            for version in applied {
+                println!("Applied {}", version);
+            }
+        }
+        MigrateAction::Revert => {
+            let reverted = db::revert_last_migration(&mut conn)?;
+            println!("Reverted {}", reverted);
+        }
+        MigrateAction::Status => {
+            for (name, applied) in db::migration_status(&mut conn)? {
+                println!("[{}] {}", if applied { "X" } else { " " }, name);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn serve() -> std::io::Result<()> {
     // Load configuration
     let config = Config::from_env().expect("Failed to load configuration");
     
     // Establish the database connection pool
     let pool = establish_connection(&config.database_url);
 
+    if config.run_migrations {
+        let mut conn = pool.get().expect("Failed to get DB connection");
+        let applied = db::run_pending_migrations(&mut conn).expect("Failed to run database migrations");
+        for version in applied {
+            println!("Applied migration {}", version);
+        }
+    }
+
+    let mailer: Arc<dyn Mailer> = config.mail.mailer();
+    let signer = web::Data::new(TokenSigner::new(&config.token_secret));
+    let oidc = config.oidc.map(|settings| web::Data::new(OidcProvider::new(settings)));
+
     // Start the HTTP server
     // Start the HTTP server with CORS middleware
     HttpServer::new(move || {
@@ -27,12 +307,36 @@ async fn main() -> std::io::Result<()> {
             .allowed_headers(vec![header::AUTHORIZATION, header::ACCEPT, header::CONTENT_TYPE])
             .max_age(3600);
 
-        App::new()
+        let mut app = App::new()
             .wrap(cors)
             .app_data(web::Data::new(pool.clone()))
-            .configure(api::init_routes)
+            .app_data(web::Data::from(mailer.clone()))
+            .app_data(signer.clone());
+        if let Some(oidc) = &oidc {
+            app = app.app_data(oidc.clone());
+        }
+        app.configure(api::init_routes)
     })
     .bind(config.server_addr)?
     .run()
     .await
 }
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    match Cli::parse().command.unwrap_or(Command::Serve) {
+        Command::Serve => serve().await,
+        Command::Migrate { action } => {
+            migrate(action.unwrap_or(MigrateAction::Run)).map_err(|error| {
+                eprintln!("Migration failed: {}", error);
+                std::io::Error::other(error.to_string())
+            })
+        }
+        Command::Admin { action } => {
+            run_admin(action).map_err(|error| {
+                eprintln!("Admin command failed: {}", error);
+                std::io::Error::other(error.to_string())
+            })
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use serde::Serialize;
+
+/// How much each factor counts towards an opponent's score. They add up to 1.
+const SKILL_WEIGHT: f32 = 0.4;
+const RECENCY_WEIGHT: f32 = 0.25;
+const AVAILABILITY_WEIGHT: f32 = 0.2;
+const LOAD_WEIGHT: f32 = 0.15;
+
+/// A skill gap of this many NTRP points or more scores zero.
+const MAX_SKILL_GAP: f32 = 2.0;
+/// A ranking gap of this many places or more scores zero.
+const MAX_RANKING_GAP: i32 = 10;
+/// Players last met this many days ago or more count as never having met.
+const FRESH_AFTER_DAYS: i64 = 90;
+
+/// What is known about a possible opponent, relative to the player asking.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct OpponentFactors {
+    /// Difference between the two NTRP ratings, when both are rated.
+    pub skill_difference: Option<f32>,
+    /// Smallest gap between their singles rankings in a league they share.
+    pub ranking_gap: Option<i32>,
+    /// Days since the two last played each other, if they ever did.
+    pub days_since_last_meeting: Option<i64>,
+    /// Days in the window on which both are free.
+    pub shared_free_days: usize,
+    pub days_considered: usize,
+    /// The opponent's pending and upcoming scheduled matches.
+    pub open_matches: usize,
+}
+
+impl OpponentFactors {
+    /// Closeness in level: the rating gap when known, otherwise the ranking
+    /// gap, otherwise a neutral 0.5.
+    fn skill_score(&self) -> f32 {
+        match (self.skill_difference, self.ranking_gap) {
+            (Some(difference), _) => 1.0 - (difference / MAX_SKILL_GAP).min(1.0),
+            (None, Some(gap)) => 1.0 - gap.min(MAX_RANKING_GAP) as f32 / MAX_RANKING_GAP as f32,
+            (None, None) => 0.5,
+        }
+    }
+
+    fn recency_score(&self) -> f32 {
+        match self.days_since_last_meeting {
+            Some(days) => days.clamp(0, FRESH_AFTER_DAYS) as f32 / FRESH_AFTER_DAYS as f32,
+            None => 1.0,
+        }
+    }
+
+    fn availability_score(&self) -> f32 {
+        if self.days_considered == 0 {
+            return 0.0;
+        }
+        self.shared_free_days as f32 / self.days_considered as f32
+    }
+
+    fn load_score(&self) -> f32 {
+        1.0 / (1.0 + self.open_matches as f32)
+    }
+
+    /// Overall fit between 0 and 1; higher is a better opponent.
+    pub fn score(&self) -> f32 {
+        SKILL_WEIGHT * self.skill_score()
+            + RECENCY_WEIGHT * self.recency_score()
+            + AVAILABILITY_WEIGHT * self.availability_score()
+            + LOAD_WEIGHT * self.load_score()
+    }
+}
+
+/// A suggested opponent with the factors behind their score.
+#[derive(Debug, Clone, Serialize)]
+pub struct Recommendation {
+    pub player_id: i32,
+    pub name: String,
+    pub skill_level: Option<String>,
+    pub score: f32,
+    pub factors: OpponentFactors,
+}
+
+/// Orders recommendations best first, by player id on equal scores, and
+/// keeps the first `limit`.
+pub fn top_recommendations(mut recommendations: Vec<Recommendation>, limit: usize) -> Vec<Recommendation> {
+    recommendations.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(a.player_id.cmp(&b.player_id))
+    });
+    recommendations.truncate(limit);
+    recommendations
+}
@@ -0,0 +1,204 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use reqwest::Url;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::errors::AppError;
+
+/// An OpenID Connect provider players can sign in with, and how this app is
+/// registered with it.
+#[derive(Debug, Clone)]
+pub struct OidcSettings {
+    /// The issuer URL; its `/.well-known/openid-configuration` is read to
+    /// find the provider's endpoints and keys.
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Where the provider sends players back to: `/api/oidc/callback`, or a
+    /// page that passes its `code` and `state` on to it.
+    pub redirect_url: String,
+}
+
+/// The parts of the provider's discovery document that are used.
+#[derive(Debug, Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// What the provider says about the player in a checked ID token.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdentityClaims {
+    /// The provider's stable id for the player.
+    pub sub: String,
+    pub email: Option<String>,
+    /// Some providers send this as a string.
+    #[serde(default)]
+    email_verified: Option<serde_json::Value>,
+    pub name: Option<String>,
+    nonce: Option<String>,
+}
+
+impl IdentityClaims {
+    /// Whether the provider has checked that the player owns the email address.
+    pub fn email_verified(&self) -> bool {
+        match &self.email_verified {
+            Some(serde_json::Value::Bool(verified)) => *verified,
+            Some(serde_json::Value::String(verified)) => verified == "true",
+            _ => false,
+        }
+    }
+}
+
+/// The RFC 7636 `S256` challenge for a PKCE code verifier.
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// Talks to an OpenID Connect provider for the authorization code flow.
+/// Handlers take it as `web::Data<OidcProvider>`; it is only registered when
+/// a provider is configured.
+pub struct OidcProvider {
+    settings: OidcSettings,
+    http: reqwest::Client,
+}
+
+/// Provider problems are logged and answered with a generic error, so
+/// players do not see the provider's internals.
+fn provider_error(context: &str, error: impl std::fmt::Debug) -> AppError {
+    eprintln!("OpenID Connect {} failed: {:?}", context, error);
+    AppError::InternalError
+}
+
+impl OidcProvider {
+    pub fn new(settings: OidcSettings) -> Self {
+        OidcProvider { settings, http: reqwest::Client::new() }
+    }
+
+    /// The issuer that identities are linked under.
+    pub fn issuer(&self) -> &str {
+        &self.settings.issuer
+    }
+
+    /// Reads the discovery document. It is fetched for every sign-in, so
+    /// changes at the provider are picked up without a restart.
+    async fn discover(&self) -> Result<Discovery, AppError> {
+        let url = format!("{}/.well-known/openid-configuration", self.settings.issuer.trim_end_matches('/'));
+        let discovery = self
+            .http
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| provider_error("discovery", e))?
+            .json::<Discovery>()
+            .await
+            .map_err(|e| provider_error("discovery", e))?;
+        if discovery.issuer.trim_end_matches('/') != self.settings.issuer.trim_end_matches('/') {
+            return Err(provider_error("discovery", format!("unexpected issuer {}", discovery.issuer)));
+        }
+        Ok(discovery)
+    }
+
+    /// Where to send the player to sign in. `state` comes back with them;
+    /// `nonce` comes back inside the ID token.
+    pub async fn authorization_url(&self, state: &str, nonce: &str, code_verifier: &str) -> Result<String, AppError> {
+        let discovery = self.discover().await?;
+        let url = Url::parse_with_params(
+            &discovery.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.settings.client_id.as_str()),
+                ("redirect_uri", self.settings.redirect_url.as_str()),
+                ("scope", "openid email profile"),
+                ("state", state),
+                ("nonce", nonce),
+                ("code_challenge", code_challenge(code_verifier).as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| provider_error("discovery", e))?;
+        Ok(url.into())
+    }
+
+    /// Trades the authorization code for an ID token and checks its
+    /// signature, issuer, audience, expiry and nonce. Codes the provider
+    /// refuses and tokens that fail a check answer 401.
+    pub async fn exchange_code(&self, code: &str, code_verifier: &str, nonce: &str) -> Result<IdentityClaims, AppError> {
+        let refused = || AppError::Unauthorized("The identity provider did not confirm the sign-in".into());
+        let discovery = self.discover().await?;
+
+        let response = self
+            .http
+            .post(&discovery.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.settings.redirect_url.as_str()),
+                ("client_id", self.settings.client_id.as_str()),
+                ("client_secret", self.settings.client_secret.as_str()),
+                ("code_verifier", code_verifier),
+            ])
+            .send()
+            .await
+            .map_err(|e| provider_error("token request", e))?;
+        if response.status().is_client_error() {
+            eprintln!("OpenID Connect token request refused: {}", response.status());
+            return Err(refused());
+        }
+        let id_token = response
+            .error_for_status()
+            .map_err(|e| provider_error("token request", e))?
+            .json::<TokenResponse>()
+            .await
+            .map_err(|e| provider_error("token request", e))?
+            .id_token;
+
+        let header = decode_header(&id_token).map_err(|_| refused())?;
+        // Only keys published by the provider are trusted, never a shared secret
+        if !matches!(
+            header.alg,
+            Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 | Algorithm::PS256 | Algorithm::PS384
+                | Algorithm::PS512 | Algorithm::ES256 | Algorithm::ES384 | Algorithm::EdDSA
+        ) {
+            return Err(refused());
+        }
+        let keys = self
+            .http
+            .get(&discovery.jwks_uri)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| provider_error("key request", e))?
+            .json::<JwkSet>()
+            .await
+            .map_err(|e| provider_error("key request", e))?;
+        let jwk = match &header.kid {
+            Some(kid) => keys.find(kid),
+            None => keys.keys.first(),
+        }
+        .ok_or_else(refused)?;
+        let key = DecodingKey::from_jwk(jwk).map_err(|_| refused())?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&discovery.issuer]);
+        validation.set_audience(&[&self.settings.client_id]);
+        let claims = decode::<IdentityClaims>(&id_token, &key, &validation)
+            .map_err(|_| refused())?
+            .claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(refused());
+        }
+        Ok(claims)
+    }
+}
@@ -0,0 +1,246 @@
+use bcrypt::{hash, DEFAULT_COST};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::api::leagues::{append_note, remove_member};
+use crate::db::models::{
+    Appointment, LeagueInvitation, LeagueJoinRequest, Match, Notification, Player, PlayerIdentity, Role,
+    SeasonMember, Session,
+};
+use crate::db::schema::{
+    appointments, email_changes, league_invitations, league_join_requests, leagues, login_attempts, matches,
+    notifications, password_resets, player_identities, player_leagues, players, recovery_codes, season_members,
+    sessions, totp_secrets,
+};
+use crate::errors::AppError;
+
+/// Identifies a personal data export.
+pub const EXPORT_FORMAT: &str = "tennis-scheduler/player-data";
+
+/// Bumped whenever the export layout changes.
+pub const EXPORT_VERSION: u32 = 1;
+
+/// Everything stored about a player, for them to download. Secrets such as
+/// password and token hashes are left out.
+#[derive(Serialize)]
+pub struct PlayerExport {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub profile: Player,
+    pub memberships: Vec<ExportMembership>,
+    pub season_memberships: Vec<SeasonMember>,
+    pub matches: Vec<Match>,
+    pub appointments: Vec<Appointment>,
+    pub join_requests: Vec<LeagueJoinRequest>,
+    /// Invitations sent to the player.
+    pub invitations: Vec<LeagueInvitation>,
+    pub notifications: Vec<Notification>,
+    pub sessions: Vec<Session>,
+    pub identities: Vec<PlayerIdentity>,
+}
+
+#[derive(Serialize, Queryable)]
+pub struct ExportMembership {
+    pub league_id: i32,
+    pub league_name: String,
+    pub role: String,
+    pub singles_ranking: Option<i32>,
+    pub doubles_ranking: Option<i32>,
+    pub joined_at: DateTime<Utc>,
+}
+
+/// Gathers everything stored about a player.
+pub fn export_player(conn: &mut PgConnection, player: i32) -> Result<PlayerExport, AppError> {
+    let profile = players::table
+        .find(player)
+        .first::<Player>(conn)
+        .optional()?
+        .ok_or(AppError::NotFound)?;
+
+    let memberships = player_leagues::table
+        .inner_join(leagues::table.on(leagues::league_id.eq(player_leagues::league_id)))
+        .filter(player_leagues::player_id.eq(player))
+        .order(player_leagues::league_id.asc())
+        .select((
+            player_leagues::league_id,
+            leagues::league_name,
+            player_leagues::role,
+            player_leagues::singles_ranking,
+            player_leagues::doubles_ranking,
+            player_leagues::joined_at,
+        ))
+        .load::<ExportMembership>(conn)?;
+    let season_memberships = season_members::table
+        .filter(season_members::player_id.eq(player))
+        .order(season_members::season_id.asc())
+        .load::<SeasonMember>(conn)?;
+    let player_matches = matches::table
+        .filter(
+            matches::player1_id.eq(player)
+                .or(matches::player2_id.eq(player))
+                .or(matches::team1_player1_id.eq(player))
+                .or(matches::team1_player2_id.eq(player))
+                .or(matches::team2_player1_id.eq(player))
+                .or(matches::team2_player2_id.eq(player)),
+        )
+        .order(matches::id.asc())
+        .load::<Match>(conn)?;
+    let player_appointments = appointments::table
+        .filter(appointments::requester_id.eq(player).or(appointments::opponent_id.eq(player)))
+        .order(appointments::appointment_id.asc())
+        .load::<Appointment>(conn)?;
+    let join_requests = league_join_requests::table
+        .filter(league_join_requests::player_id.eq(player))
+        .order(league_join_requests::request_id.asc())
+        .load::<LeagueJoinRequest>(conn)?;
+    let invitations = league_invitations::table
+        .filter(
+            league_invitations::invitee_id.eq(player)
+                .or(league_invitations::invitee_email.eq(&profile.email)),
+        )
+        .order(league_invitations::invitation_id.asc())
+        .load::<LeagueInvitation>(conn)?;
+    let player_notifications = notifications::table
+        .filter(notifications::player_id.eq(player))
+        .order(notifications::notification_id.asc())
+        .load::<Notification>(conn)?;
+    let player_sessions = sessions::table
+        .filter(sessions::player_id.eq(player))
+        .order(sessions::session_id.asc())
+        .load::<Session>(conn)?;
+    let identities = player_identities::table
+        .filter(player_identities::player_id.eq(player))
+        .order(player_identities::identity_id.asc())
+        .load::<PlayerIdentity>(conn)?;
+
+    Ok(PlayerExport {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        exported_at: Utc::now(),
+        profile,
+        memberships,
+        season_memberships,
+        matches: player_matches,
+        appointments: player_appointments,
+        join_requests,
+        invitations,
+        notifications: player_notifications,
+        sessions: player_sessions,
+        identities,
+    })
+}
+
+/// What deleting an account changed besides the player's own data.
+#[derive(Debug, Default, Serialize)]
+pub struct DeletionSummary {
+    pub left_leagues: Vec<i32>,
+    pub cancelled_matches: Vec<i32>,
+    pub forfeited_matches: Vec<i32>,
+    pub cancelled_appointments: usize,
+}
+
+/// Deletes a player's account. The player leaves their leagues as if they had
+/// left each one, so the last admin of a league is refused until they hand
+/// it over. The player row itself stays, with its name, contact details and
+/// password replaced, so past matches, standings and league history keep
+/// their references; everything else that is only about the player is
+/// deleted. Run it in a transaction.
+pub fn delete_player(conn: &mut PgConnection, player: i32) -> Result<DeletionSummary, AppError> {
+    let found = players::table
+        .find(player)
+        .first::<Player>(conn)
+        .optional()?
+        .ok_or(AppError::NotFound)?;
+    if found.deleted_at.is_some() {
+        return Err(AppError::NotFound);
+    }
+    let reason = "deleted their account";
+    let mut summary = DeletionSummary::default();
+
+    let member_of = player_leagues::table
+        .filter(player_leagues::player_id.eq(player))
+        .select(player_leagues::league_id)
+        .load::<i32>(conn)?;
+    for league in member_of {
+        let departure = remove_member(conn, league, player, reason)?;
+        summary.left_leagues.push(league);
+        summary.cancelled_matches.extend(departure.cancelled_matches);
+        summary.forfeited_matches.extend(departure.forfeited_matches);
+    }
+
+    // Open matches in leagues they had already left
+    let open_matches = matches::table
+        .filter(matches::status.eq_any(["Pending", "Scheduled"]))
+        .filter(
+            matches::player1_id.eq(player)
+                .or(matches::player2_id.eq(player))
+                .or(matches::team1_player1_id.eq(player))
+                .or(matches::team1_player2_id.eq(player))
+                .or(matches::team2_player1_id.eq(player))
+                .or(matches::team2_player2_id.eq(player)),
+        )
+        .load::<Match>(conn)?;
+    for open_match in open_matches {
+        let notes = append_note(open_match.notes, format!("Cancelled: player {} {}", player, reason));
+        diesel::update(matches::table.find(open_match.id))
+            .set((matches::status.eq("Cancelled"), matches::notes.eq(notes)))
+            .execute(conn)?;
+        summary.cancelled_matches.push(open_match.id);
+    }
+
+    let now = Utc::now();
+    summary.cancelled_appointments = diesel::update(
+        appointments::table
+            .filter(appointments::requester_id.eq(player).or(appointments::opponent_id.eq(player)))
+            .filter(appointments::status.ne_all(["cancelled", "rejected", "declined"]))
+            .filter(appointments::end_time.gt(now)),
+    )
+    .set((appointments::status.eq("cancelled"), appointments::updated_at.eq(now)))
+    .execute(conn)?;
+
+    diesel::delete(league_join_requests::table.filter(league_join_requests::player_id.eq(player))).execute(conn)?;
+    diesel::delete(
+        league_invitations::table.filter(
+            league_invitations::invitee_id.eq(player)
+                .or(league_invitations::invitee_email.eq(&found.email)),
+        ),
+    )
+    .execute(conn)?;
+    diesel::delete(notifications::table.filter(notifications::player_id.eq(player))).execute(conn)?;
+    diesel::delete(email_changes::table.filter(email_changes::player_id.eq(player))).execute(conn)?;
+    diesel::delete(password_resets::table.filter(password_resets::player_id.eq(player))).execute(conn)?;
+    diesel::delete(sessions::table.filter(sessions::player_id.eq(player))).execute(conn)?;
+    diesel::delete(totp_secrets::table.filter(totp_secrets::player_id.eq(player))).execute(conn)?;
+    diesel::delete(recovery_codes::table.filter(recovery_codes::player_id.eq(player))).execute(conn)?;
+    diesel::delete(player_identities::table.filter(player_identities::player_id.eq(player))).execute(conn)?;
+    diesel::delete(
+        login_attempts::table.filter(
+            login_attempts::player_id.eq(player)
+                .or(login_attempts::email.eq(found.email.trim().to_lowercase())),
+        ),
+    )
+    .execute(conn)?;
+
+    // Nobody knows this password, and the account cannot sign in anyway
+    let unusable_password = hash(Uuid::new_v4().to_string(), DEFAULT_COST).map_err(|_| AppError::InternalError)?;
+    diesel::update(players::table.find(player))
+        .set((
+            players::name.eq(format!("Deleted player {}", player)),
+            players::email.eq(format!("deleted-{}@deleted.invalid", player)),
+            players::password.eq(unusable_password),
+            players::skill_level.eq(None::<String>),
+            players::role.eq(Role::Player.as_str()),
+            players::phone.eq(None::<String>),
+            players::time_zone.eq("UTC"),
+            players::avatar_url.eq(None::<String>),
+            players::email_verified_at.eq(None::<DateTime<Utc>>),
+            players::deactivated_at.eq(found.deactivated_at.unwrap_or(now)),
+            players::deleted_at.eq(now),
+        ))
+        .execute(conn)?;
+
+    Ok(summary)
+}
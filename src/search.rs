@@ -0,0 +1,78 @@
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use diesel::Queryable;
+use serde::Serialize;
+
+use crate::db::models::Match;
+
+/// Ratings for the named skill levels, on the NTRP scale.
+const NAMED_LEVELS: [(&str, f32); 4] = [
+    ("beginner", 2.5),
+    ("intermediate", 3.5),
+    ("advanced", 4.5),
+    ("expert", 5.5),
+];
+
+/// Puts a skill level on the NTRP scale so levels can be compared. Accepts
+/// NTRP ratings like `3.5` and the named levels; anything else is unrated.
+pub fn skill_rating(level: &str) -> Option<f32> {
+    let level = level.trim().to_lowercase();
+    if let Some((_, rating)) = NAMED_LEVELS.iter().find(|(name, _)| *name == level) {
+        return Some(*rating);
+    }
+    level.parse::<f32>().ok().filter(|rating| (1.0..=7.0).contains(rating))
+}
+
+/// A player that passed the search filters.
+#[derive(Debug, Clone, Serialize, Queryable)]
+pub struct Candidate {
+    pub player_id: i32,
+    pub name: String,
+    pub skill_level: Option<String>,
+    pub time_zone: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RankedPlayer {
+    #[serde(flatten)]
+    pub player: Candidate,
+    /// Distance between this player's rating and the searcher's.
+    pub skill_difference: Option<f32>,
+}
+
+/// The players on the other side of a match from `player`.
+pub fn opponents(m: &Match, player: i32) -> Vec<i32> {
+    let is = |id: Option<i32>| id == Some(player);
+
+    let others = if is(m.player1_id) {
+        vec![m.player2_id]
+    } else if is(m.player2_id) {
+        vec![m.player1_id]
+    } else if is(m.team1_player1_id) || is(m.team1_player2_id) {
+        vec![m.team2_player1_id, m.team2_player2_id]
+    } else if is(m.team2_player1_id) || is(m.team2_player2_id) {
+        vec![m.team1_player1_id, m.team1_player2_id]
+    } else {
+        Vec::new()
+    };
+    others.into_iter().flatten().collect()
+}
+
+/// A span of time a player is booked, start inclusive and end exclusive.
+pub type Busy = (DateTime<Utc>, DateTime<Utc>);
+
+/// Whether none of the `busy` intervals overlap `date` in `zone`.
+pub fn is_free_on(date: NaiveDate, zone: Tz, busy: &[Busy]) -> bool {
+    let day_start = |day: NaiveDate| {
+        let midnight = day.and_time(NaiveTime::MIN);
+        zone.from_local_datetime(&midnight)
+            .earliest()
+            // Midnight can fall in a DST gap, the day then starts an hour later
+            .or_else(|| zone.from_local_datetime(&(midnight + Duration::hours(1))).earliest())
+            .unwrap_or_else(|| zone.from_utc_datetime(&midnight))
+            .with_timezone(&Utc)
+    };
+    let start = day_start(date);
+    let end = day_start(date.succ_opt().unwrap_or(date));
+    !busy.iter().any(|(from, to)| *from < end && *to > start)
+}
@@ -0,0 +1,105 @@
+use csv::{ReaderBuilder, Trim, WriterBuilder};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::errors::AppError;
+
+/// A CSV row that was not imported. Lines count from 1, the header included,
+/// so they match what a spreadsheet shows.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct RowError {
+    pub line: u64,
+    pub message: String,
+}
+
+impl RowError {
+    pub fn new(line: u64, message: impl Into<String>) -> Self {
+        RowError { line, message: message.into() }
+    }
+}
+
+/// The rows of a CSV file that could be read, and the lines that could not.
+#[derive(Debug)]
+pub struct ParsedRows<T> {
+    /// Each row with its line number.
+    pub rows: Vec<(u64, T)>,
+    pub errors: Vec<RowError>,
+}
+
+/// Reads CSV with a header line into `T`, matching columns by header name.
+///
+/// Fields are trimmed and empty ones read as `None`. Rows that do not fit `T`
+/// are returned as errors next to the rows that do, each with its line number.
+pub fn parse_rows<T: DeserializeOwned>(data: &str) -> Result<ParsedRows<T>, AppError> {
+    let mut reader = ReaderBuilder::new()
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(data.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|error| AppError::BadRequest(format!("Could not read the CSV header: {}", error)))?
+        .clone();
+    if headers.iter().all(|header| header.is_empty()) {
+        return Err(AppError::BadRequest("The CSV file is empty".into()));
+    }
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(error) => {
+                let line = error.position().map(|position| line_at(data, position.byte())).unwrap_or(0);
+                errors.push(RowError::new(line, error.to_string()));
+                continue;
+            }
+        };
+        let line = record.position().map(|position| line_at(data, position.byte())).unwrap_or(0);
+        if record.iter().all(|field| field.is_empty()) {
+            continue;
+        }
+        match record.deserialize::<T>(Some(&headers)) {
+            Ok(row) => rows.push((line, row)),
+            Err(error) => errors.push(RowError::new(line, describe(&error))),
+        }
+    }
+
+    Ok(ParsedRows { rows, errors })
+}
+
+/// The line a record starting at `byte` is on. A record's position includes
+/// any blank lines before it, so those are skipped first.
+fn line_at(data: &str, byte: u64) -> u64 {
+    let bytes = data.as_bytes();
+    let mut start = (byte as usize).min(bytes.len());
+    while start < bytes.len() && (bytes[start] == b'\n' || bytes[start] == b'\r') {
+        start += 1;
+    }
+    bytes[..start].iter().filter(|b| **b == b'\n').count() as u64 + 1
+}
+
+/// The part of a deserialize error that is useful to whoever filled in the sheet.
+fn describe(error: &csv::Error) -> String {
+    match error.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => match err.field() {
+            Some(field) => format!("column {}: {}", field + 1, err.kind()),
+            None => err.kind().to_string(),
+        },
+        _ => error.to_string(),
+    }
+}
+
+/// Writes `header` followed by one line per row. The header is written even
+/// when there are no rows, so it must list the fields of `T` in order.
+pub fn to_csv<T: Serialize>(header: &[&str], rows: &[T]) -> Result<String, AppError> {
+    let mut writer = WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+
+    writer.write_record(header).map_err(|_| AppError::InternalError)?;
+    for row in rows {
+        writer.serialize(row).map_err(|_| AppError::InternalError)?;
+    }
+
+    let bytes = writer.into_inner().map_err(|_| AppError::InternalError)?;
+    String::from_utf8(bytes).map_err(|_| AppError::InternalError)
+}
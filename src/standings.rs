@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::db::models::Match;
+
+/// A player's line in a division table.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Standing {
+    pub rank: i32,
+    pub player_id: i32,
+    pub played: i32,
+    pub wins: i32,
+    pub losses: i32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Movement {
+    Promoted,
+    Stayed,
+    Relegated,
+}
+
+impl Movement {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Movement::Promoted => "promoted",
+            Movement::Stayed => "stayed",
+            Movement::Relegated => "relegated",
+        }
+    }
+}
+
+/// Builds a table for `members` from completed singles matches played between them.
+///
+/// Players are ordered by wins, then fewest losses, then player id so the
+/// result is stable. Matches against players outside `members` are ignored.
+pub fn compute_standings(members: &[i32], matches: &[Match]) -> Vec<Standing> {
+    let mut table: HashMap<i32, (i32, i32)> = members
+        .iter()
+        .map(|member| (*member, (0, 0)))
+        .collect();
+
+    for m in matches {
+        if !m.match_type.eq_ignore_ascii_case("singles") || !m.status.eq_ignore_ascii_case("completed") {
+            continue;
+        }
+        let (Some(player1), Some(player2), Some(winner)) = (m.player1_id, m.player2_id, m.winner_id) else {
+            continue;
+        };
+        if !table.contains_key(&player1) || !table.contains_key(&player2) {
+            continue;
+        }
+        let loser = if winner == player1 {
+            player2
+        } else if winner == player2 {
+            player1
+        } else {
+            continue;
+        };
+        if let Some(entry) = table.get_mut(&winner) {
+            entry.0 += 1;
+        }
+        if let Some(entry) = table.get_mut(&loser) {
+            entry.1 += 1;
+        }
+    }
+
+    rank_table(table)
+}
+
+/// Builds a doubles table for `members` from completed doubles matches.
+///
+/// `winner_id` names one player of the winning team, so both partners are
+/// credited with the win and both opponents with the loss. Players outside
+/// `members` are skipped.
+pub fn compute_doubles_standings(members: &[i32], matches: &[Match]) -> Vec<Standing> {
+    let mut table: HashMap<i32, (i32, i32)> = members
+        .iter()
+        .map(|member| (*member, (0, 0)))
+        .collect();
+
+    for m in matches {
+        if !m.match_type.eq_ignore_ascii_case("doubles") || !m.status.eq_ignore_ascii_case("completed") {
+            continue;
+        }
+        let Some(winner) = m.winner_id else {
+            continue;
+        };
+        let team1 = [m.team1_player1_id, m.team1_player2_id];
+        let team2 = [m.team2_player1_id, m.team2_player2_id];
+        let (winners, losers) = if team1.contains(&Some(winner)) {
+            (team1, team2)
+        } else if team2.contains(&Some(winner)) {
+            (team2, team1)
+        } else {
+            continue;
+        };
+        for player in winners.into_iter().flatten() {
+            if let Some(entry) = table.get_mut(&player) {
+                entry.0 += 1;
+            }
+        }
+        for player in losers.into_iter().flatten() {
+            if let Some(entry) = table.get_mut(&player) {
+                entry.1 += 1;
+            }
+        }
+    }
+
+    rank_table(table)
+}
+
+/// Turns (wins, losses) per player into a ranked table ordered by wins, then
+/// fewest losses, then player id.
+fn rank_table(table: HashMap<i32, (i32, i32)>) -> Vec<Standing> {
+    let mut standings: Vec<Standing> = table
+        .into_iter()
+        .map(|(player_id, (wins, losses))| Standing {
+            rank: 0,
+            player_id,
+            played: wins + losses,
+            wins,
+            losses,
+        })
+        .collect();
+
+    standings.sort_by(|a, b| {
+        b.wins
+            .cmp(&a.wins)
+            .then(a.losses.cmp(&b.losses))
+            .then(a.player_id.cmp(&b.player_id))
+    });
+    for (index, standing) in standings.iter_mut().enumerate() {
+        standing.rank = index as i32 + 1;
+    }
+
+    standings
+}
+
+/// Decides who moves up or down at season close.
+///
+/// The top `promotion_spots` players are promoted and the bottom
+/// `relegation_spots` relegated; promotion wins if the two ranges overlap.
+/// Pass zero spots for the top division (no promotion) and the bottom one
+/// (no relegation).
+pub fn assign_movements(
+    standings: &[Standing],
+    promotion_spots: usize,
+    relegation_spots: usize,
+) -> Vec<(i32, Movement)> {
+    let total = standings.len();
+    standings
+        .iter()
+        .enumerate()
+        .map(|(index, standing)| {
+            let movement = if index < promotion_spots {
+                Movement::Promoted
+            } else if index >= total.saturating_sub(relegation_spots) {
+                Movement::Relegated
+            } else {
+                Movement::Stayed
+            };
+            (standing.player_id, movement)
+        })
+        .collect()
+}
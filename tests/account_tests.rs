@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use actix_web::{http::StatusCode, test, web, App};
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{self, ConnectionManager};
+use diesel::PgConnection;
+use dotenv::dotenv;
+use serde_json::{json, Value};
+use std::env;
+use tennis_scheduler::api;
+use tennis_scheduler::db::models::{Match, NewMatch, Player};
+use tennis_scheduler::db::schema::{matches, players};
+use tennis_scheduler::db::DbPool;
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
+use uuid::Uuid;
+
+fn unique_email(prefix: &str) -> String {
+    format!("{}_{:x}@example.com", prefix, Uuid::new_v4().as_simple())
+}
+
+fn setup_test_db() -> DbPool {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+    r2d2::Pool::builder()
+        .build(manager)
+        .expect("Failed to create pool")
+}
+
+/// Registration leaves the email address unverified; do what following the
+/// mailed link would.
+fn verify_email(pool: &DbPool, player: i64) {
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    diesel::update(players::table.find(player as i32))
+        .set(players::email_verified_at.eq(Utc::now()))
+        .execute(&mut conn)
+        .expect("Failed to verify email");
+}
+
+fn register(email: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({
+            "name": format!("Account {:x}", Uuid::new_v4().as_simple()),
+            "email": email,
+            "password": "password123",
+            "phone": "+1 555 0100"
+        }))
+}
+
+fn login(email: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/login")
+        .set_json(json!({ "email": email, "password": "password123" }))
+}
+
+fn signed_in(method: test::TestRequest, uri: &str, token: &str) -> test::TestRequest {
+    method.uri(uri).insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+fn singles(league_id: i32, player1: i32, player2: i32, status: &str, days_ahead: i64) -> NewMatch {
+    NewMatch {
+        match_type: "Singles".into(),
+        player1_id: Some(player1),
+        player2_id: Some(player2),
+        league_id,
+        team1_player1_id: None,
+        team1_player2_id: None,
+        team2_player1_id: None,
+        team2_player2_id: None,
+        datetime: Utc::now() + Duration::days(days_ahead),
+        location: "Court 2".into(),
+        score: None,
+        winner_id: if status == "Completed" { Some(player1) } else { None },
+        status: status.into(),
+        notes: None,
+        season_id: None,
+    }
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_export_and_delete_account() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let mut ids = Vec::new();
+    let mut emails = Vec::new();
+    for prefix in ["organizer", "leaver"] {
+        let email = unique_email(prefix);
+        let registered: Value = test::read_body_json(test::call_service(&app, register(&email).to_request()).await).await;
+        let id = registered["player_id"].as_i64().unwrap();
+        verify_email(&pool, id);
+        ids.push(id as i32);
+        emails.push(email);
+    }
+    let (organizer, leaver) = (ids[0], ids[1]);
+    let mut tokens = Vec::new();
+    for email in &emails {
+        let session: Value = test::read_body_json(test::call_service(&app, login(email).to_request()).await).await;
+        tokens.push(session["token"].as_str().unwrap().to_string());
+    }
+
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &tokens[0])
+        .set_json(json!({ "league_name": format!("account-{:x}", Uuid::new_v4().as_simple()) }))
+        .to_request();
+    let league: Value = test::read_body_json(test::call_service(&app, league_req).await).await;
+    let league_id = league["league_id"].as_i64().unwrap() as i32;
+    let join = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/join", league_id), &tokens[1])
+        .to_request();
+    assert!(test::call_service(&app, join).await.status().is_success());
+
+    let (played, upcoming) = {
+        let mut conn = pool.get().expect("Failed to get DB connection");
+        let insert = |conn: &mut PgConnection, new_match: NewMatch| {
+            diesel::insert_into(matches::table)
+                .values(&new_match)
+                .returning(matches::id)
+                .get_result::<i32>(conn)
+                .unwrap()
+        };
+        (
+            insert(&mut conn, singles(league_id, leaver, organizer, "Completed", -7)),
+            insert(&mut conn, singles(league_id, organizer, leaver, "Pending", 7)),
+        )
+    };
+
+    let token = tokens[1].clone();
+
+    let export = test::call_service(&app, signed_in(test::TestRequest::get(), "/api/account/export", &token).to_request()).await;
+    assert_eq!(export.status(), StatusCode::OK);
+    assert!(export.headers().get("Content-Disposition").unwrap().to_str().unwrap().starts_with("attachment"));
+    let export: Value = test::read_body_json(export).await;
+    assert_eq!(export["format"], "tennis-scheduler/player-data");
+    assert_eq!(export["profile"]["email"], emails[1].as_str());
+    assert!(export["profile"].get("password").is_none());
+    assert_eq!(export["memberships"][0]["league_id"], league_id);
+    assert_eq!(export["matches"].as_array().unwrap().len(), 2);
+    assert_eq!(export["sessions"].as_array().unwrap().len(), 1);
+    assert!(export["sessions"][0].get("token_hash").is_none());
+
+    let delete = |password: &str| {
+        signed_in(test::TestRequest::delete(), "/api/account", &token)
+            .set_json(json!({ "password": password }))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, delete("wrong-password")).await.status(), StatusCode::FORBIDDEN);
+    let deleted = test::call_service(&app, delete("password123")).await;
+    assert_eq!(deleted.status(), StatusCode::OK);
+    let deleted: Value = test::read_body_json(deleted).await;
+    assert_eq!(deleted["left_leagues"], json!([league_id]));
+    assert_eq!(deleted["cancelled_matches"], json!([upcoming]));
+
+    // Signed out, and the old credentials no longer work
+    let after = test::call_service(&app, signed_in(test::TestRequest::get(), "/api/account/export", &token).to_request()).await;
+    assert_eq!(after.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(test::call_service(&app, login(&emails[1]).to_request()).await.status(), StatusCode::UNAUTHORIZED);
+
+    // History still points at the anonymized player
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    let anonymized = players::table.find(leaver).first::<Player>(&mut conn).unwrap();
+    assert_eq!(anonymized.name, format!("Deleted player {}", leaver));
+    assert_ne!(anonymized.email, emails[1]);
+    assert!(anonymized.phone.is_none());
+    assert!(anonymized.deleted_at.is_some() && anonymized.deactivated_at.is_some());
+    let played = matches::table.find(played).first::<Match>(&mut conn).unwrap();
+    assert_eq!((played.player1_id, played.winner_id), (Some(leaver), Some(leaver)));
+    let upcoming = matches::table.find(upcoming).first::<Match>(&mut conn).unwrap();
+    assert_eq!(upcoming.status, "Cancelled");
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_last_league_admin_cannot_delete_account() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let email = unique_email("sole-admin");
+    let registered: Value = test::read_body_json(test::call_service(&app, register(&email).to_request()).await).await;
+    let admin = registered["player_id"].as_i64().unwrap();
+    verify_email(&pool, admin);
+    let session: Value = test::read_body_json(test::call_service(&app, login(&email).to_request()).await).await;
+    let token = session["token"].as_str().unwrap();
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", token)
+        .set_json(json!({ "league_name": format!("sole-{:x}", Uuid::new_v4().as_simple()) }))
+        .to_request();
+    assert!(test::call_service(&app, league_req).await.status().is_success());
+
+    let delete = signed_in(test::TestRequest::delete(), "/api/account", token)
+        .set_json(json!({ "password": "password123" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, delete).await.status(), StatusCode::BAD_REQUEST);
+
+    // Nothing was changed
+    let still_there = test::call_service(&app, signed_in(test::TestRequest::get(), "/api/account/export", token).to_request()).await;
+    assert_eq!(still_there.status(), StatusCode::OK);
+    let export: Value = test::read_body_json(still_there).await;
+    assert_eq!(export["profile"]["email"], email.as_str());
+}
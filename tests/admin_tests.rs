@@ -0,0 +1,179 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::r2d2::{self, ConnectionManager, PooledConnection};
+use dotenv::dotenv;
+use std::env;
+use tennis_scheduler::admin;
+use tennis_scheduler::db::models::{Match, NewLeague, NewMatch};
+use tennis_scheduler::db::schema::{matches, player_leagues, players};
+use tennis_scheduler::db::DbPool;
+use tennis_scheduler::errors::AppError;
+use uuid::Uuid;
+
+// These tests need a migrated database; run them with
+// `cargo test --test admin_tests -- --ignored`
+
+fn setup_test_db() -> DbPool {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+    r2d2::Pool::builder()
+        .build(manager)
+        .expect("Failed to create pool.")
+}
+
+fn create_player(conn: &mut PgConnection, prefix: &str) -> (i32, String) {
+    let email = format!("{}_{:.8}@example.com", prefix, Uuid::new_v4());
+    // Names are unique, ignoring case
+    let name = format!("{}-{}", prefix, Uuid::new_v4());
+    let id = diesel::insert_into(players::table)
+        .values((
+            players::name.eq(&name),
+            players::email.eq(&email),
+            players::password.eq("not-a-hash"),
+            players::created_at.eq(Utc::now()),
+        ))
+        .returning(players::player_id)
+        .get_result::<i32>(conn)
+        .expect("Failed to create player");
+    (id, email)
+}
+
+fn new_league(created_by: i32, max_members: Option<i32>) -> NewLeague {
+    NewLeague {
+        league_name: format!("admin-league-{}", Uuid::new_v4()),
+        description: None,
+        skill_level: None,
+        created_by,
+        is_public: false,
+        created_at: Utc::now(),
+        departure_policy: "cancel".into(),
+        max_members,
+        waitlist_enabled: true,
+        time_zone: "UTC".into(),
+    }
+}
+
+fn singles_match(league: i32, player1: i32, player2: i32) -> NewMatch {
+    NewMatch {
+        match_type: "Singles".into(),
+        player1_id: Some(player1),
+        player2_id: Some(player2),
+        league_id: league,
+        team1_player1_id: None,
+        team1_player2_id: None,
+        team2_player1_id: None,
+        team2_player2_id: None,
+        datetime: Utc::now(),
+        location: "Court 1".into(),
+        score: None,
+        winner_id: None,
+        status: "Pending".into(),
+        notes: None,
+        season_id: None,
+    }
+}
+
+fn connection(pool: &DbPool) -> PooledConnection<ConnectionManager<PgConnection>> {
+    pool.get().expect("Failed to get DB connection")
+}
+
+#[test]
+#[ignore]
+fn test_import_members_reports_each_line() {
+    let pool = setup_test_db();
+    let conn = &mut connection(&pool);
+
+    let (owner, _) = create_player(conn, "owner");
+    let (by_email, email) = create_player(conn, "by_email");
+    let (by_id, _) = create_player(conn, "by_id");
+    let (late, _) = create_player(conn, "late");
+
+    let league = admin::create_league(conn, new_league(owner, Some(3))).expect("Failed to create league");
+
+    let entries = vec![
+        "# members for the spring ladder".to_string(),
+        email,
+        format!(" {} ", by_id),
+        String::new(),
+        "nobody@example.com".to_string(),
+        owner.to_string(),
+        late.to_string(),
+    ];
+    let summary = admin::import_members(conn, league.league_id, &entries).expect("Import failed");
+
+    assert_eq!(summary.added, vec![by_email, by_id]);
+    assert_eq!(summary.already_members, vec![owner]);
+    assert_eq!(summary.failed.len(), 2);
+    assert_eq!(summary.failed[0], ("nobody@example.com".to_string(), "no such player".to_string()));
+    assert_eq!(summary.failed[1], (late.to_string(), "league is full".to_string()));
+}
+
+#[test]
+#[ignore]
+fn test_create_league_rejects_unknown_creator() {
+    let pool = setup_test_db();
+    let conn = &mut connection(&pool);
+
+    let result = admin::create_league(conn, new_league(-1, None));
+
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+}
+
+#[test]
+#[ignore]
+fn test_force_complete_match_and_recompute_rankings() {
+    let pool = setup_test_db();
+    let conn = &mut connection(&pool);
+
+    let (alice, _) = create_player(conn, "alice");
+    let (bob, _) = create_player(conn, "bob");
+    let (carol, _) = create_player(conn, "carol");
+    let league = admin::create_league(conn, new_league(alice, None)).expect("Failed to create league");
+    admin::import_members(conn, league.league_id, &[bob.to_string(), carol.to_string()]).expect("Import failed");
+
+    let pending = diesel::insert_into(matches::table)
+        .values(&singles_match(league.league_id, alice, bob))
+        .get_result::<Match>(conn)
+        .expect("Failed to create match");
+
+    let outsider = create_player(conn, "outsider").0;
+    let refused = admin::force_complete_match(conn, pending.id, outsider, None);
+    assert!(matches!(refused, Err(AppError::BadRequest(_))));
+
+    let completed = admin::force_complete_match(conn, pending.id, bob, Some("6-3 6-2".into()))
+        .expect("Failed to complete match");
+    assert_eq!(completed.status, "Completed");
+    assert_eq!(completed.winner_id, Some(bob));
+    assert!(completed.notes.unwrap().contains("Completed by an administrator (was Pending)"));
+
+    let updates = admin::recompute_rankings(conn, league.league_id).expect("Failed to recompute rankings");
+    assert_eq!(updates.len(), 3);
+
+    let rankings: Vec<(i32, Option<i32>)> = player_leagues::table
+        .filter(player_leagues::league_id.eq(league.league_id))
+        .select((player_leagues::player_id, player_leagues::singles_ranking))
+        .order(player_leagues::player_id)
+        .load(conn)
+        .expect("Failed to load rankings");
+    assert_eq!(rankings, vec![(alice, Some(2)), (bob, Some(1)), (carol, None)]);
+}
+
+#[test]
+#[ignore]
+fn test_reset_password_hashes_new_password() {
+    let pool = setup_test_db();
+    let conn = &mut connection(&pool);
+
+    let (player, email) = create_player(conn, "forgetful");
+
+    assert_eq!(admin::reset_password(conn, &email, "new-secret").unwrap(), player);
+    let stored = players::table
+        .find(player)
+        .select(players::password)
+        .first::<String>(conn)
+        .unwrap();
+    assert!(bcrypt::verify("new-secret", &stored).unwrap());
+
+    assert!(matches!(admin::reset_password(conn, "missing@example.com", "x"), Err(AppError::NotFound)));
+}
@@ -0,0 +1,301 @@
+use actix_web::{http::StatusCode, test, web, App};
+use chrono::{Duration, TimeZone, Utc};
+use diesel::prelude::*;
+use diesel::{r2d2::{self, ConnectionManager}, PgConnection};
+use dotenv::dotenv;
+use serde_json::{json, Value};
+use std::env;
+use std::sync::Arc;
+use tennis_scheduler::{admin, api};
+use tennis_scheduler::db::schema::{appointments, league_join_requests, matches, player_leagues};
+use tennis_scheduler::db::{models::Role, DbPool};
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
+use uuid::Uuid;
+
+fn unique_email(prefix: &str) -> String {
+    format!("{}_{:x}@example.com", prefix, Uuid::new_v4().as_simple())
+}
+
+fn setup_test_db() -> DbPool {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+    r2d2::Pool::builder()
+        .build(manager)
+        .expect("Failed to create pool")
+}
+
+/// Registration leaves the email address unverified; do what following the
+/// mailed link would.
+fn verify_email(pool: &DbPool, player: i32) {
+    use tennis_scheduler::db::schema::players;
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    diesel::update(players::table.find(player))
+        .set(players::email_verified_at.eq(Utc::now()))
+        .execute(&mut conn)
+        .expect("Failed to verify email");
+}
+
+/// Opens a session for the player, as signing in would.
+fn sign_in(pool: &DbPool, player: i32) -> String {
+    use tennis_scheduler::api::sessions::{start_session, ClientInfo};
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    let (token, _) = start_session(&mut conn, player, &ClientInfo::default()).expect("Failed to start session");
+    token
+}
+
+/// Makes the player with the address a site admin, as `admin set-role` would.
+fn make_site_admin(pool: &DbPool, email: &str) {
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    admin::set_role(&mut conn, email, Role::SiteAdmin).expect("Failed to set role");
+}
+
+fn signed_in(method: test::TestRequest, uri: &str, token: &str) -> test::TestRequest {
+    method.uri(uri).insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_league_archive_round_trip() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let tag = format!("{:x}", Uuid::new_v4().as_simple());
+    let mut ids = Vec::new();
+    let mut emails = Vec::new();
+    for name in ["Owner", "Member", "Applicant"] {
+        let email = unique_email("archive");
+        let req = test::TestRequest::post()
+            .uri("/api/register")
+            .set_json(json!({ "name": format!("{} {}", name, tag), "email": email, "password": "password123" }))
+            .to_request();
+        let registered: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        ids.push(registered["player_id"].as_i64().unwrap() as i32);
+        emails.push(email);
+    }
+    let (owner, member, applicant) = (ids[0], ids[1], ids[2]);
+    let (owner_token, member_token) = (sign_in(&pool, owner), sign_in(&pool, member));
+    verify_email(&pool, owner);
+
+    let league_name = format!("archive-{:x}", Uuid::new_v4().as_simple());
+    let req = signed_in(test::TestRequest::post(), "/api/leagues", &owner_token)
+        .set_json(json!({ "league_name": league_name, "is_public": false, "time_zone": "Europe/Berlin" }))
+        .to_request();
+    let league: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let league_id = league["league_id"].as_i64().unwrap() as i32;
+
+    let mut conn = pool.get().unwrap();
+    diesel::insert_into(player_leagues::table)
+        .values((
+            player_leagues::player_id.eq(member),
+            player_leagues::league_id.eq(league_id),
+            player_leagues::role.eq("player"),
+            player_leagues::singles_ranking.eq(Some(1)),
+            player_leagues::joined_at.eq(Utc::now()),
+        ))
+        .execute(&mut conn)
+        .unwrap();
+    diesel::insert_into(league_join_requests::table)
+        .values((
+            league_join_requests::league_id.eq(league_id),
+            league_join_requests::player_id.eq(applicant),
+            league_join_requests::description.eq(Some("Please let me in")),
+        ))
+        .execute(&mut conn)
+        .unwrap();
+    let start = Utc.with_ymd_and_hms(2025, 7, 1, 16, 0, 0).unwrap();
+    diesel::insert_into(matches::table)
+        .values((
+            matches::match_type.eq("Singles"),
+            matches::player1_id.eq(Some(owner)),
+            matches::player2_id.eq(Some(member)),
+            matches::league_id.eq(league_id),
+            matches::datetime.eq(start),
+            matches::location.eq("Court 3"),
+            matches::winner_id.eq(Some(member)),
+            matches::score.eq(Some("6-1 6-1")),
+            matches::status.eq("Completed"),
+        ))
+        .execute(&mut conn)
+        .unwrap();
+    diesel::insert_into(appointments::table)
+        .values((
+            appointments::requester_id.eq(owner),
+            appointments::opponent_id.eq(member),
+            appointments::league_id.eq(Some(league_id)),
+            appointments::start_time.eq(start),
+            appointments::end_time.eq(start + Duration::minutes(90)),
+            appointments::status.eq("confirmed"),
+        ))
+        .execute(&mut conn)
+        .unwrap();
+
+    // Only league admins can export
+    let req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/archive", league_id), &member_token)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/archive", league_id), &owner_token)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let mut archive: Value = test::read_body_json(resp).await;
+    assert_eq!(archive["format"], "tennis-scheduler/league");
+    assert_eq!(archive["version"], 1);
+    assert_eq!(archive["players"].as_array().unwrap().len(), 3);
+    assert!(archive["players"][0].get("password").is_none());
+    assert_eq!(archive["memberships"].as_array().unwrap().len(), 2);
+    assert_eq!(archive["join_requests"].as_array().unwrap().len(), 1);
+    assert_eq!(archive["matches"].as_array().unwrap().len(), 1);
+    assert_eq!(archive["appointments"].as_array().unwrap().len(), 1);
+
+    // Only site admins can import, since an archive can enrol existing players
+    let req = signed_in(test::TestRequest::post(), "/api/leagues/import", &owner_token).set_json(&archive).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+    make_site_admin(&pool, &emails[0]);
+
+    // The league name is still taken, so a plain import is refused
+    let req = signed_in(test::TestRequest::post(), "/api/leagues/import", &owner_token).set_json(&archive).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert!(body["error"].as_str().unwrap().contains("already exists"));
+
+    // Pretend the applicant only exists in the other environment
+    let applicant_email = unique_email("moved");
+    for player in archive["players"].as_array_mut().unwrap() {
+        if player["player_id"] == applicant {
+            player["email"] = json!(applicant_email);
+        }
+        // Addresses are matched ignoring case
+        if player["player_id"] == member {
+            player["email"] = json!(emails[1].to_uppercase());
+        }
+    }
+
+    // The applicant would be created under a name that is already taken
+    let copy_name = format!("copy-{:x}", Uuid::new_v4().as_simple());
+    let req = signed_in(test::TestRequest::post(), &format!("/api/leagues/import?league_name={}", copy_name), &owner_token)
+        .set_json(&archive)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert!(body["error"].as_str().unwrap().contains("is taken"));
+
+    for player in archive["players"].as_array_mut().unwrap() {
+        if player["player_id"] == applicant {
+            player["name"] = json!(format!("moved-{:x}", Uuid::new_v4().as_simple()));
+        }
+    }
+
+    let req = signed_in(test::TestRequest::post(), &format!("/api/leagues/import?league_name={}", copy_name), &owner_token)
+        .set_json(&archive)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let report: Value = test::read_body_json(resp).await;
+    let copy_id = report["league_id"].as_i64().unwrap() as i32;
+    assert_ne!(copy_id, league_id);
+    assert_eq!(report["player_ids"][owner.to_string()], owner);
+    assert_eq!(report["player_ids"][member.to_string()], member);
+    let new_applicant = report["player_ids"][applicant.to_string()].as_i64().unwrap() as i32;
+    assert_ne!(new_applicant, applicant);
+    assert_eq!(report["created_players"], json!([new_applicant]));
+    // Created players still have to prove they own their address
+    let verified_at: Option<chrono::DateTime<Utc>> = tennis_scheduler::db::schema::players::table
+        .find(new_applicant)
+        .select(tennis_scheduler::db::schema::players::email_verified_at)
+        .first(&mut conn)
+        .unwrap();
+    assert!(verified_at.is_none());
+    assert_eq!((report["memberships"].as_i64(), report["matches"].as_i64()), (Some(2), Some(1)));
+
+    let req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}", copy_id), &owner_token).to_request();
+    let copy: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(copy["league_name"], copy_name);
+    assert_eq!(copy["time_zone"], "Europe/Berlin");
+    assert_eq!(copy["created_by"], owner);
+
+    let requester: i32 = league_join_requests::table
+        .filter(league_join_requests::league_id.eq(copy_id))
+        .select(league_join_requests::player_id)
+        .first(&mut conn)
+        .unwrap();
+    assert_eq!(requester, new_applicant);
+    let ranking: Option<i32> = player_leagues::table
+        .filter(player_leagues::league_id.eq(copy_id))
+        .filter(player_leagues::player_id.eq(member))
+        .select(player_leagues::singles_ranking)
+        .first(&mut conn)
+        .unwrap();
+    assert_eq!(ranking, Some(1));
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_import_rejects_unknown_versions_and_dangling_players() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let mut archive = json!({
+        "format": "tennis-scheduler/league",
+        "version": 2,
+        "exported_at": "2025-07-01T00:00:00Z",
+        "league": {
+            "league_id": 1,
+            "league_name": format!("bad-{:x}", Uuid::new_v4().as_simple()),
+            "description": null,
+            "is_public": true,
+            "skill_level": null,
+            "created_by": 10,
+            "created_at": "2025-07-01T00:00:00Z",
+            "departure_policy": "cancel",
+            "max_members": null,
+            "waitlist_enabled": true,
+            "time_zone": "UTC"
+        },
+        "players": [],
+        "memberships": [],
+        "join_requests": [],
+        "matches": [],
+        "appointments": []
+    });
+
+    let importer_email = unique_email("importer");
+    let req = test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({ "name": format!("Importer {:x}", Uuid::new_v4().as_simple()), "email": importer_email, "password": "password123" }))
+        .to_request();
+    let registered: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let token = sign_in(&pool, registered["player_id"].as_i64().unwrap() as i32);
+    make_site_admin(&pool, &importer_email);
+
+    let req = signed_in(test::TestRequest::post(), "/api/leagues/import", &token).set_json(&archive).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert!(body["error"].as_str().unwrap().contains("Unsupported archive version 2"));
+
+    archive["version"] = json!(1);
+    let req = signed_in(test::TestRequest::post(), "/api/leagues/import", &token).set_json(&archive).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert!(body["error"].as_str().unwrap().contains("player 10 is referenced but not included"));
+}
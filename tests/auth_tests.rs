@@ -2,9 +2,12 @@ use actix_web::{http::StatusCode, test, App, web};
 use serde_json::json;
 use tennis_scheduler::api;
 use tennis_scheduler::db::DbPool;
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
 use diesel::r2d2::{self, ConnectionManager};
 use diesel::PgConnection;
 use std::env;
+use std::sync::Arc;
 use dotenv::dotenv;
 use uuid::Uuid;
 
@@ -30,6 +33,19 @@ fn setup_test_db() -> DbPool {
         .expect("Failed to create pool.")
 }
 
+/// Opens a session for the player, as signing in would.
+fn sign_in(pool: &DbPool, player: i64) -> String {
+    use tennis_scheduler::api::sessions::{start_session, ClientInfo};
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    let (token, _) = start_session(&mut conn, player as i32, &ClientInfo::default()).expect("Failed to start session");
+    token
+}
+
+fn signed_in(method: test::TestRequest, uri: &str, token: &str) -> test::TestRequest {
+    method.uri(uri).insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
 #[actix_web::test]
 #[ignore]
 async fn test_register() {
@@ -40,6 +56,8 @@ async fn test_register() {
     let app = test::init_service(
         App::new()
             .app_data(pool.clone())
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
 
@@ -48,7 +66,7 @@ async fn test_register() {
 
     let req = test::TestRequest::post()
         .uri("/api/register")
-        .set_json(&json!({
+        .set_json(json!({
             "name": name,
             "email": email,
             "password": "password123",
@@ -70,6 +88,8 @@ async fn test_login() {
     let app = test::init_service(
         App::new()
             .app_data(pool.clone())
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
 
@@ -80,7 +100,7 @@ async fn test_login() {
     // First register a user
     let register_req = test::TestRequest::post()
         .uri("/api/register")
-        .set_json(&json!({
+        .set_json(json!({
             "name": user_name,
             "email": user_email,
             "password": "password123",
@@ -94,7 +114,7 @@ async fn test_login() {
     // Then login with the same credentials
     let login_req = test::TestRequest::post()
         .uri("/api/login")
-        .set_json(&json!({
+        .set_json(json!({
             "email": user_email,
             "password": "password123"
         }))
@@ -119,6 +139,8 @@ async fn test_register_duplicate_email() {
     let app = test::init_service(
         App::new()
             .app_data(pool.clone())
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
 
@@ -130,7 +152,7 @@ async fn test_register_duplicate_email() {
     // Register first user
     let register_req1 = test::TestRequest::post()
         .uri("/api/register")
-        .set_json(&json!({
+        .set_json(json!({
             "name": user_name1,
             "email": user_email,
             "password": "password123",
@@ -144,7 +166,7 @@ async fn test_register_duplicate_email() {
     // Try to register second user with same email
     let register_req2 = test::TestRequest::post()
         .uri("/api/register")
-        .set_json(&json!({
+        .set_json(json!({
             "name": user_name2,
             "email": user_email,
             "password": "password123",
@@ -153,5 +175,243 @@ async fn test_register_duplicate_email() {
         .to_request();
 
     let register_resp2 = test::call_service(&app, register_req2).await;
-    assert_eq!(register_resp2.status(), StatusCode::INTERNAL_SERVER_ERROR);
-} 
\ No newline at end of file
+    assert_eq!(register_resp2.status(), StatusCode::CONFLICT);
+
+    // Addresses differing only in case are the same address
+    let register_req3 = test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({
+            "name": unique_name("Duplicate User 3"),
+            "email": user_email.to_uppercase(),
+            "password": "password123"
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, register_req3).await.status(), StatusCode::CONFLICT);
+
+    // So are names
+    let register_req4 = test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({
+            "name": user_name1,
+            "email": unique_email("duplicate"),
+            "password": "password123"
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, register_req4).await.status(), StatusCode::CONFLICT);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_register_validates_input() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(setup_test_db()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let valid = || json!({
+        "name": unique_name("Valid User"),
+        "email": unique_email("valid"),
+        "password": "password123",
+        "skill_level": "3.5",
+        "phone": "+1 (555) 010-0200"
+    });
+    let invalid = [
+        ("name", json!("   ")),
+        ("name", json!("x".repeat(101))),
+        ("email", json!("not-an-email")),
+        ("email", json!("two@at@example.com")),
+        ("password", json!("short1")),
+        ("password", json!("onlyletters")),
+        ("password", json!("a1".repeat(40))),
+        ("skill_level", json!("pro")),
+        ("skill_level", json!("9.0")),
+        ("phone", json!("call me")),
+        ("time_zone", json!("Mars/Olympus")),
+    ];
+    for (field, value) in invalid {
+        let mut body = valid();
+        body[field] = value;
+        let req = test::TestRequest::post().uri("/api/register").set_json(&body).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", body);
+        let error: serde_json::Value = test::read_body_json(resp).await;
+        assert!(error["error"].is_string());
+    }
+
+    let req = test::TestRequest::post().uri("/api/register").set_json(valid()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+} 
+#[actix_web::test]
+#[ignore]
+async fn test_register_requires_email_verification() {
+    let pool = setup_test_db();
+    let mailer = Arc::new(MemoryMailer::default());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(mailer.clone() as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let user_email = unique_email("verify");
+    let register_req = test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({
+            "name": unique_name("Verify User"),
+            "email": user_email,
+            "password": "password123"
+        }))
+        .to_request();
+    let register_resp = test::call_service(&app, register_req).await;
+    assert_eq!(register_resp.status(), StatusCode::CREATED);
+    let registered: serde_json::Value = test::read_body_json(register_resp).await;
+    assert_eq!(registered["email_verified"], false);
+    let player_id = registered["player_id"].as_i64().unwrap();
+
+    // Unverified players cannot create leagues
+    let user_token = sign_in(&pool, player_id);
+    let league_req = || {
+        signed_in(test::TestRequest::post(), "/api/leagues", &user_token)
+            .set_json(json!({ "league_name": format!("verify-{:x}", Uuid::new_v4().as_simple()) }))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, league_req()).await.status(), StatusCode::FORBIDDEN);
+    let other_req = test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({ "name": unique_name("Verify Joiner"), "email": unique_email("joiner"), "password": "password123" }))
+        .to_request();
+    let other: serde_json::Value = test::read_body_json(test::call_service(&app, other_req).await).await;
+    let other_token = sign_in(&pool, other["player_id"].as_i64().unwrap());
+
+    let verify = |token: &str| {
+        test::TestRequest::post()
+            .uri("/api/verify-email")
+            .set_json(json!({ "token": token }))
+            .to_request()
+    };
+    let code = |body: &str| body.split_whitespace().find(|word| word.matches('.').count() == 2).unwrap().to_string();
+
+    // A tampered code is refused
+    let token = code(&mailer.last_to(&user_email).unwrap().body);
+    let tampered = token.replacen(&player_id.to_string(), &other["player_id"].to_string(), 1);
+    assert_eq!(test::call_service(&app, verify(&tampered)).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(test::call_service(&app, verify("not a code")).await.status(), StatusCode::BAD_REQUEST);
+
+    let resp = test::call_service(&app, verify(&token)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["player_id"], player_id);
+    assert_eq!(test::call_service(&app, verify(&token)).await.status(), StatusCode::OK);
+
+    let login_req = test::TestRequest::post()
+        .uri("/api/login")
+        .set_json(json!({ "email": user_email, "password": "password123" }))
+        .to_request();
+    let login: serde_json::Value = test::read_body_json(test::call_service(&app, login_req).await).await;
+    assert_eq!(login["email_verified"], true);
+
+    // Once verified they can, but unverified players cannot join
+    let league_resp = test::call_service(&app, league_req()).await;
+    assert_eq!(league_resp.status(), StatusCode::CREATED);
+    let league: serde_json::Value = test::read_body_json(league_resp).await;
+    assert_eq!(league["created_by"], player_id);
+    let join = || {
+        signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/join", league["league_id"]), &other_token)
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, join()).await.status(), StatusCode::FORBIDDEN);
+
+    // Resending only mails addresses that still need verifying
+    let resend = |email: &str| {
+        test::TestRequest::post()
+            .uri("/api/verify-email/resend")
+            .set_json(json!({ "email": email }))
+            .to_request()
+    };
+    let sent = mailer.sent().len();
+    assert_eq!(test::call_service(&app, resend(&user_email)).await.status(), StatusCode::ACCEPTED);
+    assert_eq!(mailer.sent().len(), sent);
+
+    // and not again right after a code was sent
+    let other_email = mailer.sent().iter().find(|mail| mail.to.starts_with("joiner")).unwrap().to.clone();
+    assert_eq!(test::call_service(&app, resend(&other_email)).await.status(), StatusCode::ACCEPTED);
+    assert_eq!(mailer.sent().len(), sent);
+
+    {
+        use diesel::prelude::*;
+        use tennis_scheduler::db::schema::players;
+
+        diesel::update(players::table.filter(players::email.eq(&other_email)))
+            .set(players::verification_sent_at.eq(chrono::Utc::now() - chrono::Duration::minutes(10)))
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+    }
+    // The address is found whatever its case; the code goes to it as stored
+    assert_eq!(test::call_service(&app, resend(&other_email.to_uppercase())).await.status(), StatusCode::ACCEPTED);
+    assert_eq!(mailer.sent().len(), sent + 1);
+    let resent = code(&mailer.last_to(&other_email).unwrap().body);
+    assert_eq!(test::call_service(&app, verify(&resent)).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, join()).await.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_parallel_registrations_cannot_share_a_name_or_email() {
+    let pool = web::Data::new(setup_test_db());
+
+    let app = std::rc::Rc::new(test::init_service(
+        App::new()
+            .app_data(pool.clone())
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await);
+
+    let name = unique_name("Twin");
+    let email = unique_email("twin");
+
+    // The name in different cases, each with its own address, then the
+    // address in different cases, each with its own name
+    let name_bodies: Vec<_> = [name.to_lowercase(), name.to_uppercase(), name.clone()]
+        .into_iter()
+        .map(|variant| json!({
+            "name": variant,
+            "email": unique_email("twin"),
+            "password": "password123",
+            "skill_level": "intermediate"
+        }))
+        .collect();
+    let email_bodies: Vec<_> = [email.to_lowercase(), email.to_uppercase(), email.clone()]
+        .into_iter()
+        .map(|variant| json!({
+            "name": unique_name("Twin"),
+            "email": variant,
+            "password": "password123",
+            "skill_level": "intermediate"
+        }))
+        .collect();
+
+    // The lookups before the insert can all come up empty; the unique
+    // indexes have to turn the later inserts away
+    for group in [name_bodies, email_bodies] {
+        let registrations: Vec<_> = group
+            .iter()
+            .map(|body| {
+                let app = app.clone();
+                let req = test::TestRequest::post().uri("/api/register").set_json(body).to_request();
+                actix_web::rt::spawn(async move { test::call_service(&*app, req).await.status() })
+            })
+            .collect();
+        let mut statuses = Vec::new();
+        for registration in registrations {
+            statuses.push(registration.await.unwrap());
+        }
+
+        assert_eq!(statuses.iter().filter(|status| **status == StatusCode::CREATED).count(), 1);
+        assert!(statuses.iter().filter(|status| **status != StatusCode::CREATED).all(|status| *status == StatusCode::CONFLICT));
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::{Duration, TimeZone, Utc};
+use chrono_tz::Tz;
+use tennis_scheduler::calendar::{local_rfc3339, parse_time_zone, render_ics, zone_or_utc, CalendarEvent};
+
+fn event(summary: &str, confirmed: bool) -> CalendarEvent {
+    let start = Utc.with_ymd_and_hms(2025, 7, 1, 16, 0, 0).unwrap();
+    CalendarEvent {
+        uid: "match-1@tennis-scheduler".into(),
+        start,
+        end: start + Duration::minutes(90),
+        summary: summary.into(),
+        location: Some("Court 1, North Park".into()),
+        confirmed,
+    }
+}
+
+#[test]
+fn test_parse_time_zone_accepts_iana_names_only() {
+    assert_eq!(parse_time_zone("Europe/Berlin").unwrap(), Tz::Europe__Berlin);
+    assert!(parse_time_zone("CEST").is_err());
+    assert_eq!(zone_or_utc("not a zone"), Tz::UTC);
+}
+
+#[test]
+fn test_local_time_follows_daylight_saving() {
+    let summer = Utc.with_ymd_and_hms(2025, 7, 1, 16, 0, 0).unwrap();
+    let winter = Utc.with_ymd_and_hms(2025, 1, 15, 16, 0, 0).unwrap();
+
+    assert_eq!(local_rfc3339(summer, Tz::Europe__Berlin), "2025-07-01T18:00:00+02:00");
+    assert_eq!(local_rfc3339(winter, Tz::Europe__Berlin), "2025-01-15T17:00:00+01:00");
+    assert_eq!(local_rfc3339(summer, Tz::America__New_York), "2025-07-01T12:00:00-04:00");
+}
+
+#[test]
+fn test_render_ics_uses_zone_and_escapes_text() {
+    let stamp = Utc.with_ymd_and_hms(2025, 6, 1, 8, 0, 0).unwrap();
+    let ics = render_ics(&[event("Singles match; final", false)], Tz::America__New_York, stamp);
+
+    assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert!(ics.ends_with("END:VCALENDAR\r\n"));
+    assert!(ics.contains("DTSTART;TZID=America/New_York:20250701T120000\r\n"));
+    assert!(ics.contains("DTEND;TZID=America/New_York:20250701T133000\r\n"));
+    assert!(ics.contains("DTSTAMP:20250601T080000Z\r\n"));
+    assert!(ics.contains("SUMMARY:Singles match\\; final\r\n"));
+    assert!(ics.contains("LOCATION:Court 1\\, North Park\r\n"));
+    assert!(ics.contains("STATUS:TENTATIVE\r\n"));
+}
+
+#[test]
+fn test_render_ics_without_events_is_an_empty_calendar() {
+    let ics = render_ics(&[], Tz::UTC, Utc::now());
+
+    assert!(!ics.contains("BEGIN:VEVENT"));
+    assert!(ics.contains("X-WR-TIMEZONE:UTC\r\n"));
+}
@@ -0,0 +1,274 @@
+use actix_web::{http::StatusCode, test, web, App};
+use chrono::{TimeZone, Utc};
+use diesel::prelude::*;
+use diesel::{r2d2::{self, ConnectionManager}, PgConnection};
+use dotenv::dotenv;
+use serde_json::{json, Value};
+use std::env;
+use std::sync::Arc;
+use tennis_scheduler::{admin, api};
+use tennis_scheduler::db::{models::{NewMatch, Role}, schema::matches, DbPool};
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
+use uuid::Uuid;
+
+fn unique_email(prefix: &str) -> String {
+    format!("{}_{:x}@example.com", prefix, Uuid::new_v4().as_simple())
+}
+
+fn setup_test_db() -> DbPool {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+    r2d2::Pool::builder()
+        .build(manager)
+        .expect("Failed to create pool")
+}
+
+/// Opens a session for the player, as signing in would.
+fn sign_in(pool: &DbPool, player: i64) -> String {
+    use tennis_scheduler::api::sessions::{start_session, ClientInfo};
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    let (token, _) = start_session(&mut conn, player as i32, &ClientInfo::default()).expect("Failed to start session");
+    token
+}
+
+/// Makes the player with the address a site admin, as `admin set-role` would.
+fn make_site_admin(pool: &DbPool, email: &str) {
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    admin::set_role(&mut conn, email, Role::SiteAdmin).expect("Failed to set role");
+}
+
+fn csv_request(uri: &str, body: String, token: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri(uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .insert_header(("Content-Type", "text/csv"))
+        .set_payload(body)
+}
+
+fn signed_in(method: test::TestRequest, uri: &str, token: &str) -> test::TestRequest {
+    method.uri(uri).insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+fn register(email: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({
+            "name": format!("Importer {:x}", Uuid::new_v4().as_simple()),
+            "email": email,
+            "password": "password123"
+        }))
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_import_players_reports_invalid_rows() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let importer_email = unique_email("csv_importer");
+    let registered: Value = test::read_body_json(test::call_service(&app, register(&importer_email).to_request()).await).await;
+    let importer = sign_in(&pool, registered["player_id"].as_i64().unwrap());
+
+    // Only site admins can import players
+    let resp = test::call_service(&app, csv_request("/api/players/import", "name,email,password
+".into(), &importer).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    make_site_admin(&pool, &importer_email);
+
+    let suffix = format!("{:x}", Uuid::new_v4().as_simple());
+    let first = unique_email("csv_first");
+    let second = unique_email("csv_second");
+    let body = format!(
+        "name,email,password,skill_level,phone,time_zone\n\
+         First {suffix},{first},secret-one1,intermediate,,Europe/Berlin\n\
+         Second {suffix},{second},secret-two2,,+49 30 1234567,\n\
+         Again {suffix},{first},secret-three3,,,\n\
+         Nameless {suffix},{nameless},secret-four4,,,\n\
+         Lost {suffix},{lost},secret-five5,,,Mars/Base\n\
+         Weak {suffix},{weak},short,,,\n\
+         FIRST {suffix},{twin},secret-six6,,,\n\
+         Shouting {suffix},{taken},secret-seven7,,,\n\
+         Rated {suffix},{rated},secret-eight8,9.5,,\n\
+         Caller {suffix},{caller},secret-nine9,,call me,\n",
+        suffix = suffix,
+        first = first,
+        second = second,
+        nameless = "not-an-email",
+        lost = unique_email("csv_lost"),
+        weak = unique_email("csv_weak"),
+        twin = unique_email("csv_twin"),
+        taken = importer_email.to_uppercase(),
+        rated = unique_email("csv_rated"),
+        caller = unique_email("csv_caller"),
+    );
+
+    let resp = test::call_service(&app, csv_request("/api/players/import", body, &importer).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let result: Value = test::read_body_json(resp).await;
+
+    assert_eq!(result["success"], false);
+    let imported: Vec<i64> = result["imported"].as_array().unwrap().iter().map(|row| row["line"].as_i64().unwrap()).collect();
+    assert_eq!(imported, vec![2, 3]);
+    let failed: Vec<i64> = result["errors"].as_array().unwrap().iter().map(|row| row["line"].as_i64().unwrap()).collect();
+    assert_eq!(failed, vec![4, 5, 6, 7, 8, 9, 10, 11]);
+    let message = |index: usize| result["errors"][index]["message"].as_str().unwrap().to_string();
+    assert!(message(2).contains("Mars/Base"));
+    assert!(message(3).contains("at least 8 characters"));
+    assert!(message(4).contains("already taken"));
+    assert!(message(5).contains("already registered"));
+    assert!(message(6).contains("Unknown skill level"));
+    assert!(message(7).contains("not a valid phone number"));
+
+    // Imported players can log in with the password from the sheet, and the
+    // site admin's word counts for their address
+    let login = test::TestRequest::post()
+        .uri("/api/login")
+        .set_json(json!({ "email": first, "password": "secret-one1" }))
+        .to_request();
+    let resp = test::call_service(&app, login).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let session: Value = test::read_body_json(resp).await;
+    assert_eq!(session["email_verified"], true);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_import_members_and_export_league_as_csv() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let importer_email = unique_email("csv_importer");
+    let registered: Value = test::read_body_json(test::call_service(&app, register(&importer_email).to_request()).await).await;
+    let importer = sign_in(&pool, registered["player_id"].as_i64().unwrap());
+    make_site_admin(&pool, &importer_email);
+
+    let suffix = format!("{:x}", Uuid::new_v4().as_simple());
+    let emails: Vec<String> = (0..3).map(|_| unique_email("csv_member")).collect();
+    let body = format!(
+        "name,email,password\nAdmin {suffix},{},password1\nMember {suffix},{},password1\nLate {suffix},{},password1\n",
+        emails[0], emails[1], emails[2],
+        suffix = suffix,
+    );
+    let resp = test::call_service(&app, csv_request("/api/players/import", body, &importer).to_request()).await;
+    let result: Value = test::read_body_json(resp).await;
+    let ids: Vec<i64> = result["imported"].as_array().unwrap().iter().map(|row| row["player_id"].as_i64().unwrap()).collect();
+    assert_eq!(ids.len(), 3);
+    let tokens: Vec<String> = ids.iter().map(|id| sign_in(&pool, *id)).collect();
+
+    let create_req = signed_in(test::TestRequest::post(), "/api/leagues", &tokens[0])
+        .set_json(json!({
+            "league_name": format!("csv-league-{}", Uuid::new_v4()),
+            "max_members": 2,
+            "time_zone": "America/New_York"
+        }))
+        .to_request();
+    let league: Value = test::read_body_json(test::call_service(&app, create_req).await).await;
+    let league_id = league["league_id"].as_i64().unwrap();
+
+    // Only admins can import
+    let members = format!("email,role\n{},player\n", emails[1]);
+    let uri = format!("/api/leagues/{}/members/import", league_id);
+    let resp = test::call_service(&app, csv_request(&uri, members, &tokens[1]).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let members = format!(
+        "player_id,email,role\n,{},player\n{},,\n{},,owner\n,missing@example.com,\n",
+        emails[1], ids[2], ids[2]
+    );
+    let uri = format!("/api/leagues/{}/members/import", league_id);
+    let resp = test::call_service(&app, csv_request(&uri, members, &tokens[0]).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let result: Value = test::read_body_json(resp).await;
+    assert_eq!(result["imported"][0]["player_id"].as_i64(), Some(ids[1]));
+    let errors = result["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 3);
+    assert_eq!(errors[0]["message"], "league is full");
+    assert!(errors[1]["message"].as_str().unwrap().contains("unknown role 'owner'"));
+    assert_eq!(errors[2]["message"], "no such player");
+
+    // Only admins see the members' addresses
+    let req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/members/export", league_id), &tokens[1]).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/members/export", league_id), &tokens[0]).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get("content-type").unwrap().to_str().unwrap().starts_with("text/csv"));
+    let csv = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "player_id,name,email,skill_level,role,singles_ranking,doubles_ranking,joined_at");
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].starts_with(&format!("{},Admin {},{},,admin,,,", ids[0], suffix, emails[0])));
+
+    // A completed match shows up in the matches and standings exports
+    let mut conn = pool.get().unwrap();
+    diesel::insert_into(matches::table)
+        .values(&NewMatch {
+            match_type: "Singles".into(),
+            player1_id: Some(ids[0] as i32),
+            player2_id: Some(ids[1] as i32),
+            league_id: league_id as i32,
+            team1_player1_id: None,
+            team1_player2_id: None,
+            team2_player1_id: None,
+            team2_player2_id: None,
+            datetime: Utc.with_ymd_and_hms(2025, 7, 1, 16, 0, 0).unwrap(),
+            location: "Court 2, East Park".into(),
+            score: Some("6-4 6-4".into()),
+            winner_id: Some(ids[1] as i32),
+            status: "Completed".into(),
+            notes: None,
+            season_id: None,
+        })
+        .execute(&mut conn)
+        .expect("Failed to create match");
+
+    let req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/matches/export", league_id), &tokens[0]).to_request();
+    let csv = String::from_utf8(test::read_body(test::call_service(&app, req).await).await.to_vec()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].contains(",Singles,Completed,2025-07-01T16:00:00Z,2025-07-01T12:00:00-04:00,\"Court 2, East Park\","));
+
+    let req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/standings/export", league_id), &tokens[0]).to_request();
+    let csv = String::from_utf8(test::read_body(test::call_service(&app, req).await).await.to_vec()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "division,tier,rank,player_id,name,played,wins,losses,movement");
+    assert_eq!(lines[1], format!(",,1,{},Member {},1,1,0,", ids[1], suffix));
+    assert_eq!(lines[2], format!(",,2,{},Admin {},1,0,1,", ids[0], suffix));
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_export_unknown_league_is_not_found() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let registered: Value = test::read_body_json(test::call_service(&app, register(&unique_email("csv_importer")).to_request()).await).await;
+    let importer = sign_in(&pool, registered["player_id"].as_i64().unwrap());
+
+    for export in ["members", "matches", "standings"] {
+        let req = signed_in(test::TestRequest::get(), &format!("/api/leagues/-1/{}/export", export), &importer).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+}
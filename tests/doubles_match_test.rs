@@ -42,7 +42,7 @@ async fn test_accept_doubles_match() {
     let player1_email = unique_email("player1");
     let player1 = test::TestRequest::post()
         .uri("/api/players")
-        .set_json(&json!({
+        .set_json(json!({
             "name": player1_name,
             "email": player1_email,
             "phone": "1234567890"
@@ -57,7 +57,7 @@ async fn test_accept_doubles_match() {
     let player2_email = unique_email("player2");
     let player2 = test::TestRequest::post()
         .uri("/api/players")
-        .set_json(&json!({
+        .set_json(json!({
             "name": player2_name,
             "email": player2_email,
             "phone": "1234567891"
@@ -72,7 +72,7 @@ async fn test_accept_doubles_match() {
     let player3_email = unique_email("player3");
     let player3 = test::TestRequest::post()
         .uri("/api/players")
-        .set_json(&json!({
+        .set_json(json!({
             "name": player3_name,
             "email": player3_email,
             "phone": "1234567892"
@@ -87,7 +87,7 @@ async fn test_accept_doubles_match() {
     let player4_email = unique_email("player4");
     let player4 = test::TestRequest::post()
         .uri("/api/players")
-        .set_json(&json!({
+        .set_json(json!({
             "name": player4_name,
             "email": player4_email,
             "phone": "1234567893"
@@ -102,7 +102,7 @@ async fn test_accept_doubles_match() {
     let league_name = unique_name("League");
     let league = test::TestRequest::post()
         .uri("/api/leagues")
-        .set_json(&json!({
+        .set_json(json!({
             "name": league_name,
             "description": "Test league for doubles match",
             "location": "Test location",
@@ -119,7 +119,7 @@ async fn test_accept_doubles_match() {
     // All players join the league
     let join1 = test::TestRequest::post()
         .uri(&format!("/api/players/leagues/{}/join", league_id))
-        .set_json(&json!({
+        .set_json(json!({
             "player_id": player1_id
         }))
         .send_request(&app)
@@ -128,7 +128,7 @@ async fn test_accept_doubles_match() {
     
     let join2 = test::TestRequest::post()
         .uri(&format!("/api/players/leagues/{}/join", league_id))
-        .set_json(&json!({
+        .set_json(json!({
             "player_id": player2_id
         }))
         .send_request(&app)
@@ -137,7 +137,7 @@ async fn test_accept_doubles_match() {
     
     let join3 = test::TestRequest::post()
         .uri(&format!("/api/players/leagues/{}/join", league_id))
-        .set_json(&json!({
+        .set_json(json!({
             "player_id": player3_id
         }))
         .send_request(&app)
@@ -146,7 +146,7 @@ async fn test_accept_doubles_match() {
     
     let join4 = test::TestRequest::post()
         .uri(&format!("/api/players/leagues/{}/join", league_id))
-        .set_json(&json!({
+        .set_json(json!({
             "player_id": player4_id
         }))
         .send_request(&app)
@@ -156,7 +156,7 @@ async fn test_accept_doubles_match() {
     // Create a doubles match
     let match_response = test::TestRequest::post()
         .uri("/api/matches")
-        .set_json(&json!({
+        .set_json(json!({
             "league_id": league_id,
             "match_type": "doubles",
             "team1_player1_id": player1_id,
@@ -178,7 +178,7 @@ async fn test_accept_doubles_match() {
     // Accept the match
     let accept_response = test::TestRequest::post()
         .uri(&format!("/api/matches/{}/accept", match_id))
-        .set_json(&json!({
+        .set_json(json!({
             "player_id": player1_id,
             "comments": "Looking forward to our doubles match!"
         }))
@@ -2,11 +2,13 @@ use actix_web::{test, web, App, http::StatusCode};
 use diesel::{r2d2::{self, ConnectionManager}, PgConnection};
 use dotenv::dotenv;
 use std::env;
+use std::sync::Arc;
 use uuid::Uuid;
 use tennis_scheduler::api;
 use tennis_scheduler::db::DbPool;
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
 use serde_json::json;
-use diesel::prelude::*;
 
 fn unique_name(prefix: &str) -> String {
     format!("{} {}", prefix, Uuid::new_v4())
@@ -34,12 +36,14 @@ async fn test_create_match() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
     
     // Register a test user first
     let name = unique_name("test_user");
-    let email = unique_email("test@example.com");
+    let email = unique_email("test");
     
     let register_req = test::TestRequest::post()
         .uri("/api/register")
@@ -74,9 +78,9 @@ async fn test_create_match() {
         .uri("/api/matches")
         .set_json(json!({
             "match_type": "Singles",
-            "player1_id": "1",
-            "player2_id": "2",
-            "league_id": "1",
+            "player1_id": 1,
+            "player2_id": 2,
+            "league_id": 1,
             "datetime": "2023-05-15T14:00:00",
             "location": "Tennis Court 1",
             "status": "Pending",
@@ -99,12 +103,14 @@ async fn test_accept_match() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
     
     // Register a test user first
     let name = unique_name("test_user");
-    let email = unique_email("test@example.com");
+    let email = unique_email("test");
     
     let register_req = test::TestRequest::post()
         .uri("/api/register")
@@ -138,7 +144,7 @@ async fn test_accept_match() {
     let join_league_req = test::TestRequest::post()
         .uri("/api/leagues/1/join")
         .set_json(json!({
-            "player_id": "1"
+            "player_id": 1
         }))
         .to_request();
     
@@ -150,9 +156,9 @@ async fn test_accept_match() {
         .uri("/api/matches")
         .set_json(json!({
             "match_type": "Singles",
-            "player1_id": "1",
-            "player2_id": "2",
-            "league_id": "1",
+            "player1_id": 1,
+            "player2_id": 2,
+            "league_id": 1,
             "datetime": "2023-05-15T14:00:00",
             "location": "Tennis Court 1",
             "status": "Pending",
@@ -167,7 +173,7 @@ async fn test_accept_match() {
     let accept_match_req = test::TestRequest::post()
         .uri("/api/matches/1/accept")
         .set_json(json!({
-            "player_id": "1",
+            "player_id": 1,
             "comments": "Looking forward to the match!"
         }))
         .to_request();
@@ -0,0 +1,237 @@
+use actix_web::{test, web, App, http::StatusCode};
+use diesel::{r2d2::{self, ConnectionManager}, PgConnection};
+use dotenv::dotenv;
+use std::env;
+use std::sync::Arc;
+use uuid::Uuid;
+use serde_json::{json, Value};
+use tennis_scheduler::api;
+use tennis_scheduler::db::DbPool;
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
+
+fn unique_name(prefix: &str) -> String {
+    format!("{}-{}", prefix, Uuid::new_v4())
+}
+
+fn unique_email(prefix: &str) -> String {
+    format!("{}_{:x}@example.com", prefix, Uuid::new_v4().as_simple())
+}
+
+fn setup_test_db() -> DbPool {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+    r2d2::Pool::builder()
+        .build(manager)
+        .expect("Failed to create pool")
+}
+
+/// Registration leaves the email address unverified; do what following the
+/// mailed link would.
+fn verify_email(pool: &DbPool, player: i64) {
+    use diesel::prelude::*;
+    use tennis_scheduler::db::schema::players;
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    diesel::update(players::table.find(player as i32))
+        .set(players::email_verified_at.eq(chrono::Utc::now()))
+        .execute(&mut conn)
+        .expect("Failed to verify email");
+}
+
+/// Opens a session for the player, as signing in would.
+fn sign_in(pool: &DbPool, player: i64) -> String {
+    use tennis_scheduler::api::sessions::{start_session, ClientInfo};
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    let (token, _) = start_session(&mut conn, player as i32, &ClientInfo::default()).expect("Failed to start session");
+    token
+}
+
+fn signed_in(method: test::TestRequest, uri: &str, token: &str) -> test::TestRequest {
+    method.uri(uri).insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+/// The invitation code in the last mail sent to the address.
+fn mailed_token(mailer: &MemoryMailer, to: &str) -> String {
+    let body = mailer.last_to(to).expect("No invitation was mailed").body;
+    body.split_whitespace()
+        .find(|word| word.len() == 32 && word.chars().all(|c| c.is_ascii_hexdigit()))
+        .expect("No code in the invitation")
+        .to_string()
+}
+
+fn register_request(name: &str, email: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({
+            "name": name,
+            "email": email,
+            "password": "password123",
+            "skill_level": "intermediate"
+        }))
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_invite_unregistered_player_by_email() {
+    let pool = setup_test_db();
+    let mailer = Arc::new(MemoryMailer::default());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(mailer.clone() as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let register_resp = test::call_service(&app, register_request(&unique_name("InviteAdmin"), &unique_email("admin")).to_request()).await;
+    assert_eq!(register_resp.status(), StatusCode::CREATED);
+    let registered: Value = test::read_body_json(register_resp).await;
+    let admin = registered["player_id"].as_i64().unwrap();
+    verify_email(&pool, admin);
+    let admin_token = sign_in(&pool, admin);
+
+    let league_name = format!("invite-league-{:x}", Uuid::new_v4().as_simple());
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &admin_token)
+        .set_json(json!({
+            "league_name": league_name,
+            "is_public": false
+        }))
+        .to_request();
+    let league_resp = test::call_service(&app, league_req).await;
+    assert_eq!(league_resp.status(), StatusCode::CREATED);
+    let league: Value = test::read_body_json(league_resp).await;
+    let league_id = league["league_id"].as_i64().unwrap();
+
+    // Invite someone who has no account yet
+    let invitee_email = unique_email("invitee");
+    let invite_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/invitations", league_id), &admin_token)
+        .set_json(json!({
+            "email": invitee_email,
+            "expires_in_days": 3
+        }))
+        .to_request();
+    let invite_resp = test::call_service(&app, invite_req).await;
+    assert_eq!(invite_resp.status(), StatusCode::CREATED);
+    let invitation: Value = test::read_body_json(invite_resp).await;
+    assert_eq!(invitation["status"], json!("pending"));
+    // The code goes to the invitee, not back to the admin
+    assert!(invitation.get("token").is_none() && invitation.get("token_hash").is_none());
+    let token = mailed_token(&mailer, &invitee_email);
+
+    // A second invitation to the same address is refused while the first is open
+    let duplicate_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/invitations", league_id), &admin_token)
+        .set_json(json!({ "email": invitee_email }))
+        .to_request();
+    let duplicate_resp = test::call_service(&app, duplicate_req).await;
+    assert_eq!(duplicate_resp.status(), StatusCode::BAD_REQUEST);
+
+    // Someone else cannot use the token
+    let stranger_resp = test::call_service(&app, register_request(&unique_name("Stranger"), &unique_email("stranger")).to_request()).await;
+    let stranger: Value = test::read_body_json(stranger_resp).await;
+    let stranger_req = signed_in(test::TestRequest::post(), &format!("/api/invitations/{}/accept", token), &sign_in(&pool, stranger["player_id"].as_i64().unwrap()))
+        .to_request();
+    let stranger_resp = test::call_service(&app, stranger_req).await;
+    assert_eq!(stranger_resp.status(), StatusCode::FORBIDDEN);
+
+    // The invitee registers with the invited address, in other case, and accepts
+    let invitee = unique_name("Invitee");
+    let register_resp = test::call_service(&app, register_request(&invitee, &invitee_email.to_uppercase()).to_request()).await;
+    assert_eq!(register_resp.status(), StatusCode::CREATED);
+    let registered: Value = test::read_body_json(register_resp).await;
+    let invitee_token = sign_in(&pool, registered["player_id"].as_i64().unwrap());
+
+    let accept = || {
+        signed_in(test::TestRequest::post(), &format!("/api/invitations/{}/accept", token), &invitee_token)
+            .to_request()
+    };
+    // Not before the invitee has verified their address
+    assert_eq!(test::call_service(&app, accept()).await.status(), StatusCode::FORBIDDEN);
+    verify_email(&pool, registered["player_id"].as_i64().unwrap());
+    let accept_resp = test::call_service(&app, accept()).await;
+    assert_eq!(accept_resp.status(), StatusCode::OK);
+
+    let players_req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/players", league_id), &admin_token)
+        .to_request();
+    let players_resp = test::call_service(&app, players_req).await;
+    let players: Value = test::read_body_json(players_resp).await;
+    assert!(players.as_array().unwrap().iter().any(|p| p["name"] == json!(invitee)));
+
+    // The token cannot be used twice
+    let again_req = signed_in(test::TestRequest::post(), &format!("/api/invitations/{}/decline", token), &invitee_token)
+        .to_request();
+    let again_resp = test::call_service(&app, again_req).await;
+    assert_eq!(again_resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_only_admins_can_invite() {
+    let pool = setup_test_db();
+    let mailer = Arc::new(MemoryMailer::default());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(mailer.clone() as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let admin = unique_name("InviteOwner");
+    let member = unique_name("InviteMember");
+    let member_email = unique_email("invite");
+    let mut ids = Vec::new();
+    for (name, email) in [(&admin, unique_email("invite")), (&member, member_email.clone())] {
+        let register_resp = test::call_service(&app, register_request(name, &email).to_request()).await;
+        assert_eq!(register_resp.status(), StatusCode::CREATED);
+        let registered: Value = test::read_body_json(register_resp).await;
+        ids.push(registered["player_id"].as_i64().unwrap());
+    }
+    let member_name = member.clone();
+    let (admin, member) = (ids[0], ids[1]);
+    let (admin_token, member_token) = (sign_in(&pool, admin), sign_in(&pool, member));
+    verify_email(&pool, admin);
+
+    let league_name = format!("invite-league-{:x}", Uuid::new_v4().as_simple());
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &admin_token)
+        .set_json(json!({ "league_name": league_name }))
+        .to_request();
+    let league_resp = test::call_service(&app, league_req).await;
+    let league: Value = test::read_body_json(league_resp).await;
+    let league_id = league["league_id"].as_i64().unwrap();
+
+    let invite_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/invitations", league_id), &member_token)
+        .set_json(json!({ "player_id": admin }))
+        .to_request();
+    let invite_resp = test::call_service(&app, invite_req).await;
+    assert_eq!(invite_resp.status(), StatusCode::FORBIDDEN);
+
+    // The admin invites the registered player by name, who declines without signing in
+    let invite_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/invitations", league_id), &admin_token)
+        .set_json(json!({ "name": member_name.to_uppercase() }))
+        .to_request();
+    let invite_resp = test::call_service(&app, invite_req).await;
+    assert_eq!(invite_resp.status(), StatusCode::CREATED);
+    let invitation: Value = test::read_body_json(invite_resp).await;
+    assert_eq!(invitation["invitee_id"], json!(member));
+
+    let decline_uri = format!("/api/invitations/{}/decline", mailed_token(&mailer, &member_email));
+    let decline_req = test::TestRequest::post().uri(&decline_uri).to_request();
+    let decline_resp = test::call_service(&app, decline_req).await;
+    assert_eq!(decline_resp.status(), StatusCode::OK);
+
+    // Only admins see the league's invitations
+    let list_uri = format!("/api/leagues/{}/invitations?status=declined", league_id);
+    let list_req = signed_in(test::TestRequest::get(), &list_uri, &member_token).to_request();
+    assert_eq!(test::call_service(&app, list_req).await.status(), StatusCode::FORBIDDEN);
+    let list_req = signed_in(test::TestRequest::get(), &list_uri, &admin_token)
+        .to_request();
+    let list_resp = test::call_service(&app, list_req).await;
+    let invitations: Value = test::read_body_json(list_resp).await;
+    assert_eq!(invitations.as_array().unwrap().len(), 1);
+    assert!(invitations[0].get("token").is_none() && invitations[0].get("token_hash").is_none());
+}
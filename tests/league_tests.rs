@@ -2,9 +2,12 @@ use actix_web::{http::StatusCode, test, App, web};
 use serde_json::json;
 use tennis_scheduler::api;
 use tennis_scheduler::db::DbPool;
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
 use diesel::r2d2::{self, ConnectionManager};
 use diesel::PgConnection;
 use std::env;
+use std::sync::Arc;
 use dotenv::dotenv;
 use uuid::Uuid;
 
@@ -30,6 +33,32 @@ fn setup_test_db() -> DbPool {
         .expect("Failed to create pool.")
 }
 
+/// Registration leaves the email address unverified; do what following the
+/// mailed link would.
+fn verify_email(pool: &DbPool, player: i64) {
+    use diesel::prelude::*;
+    use tennis_scheduler::db::schema::players;
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    diesel::update(players::table.find(player as i32))
+        .set(players::email_verified_at.eq(chrono::Utc::now()))
+        .execute(&mut conn)
+        .expect("Failed to verify email");
+}
+
+/// Opens a session for the player, as signing in would.
+fn sign_in(pool: &DbPool, player: i64) -> String {
+    use tennis_scheduler::api::sessions::{start_session, ClientInfo};
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    let (token, _) = start_session(&mut conn, player as i32, &ClientInfo::default()).expect("Failed to start session");
+    token
+}
+
+fn signed_in(method: test::TestRequest, uri: &str, token: &str) -> test::TestRequest {
+    method.uri(uri).insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
 #[actix_web::test]
 #[ignore]
 async fn test_create_league() {
@@ -40,6 +69,8 @@ async fn test_create_league() {
     let app = test::init_service(
         App::new()
             .app_data(pool.clone())
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
 
@@ -50,7 +81,7 @@ async fn test_create_league() {
     // First register a user
     let register_req = test::TestRequest::post()
         .uri("/api/register")
-        .set_json(&json!({
+        .set_json(json!({
             "name": user_name,
             "email": user_email,
             "password": "password123",
@@ -60,16 +91,18 @@ async fn test_create_league() {
 
     let register_resp = test::call_service(&app, register_req).await;
     assert_eq!(register_resp.status(), StatusCode::CREATED);
+    let registered: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = registered["player_id"].as_i64().unwrap();
+    verify_email(&pool, user_id);
+    let user_token = sign_in(&pool, user_id);
 
     // Create a league
-    let league_req = test::TestRequest::post()
-        .uri("/api/leagues")
-        .set_json(&json!({
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &user_token)
+        .set_json(json!({
             "league_name": format!("Test League {}", Uuid::new_v4()),
             "description": "A test league for unit testing",
             "skill_level": "intermediate",
-            "is_public": true,
-            "created_by": user_email
+            "is_public": true
         }))
         .to_request();
 
@@ -90,6 +123,8 @@ async fn test_get_leagues() {
     let app = test::init_service(
         App::new()
             .app_data(pool.clone())
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
 
@@ -100,7 +135,7 @@ async fn test_get_leagues() {
     // First register a user
     let register_req = test::TestRequest::post()
         .uri("/api/register")
-        .set_json(&json!({
+        .set_json(json!({
             "name": user_name,
             "email": user_email,
             "password": "password123",
@@ -110,16 +145,18 @@ async fn test_get_leagues() {
 
     let register_resp = test::call_service(&app, register_req).await;
     assert_eq!(register_resp.status(), StatusCode::CREATED);
+    let registered: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = registered["player_id"].as_i64().unwrap();
+    verify_email(&pool, user_id);
+    let user_token = sign_in(&pool, user_id);
 
     // Create a league
-    let league_req = test::TestRequest::post()
-        .uri("/api/leagues")
-        .set_json(&json!({
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &user_token)
+        .set_json(json!({
             "league_name": format!("Viewable League {}", Uuid::new_v4()),
             "description": "A league that can be viewed",
             "skill_level": "intermediate",
-            "is_public": true,
-            "created_by": user_email
+            "is_public": true
         }))
         .to_request();
 
@@ -129,8 +166,7 @@ async fn test_get_leagues() {
     assert!(league_resp.status() == StatusCode::CREATED || league_resp.status() == StatusCode::INTERNAL_SERVER_ERROR);
 
     // Get all leagues
-    let get_req = test::TestRequest::get()
-        .uri("/api/leagues")
+    let get_req = signed_in(test::TestRequest::get(), "/api/leagues", &user_token)
         .to_request();
 
     let get_resp = test::call_service(&app, get_req).await;
@@ -147,6 +183,8 @@ async fn test_join_league() {
     let app = test::init_service(
         App::new()
             .app_data(pool.clone())
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
 
@@ -157,7 +195,7 @@ async fn test_join_league() {
     // Register admin user
     let register_req1 = test::TestRequest::post()
         .uri("/api/register")
-        .set_json(&json!({
+        .set_json(json!({
             "name": admin_name,
             "email": admin_email,
             "password": "password123",
@@ -167,10 +205,10 @@ async fn test_join_league() {
 
     let register_resp1 = test::call_service(&app, register_req1).await;
     assert_eq!(register_resp1.status(), StatusCode::CREATED);
-    
-    // For simplicity, we'll use player_id 1 for admin and 2 for member
-    // In a real test, we would extract these from the database
-    let _admin_id = 1;
+    let admin: serde_json::Value = test::read_body_json(register_resp1).await;
+    let admin_id = admin["player_id"].as_i64().unwrap();
+    verify_email(&pool, admin_id);
+    let admin_token = sign_in(&pool, admin_id);
 
     // Generate unique user data for member
     let member_name = unique_name("League Member");
@@ -179,7 +217,7 @@ async fn test_join_league() {
     // Register member user
     let register_req2 = test::TestRequest::post()
         .uri("/api/register")
-        .set_json(&json!({
+        .set_json(json!({
             "name": member_name,
             "email": member_email,
             "password": "password123",
@@ -189,20 +227,19 @@ async fn test_join_league() {
 
     let register_resp2 = test::call_service(&app, register_req2).await;
     assert_eq!(register_resp2.status(), StatusCode::CREATED);
-    
-    // For simplicity, we'll use player_id 2 for member
-    let member_id = 2;
+    let member: serde_json::Value = test::read_body_json(register_resp2).await;
+    let member_id = member["player_id"].as_i64().unwrap();
+    verify_email(&pool, member_id);
+    let member_token = sign_in(&pool, member_id);
 
     // Create a league
     let league_name = format!("Joinable League {}", Uuid::new_v4());
-    let league_req = test::TestRequest::post()
-        .uri("/api/leagues")
-        .set_json(&json!({
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &admin_token)
+        .set_json(json!({
             "league_name": league_name,
             "description": "A league that can be joined",
             "skill_level": "intermediate",
-            "is_public": true,
-            "created_by": admin_email
+            "is_public": true
         }))
         .to_request();
 
@@ -215,12 +252,7 @@ async fn test_join_league() {
     let league_id = 1;
 
     // Join the league
-    let join_req = test::TestRequest::post()
-        .uri(&format!("/api/leagues/{}/join", league_id))
-        .set_json(&json!({
-            "player_id": member_id,
-            "description": "I want to join this league"
-        }))
+    let join_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/join", league_id), &member_token)
         .to_request();
 
     let join_resp = test::call_service(&app, join_req).await;
@@ -230,7 +262,6 @@ async fn test_join_league() {
     
     // Accept any status code for now
     // The API might return various status codes depending on the implementation
-    assert!(true);
 }
 
 #[actix_web::test]
@@ -243,6 +274,8 @@ async fn test_get_league_players() {
     let app = test::init_service(
         App::new()
             .app_data(pool.clone())
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
 
@@ -253,7 +286,7 @@ async fn test_get_league_players() {
     // Register a user
     let register_req = test::TestRequest::post()
         .uri("/api/register")
-        .set_json(&json!({
+        .set_json(json!({
             "name": user_name,
             "email": user_email,
             "password": "password123",
@@ -263,20 +296,19 @@ async fn test_get_league_players() {
 
     let register_resp = test::call_service(&app, register_req).await;
     assert_eq!(register_resp.status(), StatusCode::CREATED);
-    
-    // For simplicity, we'll use player_id 1
-    let _user_id = 1;
+    let registered: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = registered["player_id"].as_i64().unwrap();
+    verify_email(&pool, user_id);
+    let user_token = sign_in(&pool, user_id);
 
     // Create a league
     let league_name = format!("Players League {}", Uuid::new_v4());
-    let league_req = test::TestRequest::post()
-        .uri("/api/leagues")
-        .set_json(&json!({
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &user_token)
+        .set_json(json!({
             "league_name": league_name,
             "description": "A league with players",
             "skill_level": "intermediate",
-            "is_public": true,
-            "created_by": user_email
+            "is_public": true
         }))
         .to_request();
 
@@ -289,10 +321,383 @@ async fn test_get_league_players() {
     let league_id = 1;
 
     // Get league players
-    let players_req = test::TestRequest::get()
-        .uri(&format!("/api/leagues/{}/players", league_id))
+    let players_req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/players", league_id), &user_token)
         .to_request();
 
     let players_resp = test::call_service(&app, players_req).await;
     assert_eq!(players_resp.status(), StatusCode::OK);
-} 
\ No newline at end of file
+} 
+fn register_request(name: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({
+            "name": name,
+            "email": unique_email("member"),
+            "password": "password123",
+            "skill_level": "intermediate"
+        }))
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_leave_league_settles_matches_and_keeps_last_admin() {
+    let pool = web::Data::new(setup_test_db());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(pool.clone())
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let admin = unique_name("LeaveAdmin");
+    let member = unique_name("LeaveMember");
+    let opponent = unique_name("LeaveOpponent");
+    let mut ids = Vec::new();
+    for name in [&admin, &member, &opponent] {
+        let register_resp = test::call_service(&app, register_request(name).to_request()).await;
+        assert_eq!(register_resp.status(), StatusCode::CREATED);
+        let registered: serde_json::Value = test::read_body_json(register_resp).await;
+        let id = registered["player_id"].as_i64().unwrap();
+        verify_email(&pool, id);
+        ids.push(id);
+    }
+    let (admin, member, opponent) = (ids[0], ids[1], ids[2]);
+    let (admin_token, member_token) = (sign_in(&pool, admin), sign_in(&pool, member));
+
+    // A league whose policy awards scheduled matches to the opponent
+    let league_name = format!("leave-league-{:x}", Uuid::new_v4().as_simple());
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &admin_token)
+        .set_json(json!({
+            "league_name": league_name,
+            "departure_policy": "forfeit"
+        }))
+        .to_request();
+    let league_resp = test::call_service(&app, league_req).await;
+    assert_eq!(league_resp.status(), StatusCode::CREATED);
+    let league: serde_json::Value = test::read_body_json(league_resp).await;
+    let league_id = league["league_id"].as_i64().unwrap();
+
+    for name in [&member, &opponent] {
+        let join_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/join", league_id), &sign_in(&pool, *name))
+            .to_request();
+        let join_resp = test::call_service(&app, join_req).await;
+        assert_eq!(join_resp.status(), StatusCode::OK);
+    }
+
+    // One pending and one scheduled match for the member
+    for status in ["Pending", "Scheduled"] {
+        let match_req = signed_in(test::TestRequest::post(), "/api/matches", &member_token)
+            .set_json(json!({
+                "match_type": "Singles",
+                "player1_id": member,
+                "player2_id": opponent,
+                "league_id": league_id,
+                "datetime": "2025-05-15T14:00:00",
+                "location": "Court 1",
+                "status": status
+            }))
+            .to_request();
+        let match_resp = test::call_service(&app, match_req).await;
+        assert_eq!(match_resp.status(), StatusCode::CREATED);
+    }
+
+    // The only admin cannot leave
+    let admin_leave_req = signed_in(test::TestRequest::delete(), &format!("/api/leagues/{}/leave", league_id), &admin_token)
+        .to_request();
+    let admin_leave_resp = test::call_service(&app, admin_leave_req).await;
+    assert_eq!(admin_leave_resp.status(), StatusCode::BAD_REQUEST);
+
+    // A regular member cannot remove someone else
+    let kick_req = signed_in(test::TestRequest::delete(), &format!("/api/leagues/{}/members/{}", league_id, opponent), &member_token)
+        .to_request();
+    let kick_resp = test::call_service(&app, kick_req).await;
+    assert_eq!(kick_resp.status(), StatusCode::FORBIDDEN);
+
+    // Nor make themselves an admin, and the only admin cannot step down
+    let role_req = |player: i64, role: &str, token: &str| {
+        signed_in(test::TestRequest::put(), &format!("/api/leagues/{}/members/{}", league_id, player), token)
+            .set_json(json!({ "role": role }))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, role_req(member, "admin", &member_token)).await.status(), StatusCode::FORBIDDEN);
+    assert_eq!(test::call_service(&app, role_req(admin, "player", &admin_token)).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(test::call_service(&app, role_req(opponent, "owner", &admin_token)).await.status(), StatusCode::BAD_REQUEST);
+
+    // The member leaves: the pending match is cancelled, the scheduled one forfeited
+    let leave_req = signed_in(test::TestRequest::delete(), &format!("/api/leagues/{}/leave", league_id), &member_token)
+        .to_request();
+    let leave_resp = test::call_service(&app, leave_req).await;
+    assert_eq!(leave_resp.status(), StatusCode::OK);
+    let leave_body: serde_json::Value = test::read_body_json(leave_resp).await;
+    assert_eq!(leave_body["cancelled_matches"].as_array().unwrap().len(), 1);
+    assert_eq!(leave_body["forfeited_matches"].as_array().unwrap().len(), 1);
+
+    let matches_req = signed_in(test::TestRequest::get(), &format!("/api/matches?league_id={}&status=Completed", league_id), &admin_token)
+        .to_request();
+    let matches_resp = test::call_service(&app, matches_req).await;
+    let matches_body: serde_json::Value = test::read_body_json(matches_resp).await;
+    assert_eq!(matches_body["matches"][0]["winner_id"], json!(opponent));
+
+    // The admin removes the remaining member
+    let remove_req = signed_in(test::TestRequest::delete(), &format!("/api/leagues/{}/members/{}", league_id, opponent), &admin_token)
+        .to_request();
+    let remove_resp = test::call_service(&app, remove_req).await;
+    assert_eq!(remove_resp.status(), StatusCode::OK);
+
+    let players_req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/players", league_id), &admin_token)
+        .to_request();
+    let players_resp = test::call_service(&app, players_req).await;
+    let players: serde_json::Value = test::read_body_json(players_resp).await;
+    assert_eq!(players.as_array().unwrap().len(), 1);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_private_league_requires_approved_request() {
+    let pool = web::Data::new(setup_test_db());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(pool.clone())
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let admin = unique_name("PrivAdmin");
+    let member = unique_name("PrivMember");
+    let mut ids = Vec::new();
+    for name in [&admin, &member] {
+        let register_resp = test::call_service(&app, register_request(name).to_request()).await;
+        assert_eq!(register_resp.status(), StatusCode::CREATED);
+        let registered: serde_json::Value = test::read_body_json(register_resp).await;
+        let id = registered["player_id"].as_i64().unwrap();
+        verify_email(&pool, id);
+        ids.push(id);
+    }
+    let (admin, member) = (ids[0], ids[1]);
+    let (admin_token, member_token) = (sign_in(&pool, admin), sign_in(&pool, member));
+
+    let league_name = format!("private-league-{:x}", Uuid::new_v4().as_simple());
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &admin_token)
+        .set_json(json!({
+            "league_name": league_name,
+            "is_public": false
+        }))
+        .to_request();
+    let league_resp = test::call_service(&app, league_req).await;
+    assert_eq!(league_resp.status(), StatusCode::CREATED);
+    let league: serde_json::Value = test::read_body_json(league_resp).await;
+    let league_id = league["league_id"].as_i64().unwrap();
+
+    // Non-members do not see the league in search results, even when they pass an admin's id
+    let search_req = signed_in(test::TestRequest::get(), &format!("/api/leagues?search={}&player_id={}", league_name, admin), &member_token)
+        .to_request();
+    let search_resp = test::call_service(&app, search_req).await;
+    let leagues: serde_json::Value = test::read_body_json(search_resp).await;
+    assert!(leagues["leagues"].as_array().unwrap().is_empty());
+
+    // Joining directly is refused
+    let join_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/join", league_id), &member_token)
+        .to_request();
+    let join_resp = test::call_service(&app, join_req).await;
+    assert_eq!(join_resp.status(), StatusCode::FORBIDDEN);
+
+    // Nor can they read the league's details, members, seasons or exports
+    let read_req = |path: &str, token: &str| {
+        signed_in(test::TestRequest::get(), &format!("/api/leagues/{}{}", league_id, path), token).to_request()
+    };
+    for path in ["", "/players", "/seasons", "/matches/export", "/standings/export"] {
+        assert_eq!(test::call_service(&app, read_req(path, &member_token)).await.status(), StatusCode::FORBIDDEN);
+    }
+
+    let request_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/join-requests", league_id), &member_token)
+        .set_json(json!({ "description": "Let me in" }))
+        .to_request();
+    test::call_service(&app, request_req).await;
+
+    let requests_req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/join-requests", league_id), &admin_token)
+        .to_request();
+    let requests_resp = test::call_service(&app, requests_req).await;
+    let requests: serde_json::Value = test::read_body_json(requests_resp).await;
+    assert_eq!(requests[0]["player_id"], json!(member));
+    let request_id = requests[0]["request_id"].as_i64().unwrap();
+
+    // Only an admin can approve the request
+    let self_approve_req = signed_in(test::TestRequest::patch(), &format!("/api/leagues/{}/join-requests/{}", league_id, request_id), &member_token)
+        .set_json(json!({ "status": "accepted" }))
+        .to_request();
+    let self_approve_resp = test::call_service(&app, self_approve_req).await;
+    assert_eq!(self_approve_resp.status(), StatusCode::FORBIDDEN);
+
+    let approve_req = signed_in(test::TestRequest::patch(), &format!("/api/leagues/{}/join-requests/{}", league_id, request_id), &admin_token)
+        .set_json(json!({ "status": "accepted" }))
+        .to_request();
+    let approve_resp = test::call_service(&app, approve_req).await;
+    assert_eq!(approve_resp.status(), StatusCode::OK);
+
+    // The member now belongs to the league and can find it
+    let players_req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/players", league_id), &admin_token)
+        .to_request();
+    let players_resp = test::call_service(&app, players_req).await;
+    let players: serde_json::Value = test::read_body_json(players_resp).await;
+    assert_eq!(players.as_array().unwrap().len(), 2);
+    assert_eq!(test::call_service(&app, read_req("", &member_token)).await.status(), StatusCode::OK);
+
+    let search_req = signed_in(test::TestRequest::get(), &format!("/api/leagues?search={}", league_name), &member_token)
+        .to_request();
+    let search_resp = test::call_service(&app, search_req).await;
+    let leagues: serde_json::Value = test::read_body_json(search_resp).await;
+    assert_eq!(leagues["leagues"].as_array().unwrap().len(), 1);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_full_league_waitlists_and_promotes_on_leave() {
+    let pool = web::Data::new(setup_test_db());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(pool.clone())
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let admin = unique_name("CapAdmin");
+    let member = unique_name("CapMember");
+    let waiting = unique_name("CapWaiting");
+    let mut ids = Vec::new();
+    for name in [&admin, &member, &waiting] {
+        let register_resp = test::call_service(&app, register_request(name).to_request()).await;
+        assert_eq!(register_resp.status(), StatusCode::CREATED);
+        let registered: serde_json::Value = test::read_body_json(register_resp).await;
+        let id = registered["player_id"].as_i64().unwrap();
+        verify_email(&pool, id);
+        ids.push(id);
+    }
+    let (admin, member, waiting) = (ids[0], ids[1], ids[2]);
+    let (admin_token, member_token, waiting_token) = (sign_in(&pool, admin), sign_in(&pool, member), sign_in(&pool, waiting));
+
+    let league_name = format!("capped-league-{:x}", Uuid::new_v4().as_simple());
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &admin_token)
+        .set_json(json!({
+            "league_name": league_name,
+            "max_members": 2
+        }))
+        .to_request();
+    let league_resp = test::call_service(&app, league_req).await;
+    assert_eq!(league_resp.status(), StatusCode::CREATED);
+    let league: serde_json::Value = test::read_body_json(league_resp).await;
+    let league_id = league["league_id"].as_i64().unwrap();
+
+    let join_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/join", league_id), &member_token)
+        .to_request();
+    let join_resp = test::call_service(&app, join_req).await;
+    assert_eq!(join_resp.status(), StatusCode::OK);
+
+    // The league is now full, so the next player is waitlisted
+    let join_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/join", league_id), &waiting_token)
+        .to_request();
+    let join_resp = test::call_service(&app, join_req).await;
+    assert_eq!(join_resp.status(), StatusCode::ACCEPTED);
+
+    let waitlist_req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/waitlist", league_id), &admin_token)
+        .to_request();
+    let waitlist_resp = test::call_service(&app, waitlist_req).await;
+    let waitlist: serde_json::Value = test::read_body_json(waitlist_resp).await;
+    assert_eq!(waitlist[0]["player_id"], json!(waiting));
+
+    // A member leaving frees the spot for the waitlisted player
+    let leave_req = signed_in(test::TestRequest::delete(), &format!("/api/leagues/{}/leave", league_id), &member_token)
+        .to_request();
+    let leave_resp = test::call_service(&app, leave_req).await;
+    assert_eq!(leave_resp.status(), StatusCode::OK);
+    let leave_body: serde_json::Value = test::read_body_json(leave_resp).await;
+    assert_eq!(leave_body["promoted_players"], json!([waiting]));
+
+    let role_req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/players/{}/role", league_id, waiting), &waiting_token)
+        .to_request();
+    let role_resp = test::call_service(&app, role_req).await;
+    assert_eq!(role_resp.status(), StatusCode::OK);
+
+    let notifications_req = signed_in(test::TestRequest::get(), &format!("/api/players/{}/notifications?unread=true", waiting), &waiting_token)
+        .to_request();
+    let notifications_resp = test::call_service(&app, notifications_req).await;
+    assert_eq!(notifications_resp.status(), StatusCode::OK);
+    let notifications: serde_json::Value = test::read_body_json(notifications_resp).await;
+    assert_eq!(notifications.as_array().unwrap().len(), 1);
+    assert_eq!(notifications[0]["league_id"], json!(league_id));
+
+    // Without a waitlist a full league turns players away
+    let update_req = signed_in(test::TestRequest::patch(), &format!("/api/leagues/{}", league_id), &admin_token)
+        .set_json(json!({ "waitlist_enabled": false }))
+        .to_request();
+    let update_resp = test::call_service(&app, update_req).await;
+    assert_eq!(update_resp.status(), StatusCode::OK);
+
+    let request_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/join-requests", league_id), &member_token)
+        .set_json(json!({}))
+        .to_request();
+    let request_resp = test::call_service(&app, request_req).await;
+    assert_eq!(request_resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_parallel_joins_cannot_pass_the_member_limit() {
+    let pool = web::Data::new(setup_test_db());
+
+    let app = std::rc::Rc::new(test::init_service(
+        App::new()
+            .app_data(pool.clone())
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await);
+
+    let (mut ids, mut tokens) = (Vec::new(), Vec::new());
+    for _ in 0..6 {
+        let register_resp = test::call_service(&*app, register_request(&unique_name("Racer")).to_request()).await;
+        assert_eq!(register_resp.status(), StatusCode::CREATED);
+        let registered: serde_json::Value = test::read_body_json(register_resp).await;
+        let id = registered["player_id"].as_i64().unwrap();
+        verify_email(&pool, id);
+        ids.push(id);
+        tokens.push(sign_in(&pool, id));
+    }
+
+    // Room for the admin and two more
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &tokens[0])
+        .set_json(json!({
+            "league_name": format!("race-league-{:x}", Uuid::new_v4().as_simple()),
+            "max_members": 3,
+            "waitlist_enabled": false
+        }))
+        .to_request();
+    let league: serde_json::Value = test::read_body_json(test::call_service(&*app, league_req).await).await;
+    let league_id = league["league_id"].as_i64().unwrap();
+
+    // The others all ask at once; the league row lock lets them in one by one
+    let joins: Vec<_> = tokens[1..]
+        .iter()
+        .map(|token| {
+            let app = app.clone();
+            let join_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/join", league_id), token).to_request();
+            actix_web::rt::spawn(async move { test::call_service(&*app, join_req).await.status() })
+        })
+        .collect();
+    let mut statuses = Vec::new();
+    for join in joins {
+        statuses.push(join.await.unwrap());
+    }
+
+    assert_eq!(statuses.iter().filter(|status| **status == StatusCode::OK).count(), 2);
+    assert!(statuses.iter().filter(|status| **status != StatusCode::OK).all(|status| *status == StatusCode::BAD_REQUEST));
+
+    let players_req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/players", league_id), &tokens[0]).to_request();
+    let players: serde_json::Value = test::read_body_json(test::call_service(&*app, players_req).await).await;
+    assert_eq!(players.as_array().unwrap().len(), 3);
+}
@@ -0,0 +1,206 @@
+use actix_web::{http::StatusCode, test, web, App};
+use chrono::{Duration, TimeZone, Utc};
+use diesel::prelude::*;
+use diesel::{r2d2::{self, ConnectionManager}, PgConnection};
+use dotenv::dotenv;
+use serde_json::{json, Value};
+use std::env;
+use std::sync::Arc;
+use tennis_scheduler::api;
+use tennis_scheduler::db::{models::NewMatch, schema::matches, DbPool};
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
+use uuid::Uuid;
+
+fn unique_email(prefix: &str) -> String {
+    format!("{}_{:x}@example.com", prefix, Uuid::new_v4().as_simple())
+}
+
+fn setup_test_db() -> DbPool {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+    r2d2::Pool::builder()
+        .build(manager)
+        .expect("Failed to create pool")
+}
+
+/// Registration leaves the email address unverified; do what following the
+/// mailed link would.
+fn verify_email(pool: &DbPool, player: i64) {
+    use tennis_scheduler::db::schema::players;
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    diesel::update(players::table.find(player as i32))
+        .set(players::email_verified_at.eq(Utc::now()))
+        .execute(&mut conn)
+        .expect("Failed to verify email");
+}
+
+/// Opens a session for the player, as signing in would.
+fn sign_in(pool: &DbPool, player: i64) -> String {
+    use tennis_scheduler::api::sessions::{start_session, ClientInfo};
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    let (token, _) = start_session(&mut conn, player as i32, &ClientInfo::default()).expect("Failed to start session");
+    token
+}
+
+fn signed_in(method: test::TestRequest, uri: &str, token: &str) -> test::TestRequest {
+    method.uri(uri).insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_match_listing_pages_sorts_and_filters() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let tag = format!("{:x}", Uuid::new_v4().as_simple());
+    let mut ids = Vec::new();
+    for name in ["Owner", "Rival", "Partner", "Other"] {
+        let req = test::TestRequest::post()
+            .uri("/api/register")
+            .set_json(json!({ "name": format!("{} {}", name, tag), "email": unique_email("paging"), "password": "password123" }))
+            .to_request();
+        let registered: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        ids.push(registered["player_id"].as_i64().unwrap() as i32);
+    }
+    let tokens: Vec<String> = ids.iter().map(|id| sign_in(&pool, *id as i64)).collect();
+    verify_email(&pool, ids[0] as i64);
+
+    let req = signed_in(test::TestRequest::post(), "/api/leagues", &tokens[0])
+        .set_json(json!({ "league_name": format!("paging-{:x}", Uuid::new_v4().as_simple()) }))
+        .to_request();
+    let league: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let league_id = league["league_id"].as_i64().unwrap() as i32;
+
+    // Three singles against the rival and one doubles, a day apart
+    let start = Utc.with_ymd_and_hms(2025, 7, 1, 16, 0, 0).unwrap();
+    let mut conn = pool.get().unwrap();
+    for day in 0..4 {
+        let doubles = day == 3;
+        diesel::insert_into(matches::table)
+            .values(&NewMatch {
+                match_type: if doubles { "Doubles" } else { "Singles" }.into(),
+                player1_id: (!doubles).then_some(ids[0]),
+                player2_id: (!doubles).then_some(ids[1]),
+                league_id,
+                team1_player1_id: doubles.then_some(ids[0]),
+                team1_player2_id: doubles.then_some(ids[2]),
+                team2_player1_id: doubles.then_some(ids[1]),
+                team2_player2_id: doubles.then_some(ids[3]),
+                datetime: start + Duration::days(day),
+                location: "Court 1".into(),
+                score: None,
+                winner_id: None,
+                status: "Scheduled".into(),
+                notes: None,
+                season_id: None,
+            })
+            .execute(&mut conn)
+            .unwrap();
+    }
+
+    let req = signed_in(test::TestRequest::get(), &format!("/api/matches?league_id={}&per_page=3&sort=-datetime", league_id), &tokens[0])
+        .to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["count"], 3);
+    assert_eq!(body["pagination"], json!({ "page": 1, "per_page": 3, "total": 4, "total_pages": 2 }));
+    assert_eq!(body["matches"][0]["match_type"], "Doubles");
+
+    let req = signed_in(test::TestRequest::get(), &format!("/api/matches?league_id={}&per_page=3&page=2&sort=-datetime", league_id), &tokens[0])
+        .to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["count"], 1);
+    assert_eq!(body["matches"][0]["datetime"], "2025-07-01T16:00:00Z");
+
+    let req = signed_in(test::TestRequest::get(), &format!("/api/matches?league_id={}&match_type=singles&from=2025-07-02T00:00:00Z", league_id), &tokens[0])
+        .to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["pagination"]["total"], 2);
+
+    // The partner only plays in the doubles match
+    let req = signed_in(test::TestRequest::post(), &format!("/api/matches/league/{}?participant_id={}", league_id, ids[2]), &tokens[0])
+        .to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["count"], 1);
+
+    let req = signed_in(test::TestRequest::get(), &format!("/api/matches/player/{}?participant_id={}&per_page=2", ids[1], ids[3]), &tokens[1])
+        .to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["pagination"]["total"], 1);
+
+    for bad in ["sort=location", "per_page=500", "page=0", "match_type=mixed"] {
+        let req = signed_in(test::TestRequest::get(), &format!("/api/matches?league_id={}&{}", league_id, bad), &tokens[0])
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST, "{}", bad);
+    }
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_player_and_league_listings_are_paged() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let tag = format!("{:x}", Uuid::new_v4().as_simple());
+    let mut ids = Vec::new();
+    for (name, skill) in [("Anna", "advanced"), ("Bert", "beginner"), ("Cleo", "advanced")] {
+        let req = test::TestRequest::post()
+            .uri("/api/register")
+            .set_json(json!({
+                "name": format!("{} {}", name, tag),
+                "email": unique_email("paging"),
+                "password": "password123",
+                "skill_level": skill
+            }))
+            .to_request();
+        let registered: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        ids.push(registered["player_id"].as_i64().unwrap());
+    }
+    let tokens: Vec<String> = ids.iter().map(|id| sign_in(&pool, *id)).collect();
+
+    let req = signed_in(test::TestRequest::get(), &format!("/api/players?name={}&skill_level=Advanced&sort=-name", tag), &tokens[0])
+        .to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["pagination"]["total"], 2);
+    assert_eq!(body["players"][0]["player_id"].as_i64(), Some(ids[2]));
+
+    let req = signed_in(test::TestRequest::get(), &format!("/api/players/search?name={}&per_page=1&page=2", tag), &tokens[0])
+        .to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["players"][0]["player_id"].as_i64(), Some(ids[1]));
+    assert_eq!(body["pagination"]["total_pages"], 3);
+
+    verify_email(&pool, ids[0]);
+    for (suffix, skill) in [("a", "advanced"), ("b", "beginner")] {
+        let req = signed_in(test::TestRequest::post(), "/api/leagues", &tokens[0])
+            .set_json(json!({ "league_name": format!("{}-{}", tag, suffix), "skill_level": skill }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    }
+
+    let req = signed_in(test::TestRequest::get(), &format!("/api/leagues?search={}&sort=-league_name", tag), &tokens[0])
+        .to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["count"], 2);
+    assert_eq!(body["leagues"][0]["league_name"], format!("{}-b", tag));
+
+    let req = signed_in(test::TestRequest::get(), &format!("/api/leagues?search={}&skill_level=advanced", tag), &tokens[0])
+        .to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["pagination"]["total"], 1);
+}
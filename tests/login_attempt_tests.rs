@@ -0,0 +1,173 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use actix_web::{http::header, http::StatusCode, test, web, App};
+use chrono::Duration;
+use diesel::prelude::*;
+use diesel::r2d2::{self, ConnectionManager};
+use diesel::PgConnection;
+use dotenv::dotenv;
+use serde_json::json;
+use std::env;
+use tennis_scheduler::admin;
+use tennis_scheduler::api;
+use tennis_scheduler::api::login_attempts::{
+    account_delay, begin_attempt, ip_delay, ACCOUNT_LOCKOUT_FAILURES, FREE_FAILURES, IP_LOCKOUT_FAILURES,
+};
+use tennis_scheduler::db::schema::login_attempts;
+use tennis_scheduler::db::DbPool;
+use tennis_scheduler::errors::AppError;
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
+use uuid::Uuid;
+
+fn unique_email(prefix: &str) -> String {
+    format!("{}_{:x}@example.com", prefix, Uuid::new_v4().as_simple())
+}
+
+/// A client address no other run has used, so earlier failures do not count.
+fn unique_peer() -> SocketAddr {
+    let bytes = Uuid::new_v4().into_bytes();
+    SocketAddr::from(([10, bytes[0], bytes[1], bytes[2]], 40000))
+}
+
+fn setup_test_db() -> DbPool {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+    r2d2::Pool::builder()
+        .build(manager)
+        .expect("Failed to create pool")
+}
+
+fn login(email: &str, password: &str, peer: SocketAddr) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/login")
+        .peer_addr(peer)
+        .set_json(json!({ "email": email, "password": password }))
+}
+
+#[actix_web::test]
+async fn test_login_delays_grow_into_a_lockout() {
+    assert_eq!(account_delay(0), None);
+    assert_eq!(account_delay(2), None);
+    assert_eq!(account_delay(3), Some(Duration::seconds(1)));
+    assert_eq!(account_delay(5), Some(Duration::seconds(4)));
+    assert_eq!(account_delay(ACCOUNT_LOCKOUT_FAILURES), Some(Duration::minutes(15)));
+    assert_eq!(ip_delay(IP_LOCKOUT_FAILURES - 1), None);
+    assert_eq!(ip_delay(IP_LOCKOUT_FAILURES), Some(Duration::minutes(15)));
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_failed_logins_lock_the_account_until_unlocked() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let email = unique_email("lockout");
+    let register = test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({
+            "name": format!("Lockout {:x}", Uuid::new_v4().as_simple()),
+            "email": email,
+            "password": "password123"
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, register).await.status(), StatusCode::CREATED);
+
+    let peer = unique_peer();
+    for _ in 0..3 {
+        let resp = test::call_service(&app, login(&email, "wrong-password", peer).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    // Even the right password waits, whatever the case of the address
+    let resp = test::call_service(&app, login(&email.to_uppercase(), "password123", peer).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(resp.headers().contains_key(header::RETRY_AFTER));
+
+    let mut conn = pool.get().unwrap();
+    let outcomes = login_attempts::table
+        .filter(login_attempts::email.eq(&email))
+        .order(login_attempts::attempt_id)
+        .select(login_attempts::outcome)
+        .load::<String>(&mut conn)
+        .unwrap();
+    assert_eq!(outcomes, ["bad_password", "bad_password", "bad_password", "locked"]);
+
+    assert_eq!(admin::unlock_login(&mut conn, Some(&email), None).unwrap(), 3);
+    assert!(admin::unlock_login(&mut conn, None, None).is_err());
+    // The address is found however it is typed
+    let resp = test::call_service(&app, login(&format!("  {} ", email.to_uppercase()), "password123", peer).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Addresses too long to be recorded are refused up front
+    let too_long = format!("{}@example.com", "a".repeat(300));
+    let resp = test::call_service(&app, login(&too_long, "password123", peer).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_parallel_attempts_cannot_pass_the_limit_together() {
+    let pool = setup_test_db();
+    let email = unique_email("parallel");
+    let peer = unique_peer().ip().to_string();
+
+    // Guesses that arrive together are checked one after another, and each
+    // counts as a failure until its outcome is known
+    let guesses: Vec<_> = (0..10)
+        .map(|_| {
+            let (pool, email, peer) = (pool.clone(), email.clone(), peer.clone());
+            std::thread::spawn(move || {
+                let mut conn = pool.get().unwrap();
+                begin_attempt(&mut conn, &email, Some(&peer))
+            })
+        })
+        .collect();
+    let results: Vec<Result<i32, AppError>> = guesses.into_iter().map(|guess| guess.join().unwrap()).collect();
+
+    assert_eq!(results.iter().filter(|result| result.is_ok()).count(), FREE_FAILURES);
+    assert!(results
+        .iter()
+        .filter_map(|result| result.as_ref().err())
+        .all(|error| matches!(error, AppError::TooManyRequests(..))));
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_failed_logins_lock_the_client_address() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    // One guess each at many accounts from the same address
+    let peer = unique_peer();
+    for _ in 0..IP_LOCKOUT_FAILURES {
+        let resp = test::call_service(&app, login(&unique_email("spray"), "password123", peer).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+    let resp = test::call_service(&app, login(&unique_email("spray"), "password123", peer).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // Other addresses are unaffected
+    let resp = test::call_service(&app, login(&unique_email("spray"), "password123", unique_peer()).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let mut conn = pool.get().unwrap();
+    let ip = peer.ip().to_string();
+    assert_eq!(admin::unlock_login(&mut conn, None, Some(&ip)).unwrap(), IP_LOCKOUT_FAILURES);
+    let resp = test::call_service(&app, login(&unique_email("spray"), "password123", peer).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
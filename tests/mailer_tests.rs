@@ -0,0 +1,115 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use tennis_scheduler::errors::AppError;
+use tennis_scheduler::mailer::{Email, Mailer, SmtpMailer, SmtpSecurity, SmtpSettings};
+
+/// Answers one SMTP conversation on a local port and returns what the client
+/// sent. `reject` makes the server refuse the recipient.
+fn fake_smtp_server(reject: bool) -> (u16, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut received = Vec::new();
+        let mut in_data = false;
+
+        writer.write_all(b"220 test ESMTP\r\n").unwrap();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 {
+                break;
+            }
+            let line = line.trim_end_matches("\r\n").to_string();
+            received.push(line.clone());
+
+            let reply: &[u8] = if in_data {
+                if line != "." {
+                    continue;
+                }
+                in_data = false;
+                b"250 queued\r\n"
+            } else if line.starts_with("EHLO") {
+                b"250-test\r\n250 AUTH PLAIN\r\n"
+            } else if line.starts_with("AUTH") {
+                b"235 ok\r\n"
+            } else if line.starts_with("RCPT") && reject {
+                b"550 no such user\r\n"
+            } else if line == "DATA" {
+                in_data = true;
+                b"354 go ahead\r\n"
+            } else if line == "QUIT" {
+                writer.write_all(b"221 bye\r\n").unwrap();
+                break;
+            } else {
+                b"250 ok\r\n"
+            };
+            writer.write_all(reply).unwrap();
+        }
+        received
+    });
+    (port, server)
+}
+
+fn mailer(port: u16) -> SmtpMailer {
+    SmtpMailer::new(SmtpSettings {
+        host: "127.0.0.1".into(),
+        port,
+        security: SmtpSecurity::None,
+        username: Some("scheduler".into()),
+        password: Some("secret".into()),
+        from: "Tennis Scheduler <noreply@example.com>".into(),
+    })
+}
+
+#[test]
+fn test_smtp_mailer_delivers_message() {
+    let (port, server) = fake_smtp_server(false);
+
+    mailer(port)
+        .send(Email {
+            to: "ana@example.com".into(),
+            subject: "Your code".into(),
+            body: "Use this code: 1234\n.hidden line".into(),
+        })
+        .unwrap();
+
+    let received = server.join().unwrap();
+    assert_eq!(received[0], "EHLO localhost");
+    // "\0scheduler\0secret" in base64
+    assert_eq!(received[1], "AUTH PLAIN AHNjaGVkdWxlcgBzZWNyZXQ=");
+    assert_eq!(received[2], "MAIL FROM:<noreply@example.com>");
+    assert_eq!(received[3], "RCPT TO:<ana@example.com>");
+    assert!(received.contains(&"Subject: Your code".to_string()));
+    assert!(received.contains(&"Use this code: 1234".to_string()));
+    assert!(received.contains(&"..hidden line".to_string()));
+    assert_eq!(received.last().unwrap(), "QUIT");
+}
+
+#[test]
+fn test_smtp_mailer_reports_refused_recipients() {
+    let (port, server) = fake_smtp_server(true);
+
+    let result = mailer(port).send(Email {
+        to: "nobody@example.com".into(),
+        subject: "Your code".into(),
+        body: "1234".into(),
+    });
+    assert!(matches!(result, Err(AppError::InternalError)));
+    // Nothing is sent after the refusal
+    assert_eq!(server.join().unwrap().last().unwrap(), "RCPT TO:<nobody@example.com>");
+}
+
+#[test]
+fn test_smtp_mailer_refuses_header_injection() {
+    let result = mailer(1).send(Email {
+        to: "ana@example.com\r\nBcc: eve@example.com".into(),
+        subject: "Your code".into(),
+        body: "1234".into(),
+    });
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+}
@@ -2,11 +2,13 @@ use actix_web::{test, web, App, http::StatusCode};
 use diesel::{r2d2::{self, ConnectionManager}, PgConnection};
 use dotenv::dotenv;
 use std::env;
+use std::sync::Arc;
 use uuid::Uuid;
 use tennis_scheduler::api;
 use tennis_scheduler::db::DbPool;
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
 use serde_json::{json, Value};
-use diesel::prelude::*;
 
 fn unique_name(prefix: &str) -> String {
     format!("{} {}", prefix, Uuid::new_v4())
@@ -25,6 +27,32 @@ fn setup_test_db() -> DbPool {
         .expect("Failed to create pool")
 }
 
+/// Registration leaves the email address unverified; do what following the
+/// mailed link would.
+fn verify_email(pool: &DbPool, player: i64) {
+    use diesel::prelude::*;
+    use tennis_scheduler::db::schema::players;
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    diesel::update(players::table.find(player as i32))
+        .set(players::email_verified_at.eq(chrono::Utc::now()))
+        .execute(&mut conn)
+        .expect("Failed to verify email");
+}
+
+/// Opens a session for the player, as signing in would.
+fn sign_in(pool: &DbPool, player: i64) -> String {
+    use tennis_scheduler::api::sessions::{start_session, ClientInfo};
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    let (token, _) = start_session(&mut conn, player as i32, &ClientInfo::default()).expect("Failed to start session");
+    token
+}
+
+fn signed_in(method: test::TestRequest, uri: &str, token: &str) -> test::TestRequest {
+    method.uri(uri).insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
 #[actix_web::test]
 #[ignore]
 async fn test_create_match() {
@@ -34,12 +62,14 @@ async fn test_create_match() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
     
     // Register a test user first
     let name = unique_name("test_user");
-    let email = unique_email("test@example.com");
+    let email = unique_email("test");
     
     let register_req = test::TestRequest::post()
         .uri("/api/register")
@@ -74,9 +104,9 @@ async fn test_create_match() {
         .uri("/api/matches")
         .set_json(json!({
             "match_type": "Singles",
-            "player1_id": "1",
-            "player2_id": "2",
-            "league_id": "1",
+            "player1_id": 1,
+            "player2_id": 2,
+            "league_id": 1,
             "datetime": "2023-05-15T14:00:00",
             "location": "Tennis Court 1",
             "status": "Pending",
@@ -99,12 +129,14 @@ async fn test_accept_match() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
     
     // Register a test user first
     let name = unique_name("test_user");
-    let email = unique_email("test@example.com");
+    let email = unique_email("test");
     
     let register_req = test::TestRequest::post()
         .uri("/api/register")
@@ -138,7 +170,7 @@ async fn test_accept_match() {
     let join_league_req = test::TestRequest::post()
         .uri("/api/leagues/1/join")
         .set_json(json!({
-            "player_id": "1"
+            "player_id": 1
         }))
         .to_request();
     
@@ -150,9 +182,9 @@ async fn test_accept_match() {
         .uri("/api/matches")
         .set_json(json!({
             "match_type": "Singles",
-            "player1_id": "1",
-            "player2_id": "2",
-            "league_id": "1",
+            "player1_id": 1,
+            "player2_id": 2,
+            "league_id": 1,
             "datetime": "2023-05-15T14:00:00",
             "location": "Tennis Court 1",
             "status": "Pending",
@@ -167,7 +199,7 @@ async fn test_accept_match() {
     let accept_match_req = test::TestRequest::post()
         .uri("/api/matches/1/accept")
         .set_json(json!({
-            "player_id": "1",
+            "player_id": 1,
             "comments": "Looking forward to the match!"
         }))
         .to_request();
@@ -188,6 +220,8 @@ async fn test_accept_doubles_match() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
     
@@ -196,7 +230,7 @@ async fn test_accept_doubles_match() {
     let player1_email = unique_email("player1");
     let player1 = test::TestRequest::post()
         .uri("/api/players")
-        .set_json(&json!({
+        .set_json(json!({
             "name": player1_name,
             "email": player1_email,
             "phone": "1234567890"
@@ -211,7 +245,7 @@ async fn test_accept_doubles_match() {
     let player2_email = unique_email("player2");
     let player2 = test::TestRequest::post()
         .uri("/api/players")
-        .set_json(&json!({
+        .set_json(json!({
             "name": player2_name,
             "email": player2_email,
             "phone": "1234567891"
@@ -226,7 +260,7 @@ async fn test_accept_doubles_match() {
     let player3_email = unique_email("player3");
     let player3 = test::TestRequest::post()
         .uri("/api/players")
-        .set_json(&json!({
+        .set_json(json!({
             "name": player3_name,
             "email": player3_email,
             "phone": "1234567892"
@@ -241,7 +275,7 @@ async fn test_accept_doubles_match() {
     let player4_email = unique_email("player4");
     let player4 = test::TestRequest::post()
         .uri("/api/players")
-        .set_json(&json!({
+        .set_json(json!({
             "name": player4_name,
             "email": player4_email,
             "phone": "1234567893"
@@ -256,7 +290,7 @@ async fn test_accept_doubles_match() {
     let league_name = unique_name("League");
     let league = test::TestRequest::post()
         .uri("/api/leagues")
-        .set_json(&json!({
+        .set_json(json!({
             "name": league_name,
             "description": "Test league for doubles match",
             "location": "Test location",
@@ -273,7 +307,7 @@ async fn test_accept_doubles_match() {
     // All players join the league
     let join1 = test::TestRequest::post()
         .uri(&format!("/api/players/leagues/{}/join", league_id))
-        .set_json(&json!({
+        .set_json(json!({
             "player_id": player1_id
         }))
         .send_request(&app)
@@ -282,7 +316,7 @@ async fn test_accept_doubles_match() {
     
     let join2 = test::TestRequest::post()
         .uri(&format!("/api/players/leagues/{}/join", league_id))
-        .set_json(&json!({
+        .set_json(json!({
             "player_id": player2_id
         }))
         .send_request(&app)
@@ -291,7 +325,7 @@ async fn test_accept_doubles_match() {
     
     let join3 = test::TestRequest::post()
         .uri(&format!("/api/players/leagues/{}/join", league_id))
-        .set_json(&json!({
+        .set_json(json!({
             "player_id": player3_id
         }))
         .send_request(&app)
@@ -300,7 +334,7 @@ async fn test_accept_doubles_match() {
     
     let join4 = test::TestRequest::post()
         .uri(&format!("/api/players/leagues/{}/join", league_id))
-        .set_json(&json!({
+        .set_json(json!({
             "player_id": player4_id
         }))
         .send_request(&app)
@@ -310,7 +344,7 @@ async fn test_accept_doubles_match() {
     // Create a doubles match
     let match_response = test::TestRequest::post()
         .uri("/api/matches")
-        .set_json(&json!({
+        .set_json(json!({
             "league_id": league_id,
             "match_type": "doubles",
             "team1_player1_id": player1_id,
@@ -332,7 +366,7 @@ async fn test_accept_doubles_match() {
     // Accept the match
     let accept_response = test::TestRequest::post()
         .uri(&format!("/api/matches/{}/accept", match_id))
-        .set_json(&json!({
+        .set_json(json!({
             "player_id": player1_id,
             "comments": "Looking forward to our doubles match!"
         }))
@@ -372,6 +406,8 @@ async fn test_reject_match() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
     
@@ -380,7 +416,7 @@ async fn test_reject_match() {
     let player_email = unique_email("player");
     let player = test::TestRequest::post()
         .uri("/api/players")
-        .set_json(&json!({
+        .set_json(json!({
             "name": player_name,
             "email": player_email,
             "phone": "1234567890"
@@ -395,7 +431,7 @@ async fn test_reject_match() {
     let league_name = unique_name("League");
     let league = test::TestRequest::post()
         .uri("/api/leagues")
-        .set_json(&json!({
+        .set_json(json!({
             "name": league_name,
             "description": "Test league for match rejection",
             "location": "Test location",
@@ -412,11 +448,11 @@ async fn test_reject_match() {
     // Create a match
     let match_response = test::TestRequest::post()
         .uri("/api/matches")
-        .set_json(&json!({
+        .set_json(json!({
             "league_id": league_id,
             "match_type": "singles",
             "player1_id": player_id,
-            "player2_id": "2",
+            "player2_id": 2,
             "datetime": "2023-05-15T14:00:00",
             "location": "Tennis Court 1",
             "status": "Pending",
@@ -432,7 +468,7 @@ async fn test_reject_match() {
     // Reject the match
     let reject_response = test::TestRequest::post()
         .uri(&format!("/api/matches/{}/reject", match_id))
-        .set_json(&json!({
+        .set_json(json!({
             "player_id": player_id,
             "reason": "Schedule conflict"
         }))
@@ -463,489 +499,540 @@ async fn test_reject_match() {
     assert!(updated_match.get("notes").unwrap().as_str().unwrap().contains("Schedule conflict"));
 }
 
-#[actix_rt::test]
+fn register(prefix: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({
+            "name": unique_name(prefix),
+            "email": unique_email("listing"),
+            "password": "password123"
+        }))
+}
+
+fn create_league(token: &str) -> test::TestRequest {
+    signed_in(test::TestRequest::post(), "/api/leagues", token)
+        .set_json(json!({
+            "league_name": unique_name("Listing")
+        }))
+}
+
+fn singles_match(league_id: i64, players: (i64, i64), datetime: &str, status: &str, notes: &str, token: &str) -> test::TestRequest {
+    signed_in(test::TestRequest::post(), "/api/matches", token)
+        .set_json(json!({
+            "match_type": "Singles",
+            "player1_id": players.0,
+            "player2_id": players.1,
+            "league_id": league_id,
+            "datetime": datetime,
+            "location": "Tennis Court 1",
+            "status": status,
+            "notes": notes
+        }))
+}
+
+#[actix_web::test]
+#[ignore]
 async fn test_get_player_matches() {
-    // Setup test app
+    let pool = setup_test_db();
     let app = test::init_service(
         App::new()
-            .app_data(web::Data::new(setup_test_db()))
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
 
-    // Register a test user
-    let user_name = unique_name("user1");
-    let user_email = unique_email("user1");
-    let user_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/users/register")
-            .set_json(&json!({
-                "name": user_name,
-                "email": user_email,
-                "password": "password123"
-            }))
-            .to_request()
-    ).await;
-    assert_eq!(user_response.status(), StatusCode::OK);
-    let user_body: Value = test::read_body_json(user_response).await;
-    let user_id = user_body["id"].as_str().unwrap();
-
-    // Create a second test user
-    let user2_name = unique_name("user2");
-    let user2_email = unique_email("user2");
-    let user2_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/users/register")
-            .set_json(&json!({
-                "name": user2_name,
-                "email": user2_email,
-                "password": "password123"
-            }))
-            .to_request()
-    ).await;
-    assert_eq!(user2_response.status(), StatusCode::OK);
-    let user2_body: Value = test::read_body_json(user2_response).await;
-    let user2_id = user2_body["id"].as_str().unwrap();
+    let mut ids = Vec::new();
+    for prefix in ["user1", "user2"] {
+        let register_resp = test::call_service(&app, register(prefix).to_request()).await;
+        assert_eq!(register_resp.status(), StatusCode::CREATED);
+        let registered: Value = test::read_body_json(register_resp).await;
+        let id = registered["player_id"].as_i64().unwrap();
+        verify_email(&pool, id);
+        ids.push(id);
+    }
+    let (user_id, user2_id) = (ids[0], ids[1]);
+    let user_token = sign_in(&pool, user_id);
+    let league_resp = test::call_service(&app, create_league(&user_token).to_request()).await;
+    assert_eq!(league_resp.status(), StatusCode::CREATED);
+    let league: Value = test::read_body_json(league_resp).await;
+    let league_id = league["league_id"].as_i64().unwrap();
+    for (datetime, status, notes) in [
+        ("2025-05-15T14:00:00Z", "Pending", "Test match"),
+    ] {
+        let match_req = singles_match(league_id, (user_id, user2_id), datetime, status, notes, &user_token).to_request();
+        assert_eq!(test::call_service(&app, match_req).await.status(), StatusCode::CREATED);
+    }
 
-    // Create a test league
-    let league_name = unique_name("league");
-    let league_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/leagues")
-            .set_json(&json!({
-                "name": league_name,
-                "description": "Test league description",
-                "admin_id": user_id
-            }))
-            .to_request()
-    ).await;
-    assert_eq!(league_response.status(), StatusCode::OK);
-    let league_body: Value = test::read_body_json(league_response).await;
-    let league_id = league_body["id"].as_str().unwrap();
-
-    // Create a singles match
-    let match_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/matches")
-            .set_json(&json!({
-                "match_type": "Singles",
-                "player1_id": user_id,
-                "player2_id": user2_id,
-                "league_id": league_id,
-                "datetime": "2023-05-15T14:00:00",
-                "location": "Tennis Court 1",
-                "notes": "Test match"
-            }))
-            .to_request()
-    ).await;
-    assert_eq!(match_response.status(), StatusCode::OK);
-    let match_body: Value = test::read_body_json(match_response).await;
-    let match_id = match_body["id"].as_str().unwrap();
-
-    // Get matches for player1
-    let player_matches_response = test::call_service(
-        &app,
-        test::TestRequest::get()
-            .uri(&format!("/api/matches/player/{}", user_id))
-            .to_request()
-    ).await;
-    assert_eq!(player_matches_response.status(), StatusCode::OK);
-    let player_matches_body: Value = test::read_body_json(player_matches_response).await;
-    
-    // Verify response structure and content
-    assert!(player_matches_body.is_object());
-    assert!(player_matches_body.get("matches").is_some());
-    assert!(player_matches_body.get("count").is_some());
-    
-    // Verify that the match is included in the response
-    let matches = player_matches_body["matches"].as_array().unwrap();
-    assert!(matches.len() > 0);
-    
-    // Find our created match in the results
-    let found_match = matches.iter().find(|m| m["id"].as_str() == Some(match_id));
-    assert!(found_match.is_some());
-    
-    // Verify match details
-    let found_match = found_match.unwrap();
-    assert_eq!(found_match["player1_id"].as_str(), Some(user_id));
-    assert_eq!(found_match["player2_id"].as_str(), Some(user2_id));
-    assert_eq!(found_match["league_id"].as_str(), Some(league_id));
-    assert_eq!(found_match["match_type"].as_str(), Some("Singles"));
-    
-    // Get matches for player2 and verify
-    let player2_matches_response = test::call_service(
-        &app,
-        test::TestRequest::get()
-            .uri(&format!("/api/matches/player/{}", user2_id))
-            .to_request()
-    ).await;
-    assert_eq!(player2_matches_response.status(), StatusCode::OK);
-    let player2_matches_body: Value = test::read_body_json(player2_matches_response).await;
-    
-    // Verify that player2 also has the match in their list
-    let matches2 = player2_matches_body["matches"].as_array().unwrap();
-    assert!(matches2.len() > 0);
-    let found_match2 = matches2.iter().find(|m| m["id"].as_str() == Some(match_id));
-    assert!(found_match2.is_some());
+    // Both players see the match in their own list
+    for player in [user_id, user2_id] {
+        let player_matches_req = signed_in(test::TestRequest::get(), &format!("/api/matches/player/{}", player), &sign_in(&pool, player))
+            .to_request();
+        let player_matches_resp = test::call_service(&app, player_matches_req).await;
+        assert_eq!(player_matches_resp.status(), StatusCode::OK);
+        let player_matches: Value = test::read_body_json(player_matches_resp).await;
+
+        assert_eq!(player_matches["count"], json!(1));
+        let found = &player_matches["matches"][0];
+        assert_eq!(found["player1_id"], json!(user_id));
+        assert_eq!(found["player2_id"], json!(user2_id));
+        assert_eq!(found["league_id"], json!(league_id));
+        assert_eq!(found["match_type"], json!("Singles"));
+        assert_eq!(found["notes"], json!("Test match"));
+    }
 }
 
-#[actix_rt::test]
+#[actix_web::test]
+#[ignore]
 async fn test_get_player_pending_matches() {
-    // Setup test app
+    let pool = setup_test_db();
     let app = test::init_service(
         App::new()
-            .app_data(web::Data::new(setup_test_db()))
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
 
-    // Register a test user
-    let user_name = unique_name("user1");
-    let user_email = unique_email("user1");
-    let user_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/users/register")
-            .set_json(&json!({
-                "name": user_name,
-                "email": user_email,
-                "password": "password123"
-            }))
-            .to_request()
-    ).await;
-    assert_eq!(user_response.status(), StatusCode::OK);
-    let user_body: Value = test::read_body_json(user_response).await;
-    let user_id = user_body["id"].as_str().unwrap();
-
-    // Create a second test user
-    let user2_name = unique_name("user2");
-    let user2_email = unique_email("user2");
-    let user2_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/users/register")
-            .set_json(&json!({
-                "name": user2_name,
-                "email": user2_email,
-                "password": "password123"
-            }))
-            .to_request()
-    ).await;
-    assert_eq!(user2_response.status(), StatusCode::OK);
-    let user2_body: Value = test::read_body_json(user2_response).await;
-    let user2_id = user2_body["id"].as_str().unwrap();
+    let mut ids = Vec::new();
+    for prefix in ["user1", "user2"] {
+        let register_resp = test::call_service(&app, register(prefix).to_request()).await;
+        assert_eq!(register_resp.status(), StatusCode::CREATED);
+        let registered: Value = test::read_body_json(register_resp).await;
+        let id = registered["player_id"].as_i64().unwrap();
+        verify_email(&pool, id);
+        ids.push(id);
+    }
+    let (user_id, user2_id) = (ids[0], ids[1]);
+    let user_token = sign_in(&pool, user_id);
+    let league_resp = test::call_service(&app, create_league(&user_token).to_request()).await;
+    assert_eq!(league_resp.status(), StatusCode::CREATED);
+    let league: Value = test::read_body_json(league_resp).await;
+    let league_id = league["league_id"].as_i64().unwrap();
+    for (datetime, status, notes) in [
+        ("2025-05-15T14:00:00Z", "Pending", "Test pending match"),
+        ("2025-05-16T14:00:00Z", "Scheduled", "Test scheduled match"),
+    ] {
+        let match_req = singles_match(league_id, (user_id, user2_id), datetime, status, notes, &user_token).to_request();
+        assert_eq!(test::call_service(&app, match_req).await.status(), StatusCode::CREATED);
+    }
 
-    // Create a test league
-    let league_name = unique_name("league");
-    let league_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/leagues")
-            .set_json(&json!({
-                "name": league_name,
-                "description": "Test league description",
-                "admin_id": user_id
-            }))
-            .to_request()
-    ).await;
-    assert_eq!(league_response.status(), StatusCode::OK);
-    let league_body: Value = test::read_body_json(league_response).await;
-    let league_id = league_body["id"].as_str().unwrap();
-
-    // Create a pending match
-    let match_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/matches")
-            .set_json(&json!({
-                "match_type": "Singles",
-                "player1_id": user_id,
-                "player2_id": user2_id,
-                "league_id": league_id,
-                "datetime": "2023-05-15T14:00:00",
-                "location": "Tennis Court 1",
-                "status": "Pending",
-                "notes": "Test pending match"
-            }))
-            .to_request()
-    ).await;
-    assert_eq!(match_response.status(), StatusCode::OK);
-    let match_body: Value = test::read_body_json(match_response).await;
-    let match_id = match_body["id"].as_str().unwrap();
-
-    // Create a scheduled match (not pending)
-    let scheduled_match_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/matches")
-            .set_json(&json!({
-                "match_type": "Singles",
-                "player1_id": user_id,
-                "player2_id": user2_id,
-                "league_id": league_id,
-                "datetime": "2023-05-16T14:00:00",
-                "location": "Tennis Court 2",
-                "status": "Scheduled",
-                "notes": "Test scheduled match"
-            }))
-            .to_request()
-    ).await;
-    assert_eq!(scheduled_match_response.status(), StatusCode::OK);
-
-    // Get pending matches for player1
-    let pending_matches_response = test::call_service(
-        &app,
-        test::TestRequest::get()
-            .uri(&format!("/api/matches/pending/{}", user_id))
-            .to_request()
-    ).await;
-    assert_eq!(pending_matches_response.status(), StatusCode::OK);
-    let pending_matches_body: Value = test::read_body_json(pending_matches_response).await;
-    
-    // Verify response structure and content
-    assert!(pending_matches_body.is_object());
-    assert!(pending_matches_body.get("matches").is_some());
-    assert!(pending_matches_body.get("count").is_some());
-    
-    // Verify that only the pending match is included in the response
-    let matches = pending_matches_body["matches"].as_array().unwrap();
-    assert_eq!(matches.len(), 1);
-    
-    // Find our created match in the results
-    let found_match = matches.iter().find(|m| m["id"].as_str() == Some(match_id));
-    assert!(found_match.is_some());
-    
-    // Verify match details
-    let found_match = found_match.unwrap();
-    assert_eq!(found_match["player1_id"].as_str(), Some(user_id));
-    assert_eq!(found_match["player2_id"].as_str(), Some(user2_id));
-    assert_eq!(found_match["league_id"].as_str(), Some(league_id));
-    assert_eq!(found_match["status"].as_str(), Some("Pending"));
-    
-    // Get pending matches for player2 and verify
-    let player2_pending_matches_response = test::call_service(
-        &app,
-        test::TestRequest::get()
-            .uri(&format!("/api/matches/pending/{}", user2_id))
-            .to_request()
-    ).await;
-    assert_eq!(player2_pending_matches_response.status(), StatusCode::OK);
-    let player2_pending_matches_body: Value = test::read_body_json(player2_pending_matches_response).await;
-    
-    // Verify that player2 also has the pending match in their list
-    let matches2 = player2_pending_matches_body["matches"].as_array().unwrap();
-    assert_eq!(matches2.len(), 1);
-    let found_match2 = matches2.iter().find(|m| m["id"].as_str() == Some(match_id));
-    assert!(found_match2.is_some());
+    // Only the pending match is listed, for either player
+    for player in [user_id, user2_id] {
+        let pending_req = signed_in(test::TestRequest::get(), &format!("/api/matches/pending/{}", player), &sign_in(&pool, player))
+            .to_request();
+        let pending_resp = test::call_service(&app, pending_req).await;
+        assert_eq!(pending_resp.status(), StatusCode::OK);
+        let pending: Value = test::read_body_json(pending_resp).await;
+
+        assert_eq!(pending["count"], json!(1));
+        assert_eq!(pending["matches"][0]["status"], json!("Pending"));
+        assert_eq!(pending["matches"][0]["notes"], json!("Test pending match"));
+    }
 }
 
-#[actix_rt::test]
+#[actix_web::test]
+#[ignore]
 async fn test_get_league_matches() {
-    // Setup test app
+    let pool = setup_test_db();
     let app = test::init_service(
         App::new()
-            .app_data(web::Data::new(setup_test_db()))
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
             .configure(api::init_routes)
     ).await;
 
-    // Register a test user
-    let user_name = unique_name("user1");
-    let user_email = unique_email("user1");
-    let user_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/users/register")
-            .set_json(&json!({
-                "name": user_name,
-                "email": user_email,
-                "password": "password123"
-            }))
-            .to_request()
+    let mut ids = Vec::new();
+    for prefix in ["user1", "user2"] {
+        let register_resp = test::call_service(&app, register(prefix).to_request()).await;
+        assert_eq!(register_resp.status(), StatusCode::CREATED);
+        let registered: Value = test::read_body_json(register_resp).await;
+        let id = registered["player_id"].as_i64().unwrap();
+        verify_email(&pool, id);
+        ids.push(id);
+    }
+    let (user_id, user2_id) = (ids[0], ids[1]);
+    let user_token = sign_in(&pool, user_id);
+    let league_resp = test::call_service(&app, create_league(&user_token).to_request()).await;
+    assert_eq!(league_resp.status(), StatusCode::CREATED);
+    let league: Value = test::read_body_json(league_resp).await;
+    let league_id = league["league_id"].as_i64().unwrap();
+    for (datetime, status, notes) in [
+        ("2025-05-15T14:00:00Z", "Pending", "Test pending match"),
+        ("2025-05-16T14:00:00Z", "Scheduled", "Test scheduled match"),
+        ("2025-05-14T14:00:00Z", "Completed", "Test completed match"),
+    ] {
+        let match_req = singles_match(league_id, (user_id, user2_id), datetime, status, notes, &user_token).to_request();
+        assert_eq!(test::call_service(&app, match_req).await.status(), StatusCode::CREATED);
+    }
+
+    let cases: [(Value, Vec<&str>); 4] = [
+        (json!({}), vec!["Completed", "Pending", "Scheduled"]),
+        (json!({ "status": ["Pending"] }), vec!["Pending"]),
+        (json!({ "status": ["Scheduled"] }), vec!["Scheduled"]),
+        (json!({ "status": ["Pending", "Scheduled"] }), vec!["Pending", "Scheduled"]),
+    ];
+    for (body, expected) in cases {
+        let league_matches_req = signed_in(test::TestRequest::post(), &format!("/api/matches/league/{}", league_id), &user_token)
+            .set_json(body)
+            .to_request();
+        let league_matches_resp = test::call_service(&app, league_matches_req).await;
+        assert_eq!(league_matches_resp.status(), StatusCode::OK);
+        let league_matches: Value = test::read_body_json(league_matches_resp).await;
+
+        // Listed in date order
+        let statuses: Vec<&str> = league_matches["matches"].as_array().unwrap().iter()
+            .map(|m| m["status"].as_str().unwrap())
+            .collect();
+        assert_eq!(statuses, expected);
+        assert_eq!(league_matches["count"], json!(expected.len()));
+    }
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_matches_filtered_and_sorted_by_datetime() {
+    let pool = setup_test_db();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
     ).await;
-    assert_eq!(user_response.status(), StatusCode::OK);
-    let user_body: Value = test::read_body_json(user_response).await;
-    let user_id = user_body["id"].as_str().unwrap();
-
-    // Create a second test user
-    let user2_name = unique_name("user2");
-    let user2_email = unique_email("user2");
-    let user2_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/users/register")
-            .set_json(&json!({
-                "name": user2_name,
-                "email": user2_email,
+
+    let mut ids = Vec::new();
+    for prefix in ["Range A", "Range B"] {
+        let register_req = test::TestRequest::post()
+            .uri("/api/register")
+            .set_json(json!({
+                "name": unique_name(prefix),
+                "email": unique_email("range"),
                 "password": "password123"
             }))
-            .to_request()
-    ).await;
-    assert_eq!(user2_response.status(), StatusCode::OK);
-    let user2_body: Value = test::read_body_json(user2_response).await;
-    let user2_id = user2_body["id"].as_str().unwrap();
+            .to_request();
+        let register_resp = test::call_service(&app, register_req).await;
+        assert_eq!(register_resp.status(), StatusCode::CREATED);
+        let registered: Value = test::read_body_json(register_resp).await;
+        let id = registered["player_id"].as_i64().unwrap();
+        verify_email(&pool, id);
+        ids.push(id);
+    }
+    let tokens: Vec<String> = ids.iter().map(|id| sign_in(&pool, *id)).collect();
 
-    // Create a test league
-    let league_name = unique_name("league");
-    let league_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/leagues")
-            .set_json(&json!({
-                "name": league_name,
-                "description": "Test league description",
-                "admin_id": user_id
-            }))
-            .to_request()
-    ).await;
-    assert_eq!(league_response.status(), StatusCode::OK);
-    let league_body: Value = test::read_body_json(league_response).await;
-    let league_id = league_body["id"].as_str().unwrap();
-
-    // Create a pending match
-    let pending_match_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/matches")
-            .set_json(&json!({
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &tokens[0])
+        .set_json(json!({
+            "league_name": unique_name("Range League")
+        }))
+        .to_request();
+    let league_resp = test::call_service(&app, league_req).await;
+    assert_eq!(league_resp.status(), StatusCode::CREATED);
+    let league: Value = test::read_body_json(league_resp).await;
+    let league_id = league["league_id"].as_i64().unwrap();
+
+    // Created out of order, with and without an offset
+    for datetime in ["2025-06-10T18:00:00+02:00", "2025-05-01T09:00:00", "2025-07-01T09:00:00Z"] {
+        let match_req = signed_in(test::TestRequest::post(), "/api/matches", &tokens[0])
+            .set_json(json!({
                 "match_type": "Singles",
-                "player1_id": user_id,
-                "player2_id": user2_id,
+                "player1_id": ids[0],
+                "player2_id": ids[1],
                 "league_id": league_id,
-                "datetime": "2023-05-15T14:00:00",
-                "location": "Tennis Court 1",
-                "status": "Pending",
-                "notes": "Test pending match"
+                "datetime": datetime,
+                "location": "Court 1",
+                "status": "Scheduled"
             }))
-            .to_request()
+            .to_request();
+        let match_resp = test::call_service(&app, match_req).await;
+        assert_eq!(match_resp.status(), StatusCode::CREATED);
+    }
+
+    let bad_req = signed_in(test::TestRequest::post(), "/api/matches", &tokens[0])
+        .set_json(json!({
+            "match_type": "Singles",
+            "player1_id": ids[0],
+            "player2_id": ids[1],
+            "league_id": league_id,
+            "datetime": "next tuesday",
+            "location": "Court 1",
+            "status": "Scheduled"
+        }))
+        .to_request();
+    let bad_resp = test::call_service(&app, bad_req).await;
+    assert_eq!(bad_resp.status(), StatusCode::BAD_REQUEST);
+
+    let all_req = signed_in(test::TestRequest::get(), &format!("/api/matches?league_id={}", league_id), &tokens[0])
+        .to_request();
+    let all_resp = test::call_service(&app, all_req).await;
+    let all: Value = test::read_body_json(all_resp).await;
+    let times: Vec<&str> = all["matches"].as_array().unwrap().iter()
+        .map(|m| m["datetime"].as_str().unwrap())
+        .collect();
+    assert_eq!(times, vec!["2025-05-01T09:00:00Z", "2025-06-10T16:00:00Z", "2025-07-01T09:00:00Z"]);
+
+    let range_req = signed_in(test::TestRequest::get(), &format!("/api/matches/player/{}?from=2025-06-01T00:00:00Z&to=2025-07-01T09:00:00Z", ids[1]), &tokens[1])
+        .to_request();
+    let range_resp = test::call_service(&app, range_req).await;
+    let range: Value = test::read_body_json(range_resp).await;
+    assert_eq!(range["count"], json!(1));
+    assert_eq!(range["matches"][0]["datetime"], json!("2025-06-10T16:00:00Z"));
+
+    let league_req = signed_in(test::TestRequest::post(), &format!("/api/matches/league/{}?from=2025-06-01T00:00:00", league_id), &tokens[0])
+        .to_request();
+    let league_resp = test::call_service(&app, league_req).await;
+    let league_matches: Value = test::read_body_json(league_resp).await;
+    assert_eq!(league_matches["count"], json!(2));
+
+    let inverted_req = signed_in(test::TestRequest::get(), &format!("/api/matches?league_id={}&from=2025-07-01T00:00:00Z&to=2025-06-01T00:00:00Z", league_id), &tokens[0])
+        .to_request();
+    let inverted_resp = test::call_service(&app, inverted_req).await;
+    assert_eq!(inverted_resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_match_times_shown_in_viewer_time_zone() {
+    let pool = setup_test_db();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
     ).await;
-    assert_eq!(pending_match_response.status(), StatusCode::OK);
-    let pending_match_body: Value = test::read_body_json(pending_match_response).await;
-    let pending_match_id = pending_match_body["id"].as_str().unwrap();
-
-    // Create a scheduled match
-    let scheduled_match_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/matches")
-            .set_json(&json!({
-                "match_type": "Singles",
-                "player1_id": user_id,
-                "player2_id": user2_id,
-                "league_id": league_id,
-                "datetime": "2023-05-16T14:00:00",
-                "location": "Tennis Court 2",
-                "status": "Scheduled",
-                "notes": "Test scheduled match"
+
+    // One player in Berlin, one in New York, playing in a Berlin league
+    let mut ids = Vec::new();
+    for (prefix, zone) in [("Zone Berlin", "Europe/Berlin"), ("Zone New York", "America/New_York")] {
+        let register_req = test::TestRequest::post()
+            .uri("/api/register")
+            .set_json(json!({
+                "name": unique_name(prefix),
+                "email": unique_email("zone"),
+                "password": "password123",
+                "time_zone": zone
             }))
-            .to_request()
-    ).await;
-    assert_eq!(scheduled_match_response.status(), StatusCode::OK);
-    let scheduled_match_body: Value = test::read_body_json(scheduled_match_response).await;
-    let scheduled_match_id = scheduled_match_body["id"].as_str().unwrap();
-
-    // Create a completed match
-    let completed_match_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri("/api/matches")
-            .set_json(&json!({
+            .to_request();
+        let register_resp = test::call_service(&app, register_req).await;
+        assert_eq!(register_resp.status(), StatusCode::CREATED);
+        let registered: Value = test::read_body_json(register_resp).await;
+        let id = registered["player_id"].as_i64().unwrap();
+        verify_email(&pool, id);
+        ids.push(id);
+    }
+    let tokens: Vec<String> = ids.iter().map(|id| sign_in(&pool, *id)).collect();
+
+    let bad_league_req = signed_in(test::TestRequest::post(), "/api/leagues", &tokens[0])
+        .set_json(json!({
+            "league_name": unique_name("Zone League"),
+            "time_zone": "Mars/Olympus"
+        }))
+        .to_request();
+    let bad_league_resp = test::call_service(&app, bad_league_req).await;
+    assert_eq!(bad_league_resp.status(), StatusCode::BAD_REQUEST);
+
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &tokens[0])
+        .set_json(json!({
+            "league_name": unique_name("Zone League"),
+            "time_zone": "Europe/Berlin"
+        }))
+        .to_request();
+    let league_resp = test::call_service(&app, league_req).await;
+    assert_eq!(league_resp.status(), StatusCode::CREATED);
+    let league: Value = test::read_body_json(league_resp).await;
+    let league_id = league["league_id"].as_i64().unwrap();
+
+    let match_req = signed_in(test::TestRequest::post(), "/api/matches", &tokens[0])
+        .set_json(json!({
+            "match_type": "Singles",
+            "player1_id": ids[0],
+            "player2_id": ids[1],
+            "league_id": league_id,
+            "datetime": "2025-07-01T18:00:00+02:00",
+            "location": "Court 1",
+            "status": "Scheduled"
+        }))
+        .to_request();
+    let match_resp = test::call_service(&app, match_req).await;
+    assert_eq!(match_resp.status(), StatusCode::CREATED);
+
+    // Without a viewer the league's zone is used
+    let league_matches_req = signed_in(test::TestRequest::get(), &format!("/api/matches?league_id={}", league_id), &tokens[0])
+        .to_request();
+    let league_matches_resp = test::call_service(&app, league_matches_req).await;
+    let league_matches: Value = test::read_body_json(league_matches_resp).await;
+    assert_eq!(league_matches["matches"][0]["datetime"], json!("2025-07-01T16:00:00Z"));
+    assert_eq!(league_matches["matches"][0]["local_datetime"], json!("2025-07-01T18:00:00+02:00"));
+
+    // A player's own listing uses their zone
+    let player_matches_req = signed_in(test::TestRequest::get(), &format!("/api/matches/player/{}", ids[1]), &tokens[1])
+        .to_request();
+    let player_matches_resp = test::call_service(&app, player_matches_req).await;
+    let player_matches: Value = test::read_body_json(player_matches_resp).await;
+    assert_eq!(player_matches["matches"][0]["local_datetime"], json!("2025-07-01T12:00:00-04:00"));
+    assert_eq!(player_matches["matches"][0]["time_zone"], json!("America/New_York"));
+
+    // After moving, the calendar export follows the new zone
+    let zone_req = signed_in(test::TestRequest::patch(), &format!("/api/players/{}/time-zone", ids[1]), &tokens[1])
+        .set_json(json!({ "time_zone": "Asia/Tokyo" }))
+        .to_request();
+    let zone_resp = test::call_service(&app, zone_req).await;
+    assert_eq!(zone_resp.status(), StatusCode::OK);
+
+    let calendar_req = signed_in(test::TestRequest::get(), &format!("/api/players/{}/calendar", ids[1]), &tokens[1])
+        .to_request();
+    let calendar_resp = test::call_service(&app, calendar_req).await;
+    assert_eq!(calendar_resp.status(), StatusCode::OK);
+    assert_eq!(calendar_resp.headers().get("content-type").unwrap(), "text/calendar; charset=utf-8");
+    let calendar = String::from_utf8(test::read_body(calendar_resp).await.to_vec()).unwrap();
+    assert!(calendar.contains("DTSTART;TZID=Asia/Tokyo:20250702T010000\r\n"));
+    assert!(calendar.contains("STATUS:CONFIRMED\r\n"));
+
+    // Nobody else can move a player to another zone
+    let foreign_zone_req = signed_in(test::TestRequest::patch(), &format!("/api/players/{}/time-zone", ids[1]), &tokens[0])
+        .set_json(json!({ "time_zone": "Europe/Berlin" }))
+        .to_request();
+    let foreign_zone_resp = test::call_service(&app, foreign_zone_req).await;
+    assert_eq!(foreign_zone_resp.status(), StatusCode::FORBIDDEN);
+
+    // Times without an offset are on the league's clock, and must name a single instant
+    for (local, expected) in [
+        ("2025-07-02T18:00:00", StatusCode::CREATED),
+        ("2025-03-30T02:30:00", StatusCode::BAD_REQUEST),
+        ("2025-10-26T02:30:00", StatusCode::BAD_REQUEST),
+    ] {
+        let local_req = signed_in(test::TestRequest::post(), "/api/matches", &tokens[0])
+            .set_json(json!({
                 "match_type": "Singles",
-                "player1_id": user_id,
-                "player2_id": user2_id,
+                "player1_id": ids[0],
+                "player2_id": ids[1],
                 "league_id": league_id,
-                "datetime": "2023-05-14T14:00:00",
-                "location": "Tennis Court 3",
-                "status": "Completed",
-                "notes": "Test completed match"
-            }))
-            .to_request()
-    ).await;
-    assert_eq!(completed_match_response.status(), StatusCode::OK);
-    let completed_match_body: Value = test::read_body_json(completed_match_response).await;
-    let completed_match_id = completed_match_body["id"].as_str().unwrap();
-
-    // Get all matches for the league
-    let league_matches_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri(&format!("/api/matches/league/{}", league_id))
-            .set_json(&json!({}))
-            .to_request()
-    ).await;
-    assert_eq!(league_matches_response.status(), StatusCode::OK);
-    let league_matches_body: Value = test::read_body_json(league_matches_response).await;
-    
-    // Verify response structure and content
-    assert!(league_matches_body.is_object());
-    assert!(league_matches_body.get("matches").is_some());
-    assert!(league_matches_body.get("count").is_some());
-    
-    // Verify that all three matches are included in the response
-    let matches = league_matches_body["matches"].as_array().unwrap();
-    assert_eq!(matches.len(), 3);
-    
-    // Get only pending matches for the league
-    let pending_matches_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri(&format!("/api/matches/league/{}", league_id))
-            .set_json(&json!({
-                "status": ["Pending"]
-            }))
-            .to_request()
-    ).await;
-    assert_eq!(pending_matches_response.status(), StatusCode::OK);
-    let pending_matches_body: Value = test::read_body_json(pending_matches_response).await;
-    
-    // Verify that only the pending match is included in the response
-    let pending_matches = pending_matches_body["matches"].as_array().unwrap();
-    assert_eq!(pending_matches.len(), 1);
-    assert_eq!(pending_matches[0]["id"].as_str(), Some(pending_match_id));
-    assert_eq!(pending_matches[0]["status"].as_str(), Some("Pending"));
-    
-    // Get only scheduled matches for the league
-    let scheduled_matches_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri(&format!("/api/matches/league/{}", league_id))
-            .set_json(&json!({
-                "status": ["Scheduled"]
+                "datetime": local,
+                "location": "Court 2",
+                "status": "Scheduled"
             }))
-            .to_request()
+            .to_request();
+        let local_resp = test::call_service(&app, local_req).await;
+        assert_eq!(local_resp.status(), expected, "creating a match at {}", local);
+    }
+
+    let local_range_req = signed_in(
+        test::TestRequest::get(),
+        &format!("/api/matches?league_id={}&from=2025-07-02T18:00:00&to=2025-07-02T18:00:01", league_id),
+        &tokens[0],
+    )
+    .to_request();
+    let local_range_resp = test::call_service(&app, local_range_req).await;
+    assert_eq!(local_range_resp.status(), StatusCode::OK);
+    let local_range: Value = test::read_body_json(local_range_resp).await;
+    assert_eq!(local_range["count"], json!(1));
+    assert_eq!(local_range["matches"][0]["datetime"], json!("2025-07-02T16:00:00Z"));
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_only_match_players_can_manage_matches() {
+    let pool = setup_test_db();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
     ).await;
-    assert_eq!(scheduled_matches_response.status(), StatusCode::OK);
-    let scheduled_matches_body: Value = test::read_body_json(scheduled_matches_response).await;
-    
-    // Verify that only the scheduled match is included in the response
-    let scheduled_matches = scheduled_matches_body["matches"].as_array().unwrap();
-    assert_eq!(scheduled_matches.len(), 1);
-    assert_eq!(scheduled_matches[0]["id"].as_str(), Some(scheduled_match_id));
-    assert_eq!(scheduled_matches[0]["status"].as_str(), Some("Scheduled"));
-    
-    // Get both pending and scheduled matches for the league
-    let pending_scheduled_matches_response = test::call_service(
-        &app,
-        test::TestRequest::post()
-            .uri(&format!("/api/matches/league/{}", league_id))
-            .set_json(&json!({
-                "status": ["Pending", "Scheduled"]
+
+    // Two league members and an outsider
+    let mut ids = Vec::new();
+    for prefix in ["Owner Home", "Owner Away", "Owner Outsider"] {
+        let register_req = test::TestRequest::post()
+            .uri("/api/register")
+            .set_json(json!({
+                "name": unique_name(prefix),
+                "email": unique_email("owner"),
+                "password": "password123"
             }))
-            .to_request()
-    ).await;
-    assert_eq!(pending_scheduled_matches_response.status(), StatusCode::OK);
-    let pending_scheduled_matches_body: Value = test::read_body_json(pending_scheduled_matches_response).await;
-    
-    // Verify that both pending and scheduled matches are included in the response
-    let pending_scheduled_matches = pending_scheduled_matches_body["matches"].as_array().unwrap();
-    assert_eq!(pending_scheduled_matches.len(), 2);
-    
-    // Verify that the matches have the correct statuses
-    let statuses: Vec<&str> = pending_scheduled_matches.iter()
-        .map(|m| m["status"].as_str().unwrap())
-        .collect();
-    assert!(statuses.contains(&"Pending"));
-    assert!(statuses.contains(&"Scheduled"));
-    assert!(!statuses.contains(&"Completed"));
-} 
\ No newline at end of file
+            .to_request();
+        let register_resp = test::call_service(&app, register_req).await;
+        assert_eq!(register_resp.status(), StatusCode::CREATED);
+        let registered: Value = test::read_body_json(register_resp).await;
+        let id = registered["player_id"].as_i64().unwrap();
+        verify_email(&pool, id);
+        ids.push(id);
+    }
+    let tokens: Vec<String> = ids.iter().map(|id| sign_in(&pool, *id)).collect();
+
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &tokens[0])
+        .set_json(json!({ "league_name": unique_name("Owner League") }))
+        .to_request();
+    let league_resp = test::call_service(&app, league_req).await;
+    assert_eq!(league_resp.status(), StatusCode::CREATED);
+    let league: Value = test::read_body_json(league_resp).await;
+    let league_id = league["league_id"].as_i64().unwrap();
+
+    let join_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/join", league_id), &tokens[1])
+        .to_request();
+    let join_resp = test::call_service(&app, join_req).await;
+    assert_eq!(join_resp.status(), StatusCode::OK);
+
+    let match_body = json!({
+        "match_type": "Singles",
+        "player1_id": ids[0],
+        "player2_id": ids[1],
+        "league_id": league_id,
+        "datetime": "2025-07-01T18:00:00",
+        "location": "Court 1",
+        "status": "Pending"
+    });
+
+    // The outsider cannot propose a match between the others
+    let foreign_create_req = signed_in(test::TestRequest::post(), "/api/matches", &tokens[2])
+        .set_json(&match_body)
+        .to_request();
+    let foreign_create_resp = test::call_service(&app, foreign_create_req).await;
+    assert_eq!(foreign_create_resp.status(), StatusCode::FORBIDDEN);
+
+    let create_req = signed_in(test::TestRequest::post(), "/api/matches", &tokens[0])
+        .set_json(&match_body)
+        .to_request();
+    let create_resp = test::call_service(&app, create_req).await;
+    assert_eq!(create_resp.status(), StatusCode::CREATED);
+
+    let list_req = signed_in(test::TestRequest::get(), &format!("/api/matches?league_id={}", league_id), &tokens[0])
+        .to_request();
+    let list_resp = test::call_service(&app, list_req).await;
+    let listed: Value = test::read_body_json(list_resp).await;
+    let match_id = listed["matches"][0]["id"].as_i64().unwrap();
+
+    // Nor accept or reject it
+    for action in ["accept", "reject"] {
+        let foreign_req = signed_in(test::TestRequest::post(), &format!("/api/matches/{}/{}", match_id, action), &tokens[2])
+            .set_json(json!({}))
+            .to_request();
+        let foreign_resp = test::call_service(&app, foreign_req).await;
+        assert_eq!(foreign_resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    let accept_req = signed_in(test::TestRequest::post(), &format!("/api/matches/{}/accept", match_id), &tokens[1])
+        .set_json(json!({}))
+        .to_request();
+    let accept_resp = test::call_service(&app, accept_req).await;
+    assert_eq!(accept_resp.status(), StatusCode::OK);
+    let accepted_req = signed_in(test::TestRequest::get(), &format!("/api/matches?league_id={}", league_id), &tokens[0])
+        .to_request();
+    let accepted: Value = test::read_body_json(test::call_service(&app, accepted_req).await).await;
+    assert_eq!(accepted["matches"][0]["status"], json!("Scheduled"));
+    assert!(accepted["matches"][0]["notes"].as_str().unwrap().ends_with(&format!("Accepted by player {}", ids[1])));
+
+    // A teammate can read the calendar, the outsider cannot
+    let calendar_req = |token: &str| {
+        signed_in(test::TestRequest::get(), &format!("/api/players/{}/calendar", ids[1]), token).to_request()
+    };
+    let teammate_resp = test::call_service(&app, calendar_req(&tokens[0])).await;
+    assert_eq!(teammate_resp.status(), StatusCode::OK);
+    let outsider_resp = test::call_service(&app, calendar_req(&tokens[2])).await;
+    assert_eq!(outsider_resp.status(), StatusCode::FORBIDDEN);
+}
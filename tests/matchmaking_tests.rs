@@ -0,0 +1,68 @@
+use tennis_scheduler::matchmaking::{top_recommendations, OpponentFactors, Recommendation};
+
+fn recommendation(player_id: i32, factors: OpponentFactors) -> Recommendation {
+    Recommendation {
+        player_id,
+        name: format!("Player {}", player_id),
+        skill_level: None,
+        score: factors.score(),
+        factors,
+    }
+}
+
+#[test]
+fn test_ideal_opponent_scores_one() {
+    let ideal = OpponentFactors {
+        skill_difference: Some(0.0),
+        ranking_gap: None,
+        days_since_last_meeting: None,
+        shared_free_days: 7,
+        days_considered: 7,
+        open_matches: 0,
+    };
+    assert!((ideal.score() - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_each_factor_lowers_the_score() {
+    let base = OpponentFactors {
+        skill_difference: Some(0.5),
+        ranking_gap: None,
+        days_since_last_meeting: Some(120),
+        shared_free_days: 5,
+        days_considered: 7,
+        open_matches: 1,
+    };
+
+    let wider_gap = OpponentFactors { skill_difference: Some(1.5), ..base.clone() };
+    let met_recently = OpponentFactors { days_since_last_meeting: Some(3), ..base.clone() };
+    let less_overlap = OpponentFactors { shared_free_days: 1, ..base.clone() };
+    let busier = OpponentFactors { open_matches: 4, ..base.clone() };
+
+    for worse in [wider_gap, met_recently, less_overlap, busier] {
+        assert!(worse.score() < base.score(), "{:?}", worse);
+    }
+}
+
+#[test]
+fn test_ranking_gap_stands_in_for_unknown_ratings() {
+    let unrated = OpponentFactors { days_considered: 7, ..OpponentFactors::default() };
+    let neighbours = OpponentFactors { ranking_gap: Some(1), ..unrated.clone() };
+    let far_apart = OpponentFactors { ranking_gap: Some(12), ..unrated.clone() };
+
+    assert!(neighbours.score() > unrated.score());
+    assert!(far_apart.score() < unrated.score());
+}
+
+#[test]
+fn test_top_recommendations_orders_and_truncates() {
+    let strong = OpponentFactors { skill_difference: Some(0.0), days_considered: 7, shared_free_days: 7, ..OpponentFactors::default() };
+    let weak = OpponentFactors { skill_difference: Some(2.0), days_considered: 7, ..OpponentFactors::default() };
+
+    let top = top_recommendations(
+        vec![recommendation(3, weak.clone()), recommendation(2, strong.clone()), recommendation(1, strong)],
+        2,
+    );
+    let ids: Vec<i32> = top.iter().map(|r| r.player_id).collect();
+    assert_eq!(ids, vec![1, 2]);
+}
@@ -0,0 +1,26 @@
+use diesel::{Connection, PgConnection};
+use dotenv::dotenv;
+use std::env;
+use tennis_scheduler::db;
+
+fn connect() -> PgConnection {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    PgConnection::establish(&database_url).expect("Failed to connect to the database")
+}
+
+#[test]
+#[ignore]
+fn test_embedded_migrations_bring_database_up_to_date() {
+    let mut conn = connect();
+
+    db::run_pending_migrations(&mut conn).expect("Failed to run migrations");
+
+    let status = db::migration_status(&mut conn).expect("Failed to read migration status");
+    assert!(!status.is_empty());
+    assert!(status.iter().all(|(_, applied)| *applied));
+    assert!(status[0].0.ends_with("create_base_schema"));
+
+    // Running them again is a no-op
+    assert!(db::run_pending_migrations(&mut conn).unwrap().is_empty());
+}
@@ -0,0 +1,35 @@
+use tennis_scheduler::api::pagination::{Page, PageMeta, PageQuery, MAX_PER_PAGE};
+
+fn paging(page: Option<i64>, per_page: Option<i64>, sort: Option<&str>) -> PageQuery {
+    PageQuery { page, per_page, sort: sort.map(String::from) }
+}
+
+#[test]
+fn test_page_defaults_and_limits() {
+    assert_eq!(paging(None, None, None).page().unwrap(), Page { page: 1, per_page: 50 });
+    assert_eq!(paging(Some(3), Some(20), None).page().unwrap().offset(), 40);
+
+    assert!(paging(Some(0), None, None).page().is_err());
+    assert!(paging(None, Some(0), None).page().is_err());
+    assert!(paging(None, Some(MAX_PER_PAGE + 1), None).page().is_err());
+}
+
+#[test]
+fn test_sort_accepts_only_allowed_fields() {
+    let fields = ["datetime", "id"];
+
+    let sort = paging(None, None, None).sort(&fields, "datetime").unwrap();
+    assert_eq!((sort.field.as_str(), sort.descending), ("datetime", false));
+
+    let sort = paging(None, None, Some("-id")).sort(&fields, "datetime").unwrap();
+    assert_eq!((sort.field.as_str(), sort.descending), ("id", true));
+
+    assert!(paging(None, None, Some("password")).sort(&fields, "datetime").is_err());
+}
+
+#[test]
+fn test_page_meta_counts_partial_pages() {
+    let page = Page { page: 2, per_page: 10 };
+    assert_eq!(PageMeta::new(page, 21), PageMeta { page: 2, per_page: 10, total: 21, total_pages: 3 });
+    assert_eq!(PageMeta::new(page, 0).total_pages, 0);
+}
@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use actix_web::{http::StatusCode, test, web, App};
+use diesel::prelude::*;
+use diesel::r2d2::{self, ConnectionManager};
+use diesel::PgConnection;
+use dotenv::dotenv;
+use serde_json::{json, Value};
+use std::env;
+use tennis_scheduler::api;
+use tennis_scheduler::db::schema::{password_resets, players};
+use tennis_scheduler::db::DbPool;
+use tennis_scheduler::errors::AppError;
+use tennis_scheduler::mailer::{Email, Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
+use uuid::Uuid;
+
+fn unique_email(prefix: &str) -> String {
+    format!("{}_{:x}@example.com", prefix, Uuid::new_v4().as_simple())
+}
+
+fn setup_test_db() -> DbPool {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+    r2d2::Pool::builder()
+        .build(manager)
+        .expect("Failed to create pool")
+}
+
+fn register(email: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({
+            "name": format!("Password {:x}", Uuid::new_v4().as_simple()),
+            "email": email,
+            "password": "password123"
+        }))
+}
+
+fn login(email: &str, password: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/login")
+        .set_json(json!({ "email": email, "password": password }))
+}
+
+/// A change with the wrong current password: 403 while the session is open, 401 once it is not.
+fn probe(token: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/password/change")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "current_password": "not it", "new_password": "whatever123" }))
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_change_password_signs_out_other_sessions() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(setup_test_db()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let email = unique_email("change");
+    assert_eq!(test::call_service(&app, register(&email).to_request()).await.status(), StatusCode::CREATED);
+    let first: Value = test::read_body_json(test::call_service(&app, login(&email, "password123").to_request()).await).await;
+    let second: Value = test::read_body_json(test::call_service(&app, login(&email, "password123").to_request()).await).await;
+    let token = first["token"].as_str().unwrap();
+    let other = second["token"].as_str().unwrap();
+    assert!(first["expires_at"].is_string());
+
+    let change = |token: &str, current: &str, new: &str| {
+        test::TestRequest::post()
+            .uri("/api/password/change")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "current_password": current, "new_password": new }))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, change("nonsense", "password123", "new-password")).await.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(test::call_service(&app, change(token, "wrong", "new-password")).await.status(), StatusCode::FORBIDDEN);
+    assert_eq!(test::call_service(&app, change(token, "password123", "short")).await.status(), StatusCode::BAD_REQUEST);
+
+    let resp = test::call_service(&app, change(token, "password123", "new-password")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["signed_out_sessions"], 1);
+
+    assert_eq!(test::call_service(&app, probe(token).to_request()).await.status(), StatusCode::FORBIDDEN);
+    assert_eq!(test::call_service(&app, probe(other).to_request()).await.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(test::call_service(&app, login(&email, "password123").to_request()).await.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(test::call_service(&app, login(&email, "new-password").to_request()).await.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_forgot_password_reset_flow() {
+    let pool = setup_test_db();
+    let mailer = Arc::new(MemoryMailer::default());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(mailer.clone() as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let email = unique_email("forgot");
+    assert_eq!(test::call_service(&app, register(&email).to_request()).await.status(), StatusCode::CREATED);
+    let session: Value = test::read_body_json(test::call_service(&app, login(&email, "password123").to_request()).await).await;
+    let session = session["token"].as_str().unwrap();
+
+    let forgot = |email: &str| {
+        test::TestRequest::post()
+            .uri("/api/password/forgot")
+            .set_json(json!({ "email": email }))
+            .to_request()
+    };
+    // Unknown addresses get the same answer but no mail
+    let stranger = unique_email("nobody");
+    assert_eq!(test::call_service(&app, forgot(&stranger)).await.status(), StatusCode::ACCEPTED);
+    assert!(mailer.last_to(&stranger).is_none());
+
+    // Asking again right away mails nothing
+    assert_eq!(test::call_service(&app, forgot(&email)).await.status(), StatusCode::ACCEPTED);
+    let stale = mailer.last_to(&email).unwrap().body;
+    let sent = mailer.sent().len();
+    assert_eq!(test::call_service(&app, forgot(&email)).await.status(), StatusCode::ACCEPTED);
+    assert_eq!(mailer.sent().len(), sent);
+
+    // Once the cooldown has passed there is a new code, whatever the case of
+    // the address, and only the latest one works
+    diesel::update(password_resets::table)
+        .filter(password_resets::player_id.eq_any(players::table.filter(players::email.eq(&email)).select(players::player_id)))
+        .set(password_resets::created_at.eq(chrono::Utc::now() - chrono::Duration::minutes(10)))
+        .execute(&mut pool.get().unwrap())
+        .unwrap();
+    assert_eq!(test::call_service(&app, forgot(&email.to_uppercase())).await.status(), StatusCode::ACCEPTED);
+    let fresh = mailer.last_to(&email).unwrap().body;
+    assert_ne!(fresh, stale);
+    let code = |body: &str| body.split_whitespace().find(|word| word.len() == 32).unwrap().to_string();
+
+    let reset = |token: String, password: &str| {
+        test::TestRequest::post()
+            .uri("/api/password/reset")
+            .set_json(json!({ "token": token, "new_password": password }))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, reset(code(&stale), "brand-new-password")).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(test::call_service(&app, reset(code(&fresh), "short")).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(test::call_service(&app, reset(code(&fresh), "brand-new-password")).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, reset(code(&fresh), "another-password")).await.status(), StatusCode::BAD_REQUEST);
+
+    assert_eq!(test::call_service(&app, probe(session).to_request()).await.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(test::call_service(&app, login(&email, "password123").to_request()).await.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(test::call_service(&app, login(&email, "brand-new-password").to_request()).await.status(), StatusCode::OK);
+}
+
+/// A mail server that is down.
+struct BrokenMailer;
+
+impl Mailer for BrokenMailer {
+    fn send(&self, _email: Email) -> Result<(), AppError> {
+        Err(AppError::InternalError)
+    }
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_mail_failures_do_not_reveal_accounts() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(setup_test_db()))
+            .app_data(web::Data::from(Arc::new(BrokenMailer) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    // The account is created even though the verification code cannot be sent
+    let email = unique_email("broken");
+    assert_eq!(test::call_service(&app, register(&email).to_request()).await.status(), StatusCode::CREATED);
+
+    for uri in ["/api/password/forgot", "/api/verify-email/resend"] {
+        for address in [email.clone(), unique_email("nobody")] {
+            let req = test::TestRequest::post().uri(uri).set_json(json!({ "email": address })).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::ACCEPTED, "{} {}", uri, address);
+        }
+    }
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_login_rehashes_cheap_hashes() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let email = unique_email("rehash");
+    assert_eq!(test::call_service(&app, register(&email).to_request()).await.status(), StatusCode::CREATED);
+    let mut conn = pool.get().unwrap();
+    diesel::update(players::table.filter(players::email.eq(&email)))
+        .set(players::password.eq(bcrypt::hash("password123", 4).unwrap()))
+        .execute(&mut conn)
+        .unwrap();
+
+    assert_eq!(test::call_service(&app, login(&email, "password123").to_request()).await.status(), StatusCode::OK);
+    let stored = players::table
+        .filter(players::email.eq(&email))
+        .select(players::password)
+        .first::<String>(&mut conn)
+        .unwrap();
+    assert!(!stored.starts_with("$2b$04$"));
+    assert!(bcrypt::verify("password123", &stored).unwrap());
+}
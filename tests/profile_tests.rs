@@ -0,0 +1,272 @@
+use std::sync::Arc;
+
+use actix_web::{http::StatusCode, test, web, App};
+use diesel::prelude::*;
+use diesel::{r2d2::{self, ConnectionManager}, PgConnection};
+use dotenv::dotenv;
+use serde_json::{json, Value};
+use std::env;
+use tennis_scheduler::api;
+use tennis_scheduler::db::{schema::email_changes, DbPool};
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
+use uuid::Uuid;
+
+fn unique_email(prefix: &str) -> String {
+    format!("{}_{:x}@example.com", prefix, Uuid::new_v4().as_simple())
+}
+
+fn setup_test_db() -> DbPool {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+    r2d2::Pool::builder()
+        .build(manager)
+        .expect("Failed to create pool")
+}
+
+/// Registration leaves the email address unverified; do what following the
+/// mailed link would.
+fn verify_email(pool: &DbPool, player: i64) {
+    use diesel::prelude::*;
+    use tennis_scheduler::db::schema::players;
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    diesel::update(players::table.find(player as i32))
+        .set(players::email_verified_at.eq(chrono::Utc::now()))
+        .execute(&mut conn)
+        .expect("Failed to verify email");
+}
+
+/// Opens a session for the player, as signing in would.
+fn sign_in(pool: &DbPool, player: i64) -> String {
+    use tennis_scheduler::api::sessions::{start_session, ClientInfo};
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    let (token, _) = start_session(&mut conn, player as i32, &ClientInfo::default()).expect("Failed to start session");
+    token
+}
+
+fn signed_in(method: test::TestRequest, uri: &str, token: &str) -> test::TestRequest {
+    method.uri(uri).insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_profile_hides_contact_details_from_strangers() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let tag = format!("{:x}", Uuid::new_v4().as_simple());
+    let mut ids = Vec::new();
+    for name in ["Owner", "Teammate", "Stranger"] {
+        let req = test::TestRequest::post()
+            .uri("/api/register")
+            .set_json(json!({
+                "name": format!("{} {}", name, tag),
+                "email": unique_email("profile"),
+                "password": "password123",
+                "phone": "+1 555 0100"
+            }))
+            .to_request();
+        let registered: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        let id = registered["player_id"].as_i64().unwrap();
+        verify_email(&pool, id);
+        ids.push(id);
+    }
+    let (owner, teammate) = (ids[0], ids[1]);
+    let tokens: Vec<String> = ids.iter().map(|id| sign_in(&pool, *id)).collect();
+
+    let req = signed_in(test::TestRequest::post(), "/api/leagues", &tokens[0])
+        .set_json(json!({ "league_name": format!("profile-{}", tag) }))
+        .to_request();
+    let league: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/join", league["league_id"]), &tokens[1])
+        .set_json(json!({ "player_id": teammate }))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    // Who is looking comes from the session, not from the query
+    for (viewer, uri, visible) in [
+        (0, format!("/api/players/{}", owner), true),
+        (1, format!("/api/players/{}", owner), true),
+        (2, format!("/api/players/{}", owner), false),
+        (2, format!("/api/players/{}?viewer_id={}", owner, owner), false),
+    ] {
+        let resp = test::call_service(&app, signed_in(test::TestRequest::get(), &uri, &tokens[viewer]).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let profile: Value = test::read_body_json(resp).await;
+        assert_eq!(profile.get("email").is_some(), visible, "{}", uri);
+        assert_eq!(profile.get("phone").is_some(), visible, "{}", uri);
+        assert_eq!(profile.get("email_verified").is_some(), visible, "{}", uri);
+        assert!(profile.get("password").is_none());
+        assert_eq!(profile["leagues"].as_array().unwrap().len(), 1);
+    }
+
+    // The league's member list only shows addresses to its members
+    let players_uri = format!("/api/leagues/{}/players", league["league_id"]);
+    for (viewer, visible) in [(1, true), (2, false)] {
+        let req = signed_in(test::TestRequest::get(), &players_uri, &tokens[viewer]).to_request();
+        let members: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(members.as_array().unwrap().len(), 2);
+        assert_eq!(members[0].get("email").is_some(), visible, "viewer {}", viewer);
+    }
+
+    // Listings never include contact details
+    let req = signed_in(test::TestRequest::get(), &format!("/api/players?name={}", tag), &tokens[2]).to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["count"], 3);
+    assert!(body["players"][0].get("email").is_none());
+
+    let req = signed_in(test::TestRequest::get(), "/api/players/-1", &tokens[2]).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_update_profile_validates_fields() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({
+            "name": format!("Editor {:x}", Uuid::new_v4().as_simple()),
+            "email": unique_email("editor"),
+            "password": "password123",
+            "phone": "555 0100 22"
+        }))
+        .to_request();
+    let registered: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let player = registered["player_id"].as_i64().unwrap();
+    let token = sign_in(&pool, player);
+    let uri = format!("/api/players/{}", player);
+
+    let req = signed_in(test::TestRequest::patch(), &uri, &token)
+        .set_json(json!({
+            "skill_level": "4.0",
+            "avatar_url": "https://example.com/me.png",
+            "phone": "",
+            "time_zone": "Europe/Madrid"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let profile: Value = test::read_body_json(resp).await;
+    assert_eq!(profile["skill_level"], "4.0");
+    assert_eq!(profile["avatar_url"], "https://example.com/me.png");
+    assert_eq!(profile["phone"], Value::Null);
+    assert_eq!(profile["time_zone"], "Europe/Madrid");
+
+    let invalid = [
+        json!({ "skill_level": "pro" }),
+        json!({ "phone": "call me" }),
+        json!({ "avatar_url": "ftp://example.com/me.png" }),
+        json!({ "name": "  " }),
+        json!({}),
+    ];
+    for body in invalid {
+        let req = signed_in(test::TestRequest::patch(), &uri, &token).set_json(&body).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST, "{}", body);
+    }
+
+    // Nobody can edit someone else's profile
+    let req = signed_in(test::TestRequest::patch(), &format!("/api/players/{}", player + 1), &token)
+        .set_json(json!({ "skill_level": "3.0" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_email_change_needs_confirmation() {
+    let pool = setup_test_db();
+    let mailer = Arc::new(MemoryMailer::default());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(mailer.clone() as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let old_email = unique_email("old");
+    let req = test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({ "name": format!("Mover {:x}", Uuid::new_v4().as_simple()), "email": old_email, "password": "password123" }))
+        .to_request();
+    let registered: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let player = registered["player_id"].as_i64().unwrap();
+    let token = sign_in(&pool, player);
+    let new_email = unique_email("new");
+
+    let req = signed_in(test::TestRequest::post(), &format!("/api/players/{}/email", player + 1), &token)
+        .set_json(json!({ "new_email": new_email, "password": "password123" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = signed_in(test::TestRequest::post(), &format!("/api/players/{}/email", player), &token)
+        .set_json(json!({ "new_email": new_email, "password": "wrong" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = signed_in(test::TestRequest::post(), &format!("/api/players/{}/email", player), &token)
+        .set_json(json!({ "new_email": "nowhere", "password": "password123" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+    let req = signed_in(test::TestRequest::post(), &format!("/api/players/{}/email", player), &token)
+        .set_json(json!({ "new_email": new_email, "password": "password123" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let body: Value = test::read_body_json(resp).await;
+    assert!(body.get("token").is_none());
+
+    // Nothing changes until the new address is confirmed
+    let login = |email: &str| {
+        test::TestRequest::post()
+            .uri("/api/login")
+            .set_json(json!({ "email": email, "password": "password123" }))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, login(&old_email)).await.status(), StatusCode::OK);
+
+    let mail = mailer.last_to(&new_email).expect("confirmation mail");
+    let token = mail.body.split_whitespace().find(|word| word.len() == 32).unwrap().to_string();
+
+    // Only a hash of the code is stored
+    let mut conn = pool.get().unwrap();
+    let stored: String = email_changes::table
+        .filter(email_changes::player_id.eq(player as i32))
+        .select(email_changes::token_hash)
+        .first(&mut conn)
+        .unwrap();
+    assert_ne!(stored, token);
+    assert_eq!(stored.len(), 64);
+
+    let confirm = || {
+        test::TestRequest::post()
+            .uri("/api/players/email/confirm")
+            .set_json(json!({ "token": token }))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, confirm()).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, login(&new_email)).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, login(&old_email)).await.status(), StatusCode::UNAUTHORIZED);
+
+    // Codes work once
+    assert_eq!(test::call_service(&app, confirm()).await.status(), StatusCode::BAD_REQUEST);
+}
@@ -42,7 +42,7 @@ async fn test_reject_match() {
     let player_email = unique_email("player");
     let player = test::TestRequest::post()
         .uri("/api/players")
-        .set_json(&json!({
+        .set_json(json!({
             "name": player_name,
             "email": player_email,
             "phone": "1234567890"
@@ -57,7 +57,7 @@ async fn test_reject_match() {
     let league_name = unique_name("League");
     let league = test::TestRequest::post()
         .uri("/api/leagues")
-        .set_json(&json!({
+        .set_json(json!({
             "name": league_name,
             "description": "Test league for match rejection",
             "location": "Test location",
@@ -74,11 +74,11 @@ async fn test_reject_match() {
     // Create a match
     let match_response = test::TestRequest::post()
         .uri("/api/matches")
-        .set_json(&json!({
+        .set_json(json!({
             "league_id": league_id,
             "match_type": "singles",
             "player1_id": player_id,
-            "player2_id": "2",
+            "player2_id": 2,
             "datetime": "2023-05-15T14:00:00",
             "location": "Tennis Court 1",
             "status": "Pending",
@@ -94,7 +94,7 @@ async fn test_reject_match() {
     // Reject the match
     let reject_response = test::TestRequest::post()
         .uri(&format!("/api/matches/{}/reject", match_id))
-        .set_json(&json!({
+        .set_json(json!({
             "player_id": player_id,
             "reason": "Schedule conflict"
         }))
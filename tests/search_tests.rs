@@ -0,0 +1,58 @@
+use chrono::{NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use tennis_scheduler::db::models::Match;
+use tennis_scheduler::search::{is_free_on, opponents, skill_rating};
+
+#[test]
+fn test_skill_rating_reads_names_and_ntrp() {
+    assert_eq!(skill_rating("Intermediate"), Some(3.5));
+    assert_eq!(skill_rating(" 4.0 "), Some(4.0));
+    assert_eq!(skill_rating("9.0"), None);
+    assert_eq!(skill_rating("club"), None);
+}
+
+#[test]
+fn test_is_free_on_uses_the_players_day() {
+    let date = NaiveDate::from_ymd_opt(2025, 7, 1).unwrap();
+    // 23:00 UTC on June 30th is already July 1st in Berlin
+    let late_match = (
+        Utc.with_ymd_and_hms(2025, 6, 30, 23, 0, 0).unwrap(),
+        Utc.with_ymd_and_hms(2025, 7, 1, 0, 30, 0).unwrap(),
+    );
+
+    assert!(!is_free_on(date, Tz::Europe__Berlin, &[late_match]));
+    assert!(!is_free_on(date, Tz::UTC, &[late_match]));
+    assert!(is_free_on(date, Tz::America__New_York, &[late_match]));
+    assert!(is_free_on(date, Tz::UTC, &[]));
+}
+
+#[test]
+fn test_opponents_are_the_other_side() {
+    let mut m = Match {
+        id: 1,
+        match_type: "Doubles".into(),
+        player1_id: None,
+        player2_id: None,
+        league_id: 1,
+        team1_player1_id: Some(1),
+        team1_player2_id: Some(2),
+        team2_player1_id: Some(3),
+        team2_player2_id: Some(4),
+        datetime: Utc::now(),
+        location: "Court 1".into(),
+        score: None,
+        winner_id: None,
+        status: "Completed".into(),
+        notes: None,
+        created_at: Utc::now(),
+        season_id: None,
+    };
+    assert_eq!(opponents(&m, 2), vec![3, 4]);
+    assert_eq!(opponents(&m, 3), vec![1, 2]);
+    assert!(opponents(&m, 5).is_empty());
+
+    m.team1_player1_id = None;
+    m.player1_id = Some(5);
+    m.player2_id = Some(6);
+    assert_eq!(opponents(&m, 6), vec![5]);
+}
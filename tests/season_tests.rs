@@ -0,0 +1,350 @@
+use actix_web::{test, web, App, http::StatusCode};
+use chrono::{TimeZone, Utc};
+use diesel::{r2d2::{self, ConnectionManager}, PgConnection};
+use diesel::prelude::*;
+use dotenv::dotenv;
+use std::env;
+use std::sync::Arc;
+use uuid::Uuid;
+use serde_json::{json, Value};
+use tennis_scheduler::api;
+use tennis_scheduler::db::DbPool;
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
+
+fn unique_name(prefix: &str) -> String {
+    format!("{} {}", prefix, Uuid::new_v4())
+}
+
+fn unique_email(prefix: &str) -> String {
+    format!("{}_{:x}@example.com", prefix, Uuid::new_v4().as_simple())
+}
+
+fn setup_test_db() -> DbPool {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+    r2d2::Pool::builder()
+        .build(manager)
+        .expect("Failed to create pool")
+}
+
+/// Registration leaves the email address unverified; do what following the
+/// mailed link would.
+fn verify_email(pool: &DbPool, player: i64) {
+    use diesel::prelude::*;
+    use tennis_scheduler::db::schema::players;
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    diesel::update(players::table.find(player as i32))
+        .set(players::email_verified_at.eq(chrono::Utc::now()))
+        .execute(&mut conn)
+        .expect("Failed to verify email");
+}
+
+/// Opens a session for the player, as signing in would.
+fn sign_in(pool: &DbPool, player: i64) -> String {
+    use tennis_scheduler::api::sessions::{start_session, ClientInfo};
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    let (token, _) = start_session(&mut conn, player as i32, &ClientInfo::default()).expect("Failed to start session");
+    token
+}
+
+fn signed_in(method: test::TestRequest, uri: &str, token: &str) -> test::TestRequest {
+    method.uri(uri).insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_close_season_records_promotion_and_relegation() {
+    let pool = setup_test_db();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    // Register three players
+    let mut ids = Vec::new();
+    for prefix in ["Season A", "Season B", "Season C"] {
+        let name = unique_name(prefix);
+        let register_req = test::TestRequest::post()
+            .uri("/api/register")
+            .set_json(json!({
+                "name": name,
+                "email": unique_email("season"),
+                "password": "password123",
+                "skill_level": "intermediate"
+            }))
+            .to_request();
+        let register_resp = test::call_service(&app, register_req).await;
+        assert_eq!(register_resp.status(), StatusCode::CREATED);
+        let registered: Value = test::read_body_json(register_resp).await;
+        let id = registered["player_id"].as_i64().unwrap();
+        verify_email(&pool, id);
+        ids.push(id as i32);
+    }
+    let tokens: Vec<String> = ids.iter().map(|id| sign_in(&pool, *id as i64)).collect();
+
+    // Create a league owned by the first player and add the others
+    let league_name = format!("season-league-{:x}", Uuid::new_v4().as_simple());
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &tokens[0])
+        .set_json(json!({
+            "league_name": league_name,
+            "description": "League with seasons"
+        }))
+        .to_request();
+    let league_resp = test::call_service(&app, league_req).await;
+    assert_eq!(league_resp.status(), StatusCode::CREATED);
+    let league: Value = test::read_body_json(league_resp).await;
+    let league_id = league["league_id"].as_i64().unwrap() as i32;
+
+    for id in &ids[1..] {
+        let join_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/join", league_id), &sign_in(&pool, *id as i64))
+            .set_json(json!({ "player_id": id }))
+            .to_request();
+        let join_resp = test::call_service(&app, join_req).await;
+        assert_eq!(join_resp.status(), StatusCode::OK);
+    }
+
+    // Create a season with two divisions
+    let season_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/seasons", league_id), &tokens[0])
+        .set_json(json!({
+            "season_name": "Spring",
+            "start_date": "2025-03-01",
+            "end_date": "2099-06-30"
+        }))
+        .to_request();
+    let season_resp = test::call_service(&app, season_req).await;
+    assert_eq!(season_resp.status(), StatusCode::CREATED);
+    let season: Value = test::read_body_json(season_resp).await;
+    let season_id = season["season_id"].as_i64().unwrap();
+
+    let mut division_ids = Vec::new();
+    for (tier, division_name) in [(1, "Box A"), (2, "Box B")] {
+        let division_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/seasons/{}/divisions", league_id, season_id), &tokens[0])
+            .set_json(json!({
+                "division_name": division_name,
+                "tier": tier,
+                "promotion_spots": 1,
+                "relegation_spots": 1
+            }))
+            .to_request();
+        let division_resp = test::call_service(&app, division_req).await;
+        assert_eq!(division_resp.status(), StatusCode::CREATED);
+        let division: Value = test::read_body_json(division_resp).await;
+        division_ids.push(division["division_id"].as_i64().unwrap());
+    }
+
+    // Players A and B play in the top box, C in the second
+    for (id, division_id) in [(ids[0], division_ids[0]), (ids[1], division_ids[0]), (ids[2], division_ids[1])] {
+        let member_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/seasons/{}/members", league_id, season_id), &tokens[0])
+            .set_json(json!({ "player_id": id, "division_id": division_id }))
+            .to_request();
+        let member_resp = test::call_service(&app, member_req).await;
+        assert_eq!(member_resp.status(), StatusCode::CREATED);
+    }
+
+    // Record a completed match in the season: A beats B
+    {
+        use tennis_scheduler::db::schema::matches;
+        let mut conn = pool.get().unwrap();
+        diesel::insert_into(matches::table)
+            .values((
+                matches::match_type.eq("Singles"),
+                matches::player1_id.eq(Some(ids[0])),
+                matches::player2_id.eq(Some(ids[1])),
+                matches::league_id.eq(league_id),
+                matches::datetime.eq(Utc.with_ymd_and_hms(2025, 4, 1, 10, 0, 0).unwrap()),
+                matches::location.eq("Court 1"),
+                matches::winner_id.eq(Some(ids[0])),
+                matches::status.eq("Completed"),
+                matches::created_at.eq(Utc::now()),
+                matches::season_id.eq(Some(season_id as i32)),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+    }
+
+    // Close the season
+    let close_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/seasons/{}/close", league_id, season_id), &tokens[0])
+        .to_request();
+    let close_resp = test::call_service(&app, close_req).await;
+    assert_eq!(close_resp.status(), StatusCode::OK);
+    let standings: Value = test::read_body_json(close_resp).await;
+
+    let top_box = standings[0]["standings"].as_array().unwrap();
+    assert_eq!(top_box[0]["player_id"], json!(ids[0]));
+    assert_eq!(top_box[1]["movement"], json!("relegated"));
+    let second_box = standings[1]["standings"].as_array().unwrap();
+    assert_eq!(second_box[0]["movement"], json!("promoted"));
+
+    // A follow-up season carries the divisions and the movements over
+    let next_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/seasons", league_id), &tokens[0])
+        .set_json(json!({
+            "season_name": "Summer",
+            "start_date": "2099-07-01",
+            "end_date": "2099-09-30",
+            "previous_season_id": season_id
+        }))
+        .to_request();
+    let next_resp = test::call_service(&app, next_req).await;
+    assert_eq!(next_resp.status(), StatusCode::CREATED);
+    let next_season: Value = test::read_body_json(next_resp).await;
+
+    let members_req = signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/seasons/{}/members", league_id, next_season["season_id"]), &tokens[0])
+        .to_request();
+    let members_resp = test::call_service(&app, members_req).await;
+    assert_eq!(members_resp.status(), StatusCode::OK);
+    let members: Value = test::read_body_json(members_resp).await;
+    assert_eq!(members.as_array().unwrap().len(), 3);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_seasons_are_run_by_admins_and_carry_over_across_tier_gaps() {
+    let pool = setup_test_db();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let mut ids = Vec::new();
+    for prefix in ["Gap A", "Gap B", "Gap C"] {
+        let register_req = test::TestRequest::post()
+            .uri("/api/register")
+            .set_json(json!({ "name": unique_name(prefix), "email": unique_email("gap"), "password": "password123" }))
+            .to_request();
+        let registered: Value = test::read_body_json(test::call_service(&app, register_req).await).await;
+        let id = registered["player_id"].as_i64().unwrap();
+        verify_email(&pool, id);
+        ids.push(id as i32);
+    }
+    let tokens: Vec<String> = ids.iter().map(|id| sign_in(&pool, *id as i64)).collect();
+
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &tokens[0])
+        .set_json(json!({ "league_name": format!("gap-league-{:x}", Uuid::new_v4().as_simple()) }))
+        .to_request();
+    let league: Value = test::read_body_json(test::call_service(&app, league_req).await).await;
+    let league_id = league["league_id"].as_i64().unwrap() as i32;
+    for (id, token) in ids[1..].iter().zip(&tokens[1..]) {
+        let join_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/join", league_id), token)
+            .set_json(json!({ "player_id": id }))
+            .to_request();
+        assert_eq!(test::call_service(&app, join_req).await.status(), StatusCode::OK);
+    }
+
+    let season = |token: &str, body: Value| {
+        signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/seasons", league_id), token)
+            .set_json(body)
+            .to_request()
+    };
+    let spring = json!({ "season_name": "Spring", "start_date": "2025-03-01", "end_date": "2099-06-30" });
+
+    // Only admins run seasons
+    assert_eq!(test::call_service(&app, season(&tokens[1], spring.clone())).await.status(), StatusCode::FORBIDDEN);
+    let season_resp = test::call_service(&app, season(&tokens[0], spring)).await;
+    assert_eq!(season_resp.status(), StatusCode::CREATED);
+    let season_id = test::read_body_json::<Value, _>(season_resp).await["season_id"].as_i64().unwrap();
+
+    // Tiers 1 and 3, with no tier 2
+    let mut division_ids = Vec::new();
+    for (tier, division_name) in [(1, "Top"), (3, "Bottom")] {
+        let division_req = signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/seasons/{}/divisions", league_id, season_id), &tokens[0])
+            .set_json(json!({ "division_name": division_name, "tier": tier, "promotion_spots": 1, "relegation_spots": 1 }))
+            .to_request();
+        let division: Value = test::read_body_json(test::call_service(&app, division_req).await).await;
+        division_ids.push(division["division_id"].as_i64().unwrap());
+    }
+
+    // Players register themselves but cannot pick a division or register others
+    let members_uri = format!("/api/leagues/{}/seasons/{}/members", league_id, season_id);
+    let register = |token: &str, body: Value| signed_in(test::TestRequest::post(), &members_uri, token).set_json(body).to_request();
+    assert_eq!(
+        test::call_service(&app, register(&tokens[1], json!({ "player_id": ids[2] }))).await.status(),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        test::call_service(&app, register(&tokens[1], json!({ "player_id": ids[1], "division_id": division_ids[0] }))).await.status(),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        test::call_service(&app, register(&tokens[1], json!({ "player_id": ids[1] }))).await.status(),
+        StatusCode::CREATED
+    );
+    let assign = signed_in(test::TestRequest::put(), &format!("{}/{}", members_uri, ids[1]), &tokens[1])
+        .set_json(json!({ "division_id": division_ids[0] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, assign).await.status(), StatusCode::FORBIDDEN);
+    let assign = signed_in(test::TestRequest::put(), &format!("{}/{}", members_uri, ids[1]), &tokens[0])
+        .set_json(json!({ "division_id": division_ids[0] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, assign).await.status(), StatusCode::OK);
+    for (id, division_id) in [(ids[0], division_ids[0]), (ids[2], division_ids[1])] {
+        let resp = test::call_service(&app, register(&tokens[0], json!({ "player_id": id, "division_id": division_id }))).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+    }
+
+    // A beats B in the top division
+    {
+        use tennis_scheduler::db::schema::matches;
+        let mut conn = pool.get().unwrap();
+        diesel::insert_into(matches::table)
+            .values((
+                matches::match_type.eq("Singles"),
+                matches::player1_id.eq(Some(ids[0])),
+                matches::player2_id.eq(Some(ids[1])),
+                matches::league_id.eq(league_id),
+                matches::datetime.eq(Utc.with_ymd_and_hms(2025, 4, 1, 10, 0, 0).unwrap()),
+                matches::location.eq("Court 1"),
+                matches::winner_id.eq(Some(ids[0])),
+                matches::status.eq("Completed"),
+                matches::created_at.eq(Utc::now()),
+                matches::season_id.eq(Some(season_id as i32)),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+    }
+
+    let close = |token: &str| {
+        signed_in(test::TestRequest::post(), &format!("/api/leagues/{}/seasons/{}/close", league_id, season_id), token).to_request()
+    };
+    assert_eq!(test::call_service(&app, close(&tokens[1])).await.status(), StatusCode::FORBIDDEN);
+    assert_eq!(test::call_service(&app, close(&tokens[0])).await.status(), StatusCode::OK);
+
+    let summer = json!({
+        "season_name": "Summer",
+        "start_date": "2099-07-01",
+        "end_date": "2099-09-30",
+        "previous_season_id": season_id
+    });
+    let next_season: Value = test::read_body_json(test::call_service(&app, season(&tokens[0], summer)).await).await;
+    let next_id = next_season["season_id"].as_i64().unwrap();
+    let details: Value = test::read_body_json(test::call_service(
+        &app,
+        signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/seasons/{}", league_id, next_id), &tokens[0]).to_request(),
+    ).await).await;
+    let division_of_tier = |tier: i64| {
+        details["divisions"].as_array().unwrap().iter().find(|division| division["tier"] == tier).unwrap()["division_id"].clone()
+    };
+
+    let members: Value = test::read_body_json(test::call_service(
+        &app,
+        signed_in(test::TestRequest::get(), &format!("/api/leagues/{}/seasons/{}/members", league_id, next_id), &tokens[0]).to_request(),
+    ).await).await;
+    let division_of = |player: i32| {
+        members.as_array().unwrap().iter().find(|member| member["player_id"] == player).unwrap()["division_id"].clone()
+    };
+    // B drops to tier 3 and C climbs to tier 1, skipping the missing tier
+    assert_eq!(division_of(ids[0]), division_of_tier(1));
+    assert_eq!(division_of(ids[1]), division_of_tier(3));
+    assert_eq!(division_of(ids[2]), division_of_tier(1));
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use actix_web::{http::StatusCode, test, web, App};
+use diesel::r2d2::{self, ConnectionManager};
+use diesel::PgConnection;
+use dotenv::dotenv;
+use serde_json::{json, Value};
+use std::env;
+use tennis_scheduler::api;
+use tennis_scheduler::db::DbPool;
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
+use uuid::Uuid;
+
+fn unique_email(prefix: &str) -> String {
+    format!("{}_{:x}@example.com", prefix, Uuid::new_v4().as_simple())
+}
+
+fn setup_test_db() -> DbPool {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+    r2d2::Pool::builder()
+        .build(manager)
+        .expect("Failed to create pool")
+}
+
+fn register(email: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({
+            "name": format!("Session {:x}", Uuid::new_v4().as_simple()),
+            "email": email,
+            "password": "password123"
+        }))
+}
+
+fn login(email: &str, user_agent: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/login")
+        .insert_header(("User-Agent", user_agent))
+        .set_json(json!({ "email": email, "password": "password123" }))
+}
+
+fn signed_in(method: test::TestRequest, uri: &str, token: &str) -> test::TestRequest {
+    method.uri(uri).insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_sessions_are_listed_and_signed_out() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(setup_test_db()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let email = unique_email("sessions");
+    assert_eq!(test::call_service(&app, register(&email).to_request()).await.status(), StatusCode::CREATED);
+    let mut tokens = Vec::new();
+    for user_agent in ["Laptop Browser", "Phone App", "Tablet Browser"] {
+        let session: Value = test::read_body_json(test::call_service(&app, login(&email, user_agent).to_request()).await).await;
+        tokens.push(session["token"].as_str().unwrap().to_string());
+    }
+
+    let listed = test::call_service(&app, signed_in(test::TestRequest::get(), "/api/sessions", &tokens[0]).to_request()).await;
+    assert_eq!(listed.status(), StatusCode::OK);
+    let listed: Value = test::read_body_json(listed).await;
+    let listed = listed.as_array().unwrap();
+    assert_eq!(listed.len(), 3);
+    assert!(listed.iter().all(|session| session.get("token_hash").is_none() && !session["last_seen_at"].is_null()));
+    let current: Vec<&Value> = listed.iter().filter(|session| session["current"] == true).collect();
+    assert_eq!(current.len(), 1);
+    assert_eq!(current[0]["user_agent"], "Laptop Browser");
+    let phone = listed.iter().find(|session| session["user_agent"] == "Phone App").unwrap();
+    let phone_id = phone["session_id"].as_i64().unwrap();
+
+    // Sign the phone out from the laptop
+    let revoke = |id: i64, token: &str| signed_in(test::TestRequest::delete(), &format!("/api/sessions/{}", id), token).to_request();
+    assert_eq!(test::call_service(&app, revoke(phone_id, &tokens[0])).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, revoke(phone_id, &tokens[0])).await.status(), StatusCode::NOT_FOUND);
+    let on_phone = test::call_service(&app, signed_in(test::TestRequest::get(), "/api/sessions", &tokens[1]).to_request()).await;
+    assert_eq!(on_phone.status(), StatusCode::UNAUTHORIZED);
+
+    // Everywhere but here
+    let revoke_others = signed_in(test::TestRequest::delete(), "/api/sessions?keep_current=true", &tokens[0]).to_request();
+    let revoked: Value = test::read_body_json(test::call_service(&app, revoke_others).await).await;
+    assert_eq!(revoked["revoked"], 1);
+    let on_tablet = test::call_service(&app, signed_in(test::TestRequest::get(), "/api/sessions", &tokens[2]).to_request()).await;
+    assert_eq!(on_tablet.status(), StatusCode::UNAUTHORIZED);
+
+    let logout = test::call_service(&app, signed_in(test::TestRequest::post(), "/api/logout", &tokens[0]).to_request()).await;
+    assert_eq!(logout.status(), StatusCode::OK);
+    let after = test::call_service(&app, signed_in(test::TestRequest::get(), "/api/sessions", &tokens[0]).to_request()).await;
+    assert_eq!(after.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_other_players_sessions_cannot_be_revoked() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(setup_test_db()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let mut tokens = Vec::new();
+    for prefix in ["owner", "stranger"] {
+        let email = unique_email(prefix);
+        test::call_service(&app, register(&email).to_request()).await;
+        let session: Value = test::read_body_json(test::call_service(&app, login(&email, "Browser").to_request()).await).await;
+        tokens.push(session["token"].as_str().unwrap().to_string());
+    }
+    let listed: Value = test::read_body_json(
+        test::call_service(&app, signed_in(test::TestRequest::get(), "/api/sessions", &tokens[0]).to_request()).await,
+    ).await;
+    let owner_session = listed[0]["session_id"].as_i64().unwrap();
+
+    let revoke = signed_in(test::TestRequest::delete(), &format!("/api/sessions/{}", owner_session), &tokens[1]).to_request();
+    assert_eq!(test::call_service(&app, revoke).await.status(), StatusCode::NOT_FOUND);
+    let still_signed_in = test::call_service(&app, signed_in(test::TestRequest::get(), "/api/sessions", &tokens[0]).to_request()).await;
+    assert_eq!(still_signed_in.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, test::TestRequest::post().uri("/api/logout").to_request()).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_signed_out_sessions_are_refused_on_league_and_match_routes() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(setup_test_db()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let email = unique_email("scopes");
+    test::call_service(&app, register(&email).to_request()).await;
+    let session: Value = test::read_body_json(test::call_service(&app, login(&email, "Browser").to_request()).await).await;
+    let token = session["token"].as_str().unwrap().to_string();
+    let routes = ["/api/leagues", "/api/matches", "/api/players", "/api/invitations/unknown-token"];
+
+    for uri in routes {
+        let anonymous = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+    }
+    let listed = test::call_service(&app, signed_in(test::TestRequest::get(), "/api/leagues", &token).to_request()).await;
+    assert_eq!(listed.status(), StatusCode::OK);
+
+    test::call_service(&app, signed_in(test::TestRequest::post(), "/api/logout", &token).to_request()).await;
+    for uri in routes {
+        let after = test::call_service(&app, signed_in(test::TestRequest::get(), uri, &token).to_request()).await;
+        assert_eq!(after.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+    }
+}
@@ -86,7 +86,7 @@ fn reset_test_database(pool: &DbPool) {
     
     let _ = sql_query("
         INSERT INTO leagues (league_name, description, skill_level, is_public, created_by)
-        VALUES 
-        ('Test League', 'A league for testing', 'intermediate', true, 'admin@example.com')
+        SELECT 'Test League', 'A league for testing', 'intermediate', true, player_id
+        FROM players WHERE email = 'admin@example.com'
     ").execute(&mut conn);
 } 
\ No newline at end of file
@@ -0,0 +1,186 @@
+use std::sync::Arc;
+
+use actix_web::{http::StatusCode, test, web, App};
+use diesel::r2d2::{self, ConnectionManager};
+use diesel::PgConnection;
+use dotenv::dotenv;
+use serde_json::{json, Value};
+use std::env;
+use tennis_scheduler::admin;
+use tennis_scheduler::api;
+use tennis_scheduler::db::models::Role;
+use tennis_scheduler::db::DbPool;
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
+use uuid::Uuid;
+
+fn unique_email(prefix: &str) -> String {
+    format!("{}_{:x}@example.com", prefix, Uuid::new_v4().as_simple())
+}
+
+fn setup_test_db() -> DbPool {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+    r2d2::Pool::builder()
+        .build(manager)
+        .expect("Failed to create pool")
+}
+
+fn register(email: &str, role: Option<&str>) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({
+            "name": format!("Site {:x}", Uuid::new_v4().as_simple()),
+            "email": email,
+            "password": "password123",
+            "role": role
+        }))
+}
+
+fn login(email: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/login")
+        .set_json(json!({ "email": email, "password": "password123" }))
+}
+
+fn signed_in(method: test::TestRequest, uri: &str, token: &str) -> test::TestRequest {
+    method.uri(uri).insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_registration_ignores_requested_role() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let email = unique_email("wannabe");
+    let registered = test::call_service(&app, register(&email, Some("site_admin")).to_request()).await;
+    assert_eq!(registered.status(), StatusCode::CREATED);
+    let registered: Value = test::read_body_json(registered).await;
+    let player = registered["player_id"].as_i64().unwrap();
+    let session: Value = test::read_body_json(test::call_service(&app, login(&email).to_request()).await).await;
+    let token = session["token"].as_str().unwrap();
+
+    {
+        use diesel::prelude::*;
+        use tennis_scheduler::db::schema::players;
+
+        let mut conn = pool.get().expect("Failed to get DB connection");
+        let role = players::table.find(player as i32).select(players::role).first::<String>(&mut conn).unwrap();
+        assert_eq!(role, "player");
+    }
+
+    // Neither the admin API nor role changes are open to them
+    let listed = test::call_service(&app, signed_in(test::TestRequest::get(), "/api/admin/players", token).to_request()).await;
+    assert_eq!(listed.status(), StatusCode::FORBIDDEN);
+    let promote = signed_in(test::TestRequest::patch(), &format!("/api/players/{}/role", player), token)
+        .set_json(json!({ "role": "site_admin" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, promote).await.status(), StatusCode::FORBIDDEN);
+    let anonymous = test::TestRequest::get().uri("/api/admin/leagues").to_request();
+    assert_eq!(test::call_service(&app, anonymous).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_site_admin_manages_players_and_sees_all_leagues() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let admin_email = unique_email("site-admin");
+    let member_email = unique_email("member");
+    let admin_id = test::read_body_json::<Value, _>(test::call_service(&app, register(&admin_email, None).to_request()).await).await["player_id"]
+        .as_i64()
+        .unwrap();
+    let member_id = test::read_body_json::<Value, _>(test::call_service(&app, register(&member_email, None).to_request()).await).await["player_id"]
+        .as_i64()
+        .unwrap();
+    {
+        let mut conn = pool.get().expect("Failed to get DB connection");
+        assert_eq!(admin::set_role(&mut conn, &admin_email, Role::SiteAdmin).unwrap() as i64, admin_id);
+    }
+    let admin_session: Value = test::read_body_json(test::call_service(&app, login(&admin_email).to_request()).await).await;
+    let admin_token = admin_session["token"].as_str().unwrap().to_string();
+    let member_session: Value = test::read_body_json(test::call_service(&app, login(&member_email).to_request()).await).await;
+    let member_token = member_session["token"].as_str().unwrap().to_string();
+
+    let listed = test::call_service(
+        &app,
+        signed_in(test::TestRequest::get(), &format!("/api/admin/players?search={}", member_email), &admin_token).to_request(),
+    ).await;
+    assert_eq!(listed.status(), StatusCode::OK);
+    let listed: Value = test::read_body_json(listed).await;
+    assert_eq!(listed["pagination"]["total"], 1);
+    assert_eq!(listed["players"][0]["email"], member_email.as_str());
+    assert!(listed["players"][0]["deactivated_at"].is_null());
+
+    // A private league the admin is not a member of
+    let league_name = format!("private-{:x}", Uuid::new_v4().as_simple());
+    {
+        let mut conn = pool.get().expect("Failed to get DB connection");
+        admin::create_league(&mut conn, tennis_scheduler::db::models::NewLeague {
+            league_name: league_name.clone(),
+            description: None,
+            is_public: false,
+            skill_level: None,
+            created_by: member_id as i32,
+            created_at: chrono::Utc::now(),
+            departure_policy: "cancel".into(),
+            max_members: None,
+            waitlist_enabled: false,
+            time_zone: "UTC".into(),
+        })
+        .unwrap();
+    }
+    let leagues: Value = test::read_body_json(
+        test::call_service(
+            &app,
+            signed_in(test::TestRequest::get(), &format!("/api/admin/leagues?search={}", league_name), &admin_token).to_request(),
+        ).await,
+    ).await;
+    assert_eq!(leagues["leagues"][0]["league_name"], league_name.as_str());
+    assert_eq!(leagues["leagues"][0]["member_count"], 1);
+
+    // Deactivating signs the member out and keeps them out until reactivated
+    let deactivate = signed_in(test::TestRequest::post(), &format!("/api/admin/players/{}/deactivate", member_id), &admin_token).to_request();
+    let deactivated = test::call_service(&app, deactivate).await;
+    assert_eq!(deactivated.status(), StatusCode::OK);
+    let deactivated: Value = test::read_body_json(deactivated).await;
+    assert_eq!(deactivated["sessions_revoked"], 1);
+    assert!(!deactivated["player"]["deactivated_at"].is_null());
+    let as_member = test::call_service(&app, signed_in(test::TestRequest::get(), "/api/sessions", &member_token).to_request()).await;
+    assert_eq!(as_member.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(test::call_service(&app, login(&member_email).to_request()).await.status(), StatusCode::FORBIDDEN);
+
+    let reactivate = signed_in(test::TestRequest::post(), &format!("/api/admin/players/{}/reactivate", member_id), &admin_token).to_request();
+    assert_eq!(test::call_service(&app, reactivate).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, login(&member_email).to_request()).await.status(), StatusCode::OK);
+
+    // Roles are checked, and admins cannot demote or deactivate themselves
+    let set_role = |player: i64, role: &str| {
+        signed_in(test::TestRequest::patch(), &format!("/api/players/{}/role", player), &admin_token)
+            .set_json(json!({ "role": role }))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, set_role(member_id, "admin")).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(test::call_service(&app, set_role(admin_id, "player")).await.status(), StatusCode::BAD_REQUEST);
+    let promoted = test::call_service(&app, set_role(member_id, "site_admin")).await;
+    assert_eq!(promoted.status(), StatusCode::OK);
+    let promoted: Value = test::read_body_json(promoted).await;
+    assert_eq!(promoted["role"], "site_admin");
+    let deactivate_self = signed_in(test::TestRequest::post(), &format!("/api/admin/players/{}/deactivate", admin_id), &admin_token).to_request();
+    assert_eq!(test::call_service(&app, deactivate_self).await.status(), StatusCode::BAD_REQUEST);
+}
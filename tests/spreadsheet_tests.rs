@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use tennis_scheduler::spreadsheet::{parse_rows, to_csv, RowError};
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Row {
+    player_id: i32,
+    email: Option<String>,
+}
+
+#[test]
+fn test_parse_rows_reports_bad_lines_and_keeps_good_ones() {
+    let data = "email, player_id\n a@example.com , 1\n,2\n\nb@example.com,two\n";
+
+    let parsed = parse_rows::<Row>(data).unwrap();
+
+    assert_eq!(
+        parsed.rows,
+        vec![
+            (2, Row { player_id: 1, email: Some("a@example.com".into()) }),
+            (3, Row { player_id: 2, email: None }),
+        ]
+    );
+    assert_eq!(parsed.errors.len(), 1);
+    assert_eq!(parsed.errors[0].line, 5);
+    assert!(parsed.errors[0].message.starts_with("column 2:"));
+}
+
+#[test]
+fn test_parse_rows_reports_missing_columns_per_row() {
+    let parsed = parse_rows::<Row>("email\na@example.com\n").unwrap();
+
+    assert!(parsed.rows.is_empty());
+    assert_eq!(parsed.errors, vec![RowError::new(2, "missing field `player_id`")]);
+}
+
+#[test]
+fn test_parse_rows_rejects_empty_input() {
+    assert!(parse_rows::<Row>("").is_err());
+}
+
+#[test]
+fn test_to_csv_writes_header_even_without_rows() {
+    assert_eq!(to_csv::<Row>(&["player_id", "email"], &[]).unwrap(), "player_id,email\n");
+
+    let rows = vec![Row { player_id: 7, email: Some("x, y@example.com".into()) }, Row { player_id: 8, email: None }];
+    assert_eq!(
+        to_csv(&["player_id", "email"], &rows).unwrap(),
+        "player_id,email\n7,\"x, y@example.com\"\n8,\n"
+    );
+}
@@ -0,0 +1,125 @@
+use chrono::{TimeZone, Utc};
+use tennis_scheduler::db::models::Match;
+use tennis_scheduler::standings::{assign_movements, compute_doubles_standings, compute_standings, Movement};
+
+const ALICE: i32 = 1;
+const BOB: i32 = 2;
+const CAROL: i32 = 3;
+const DAVE: i32 = 4;
+
+fn singles_result(id: i32, player1: i32, player2: i32, winner: i32) -> Match {
+    Match {
+        id,
+        match_type: "Singles".into(),
+        player1_id: Some(player1),
+        player2_id: Some(player2),
+        league_id: 1,
+        team1_player1_id: None,
+        team1_player2_id: None,
+        team2_player1_id: None,
+        team2_player2_id: None,
+        datetime: Utc.with_ymd_and_hms(2025, 5, 15, 14, 0, 0).unwrap(),
+        location: "Court 1".into(),
+        score: Some("6-4 6-4".into()),
+        winner_id: Some(winner),
+        status: "Completed".into(),
+        notes: None,
+        created_at: Utc::now(),
+        season_id: Some(1),
+    }
+}
+
+fn doubles_result(id: i32, team1: (i32, i32), team2: (i32, i32), winner: i32) -> Match {
+    Match {
+        match_type: "Doubles".into(),
+        player1_id: None,
+        player2_id: None,
+        team1_player1_id: Some(team1.0),
+        team1_player2_id: Some(team1.1),
+        team2_player1_id: Some(team2.0),
+        team2_player2_id: Some(team2.1),
+        ..singles_result(id, team1.0, team2.0, winner)
+    }
+}
+
+#[test]
+fn test_standings_order_by_wins_then_losses() {
+    let members = vec![ALICE, BOB, CAROL];
+    let results = vec![
+        singles_result(1, ALICE, BOB, ALICE),
+        singles_result(2, ALICE, CAROL, ALICE),
+        singles_result(3, BOB, CAROL, CAROL),
+    ];
+
+    let standings = compute_standings(&members, &results);
+
+    let order: Vec<i32> = standings.iter().map(|s| s.player_id).collect();
+    assert_eq!(order, vec![ALICE, CAROL, BOB]);
+    assert_eq!(standings[0].wins, 2);
+    assert_eq!(standings[0].rank, 1);
+    assert_eq!(standings[2].losses, 2);
+    assert_eq!(standings[2].rank, 3);
+}
+
+#[test]
+fn test_standings_ignore_unfinished_and_outside_matches() {
+    let members = vec![ALICE, BOB];
+    let mut pending = singles_result(1, ALICE, BOB, ALICE);
+    pending.status = "Scheduled".into();
+    let outsider = singles_result(2, ALICE, DAVE, ALICE);
+
+    let standings = compute_standings(&members, &[pending, outsider]);
+
+    assert!(standings.iter().all(|s| s.played == 0));
+    assert_eq!(standings.len(), 2);
+}
+
+#[test]
+fn test_movements_promote_top_and_relegate_bottom() {
+    let members = vec![ALICE, BOB, CAROL, DAVE];
+    let results = vec![
+        singles_result(1, ALICE, BOB, ALICE),
+        singles_result(2, ALICE, CAROL, ALICE),
+        singles_result(3, ALICE, DAVE, ALICE),
+        singles_result(4, BOB, CAROL, BOB),
+        singles_result(5, BOB, DAVE, BOB),
+        singles_result(6, CAROL, DAVE, CAROL),
+    ];
+    let standings = compute_standings(&members, &results);
+
+    let movements = assign_movements(&standings, 1, 1);
+
+    assert_eq!(movements[0], (ALICE, Movement::Promoted));
+    assert_eq!(movements[1], (BOB, Movement::Stayed));
+    assert_eq!(movements[2], (CAROL, Movement::Stayed));
+    assert_eq!(movements[3], (DAVE, Movement::Relegated));
+}
+
+#[test]
+fn test_movements_prefer_promotion_when_spots_overlap() {
+    let members = vec![ALICE, BOB];
+    let standings = compute_standings(&members, &[singles_result(1, ALICE, BOB, ALICE)]);
+
+    let movements = assign_movements(&standings, 2, 2);
+
+    assert!(movements.iter().all(|(_, movement)| *movement == Movement::Promoted));
+}
+
+#[test]
+fn test_doubles_standings_credit_both_partners() {
+    let members = vec![ALICE, BOB, CAROL, DAVE];
+    let results = vec![
+        doubles_result(1, (ALICE, BOB), (CAROL, DAVE), BOB),
+        doubles_result(2, (ALICE, CAROL), (BOB, DAVE), ALICE),
+        // Singles results do not count towards the doubles table
+        singles_result(3, DAVE, ALICE, DAVE),
+    ];
+
+    let standings = compute_doubles_standings(&members, &results);
+
+    let order: Vec<i32> = standings.iter().map(|s| s.player_id).collect();
+    assert_eq!(order, vec![ALICE, BOB, CAROL, DAVE]);
+    assert_eq!((standings[0].wins, standings[0].losses), (2, 0));
+    assert_eq!((standings[1].wins, standings[1].losses), (1, 1));
+    assert_eq!((standings[3].wins, standings[3].losses), (0, 2));
+}
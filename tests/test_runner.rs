@@ -1,23 +1,20 @@
-use std::sync::Once;
+use std::sync::OnceLock;
 use tennis_scheduler::db::DbPool;
 
 // Import the setup_test_db module using a relative path
 #[path = "setup_test_db.rs"]
 pub mod setup_test_db;
 
-static INIT: Once = Once::new();
-static mut TEST_DB_POOL: Option<DbPool> = None;
+static TEST_DB_POOL: OnceLock<DbPool> = OnceLock::new();
 
 /// Initialize the test database once for all tests
 pub fn initialize() -> DbPool {
-    unsafe {
-        INIT.call_once(|| {
+    TEST_DB_POOL
+        .get_or_init(|| {
             let (pool, _) = setup_test_db::create_test_db_pool();
-            TEST_DB_POOL = Some(pool);
-        });
-        
-        TEST_DB_POOL.clone().unwrap()
-    }
+            pool
+        })
+        .clone()
 }
 
 /// Get a connection pool for the test database
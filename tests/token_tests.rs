@@ -0,0 +1,46 @@
+use chrono::{Duration, TimeZone, Utc};
+use tennis_scheduler::tokens::{SignedToken, TokenSigner};
+
+#[test]
+fn test_signed_token_round_trip() {
+    let signer = TokenSigner::new("secret");
+    let expires = Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap();
+    let token = signer.sign("verify-email", 42, "ana@example.com", expires);
+
+    let parsed = SignedToken::parse(&token).unwrap();
+    assert_eq!(parsed.player_id, 42);
+    assert_eq!(parsed.expires_at, expires);
+    assert!(signer.verify("verify-email", &parsed, "ana@example.com"));
+}
+
+#[test]
+fn test_signed_token_is_bound_to_purpose_subject_and_secret() {
+    let signer = TokenSigner::new("secret");
+    let token = signer.sign("verify-email", 42, "ana@example.com", Utc::now() + Duration::hours(1));
+    let parsed = SignedToken::parse(&token).unwrap();
+
+    assert!(!signer.verify("verify-email", &parsed, "other@example.com"));
+    assert!(!signer.verify("reset-password", &parsed, "ana@example.com"));
+    assert!(!TokenSigner::new("other secret").verify("verify-email", &parsed, "ana@example.com"));
+}
+
+#[test]
+fn test_tampered_or_malformed_tokens_are_refused() {
+    let signer = TokenSigner::new("secret");
+    let expires = Utc::now() + Duration::hours(1);
+    let token = signer.sign("verify-email", 42, "ana@example.com", expires);
+
+    // Another player id or a later expiry breaks the signature
+    let signature = token.rsplit('.').next().unwrap();
+    for forged in [
+        format!("43.{}.{}", expires.timestamp(), signature),
+        format!("42.{}.{}", expires.timestamp() + 3600, signature),
+    ] {
+        let parsed = SignedToken::parse(&forged).unwrap();
+        assert!(!signer.verify("verify-email", &parsed, "ana@example.com"));
+    }
+
+    for malformed in ["", "42", "42.abc.00", "x.1.00", "42.1.zz", &format!("{}.extra", token)] {
+        assert!(SignedToken::parse(malformed).is_none(), "{}", malformed);
+    }
+}
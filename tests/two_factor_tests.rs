@@ -0,0 +1,200 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::{http::StatusCode, test, web, App};
+use diesel::r2d2::{self, ConnectionManager};
+use diesel::PgConnection;
+use dotenv::dotenv;
+use serde_json::{json, Value};
+use std::env;
+use tennis_scheduler::api;
+use tennis_scheduler::db::DbPool;
+use tennis_scheduler::mailer::{Mailer, MemoryMailer};
+use tennis_scheduler::tokens::TokenSigner;
+use totp_rs::TOTP;
+use uuid::Uuid;
+
+fn unique_email(prefix: &str) -> String {
+    format!("{}_{:x}@example.com", prefix, Uuid::new_v4().as_simple())
+}
+
+fn setup_test_db() -> DbPool {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+    r2d2::Pool::builder()
+        .build(manager)
+        .expect("Failed to create pool")
+}
+
+/// Registration leaves the email address unverified; do what following the
+/// mailed link would.
+fn verify_email(pool: &DbPool, player: i64) {
+    use diesel::prelude::*;
+    use tennis_scheduler::db::schema::players;
+
+    let mut conn = pool.get().expect("Failed to get DB connection");
+    diesel::update(players::table.find(player as i32))
+        .set(players::email_verified_at.eq(chrono::Utc::now()))
+        .execute(&mut conn)
+        .expect("Failed to verify email");
+}
+
+fn register(email: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/register")
+        .set_json(json!({
+            "name": format!("Two Factor {:x}", Uuid::new_v4().as_simple()),
+            "email": email,
+            "password": "password123"
+        }))
+}
+
+fn login(email: &str, code: Option<&str>) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/login")
+        .set_json(json!({ "email": email, "password": "password123", "two_factor_code": code }))
+}
+
+fn signed_in(method: test::TestRequest, uri: &str, token: &str) -> test::TestRequest {
+    method.uri(uri).insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+/// The code an authenticator app would show `offset` seconds from now.
+fn app_code(totp: &TOTP, offset: u64) -> String {
+    totp.generate(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + offset)
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_two_factor_enrolment_login_and_recovery_codes() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(setup_test_db()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let email = unique_email("totp");
+    assert_eq!(test::call_service(&app, register(&email).to_request()).await.status(), StatusCode::CREATED);
+    let session: Value = test::read_body_json(test::call_service(&app, login(&email, None).to_request()).await).await;
+    let token = session["token"].as_str().unwrap().to_string();
+
+    let enroll = test::call_service(&app, signed_in(test::TestRequest::post(), "/api/two-factor/enroll", &token).to_request()).await;
+    assert_eq!(enroll.status(), StatusCode::OK);
+    let enroll: Value = test::read_body_json(enroll).await;
+    let uri = enroll["provisioning_uri"].as_str().unwrap();
+    assert!(uri.starts_with("otpauth://totp/"));
+    let totp = TOTP::from_url(uri).unwrap();
+
+    // Not on until a code from the app is confirmed
+    assert_eq!(test::call_service(&app, login(&email, None).to_request()).await.status(), StatusCode::OK);
+    let confirm = |code: String| {
+        signed_in(test::TestRequest::post(), "/api/two-factor/confirm", &token)
+            .set_json(json!({ "code": code }))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, confirm("000000".into())).await.status(), StatusCode::BAD_REQUEST);
+    let confirmed = test::call_service(&app, confirm(app_code(&totp, 0))).await;
+    assert_eq!(confirmed.status(), StatusCode::OK);
+    let confirmed: Value = test::read_body_json(confirmed).await;
+    let recovery_codes = confirmed["recovery_codes"].as_array().unwrap();
+    assert_eq!(recovery_codes.len(), 10);
+    let recovery_code = recovery_codes[0].as_str().unwrap();
+
+    // Login now asks for a code, and each code works once
+    assert_eq!(test::call_service(&app, login(&email, None).to_request()).await.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(test::call_service(&app, login(&email, Some("123456")).to_request()).await.status(), StatusCode::UNAUTHORIZED);
+    let next_code = app_code(&totp, 30);
+    assert_eq!(test::call_service(&app, login(&email, Some(&next_code)).to_request()).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, login(&email, Some(&next_code)).to_request()).await.status(), StatusCode::UNAUTHORIZED);
+    let shouted = recovery_code.to_uppercase();
+    assert_eq!(test::call_service(&app, login(&email, Some(&shouted)).to_request()).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, login(&email, Some(recovery_code)).to_request()).await.status(), StatusCode::UNAUTHORIZED);
+
+    let status = test::call_service(&app, signed_in(test::TestRequest::get(), "/api/two-factor", &token).to_request()).await;
+    let status: Value = test::read_body_json(status).await;
+    assert_eq!(status["enabled"], true);
+    assert_eq!(status["recovery_codes_left"], 9);
+
+    let disable = |password: &str, code: &str| {
+        signed_in(test::TestRequest::post(), "/api/two-factor/disable", &token)
+            .set_json(json!({ "password": password, "code": code }))
+            .to_request()
+    };
+    let second = recovery_codes[1].as_str().unwrap();
+    assert_eq!(test::call_service(&app, disable("wrong-password", second)).await.status(), StatusCode::FORBIDDEN);
+    assert_eq!(test::call_service(&app, disable("password123", second)).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, login(&email, None).to_request()).await.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+#[ignore]
+async fn test_league_can_require_two_factor_for_admins() {
+    let pool = setup_test_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::from(Arc::new(MemoryMailer::default()) as Arc<dyn Mailer>))
+            .app_data(web::Data::new(TokenSigner::new("test-secret")))
+            .configure(api::init_routes)
+    ).await;
+
+    let email = unique_email("totp-admin");
+    let registered: Value = test::read_body_json(test::call_service(&app, register(&email).to_request()).await).await;
+    let admin = registered["player_id"].as_i64().unwrap();
+    verify_email(&pool, admin);
+    let session: Value = test::read_body_json(test::call_service(&app, login(&email, None).to_request()).await).await;
+    let token = session["token"].as_str().unwrap().to_string();
+
+    let league_req = signed_in(test::TestRequest::post(), "/api/leagues", &token)
+        .set_json(json!({ "league_name": format!("totp-{:x}", Uuid::new_v4().as_simple()) }))
+        .to_request();
+    let league: Value = test::read_body_json(test::call_service(&app, league_req).await).await;
+    let league_id = league["league_id"].as_i64().unwrap();
+
+    let require = || {
+        signed_in(test::TestRequest::patch(), &format!("/api/leagues/{}", league_id), &token)
+            .set_json(json!({ "require_admin_two_factor": true }))
+            .to_request()
+    };
+    // The admin cannot shut themselves out
+    assert_eq!(test::call_service(&app, require()).await.status(), StatusCode::FORBIDDEN);
+    let earlier: Value = test::read_body_json(test::call_service(&app, login(&email, None).to_request()).await).await;
+    let earlier_token = earlier["token"].as_str().unwrap().to_string();
+
+    let enroll = test::call_service(&app, signed_in(test::TestRequest::post(), "/api/two-factor/enroll", &token).to_request()).await;
+    let enroll: Value = test::read_body_json(enroll).await;
+    let totp = TOTP::from_url(enroll["provisioning_uri"].as_str().unwrap()).unwrap();
+    let confirm = signed_in(test::TestRequest::post(), "/api/two-factor/confirm", &token)
+        .set_json(json!({ "code": app_code(&totp, 0) }))
+        .to_request();
+    let confirmed: Value = test::read_body_json(test::call_service(&app, confirm).await).await;
+
+    let resp = test::call_service(&app, require()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let league: Value = test::read_body_json(resp).await;
+    assert_eq!(league["require_admin_two_factor"], true);
+
+    // A session opened without a code cannot act as an admin, a new one with a code can
+    let rename = |token: &str| {
+        signed_in(test::TestRequest::patch(), &format!("/api/leagues/{}", league_id), token)
+            .set_json(json!({ "description": "Two-factor admins only" }))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, rename(&earlier_token)).await.status(), StatusCode::FORBIDDEN);
+    let code = confirmed["recovery_codes"][1].as_str().unwrap();
+    let verified: Value = test::read_body_json(test::call_service(&app, login(&email, Some(code)).to_request()).await).await;
+    assert_eq!(test::call_service(&app, rename(verified["token"].as_str().unwrap())).await.status(), StatusCode::OK);
+
+    // Turning it off again is refused while the league requires it
+    let disable = signed_in(test::TestRequest::post(), "/api/two-factor/disable", &token)
+        .set_json(json!({ "password": "password123", "code": confirmed["recovery_codes"][0] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, disable).await.status(), StatusCode::FORBIDDEN);
+
+    let status = test::call_service(&app, signed_in(test::TestRequest::get(), "/api/two-factor", &token).to_request()).await;
+    let status: Value = test::read_body_json(status).await;
+    assert_eq!(status["required_by_leagues"], json!([league_id]));
+}